@@ -0,0 +1,368 @@
+//! The `balsa` command, which checks, renders and describes Balsa templates so that they can be
+//! validated without writing any Rust.
+//!
+//! ```text
+//! balsa check template.html
+//! balsa render template.html --params params.json
+//! balsa params template.html --json
+//! ```
+//!
+//! Templates are compiled with the default options, and `include` blocks are loaded relative to
+//! the directory of the template.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use balsa::{Balsa, BalsaError, BalsaTemplate, BalsaValue, FileLoader, ParameterInfo, Template};
+use serde_json::{json, Map, Value};
+
+const USAGE: &str = "\
+Usage:
+    balsa check <template>                      Compile a template and report its errors
+    balsa render <template> [--params <file>]   Render a template with parameters read from a
+                                                JSON object
+    balsa params <template> [--json]            List the parameters a template expects";
+
+/// A command given on the command line.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Check {
+        template: PathBuf,
+    },
+    Render {
+        template: PathBuf,
+        params: Option<PathBuf>,
+    },
+    Params {
+        template: PathBuf,
+        json: bool,
+    },
+}
+
+impl Command {
+    /// Parses the command from the arguments following the name of the program.
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let name = match args.next() {
+            Some(name) if name == "-h" || name == "--help" || name == "help" => {
+                return Ok(Self::Help)
+            }
+            Some(name) => name,
+            None => return Err("no command given".to_string()),
+        };
+
+        let mut template = None;
+        let mut params = None;
+        let mut json = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--params" if name == "render" => match args.next() {
+                    Some(path) => params = Some(PathBuf::from(path)),
+                    None => return Err("`--params` needs the path of a JSON file".to_string()),
+                },
+                "--json" if name == "params" => json = true,
+                _ if arg.starts_with('-') => {
+                    return Err(format!("unknown option `{}` for `{}`", arg, name))
+                }
+                _ if template.is_none() => template = Some(PathBuf::from(arg)),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+
+        let template = template.ok_or_else(|| format!("`{}` needs a template", name))?;
+        match name.as_str() {
+            "check" => Ok(Self::Check { template }),
+            "render" => Ok(Self::Render { template, params }),
+            "params" => Ok(Self::Params { template, json }),
+            _ => Err(format!("unknown command `{}`", name)),
+        }
+    }
+
+    /// Runs the command, returning what it prints on success, or the errors it prints on
+    /// failure.
+    fn run(&self) -> Result<String, String> {
+        match self {
+            Self::Help => Ok(format!("{}\n", USAGE)),
+            Self::Check { template } => {
+                let compiled = build(template)?;
+
+                let mut output = String::new();
+                for warning in compiled.warnings() {
+                    output.push_str(&format!("{}: {}\n", template.display(), warning));
+                }
+                output.push_str(&format!("{}: ok\n", template.display()));
+
+                Ok(output)
+            }
+            Self::Render { template, params } => {
+                let compiled = build(template)?;
+                let params = match params {
+                    Some(path) => read_params(path)?,
+                    None => Value::Object(Map::new()),
+                };
+
+                compiled
+                    .render_html_string(&params)
+                    .map_err(|e| describe_error(template, &e))
+            }
+            Self::Params { template, json } => {
+                let parameters = build(template)?.parameters();
+
+                if *json {
+                    let schema: Vec<Value> = parameters.iter().map(parameter_json).collect();
+                    let schema = serde_json::to_string_pretty(&schema)
+                        .expect("parameters should serialize to JSON");
+
+                    return Ok(format!("{}\n", schema));
+                }
+
+                let mut output = String::new();
+                for p in &parameters {
+                    push_parameter_line(&mut output, p, "");
+                }
+
+                Ok(output)
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match command.run() {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(errors) => {
+            eprint!("{}", errors);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Compiles the template at `path`, loading included templates relative to its directory.
+fn build(path: &Path) -> Result<Template, String> {
+    let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    Balsa::from_file(Box::new(path.to_path_buf()))
+        .loader(FileLoader::new(root))
+        .build()
+        .map_err(|e| describe_error(path, &e))
+}
+
+/// Reads the parameters of a render from the JSON object in the file at `path`.
+fn read_params(path: &Path) -> Result<Value, String> {
+    let fail = |reason: String| format!("{}: {}\n", path.display(), reason);
+
+    let raw = fs::read_to_string(path).map_err(|e| fail(e.to_string()))?;
+    let params: Value = serde_json::from_str(&raw).map_err(|e| fail(e.to_string()))?;
+
+    match params {
+        Value::Object(_) => Ok(params),
+        _ => Err(fail("parameters must be a JSON object".to_string())),
+    }
+}
+
+/// Describes `error` with one line for each error, prefixed with the path of the template and
+/// the error's code, e.g. `page.html: error[B0208]: ... at line 1, column 5`.
+fn describe_error(path: &Path, error: &BalsaError) -> String {
+    let line = |code: &str, message: &dyn std::fmt::Display| {
+        format!("{}: error[{}]: {}\n", path.display(), code, message)
+    };
+
+    // The messages of compile and render errors are printed without their `compile error:`
+    // prefix, which the code already gives.
+    match error {
+        BalsaError::MultipleCompileErrors(errors) => {
+            errors.iter().map(|e| line(e.code(), e)).collect()
+        }
+        BalsaError::CompileError(e) => line(e.code(), e),
+        BalsaError::RenderError(e) => line(e.code(), e),
+        e => line(e.code(), e),
+    }
+}
+
+/// Appends a line describing the parameter `p`, followed by a line for each of its fields
+/// indented below it.
+fn push_parameter_line(output: &mut String, p: &ParameterInfo, indent: &str) {
+    output.push_str(&format!("{}{}: {}", indent, p.name, p.parameter_type));
+    if let Some(default_value) = &p.default_value {
+        output.push_str(&format!(" = {}", default_value));
+    }
+    if p.optional {
+        output.push_str(" (optional)");
+    }
+    output.push('\n');
+
+    for field in &p.fields {
+        push_parameter_line(output, field, &format!("{}  ", indent));
+    }
+}
+
+/// Describes the parameter `p` as a JSON object, with keys named after the options of parameter
+/// blocks.
+fn parameter_json(p: &ParameterInfo) -> Value {
+    json!({
+        "name": p.name,
+        "type": p.parameter_type.to_string(),
+        "defaultValue": p.default_value.as_ref().map_or(Value::Null, value_json),
+        "optional": p.optional,
+        "placeholder": p.placeholder,
+        "escape": p.escaped,
+        "direction": p.direction.map(|d| d.to_string()),
+        "minItems": p.min_items,
+        "maxItems": p.max_items,
+        "itemLabel": p.item_label,
+        "accept": p.accept,
+        "maxWidth": p.max_width,
+        "maxHeight": p.max_height,
+        "oneOf": p.one_of.iter().map(value_json).collect::<Vec<Value>>(),
+        "format": p.format,
+        "safe": p.safe,
+        "filters": p.filters,
+        "options": p
+            .options
+            .iter()
+            .map(|(name, value)| (name.clone(), value_json(value)))
+            .collect::<Map<String, Value>>(),
+        "fields": p.fields.iter().map(parameter_json).collect::<Vec<Value>>(),
+    })
+}
+
+/// Converts `value` to the JSON value it would be passed to a render as. Values without a JSON
+/// equivalent, such as currencies, are written as they would be rendered.
+fn value_json(value: &BalsaValue) -> Value {
+    match value {
+        BalsaValue::String(s)
+        | BalsaValue::Color(s)
+        | BalsaValue::MessageFormat(s)
+        | BalsaValue::Url(s) => Value::from(s.as_str()),
+        BalsaValue::Integer(n) => Value::from(*n),
+        BalsaValue::Float(n) => Value::from(*n),
+        BalsaValue::Boolean(b) => Value::from(*b),
+        BalsaValue::Array(items) => items.into_iter().map(value_json).collect(),
+        BalsaValue::Dictionary(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), value_json(value)))
+                .collect(),
+        ),
+        BalsaValue::Null => Value::Null,
+        value => Value::from(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(&["check", "page.html"]),
+            Ok(Command::Check {
+                template: PathBuf::from("page.html")
+            })
+        );
+        assert_eq!(
+            parse(&["render", "page.html", "--params", "params.json"]),
+            Ok(Command::Render {
+                template: PathBuf::from("page.html"),
+                params: Some(PathBuf::from("params.json"))
+            })
+        );
+        assert_eq!(
+            parse(&["params", "--json", "page.html"]),
+            Ok(Command::Params {
+                template: PathBuf::from("page.html"),
+                json: true
+            })
+        );
+        assert_eq!(parse(&["--help"]), Ok(Command::Help));
+
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["check"]).is_err());
+        assert!(parse(&["check", "page.html", "--json"]).is_err());
+        assert!(parse(&["render", "page.html", "--params"]).is_err());
+        assert!(parse(&["lint", "page.html"]).is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("balsa-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let page = dir.join("page.html");
+        let broken = dir.join("broken.html");
+        let params = dir.join("params.json");
+        fs::write(
+            &page,
+            "<h1>{{ title : string }}</h1>{{ views : int, defaultValue: 0 }}",
+        )
+        .unwrap();
+        fs::write(&broken, "<h1>{{ title : string, bogus: 1 }}</h1>").unwrap();
+        fs::write(&params, r#"{ "title": "Home" }"#).unwrap();
+
+        let check = Command::Check {
+            template: page.clone(),
+        }
+        .run();
+        let failed = Command::Check {
+            template: broken.clone(),
+        }
+        .run();
+        let rendered = Command::Render {
+            template: page.clone(),
+            params: Some(params),
+        }
+        .run();
+        let missing = Command::Render {
+            template: page.clone(),
+            params: None,
+        }
+        .run();
+        let listed = Command::Params {
+            template: page.clone(),
+            json: false,
+        }
+        .run();
+        let schema = Command::Params {
+            template: page.clone(),
+            json: true,
+        }
+        .run();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(check, Ok(format!("{}: ok\n", page.display())));
+        assert_eq!(
+            failed,
+            Err(format!(
+                "{}: error[B0206]: invalid or unknown parameter `bogus` provided at line 1, column 5\n",
+                broken.display()
+            ))
+        );
+        assert_eq!(rendered, Ok("<h1>Home</h1>0".to_string()));
+        assert!(missing.is_err_and(|e| e.contains("error[B0401]")));
+        assert_eq!(listed, Ok("title: string\nviews: int = 0\n".to_string()));
+
+        let schema: Value = serde_json::from_str(&schema.unwrap()).unwrap();
+        assert_eq!(schema[0]["name"], "title");
+        assert_eq!(schema[0]["type"], "string");
+        assert_eq!(schema[1]["defaultValue"], 0);
+    }
+}
@@ -0,0 +1,114 @@
+//! Measures the allocations made while parsing and compiling templates of increasing size, along
+//! with how long it takes. The grammar is built once per thread, after which parsing builds owned
+//! `String`s and `Vec`s for every token, so this is the cost an arena for the parse and compile
+//! phases would have to win back.
+//!
+//! Run with `cargo bench -p balsa --bench compile_allocations`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use balsa::Balsa;
+
+/// The number of times each template is compiled to time it.
+const RUNS: u32 = 20;
+
+/// The system allocator, counting every allocation and the bytes allocated.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Makes a page with `sections` sections like those of a theme's templates, each with a few
+/// parameter and helper blocks.
+fn page(sections: usize) -> String {
+    let mut page = String::from("{{@ brandColor : color = #ff0000 }}\n");
+    for i in 0..sections {
+        page.push_str(&format!(
+            concat!(
+                "<section style=\"color: {{{{ accent{i} : color, defaultValue: brandColor }}}}\">\n",
+                "  <h2>{{{{ title{i} : string, defaultValue: \"Section {i}\" }}}}</h2>\n",
+                "  <p>{{{{ body{i} : string }}}}</p>\n",
+                "  <p>{{{{ count{i} : int, defaultValue: 0 }}}} ",
+                "{{{{plural count{i} \"item\" \"items\"}}}}</p>\n",
+                "</section>\n",
+            ),
+            i = i
+        ));
+    }
+
+    page
+}
+
+fn main() {
+    // The grammar is built by the first compile on a thread and reused after that, so it's
+    // measured on its own rather than as part of the first page.
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    Balsa::from_string("")
+        .build()
+        .expect("empty template should compile");
+    println!(
+        "building the grammar: {} allocations\n",
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations
+    );
+
+    println!(
+        "{:>8} {:>8} {:>12} {:>10} {:>12} {:>12}",
+        "sections", "blocks", "allocations", "per block", "bytes", "time"
+    );
+
+    for sections in [10, 100, 1000] {
+        let raw_template = page(sections);
+        let blocks = raw_template.matches("{{").count();
+
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+        let template = Balsa::from_string(raw_template.as_str())
+            .build()
+            .expect("page should compile");
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+        drop(template);
+
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            black_box(Balsa::from_string(black_box(raw_template.as_str())).build()).ok();
+        }
+        let elapsed = start.elapsed() / RUNS;
+
+        println!(
+            "{:>8} {:>8} {:>12} {:>10} {:>12} {:>12?}",
+            sections,
+            blocks,
+            allocations,
+            allocations / blocks,
+            bytes,
+            elapsed
+        );
+    }
+}
@@ -1,195 +1,2186 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::{
-    balsa_parser::{BalsaToken, Block, Declaration, ParameterBlockIntermediate},
-    errors::{BalsaCompileError, BalsaError, TemplateErrorContext},
-    parameter_names, BalsaResult, BalsaType, BalsaValue,
+    balsa_parser::{
+        BalsaParser, BalsaToken, Block, ComputedDeclaration, ConditionIntermediate, Declaration,
+        EachIntermediate, Helper, HelperCall, MacroCall, MacroDefinition, MacroPart,
+        ParameterBlockIntermediate,
+    },
+    balsa_types::{lookup_path, path_root, BalsaExpression, PATH_SEPARATOR},
+    errors::{
+        BalsaCompileError, BalsaError, IncludeFailure, ParameterConflict, TemplateErrorContext,
+        UnbalancedConditional,
+    },
+    expression::Expression,
+    filters::Filters,
+    options::{CompileOptions, EscapePolicy},
+    parameter_names,
+    parser::Position,
+    Array, BalsaResult, BalsaType, BalsaValue, BalsaWarning, ExtractedMessage, Locale, PluralRule,
+    TextDirection,
 };
 
+#[cfg(feature = "chrono")]
+use crate::balsa_types::is_valid_format;
+
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct CompiledTemplate {
     pub(crate) global_scope: Scope,
     pub(crate) replacements: Vec<ReplacementInstruction>,
+    /// The static text of the template split around the replacements: the text preceding each
+    /// replacement in order, followed by the text after the last one. Rendering writes them out
+    /// in turn with the replacements, so the raw template isn't kept.
+    pub(crate) segments: Vec<String>,
+    /// The position of the end of the raw template the template was compiled from.
+    pub(crate) end_pos: Position,
+    pub(crate) warnings: Vec<BalsaWarning>,
+    /// How parameter values are escaped when rendering.
+    pub(crate) escape_policy: EscapePolicy,
+    /// Whether the output is rendered with `\r\n` line endings, which were converted to `\n`
+    /// before the template was parsed.
+    pub(crate) restore_crlf: bool,
+    /// The computed parameters, in the order they were declared.
+    pub(crate) computed: Vec<ComputedDescription>,
+    /// The filters registered with [`CompileOptions::filter`], which aren't serialized and have to
+    /// be registered again with [`crate::Template::with_filter`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) filters: Filters,
+    /// The templates included in `#each` sections, by path, which are rendered for every item
+    /// rather than compiled into the section. Templates included by these are kept here as well.
+    pub(crate) partials: BTreeMap<String, CompiledTemplate>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Scope {
+    pub(crate) variables: HashMap<String, BalsaValue>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ReplacementInstruction {
+    pub(crate) start_pos: Position,
+    pub(crate) end_pos: Position,
+    pub(crate) replace_with: ReplaceWith,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum ReplaceWith {
+    Parameter(ParameterDescription),
+    /// One of several forms, chosen by the value of an integer parameter when rendering.
+    Plural(PluralDescription),
+    /// A `dir` attribute for the locale held by a string parameter when rendering.
+    Direction(ParameterDescription),
+    /// A message from the message catalog.
+    Message(MessageDescription),
+    /// Text which was resolved when the template was compiled, such as a chosen plural form.
+    Text(String),
+    /// Markup which was resolved when the template was compiled, which is never escaped.
+    Markup(String),
+    /// The HTML the application fills the named slot with when rendering, which is never
+    /// escaped.
+    Slot(String),
+    /// A section repeated for every item of an array of dictionaries.
+    Each(EachDescription),
+    /// The start of a conditional section, whose text and replacements are rendered only if the
+    /// `bool` parameter is `true`.
+    If(ParameterDescription),
+    /// The start of the part of a conditional section which is rendered only if its condition
+    /// is `false`.
+    Else,
+    /// The end of a conditional section.
+    EndIf,
+    Nothing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ParameterDescription {
+    pub(crate) variable_name: String,
+    pub(crate) variable_type: BalsaType,
+    pub(crate) default_value: Option<BalsaValue>,
+    /// The direction the value of the parameter is written in, for editors to display it with.
+    pub(crate) direction: Option<TextDirection>,
+    /// Whether the value of the parameter is escaped according to the template's
+    /// [`EscapePolicy`]. Trusted parameters opt out with `escape: false`.
+    pub(crate) escape: bool,
+    /// The limits on the number of items of an array parameter, for editors to display an
+    /// editor which adds and removes items with.
+    pub(crate) items: ItemsDescription,
+    /// The constraints on the file of an image parameter, for editors to check uploads with.
+    pub(crate) image: ImageDescription,
+    /// The values the parameter may have, given with `oneOf`, for editors to offer as a
+    /// dropdown. Any value is allowed if empty.
+    pub(crate) one_of: Vec<BalsaValue>,
+    /// The `strftime`-style format the value of a date-time parameter is written with, given
+    /// with `format`. Date-times are written in RFC 3339 format if `None`.
+    pub(crate) format: Option<String>,
+    /// Whether the value of a URL parameter must be safe to link to, given with `safe: true`,
+    /// which rejects URLs such as `javascript:alert(1)` when rendering.
+    pub(crate) safe: bool,
+    /// Whether the parameter may be left out of a render, given with `optional: true`, in which
+    /// case its value is [`BalsaValue::Null`].
+    pub(crate) optional: bool,
+    /// The text written in place of an optional parameter which was left out, given with
+    /// `placeholder`. Nothing is written if `None`.
+    pub(crate) placeholder: Option<String>,
+    /// The names of the filters registered with [`CompileOptions::filter`] which the value of
+    /// the parameter is passed through before it is written, in order, given with `filters`.
+    pub(crate) filters: Vec<String>,
+    /// The options allowed with [`CompileOptions::allow_parameter_option`] which the block was
+    /// given, along with their values, in the order they were given.
+    pub(crate) custom_options: Vec<(String, BalsaValue)>,
+    /// The block the parameter was described in, exactly as it was written in the template.
+    pub(crate) source: String,
+    /// The position where the block starts.
+    pub(crate) source_start_pos: Position,
+    /// The position where the block ends.
+    pub(crate) source_end_pos: Position,
+}
+
+/// The `minItems`, `maxItems` and `itemLabel` options of an array parameter.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ItemsDescription {
+    /// The fewest items the array may have.
+    pub(crate) min_items: Option<usize>,
+    /// The most items the array may have.
+    pub(crate) max_items: Option<usize>,
+    /// What a single item is called, i.e. `"Post"`, for editors to label items with.
+    pub(crate) item_label: Option<String>,
+}
+
+/// The `accept`, `maxWidth` and `maxHeight` options of an image parameter. They are only
+/// metadata for editors, and aren't checked when rendering.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ImageDescription {
+    /// The MIME types the image may have, i.e. `image/png`. Any type is accepted if empty.
+    pub(crate) accept: Vec<String>,
+    /// The widest the image may be, in pixels.
+    pub(crate) max_width: Option<u32>,
+    /// The tallest the image may be, in pixels.
+    pub(crate) max_height: Option<u32>,
+}
+
+/// A parameter which is computed from the parameters passed to a render, such as
+/// `{{@computed readingTime: int = wordCount / 200 }}`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ComputedDescription {
+    pub(crate) name: String,
+    /// Either [`BalsaType::Integer`] or [`BalsaType::Float`].
+    pub(crate) variable_type: BalsaType,
+    /// The expression the parameter is computed from, in which declared variables have been
+    /// replaced with their values.
+    pub(crate) expression: Expression,
+    /// The expression exactly as it was written in the template.
+    pub(crate) source: String,
+    /// The position where the block starts.
+    pub(crate) source_start_pos: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct PluralDescription {
+    /// The integer parameter holding the count.
+    pub(crate) parameter: ParameterDescription,
+    /// The rule which chooses a form for the count.
+    pub(crate) rule: PluralRule,
+    /// The forms to choose between, in the order expected by `rule`.
+    pub(crate) forms: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct EachDescription {
+    /// The array parameter holding the items, which defaults to an empty array.
+    pub(crate) parameter: ParameterDescription,
+    /// The parts rendered for every item, in order.
+    pub(crate) body: Vec<EachPart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum EachPart {
+    /// Template text, which is never escaped.
+    Markup(String),
+    /// A value of the item, described like a parameter.
+    Field(Box<ParameterDescription>),
+    /// A template rendered for the item, i.e. `{{include "menu.html" items=children}}`.
+    Include(PartialDescription),
+}
+
+/// An `include` block in the body of an `#each` section.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct PartialDescription {
+    /// The path of the included template, which is kept in [`CompiledTemplate::partials`].
+    pub(crate) path: String,
+    /// The parameters of the included template bound by name, in the order they were given.
+    pub(crate) arguments: Vec<(String, PartialArgument)>,
+}
+
+/// What a parameter of a template included in an `#each` section is bound to.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum PartialArgument {
+    /// A value of the item, i.e. `children`, which leaves the parameter to its default value if
+    /// the item doesn't have it.
+    Field(String),
+    /// A value known when compiling.
+    Value(BalsaValue),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct MessageDescription {
+    /// The ID of the message.
+    pub(crate) key: String,
+    /// The translated message, or `None` if it is missing from the catalog and the key is
+    /// rendered in its place.
+    pub(crate) text: Option<String>,
+}
+
+impl ReplaceWith {
+    /// Returns the parameter the replacement is rendered from, if any.
+    pub(crate) fn parameter(&self) -> Option<&ParameterDescription> {
+        match self {
+            Self::Parameter(p) => Some(p),
+            Self::Plural(p) => Some(&p.parameter),
+            Self::Each(e) => Some(&e.parameter),
+            Self::Direction(p) | Self::If(p) => Some(p),
+            Self::Message(_)
+            | Self::Text(_)
+            | Self::Markup(_)
+            | Self::Slot(_)
+            | Self::Else
+            | Self::EndIf
+            | Self::Nothing => None,
+        }
+    }
+
+    /// Returns a mutable reference to the parameter the replacement is rendered from, if any.
+    pub(crate) fn parameter_mut(&mut self) -> Option<&mut ParameterDescription> {
+        match self {
+            Self::Parameter(p) => Some(p),
+            Self::Plural(p) => Some(&mut p.parameter),
+            Self::Each(e) => Some(&mut e.parameter),
+            Self::Direction(p) | Self::If(p) => Some(p),
+            Self::Message(_)
+            | Self::Text(_)
+            | Self::Markup(_)
+            | Self::Slot(_)
+            | Self::Else
+            | Self::EndIf
+            | Self::Nothing => None,
+        }
+    }
+
+    /// Moves the positions of the blocks the replacement was described in to where they are in
+    /// an input which `base` is the position of the start of.
+    fn rebase(&mut self, base: Position) {
+        if let Self::Each(e) = self {
+            for part in &mut e.body {
+                if let EachPart::Field(p) = part {
+                    p.rebase(base);
+                }
+            }
+        }
+
+        if let Some(p) = self.parameter_mut() {
+            p.rebase(base);
+        }
+    }
+}
+
+impl ParameterDescription {
+    /// Moves the positions of the block the parameter was described in to where they are in an
+    /// input which `base` is the position of the start of.
+    fn rebase(&mut self, base: Position) {
+        self.source_start_pos = self.source_start_pos.rebase(base);
+        self.source_end_pos = self.source_end_pos.rebase(base);
+    }
+}
+
+impl CompiledTemplate {
+    /// Appends `other` to the template, as if the raw template of `other` directly followed the
+    /// raw template of this one.
+    ///
+    /// Variables were replaced with their values when each template was compiled, so
+    /// declarations never leak from one template into the other; the merged global scope keeps
+    /// the first value of each variable. A parameter used by both templates must have the same
+    /// type and default value, and a computed parameter declared by both must have the same type
+    /// and expression.
+    pub(crate) fn concat(&self, other: &CompiledTemplate) -> BalsaResult<Self> {
+        let end_pos = self.end_pos;
+        if self.escape_policy != other.escape_policy {
+            return Err(BalsaError::conflicting_escape_policy(
+                end_pos,
+                self.escape_policy,
+                other.escape_policy,
+            ));
+        }
+
+        let mut replacements = self.replacements.clone();
+        for replacement in &other.replacements {
+            let start_pos = replacement.start_pos.rebase(end_pos);
+            let mut replace_with = replacement.replace_with.clone();
+            replace_with.rebase(end_pos);
+
+            if let Some(p) = replace_with.parameter() {
+                self.check_composed_parameter(start_pos, p)?;
+            }
+
+            replacements.push(ReplacementInstruction {
+                start_pos,
+                end_pos: replacement.end_pos.rebase(end_pos),
+                replace_with,
+            });
+        }
+
+        let mut computed = self.computed.clone();
+        for c in &other.computed {
+            match self.computed(&c.name) {
+                Some(first)
+                    if first.variable_type == c.variable_type
+                        && first.expression == c.expression => {}
+                Some(_) => {
+                    return Err(BalsaError::duplicate_computed(
+                        c.source_start_pos.rebase(end_pos),
+                        c.name.clone(),
+                    ))
+                }
+                None => computed.push(ComputedDescription {
+                    source_start_pos: c.source_start_pos.rebase(end_pos),
+                    ..c.clone()
+                }),
+            }
+        }
+
+        let mut global_scope = self.global_scope.clone();
+        for (name, value) in &other.global_scope.variables {
+            global_scope
+                .variables
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        let warnings = self
+            .warnings
+            .iter()
+            .cloned()
+            .chain(other.warnings.iter().map(|w| w.clone().rebase(end_pos)))
+            .collect();
+
+        // The text after the last replacement of this template joins the text before the first
+        // replacement of `other`.
+        let mut segments = self.segments.clone();
+        let mut other_segments = other.segments.iter();
+        match (segments.last_mut(), other_segments.next()) {
+            (Some(last), Some(first)) => last.push_str(first),
+            (None, Some(first)) => segments.push(first.clone()),
+            (_, None) => {}
+        }
+        segments.extend(other_segments.cloned());
+
+        // Filters registered with both templates under the same name are taken from this one.
+        let mut filters = other.filters.clone();
+        filters.extend(&self.filters);
+
+        // As are included templates, which were loaded from the same path.
+        let mut partials = other.partials.clone();
+        partials.extend(self.partials.clone());
+
+        Ok(Self {
+            global_scope,
+            replacements,
+            segments,
+            end_pos: other.end_pos.rebase(end_pos),
+            warnings,
+            escape_policy: self.escape_policy,
+            restore_crlf: self.restore_crlf || other.restore_crlf,
+            computed,
+            filters,
+            partials,
+        })
+    }
+
+    /// Returns whether an `#each` section of the template includes the template at `path`.
+    fn includes_partial(&self, path: &str) -> bool {
+        self.replacements.iter().any(|r| match &r.replace_with {
+            ReplaceWith::Each(e) => e
+                .body
+                .iter()
+                .any(|part| matches!(part, EachPart::Include(i) if i.path == path)),
+            _ => false,
+        })
+    }
+
+    /// Fails with a [`BalsaError::conflicting_parameter`] error if `description`, used at `pos`
+    /// in a template appended to this one, conflicts with the first use of the parameter here.
+    fn check_composed_parameter(
+        &self,
+        pos: Position,
+        description: &ParameterDescription,
+    ) -> BalsaResult<()> {
+        let name = &description.variable_name;
+        let Some((first_pos, first)) = self.replacements.iter().find_map(|r| {
+            r.replace_with
+                .parameter()
+                .filter(|p| p.variable_name == *name)
+                .map(|p| (r.start_pos, p))
+        }) else {
+            return Ok(());
+        };
+
+        let conflict = if first.variable_type != description.variable_type {
+            ParameterConflict::Type {
+                first: first.variable_type.clone(),
+                conflicting: description.variable_type.clone(),
+            }
+        } else {
+            match (self.default_value(name), &description.default_value) {
+                (Some(a), Some(b)) if a != b => ParameterConflict::DefaultValue {
+                    first: a.clone(),
+                    conflicting: b.clone(),
+                },
+                _ => return Ok(()),
+            }
+        };
+
+        Err(BalsaError::conflicting_parameter(
+            pos,
+            name.clone(),
+            first_pos,
+            conflict,
+        ))
+    }
+
+    /// Fails with a [`BalsaError::missing_defaults`] error listing every parameter without a
+    /// default value, if there are any. Computed and optional parameters never need a default
+    /// value.
+    pub(crate) fn ensure_defaults(&self) -> BalsaResult<()> {
+        let mut first_pos = None;
+        let mut parameter_names: Vec<String> = Vec::new();
+
+        for replacement in &self.replacements {
+            if let Some(p) = replacement.replace_with.parameter() {
+                if p.default_value.is_some()
+                    || p.optional
+                    || parameter_names.contains(&p.variable_name)
+                    || self.computed(&p.variable_name).is_some()
+                {
+                    continue;
+                }
+
+                first_pos.get_or_insert(replacement.start_pos);
+                parameter_names.push(p.variable_name.clone());
+            }
+        }
+
+        match first_pos {
+            Some(pos) => Err(BalsaError::missing_defaults(pos, parameter_names)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the computed parameter named `name`, if there is one.
+    pub(crate) fn computed(&self, name: &str) -> Option<&ComputedDescription> {
+        self.computed.iter().find(|c| c.name == name)
+    }
+
+    /// Returns the default value given to the parameter `name` by any of its blocks.
+    pub(crate) fn default_value(&self, name: &str) -> Option<&BalsaValue> {
+        self.replacements
+            .iter()
+            .filter_map(|r| r.replace_with.parameter())
+            .find(|p| p.variable_name == name && p.default_value.is_some())
+            .and_then(|p| p.default_value.as_ref())
+    }
+
+    /// Returns every use of a message in the template, in order.
+    pub(crate) fn extract_messages(&self) -> Vec<ExtractedMessage> {
+        self.replacements
+            .iter()
+            .filter_map(|replacement| match &replacement.replace_with {
+                ReplaceWith::Message(m) => Some(ExtractedMessage {
+                    key: m.key.clone(),
+                    translation: m.text.clone(),
+                    line: replacement.start_pos.line,
+                    column: replacement.start_pos.column,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the static text after the last replacement.
+    pub(crate) fn trailing(&self) -> &str {
+        self.segments.last().map_or("", String::as_str)
+    }
+
+    /// Returns a copy of the template in which every parameter named `name` defaults to `value`.
+    ///
+    /// Fails if `value` can't be cast to the type of the parameter.
+    pub(crate) fn with_default(&self, name: &str, value: &BalsaValue) -> BalsaResult<Self> {
+        let mut compiled_template = self.clone();
+
+        for replacement in &mut compiled_template.replacements {
+            if let Some(p) = replacement.replace_with.parameter_mut() {
+                if p.variable_name != name {
+                    continue;
+                }
+
+                let default_value = value.try_cast(p.variable_type.clone()).map_err(|_| {
+                    BalsaError::invalid_parameter_type(
+                        replacement.start_pos,
+                        p.variable_name.clone(),
+                        value.clone(),
+                        value.get_type(),
+                        p.variable_type.clone(),
+                    )
+                })?;
+
+                p.default_value = Some(default_value);
+            }
+        }
+
+        Ok(compiled_template)
+    }
+}
+
+/// What a parameter of a macro or an included template is bound to by the arguments of a call
+/// to the macro or of an `include` block.
+#[derive(Debug, Clone, PartialEq)]
+enum MacroArgument {
+    /// A value known when compiling, which is written in place of the parameter.
+    Value(BalsaValue),
+    /// A parameter passed to the render, which the macro's parameter is renamed to.
+    Parameter(String),
+}
+
+/// Returns the `dir` attribute for text written in `direction`.
+pub(crate) fn direction_attribute(direction: TextDirection) -> String {
+    format!("dir=\"{}\"", direction)
+}
+
+/// Struct which provides compiler methods.
+pub(crate) struct Compiler<'a> {
+    pub(crate) options: &'a CompileOptions,
+    pub(crate) global_scope: Scope,
+    pub(crate) replacements: Vec<ReplacementInstruction>,
+    /// The first description of each parameter used so far, along with its position.
+    pub(crate) parameters: HashMap<String, (Position, ParameterDescription)>,
+    /// Every declared variable in the order they were declared, along with their positions.
+    pub(crate) declarations: Vec<(String, Position)>,
+    /// The index into `declarations` that each variable in the global scope is currently bound
+    /// to. Later declarations of the same variable shadow earlier ones.
+    pub(crate) bindings: HashMap<String, usize>,
+    /// The indices of the declarations which have been referenced.
+    pub(crate) used_declarations: HashSet<usize>,
+    /// The position of the first declaration of every variable in the template, used to report
+    /// variables which are referenced before they are declared.
+    pub(crate) upcoming_declarations: HashMap<String, Position>,
+    /// The computed parameters declared so far.
+    pub(crate) computed: Vec<ComputedDescription>,
+    /// The macros defined so far.
+    pub(crate) macros: HashMap<String, MacroDefinition>,
+    /// The positions of the `if` blocks which haven't been closed yet, innermost last, along
+    /// with whether each has had an `else` block.
+    pub(crate) conditionals: Vec<(Position, bool)>,
+    /// The paths of the included templates being compiled, outermost first, which a template
+    /// may not include again outside of an `#each` section.
+    pub(crate) includes: Vec<String>,
+    /// The templates included in `#each` sections so far, by path.
+    pub(crate) partials: BTreeMap<String, CompiledTemplate>,
 }
 
-#[derive(Debug, Default, Clone, PartialEq)]
-pub(crate) struct Scope {
-    pub(crate) variables: HashMap<String, BalsaValue>,
-}
+impl<'a> Compiler<'a> {
+    /// Compiles a template from a list of tokens/AST the parser produced from `raw_template`
+    /// with the provided `options`.
+    pub(crate) fn compile_from_tokens(
+        raw_template: &str,
+        tokens: &[BalsaToken],
+        options: &'a CompileOptions,
+    ) -> BalsaResult<CompiledTemplate> {
+        Self::compile_included(raw_template, tokens, options, Vec::new())
+    }
+
+    /// Compiles a template which was included through the templates at `includes`, outermost
+    /// first.
+    fn compile_included(
+        raw_template: &str,
+        tokens: &[BalsaToken],
+        options: &'a CompileOptions,
+        includes: Vec<String>,
+    ) -> BalsaResult<CompiledTemplate> {
+        let mut compiler = Self {
+            options,
+            global_scope: Scope {
+                variables: options.globals.clone(),
+            },
+            replacements: Vec::new(),
+            parameters: HashMap::new(),
+            declarations: Vec::new(),
+            bindings: HashMap::new(),
+            used_declarations: HashSet::new(),
+            upcoming_declarations: Self::find_declarations(tokens),
+            computed: Vec::new(),
+            macros: HashMap::new(),
+            conditionals: Vec::new(),
+            includes,
+            partials: BTreeMap::new(),
+        };
+
+        // Every block is compiled even after one fails, so that all of the template's errors are
+        // reported at once. Variables whose declaration failed aren't reported again wherever
+        // they're used.
+        let mut errors = Vec::new();
+        let mut failed_declarations = HashSet::new();
+        for token in tokens {
+            let result = match token {
+                BalsaToken::ParameterBlock(p) => compiler.parse_param_block(p),
+                BalsaToken::DeclarationBlock(d) => compiler.parse_dec_block(d),
+                BalsaToken::ComputedBlock(c) => compiler.parse_computed_block(c),
+                BalsaToken::HelperBlock(h) => compiler.parse_helper_block(h),
+                BalsaToken::MacroBlock(m) => compiler.parse_macro_block(m),
+                BalsaToken::CallBlock(c) => compiler.parse_call_block(c),
+                BalsaToken::EachBlock(e) => compiler.parse_each_block(e),
+                BalsaToken::IfBlock(c) => compiler.parse_if_block(c),
+                BalsaToken::ElseBlock(b) => compiler.parse_else_block(b),
+                BalsaToken::EndIfBlock(b) => compiler.parse_end_if_block(b),
+                BalsaToken::SlotBlock(s) => compiler.parse_slot_block(s),
+                BalsaToken::SubstitutionBlock(s) => compiler.parse_substitution_block(s),
+                BalsaToken::RawBlock(r) => {
+                    compiler.replacements.push(ReplacementInstruction {
+                        start_pos: r.start_pos,
+                        end_pos: r.end_pos,
+                        replace_with: ReplaceWith::Markup(r.token.clone()),
+                    });
+
+                    Ok(())
+                }
+            };
+
+            match result {
+                Ok(()) => {}
+                Err(BalsaError::CompileError(e)) => {
+                    if let BalsaToken::DeclarationBlock(d) = token {
+                        failed_declarations
+                            .extend(d.token.iter().filter_map(|d| d.identifier.as_identifier()));
+                    }
+
+                    if !Self::is_caused_by_failed_declaration(&e, &failed_declarations) {
+                        errors.push(e);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some((pos, _)) = compiler.conditionals.last() {
+            errors.push(BalsaCompileError::UnbalancedConditional(
+                TemplateErrorContext::new(*pos, UnbalancedConditional::Unclosed),
+            ));
+        }
+
+        if !errors.is_empty() {
+            return Err(BalsaError::compile_errors(errors));
+        }
+
+        compiler.spread_defaults();
+
+        let warnings = compiler.eliminate_unused_declarations();
+        let replacements = Self::optimize_replacements(compiler.replacements)?;
+        let segments = Self::split_segments(raw_template, &replacements);
+
+        Ok(CompiledTemplate {
+            global_scope: compiler.global_scope,
+            replacements,
+            segments,
+            end_pos: Position::default().advance(raw_template),
+            warnings,
+            escape_policy: options.escape_policy,
+            restore_crlf: false,
+            computed: compiler.computed,
+            filters: options.filters.clone(),
+            partials: compiler.partials,
+        })
+    }
+
+    /// Sorts the replacements by position, ensures that none of them overlap, and merges
+    /// contiguous replacements where possible.
+    ///
+    /// A [`ReplaceWith::Nothing`] replacement which directly follows another replacement is
+    /// folded into it, as removing its characters can be done as part of the previous one.
+    pub(crate) fn optimize_replacements(
+        mut replacements: Vec<ReplacementInstruction>,
+    ) -> BalsaResult<Vec<ReplacementInstruction>> {
+        replacements.sort_by_key(|r| r.start_pos);
+
+        let mut optimized: Vec<ReplacementInstruction> = Vec::with_capacity(replacements.len());
+
+        for replacement in replacements {
+            if let Some(previous) = optimized.last_mut() {
+                if replacement.start_pos.offset < previous.end_pos.offset {
+                    return Err(BalsaError::overlapping_blocks(
+                        replacement.start_pos,
+                        previous.end_pos,
+                    ));
+                }
+
+                if replacement.start_pos.offset == previous.end_pos.offset
+                    && replacement.replace_with == ReplaceWith::Nothing
+                {
+                    previous.end_pos = replacement.end_pos;
+                    continue;
+                }
+            }
+
+            optimized.push(replacement);
+        }
+
+        Ok(optimized)
+    }
+
+    /// Splits the static text of `raw_template` around the (sorted) `replacements`, returning
+    /// the text preceding each replacement followed by the text after the last one.
+    pub(crate) fn split_segments(
+        raw_template: &str,
+        replacements: &[ReplacementInstruction],
+    ) -> Vec<String> {
+        let mut chars = raw_template.chars();
+        let mut offset = 0;
+        let mut segments = Vec::with_capacity(replacements.len() + 1);
+
+        for r in replacements {
+            segments.push((&mut chars).take(r.start_pos.offset - offset).collect());
+            (&mut chars)
+                .take(r.end_pos.offset - r.start_pos.offset)
+                .for_each(drop);
+            offset = r.end_pos.offset;
+        }
+
+        segments.push(chars.collect());
+
+        segments
+    }
+
+    /// Finds the position of the first declaration of every variable in the template.
+    fn find_declarations(tokens: &[BalsaToken]) -> HashMap<String, Position> {
+        let mut declarations = HashMap::new();
+
+        for token in tokens {
+            if let BalsaToken::DeclarationBlock(block) = token {
+                for declaration in &block.token {
+                    if let Some(identifier) = declaration.identifier.as_identifier() {
+                        declarations.entry(identifier).or_insert(block.start_pos);
+                    }
+                }
+            }
+        }
+
+        declarations
+    }
+
+    /// Removes every variable whose final declaration was never referenced from the global
+    /// scope, returning an [`BalsaWarning::UnusedDeclaration`] for every unreferenced
+    /// declaration. Globals provided through [`CompileOptions`] are always kept.
+    fn eliminate_unused_declarations(&mut self) -> Vec<BalsaWarning> {
+        let bindings = &self.bindings;
+        let used_declarations = &self.used_declarations;
+
+        self.global_scope.variables.retain(|identifier, _| {
+            bindings
+                .get(identifier)
+                .is_none_or(|index| used_declarations.contains(index))
+        });
+
+        self.declarations
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !used_declarations.contains(index))
+            .map(|(_, (identifier, pos))| {
+                BalsaWarning::unused_declaration(*pos, identifier.clone())
+            })
+            .collect()
+    }
+
+    fn parse_param_block(&mut self, block: &Block<ParameterBlockIntermediate>) -> BalsaResult<()> {
+        let param_description = self.describe_param_block(block)?;
+
+        self.check_parameter_consistency(block.start_pos, &param_description)?;
+
+        let instr = ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Parameter(param_description),
+        };
+
+        self.replacements.push(instr);
+
+        Ok(())
+    }
+
+    /// Checks the name, type and options of a parameter block and describes the parameter.
+    fn describe_param_block(
+        &mut self,
+        block: &Block<ParameterBlockIntermediate>,
+    ) -> BalsaResult<ParameterDescription> {
+        let i = block.token.variable_name.as_identifier().ok_or_else(|| {
+            BalsaError::invalid_identifier_in_parameter_block(
+                block.start_pos,
+                block.token.variable_name.clone(),
+            )
+        })?;
+
+        let type_ = block.token.variable_type.as_type().ok_or_else(|| {
+            BalsaError::invalid_type_expression(block.start_pos, block.token.variable_type.clone())
+        })?;
+
+        let mut param_description = ParameterDescription {
+            variable_name: i,
+            variable_type: type_.clone(),
+            default_value: None,
+            direction: None,
+            escape: true,
+            items: ItemsDescription::default(),
+            image: ImageDescription::default(),
+            one_of: Vec::new(),
+            format: None,
+            safe: false,
+            optional: false,
+            placeholder: None,
+            filters: Vec::new(),
+            custom_options: Vec::new(),
+            source: block.source.clone(),
+            source_start_pos: block.start_pos,
+            source_end_pos: block.end_pos,
+        };
+
+        // The `minItems` and `maxItems` of array parameters, which are checked once all of the
+        // options have been read.
+        let mut limits: (Option<i64>, Option<i64>) = (None, None);
+
+        if let Some(options) = &block.token.options {
+            let mut seen = HashSet::new();
+
+            for (key, value) in options {
+                if !seen.insert(key) {
+                    return Err(BalsaError::duplicate_option(block.start_pos, key.clone()));
+                }
+
+                match key.as_str() {
+                    // `true` and `false` are keywords for the default values of conditions.
+                    parameter_names::DEFAULT_VALUE if type_ == BalsaType::Boolean => {
+                        param_description.default_value = Some(BalsaValue::Boolean(
+                            self.resolve_bool(block.start_pos, key, value)?,
+                        ));
+                    }
+                    parameter_names::DEFAULT_VALUE => {
+                        let default_value = self.resolve_value(block.start_pos, value)?;
+                        let default_value =
+                            self.cast(block.start_pos, default_value, type_.clone())?;
+
+                        param_description.default_value = Some(default_value);
+                    }
+                    parameter_names::DIRECTION => {
+                        let direction = self.resolve_string(block.start_pos, value)?;
+
+                        param_description.direction =
+                            Some(TextDirection::from_attribute(&direction).ok_or_else(|| {
+                                BalsaError::invalid_direction(block.start_pos, direction)
+                            })?);
+                    }
+                    parameter_names::ESCAPE => {
+                        param_description.escape =
+                            self.resolve_bool(block.start_pos, key, value)?;
+
+                        if self.options.sandboxed && !param_description.escape {
+                            return Err(BalsaError::sandbox_violation(
+                                block.start_pos,
+                                "escape: false".to_string(),
+                            ));
+                        }
+                    }
+                    parameter_names::MIN_ITEMS | parameter_names::MAX_ITEMS
+                        if matches!(type_, BalsaType::Array(_)) =>
+                    {
+                        let value = self.resolve_value(block.start_pos, value)?;
+                        let count = match self.cast(block.start_pos, value, BalsaType::Integer)? {
+                            BalsaValue::Integer(n) => n,
+                            _ => unreachable!("value was cast to an integer"),
+                        };
+
+                        if key == parameter_names::MIN_ITEMS {
+                            limits.0 = Some(count);
+                        } else {
+                            limits.1 = Some(count);
+                        }
+                    }
+                    parameter_names::ACCEPT if type_ == BalsaType::Image => {
+                        param_description.image.accept = self
+                            .resolve_string(block.start_pos, value)?
+                            .split(',')
+                            .map(|mime_type| mime_type.trim().to_string())
+                            .filter(|mime_type| !mime_type.is_empty())
+                            .collect();
+                    }
+                    parameter_names::MAX_WIDTH | parameter_names::MAX_HEIGHT
+                        if type_ == BalsaType::Image =>
+                    {
+                        let resolved = self.resolve_value(block.start_pos, value)?;
+                        let pixels =
+                            match self.cast(block.start_pos, resolved, BalsaType::Integer)? {
+                                BalsaValue::Integer(n) => u32::try_from(n).map_err(|_| {
+                                    BalsaError::invalid_parameter(block.start_pos, key.clone())
+                                })?,
+                                _ => unreachable!("value was cast to an integer"),
+                            };
+
+                        if key == parameter_names::MAX_WIDTH {
+                            param_description.image.max_width = Some(pixels);
+                        } else {
+                            param_description.image.max_height = Some(pixels);
+                        }
+                    }
+                    parameter_names::ITEM_LABEL if matches!(type_, BalsaType::Array(_)) => {
+                        param_description.items.item_label =
+                            Some(self.resolve_string(block.start_pos, value)?);
+                    }
+                    parameter_names::ONE_OF
+                        if matches!(
+                            type_,
+                            BalsaType::String
+                                | BalsaType::Color
+                                | BalsaType::Integer
+                                | BalsaType::Float
+                        ) =>
+                    {
+                        let choices = self.resolve_value(block.start_pos, value)?;
+                        let choices = match self.cast(
+                            block.start_pos,
+                            choices,
+                            BalsaType::array_of(type_.clone()),
+                        )? {
+                            BalsaValue::Array(choices) if !choices.is_empty() => choices,
+                            _ => {
+                                return Err(BalsaError::invalid_parameter(
+                                    block.start_pos,
+                                    key.clone(),
+                                ))
+                            }
+                        };
+
+                        param_description.one_of = choices.into_iter().collect();
+                    }
+                    #[cfg(feature = "chrono")]
+                    parameter_names::FORMAT if type_ == BalsaType::DateTime => {
+                        let format = self.resolve_string(block.start_pos, value)?;
+                        if !is_valid_format(&format) {
+                            return Err(BalsaError::invalid_parameter(
+                                block.start_pos,
+                                key.clone(),
+                            ));
+                        }
+
+                        param_description.format = Some(format);
+                    }
+                    parameter_names::OPTIONAL => {
+                        param_description.optional =
+                            self.resolve_bool(block.start_pos, key, value)?;
+                    }
+                    parameter_names::PLACEHOLDER => {
+                        param_description.placeholder =
+                            Some(self.resolve_string(block.start_pos, value)?);
+                    }
+                    parameter_names::FILTERS => {
+                        param_description.filters = self.resolve_filters(block.start_pos, value)?;
+                    }
+                    parameter_names::SAFE if type_ == BalsaType::Url => {
+                        param_description.safe = self.resolve_bool(block.start_pos, key, value)?;
+                    }
+                    _ if self.options.allowed_parameter_options.contains(key) => {
+                        let value = self.resolve_value(block.start_pos, value)?;
+                        param_description.custom_options.push((key.clone(), value));
+                    }
+                    _ => return Err(BalsaError::invalid_parameter(block.start_pos, key.clone())),
+                }
+            }
+        }
+
+        // Placeholders are only written in place of optional parameters.
+        if param_description.placeholder.is_some() && !param_description.optional {
+            return Err(BalsaError::invalid_parameter(
+                block.start_pos,
+                parameter_names::PLACEHOLDER.to_string(),
+            ));
+        }
+
+        let (min_items, max_items) = limits;
+        let invalid = || {
+            BalsaError::invalid_item_limits(
+                block.start_pos,
+                param_description.variable_name.clone(),
+                min_items,
+                max_items,
+            )
+        };
+        let to_count = |limit: Option<i64>| limit.map(usize::try_from).transpose();
+
+        param_description.items.min_items = to_count(min_items).map_err(|_| invalid())?;
+        param_description.items.max_items = to_count(max_items).map_err(|_| invalid())?;
+        if let (Some(min), Some(max)) = (min_items, max_items) {
+            if min > max {
+                return Err(invalid());
+            }
+        }
+
+        Ok(param_description)
+    }
+
+    /// Compiles a section which is repeated for every item of an array of dictionaries.
+    ///
+    /// The section's parameter takes the same options as a parameter block. The parameter blocks
+    /// in the body describe the values of each item rather than parameters passed to the render.
+    /// A section which isn't passed to the render has no items.
+    fn parse_each_block(&mut self, block: &Block<EachIntermediate>) -> BalsaResult<()> {
+        let mut parameter = self.describe_param_block(&Block {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            source: block.source.clone(),
+            token: ParameterBlockIntermediate {
+                variable_name: block.token.variable_name.clone(),
+                variable_type: block.token.variable_type.clone(),
+                options: block.token.options.clone(),
+            },
+        })?;
+
+        let item_type = match &parameter.variable_type {
+            BalsaType::Array(t) if matches!(**t, BalsaType::Dictionary(_)) => (**t).clone(),
+            type_ => {
+                return Err(BalsaError::invalid_section_type(
+                    block.start_pos,
+                    parameter.variable_name,
+                    type_.clone(),
+                ))
+            }
+        };
+
+        parameter
+            .default_value
+            .get_or_insert_with(|| BalsaValue::Array(Array::new(item_type)));
+
+        let mut body = Vec::with_capacity(block.token.body.len());
+        for part in &block.token.body {
+            body.push(match part {
+                MacroPart::Text(text) => EachPart::Markup(text.clone()),
+                MacroPart::Block(token) => match token.as_ref() {
+                    BalsaToken::ParameterBlock(p) => {
+                        EachPart::Field(Box::new(self.describe_param_block(p)?))
+                    }
+                    BalsaToken::HelperBlock(h) => EachPart::Include(self.describe_partial(h)?),
+                    _ => unreachable!("`#each` bodies only contain parameter and include blocks"),
+                },
+            });
+        }
+
+        self.check_parameter_consistency(block.start_pos, &parameter)?;
+
+        self.replacements.push(ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Each(EachDescription { parameter, body }),
+        });
+
+        Ok(())
+    }
+
+    /// Compiles the start of a conditional section. The condition is a `bool` parameter, which
+    /// takes the same options as a parameter block.
+    fn parse_if_block(&mut self, block: &Block<ConditionIntermediate>) -> BalsaResult<()> {
+        let parameter = self.describe_param_block(&Block {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            source: block.source.clone(),
+            token: ParameterBlockIntermediate {
+                variable_name: block.token.variable_name.clone(),
+                variable_type: BalsaExpression::Type(BalsaType::Boolean),
+                options: block.token.options.clone(),
+            },
+        })?;
+
+        self.check_parameter_consistency(block.start_pos, &parameter)?;
+        self.conditionals.push((block.start_pos, false));
+
+        self.replacements.push(ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::If(parameter),
+        });
+
+        Ok(())
+    }
+
+    /// Compiles the `else` block of the innermost open conditional section.
+    fn parse_else_block(&mut self, block: &Block<()>) -> BalsaResult<()> {
+        let has_else = match self.conditionals.last_mut() {
+            Some((_, has_else)) => has_else,
+            None => {
+                return Err(BalsaError::unbalanced_conditional(
+                    block.start_pos,
+                    UnbalancedConditional::UnexpectedElse,
+                ))
+            }
+        };
+
+        if *has_else {
+            return Err(BalsaError::unbalanced_conditional(
+                block.start_pos,
+                UnbalancedConditional::DuplicateElse,
+            ));
+        }
+        *has_else = true;
+
+        self.replacements.push(ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Else,
+        });
+
+        Ok(())
+    }
+
+    /// Compiles the `endif` block closing the innermost open conditional section.
+    fn parse_end_if_block(&mut self, block: &Block<()>) -> BalsaResult<()> {
+        if self.conditionals.pop().is_none() {
+            return Err(BalsaError::unbalanced_conditional(
+                block.start_pos,
+                UnbalancedConditional::UnexpectedEndIf,
+            ));
+        }
+
+        self.replacements.push(ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::EndIf,
+        });
+
+        Ok(())
+    }
+
+    /// Returns whether `error` is only caused by a variable in `failed_declarations` not being
+    /// declared, as its declaration block failed to compile.
+    fn is_caused_by_failed_declaration(
+        error: &BalsaCompileError,
+        failed_declarations: &HashSet<String>,
+    ) -> bool {
+        let variable_name = match error {
+            BalsaCompileError::UndefinedVariable(e) => &e.error.variable_name,
+            BalsaCompileError::UsedBeforeDeclaration(e) => &e.error.variable_name,
+            _ => return false,
+        };
+
+        failed_declarations.contains(path_root(variable_name))
+    }
+
+    /// Compiles a `slot` block, which is replaced with the HTML the slot is filled with.
+    fn parse_slot_block(&mut self, block: &Block<BalsaExpression>) -> BalsaResult<()> {
+        let name = self.resolve_string(block.start_pos, &block.token)?;
+
+        self.replacements.push(ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Slot(name),
+        });
+
+        Ok(())
+    }
+
+    /// Compiles a substitution block, i.e. `{{ $brandColor }}`, which is replaced with the value
+    /// of the declared variable it names. The value is escaped like any other text.
+    fn parse_substitution_block(&mut self, block: &Block<String>) -> BalsaResult<()> {
+        let value = self.resolve_value(
+            block.start_pos,
+            &BalsaExpression::Identifier(block.token.clone()),
+        )?;
+
+        self.replacements.push(ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Text(Self::value_text(value)),
+        });
+
+        Ok(())
+    }
+
+    /// Returns the text `value` is written as when it is known at compile time, which is the
+    /// value itself for strings, colors and URLs.
+    fn value_text(value: BalsaValue) -> String {
+        match value {
+            BalsaValue::String(s) | BalsaValue::Color(s) | BalsaValue::Url(s) => s,
+            value => value.to_string(),
+        }
+    }
+
+    fn parse_dec_block(&mut self, block: &Block<Vec<Declaration>>) -> BalsaResult<()> {
+        for declaration in &block.token {
+            let identifier = declaration
+                .identifier
+                .as_identifier()
+                .filter(|identifier| !identifier.contains(PATH_SEPARATOR))
+                .ok_or_else(|| {
+                    BalsaError::invalid_identifier_in_declaration_block(
+                        block.start_pos,
+                        declaration.identifier.clone(),
+                    )
+                })?;
+
+            let type_ = declaration.variable_type.as_type().ok_or_else(|| {
+                BalsaError::invalid_type_expression(
+                    block.start_pos,
+                    declaration.variable_type.clone(),
+                )
+            })?;
+
+            let value = self.resolve_value(block.start_pos, &declaration.value)?;
+            let value = self.cast(block.start_pos, value, type_)?;
+
+            // Conditional declarations are still checked when their condition is false, so that
+            // mistakes don't go unnoticed until the condition changes.
+            if let Some(condition) = &declaration.condition {
+                if !self.resolve_bool(block.start_pos, "if", condition)? {
+                    continue;
+                }
+            }
+
+            self.bindings
+                .insert(identifier.clone(), self.declarations.len());
+            self.declarations
+                .push((identifier.clone(), block.start_pos));
+            self.global_scope.variables.insert(identifier, value);
+        }
+
+        let instr = ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Nothing,
+        };
+
+        self.replacements.push(instr);
+
+        Ok(())
+    }
+
+    fn parse_computed_block(&mut self, block: &Block<ComputedDeclaration>) -> BalsaResult<()> {
+        let name = block.token.identifier.as_identifier().ok_or_else(|| {
+            BalsaError::invalid_identifier_in_declaration_block(
+                block.start_pos,
+                block.token.identifier.clone(),
+            )
+        })?;
+
+        let type_ = block.token.variable_type.as_type().ok_or_else(|| {
+            BalsaError::invalid_type_expression(block.start_pos, block.token.variable_type.clone())
+        })?;
+
+        if !matches!(type_, BalsaType::Integer | BalsaType::Float) {
+            return Err(BalsaError::invalid_computed_type(
+                block.start_pos,
+                name,
+                type_,
+            ));
+        }
+
+        if self.computed.iter().any(|c| c.name == name) {
+            return Err(BalsaError::duplicate_computed(block.start_pos, name));
+        }
+
+        let expression = self.resolve_expression(block.start_pos, &block.token.expression)?;
+
+        self.computed.push(ComputedDescription {
+            name,
+            variable_type: type_,
+            expression,
+            source: block.token.expression_source.clone(),
+            source_start_pos: block.start_pos,
+        });
+
+        let instr = ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Nothing,
+        };
+
+        self.replacements.push(instr);
+
+        Ok(())
+    }
+
+    /// Resolves the names read by the `expression` of a computed parameter.
+    ///
+    /// Names of earlier computed parameters are kept, and declared variables are replaced with
+    /// their values, which must be numbers. Any other name is a parameter passed to the render.
+    fn resolve_expression(
+        &mut self,
+        pos: Position,
+        expression: &Expression,
+    ) -> BalsaResult<Expression> {
+        Ok(match expression {
+            Expression::Parameter(name) if self.computed.iter().any(|c| &c.name == name) => {
+                Expression::Computed(name.clone())
+            }
+            Expression::Parameter(name)
+                if self.global_scope.variables.contains_key(name)
+                    || self.upcoming_declarations.contains_key(name) =>
+            {
+                let value = self.resolve_value(pos, &BalsaExpression::Identifier(name.clone()))?;
+
+                self.value_expression(pos, value)?
+            }
+            Expression::Negate(operand) => {
+                Expression::Negate(Box::new(self.resolve_expression(pos, operand)?))
+            }
+            Expression::Chain { first, rest } => Expression::Chain {
+                first: Box::new(self.resolve_expression(pos, first)?),
+                rest: rest
+                    .iter()
+                    .map(|(operator, operand)| {
+                        Ok((*operator, self.resolve_expression(pos, operand)?))
+                    })
+                    .collect::<BalsaResult<_>>()?,
+            },
+            _ => expression.clone(),
+        })
+    }
+
+    /// Converts a `value` read by a computed parameter to a number. Values which aren't
+    /// integers are cast to floats.
+    fn value_expression(&self, pos: Position, value: BalsaValue) -> BalsaResult<Expression> {
+        match value {
+            BalsaValue::Integer(i) => Ok(Expression::Integer(i)),
+            value => match self.cast(pos, value, BalsaType::Float)? {
+                BalsaValue::Float(f) => Ok(Expression::Float(f)),
+                _ => unreachable!("value was cast to a float"),
+            },
+        }
+    }
+
+    fn parse_macro_block(&mut self, block: &Block<MacroDefinition>) -> BalsaResult<()> {
+        let name = block.token.name.clone();
+
+        if self.macros.contains_key(&name) {
+            return Err(BalsaError::duplicate_macro(block.start_pos, name));
+        }
+
+        self.macros.insert(name, block.token.clone());
+
+        let instr = ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with: ReplaceWith::Nothing,
+        };
+
+        self.replacements.push(instr);
+
+        Ok(())
+    }
+
+    /// Expands a call to a macro defined earlier in the template.
+    ///
+    /// Each part of the macro's body becomes a replacement of its own. The first replaces the
+    /// call block and the rest are inserted where it ends, so that the expansion renders like
+    /// any other blocks. Arguments naming variables which haven't been declared are parameters
+    /// passed to the render, while any other arguments are resolved right away.
+    fn parse_call_block(&mut self, block: &Block<MacroCall>) -> BalsaResult<()> {
+        let definition = self.macros.get(&block.token.name).cloned().ok_or_else(|| {
+            BalsaError::undefined_macro(block.start_pos, block.token.name.clone())
+        })?;
+
+        if definition.parameters.len() != block.token.arguments.len() {
+            return Err(BalsaError::invalid_helper_arguments(
+                block.start_pos,
+                definition.name,
+                definition.parameters.len(),
+                block.token.arguments.len(),
+            ));
+        }
+
+        let mut arguments = HashMap::new();
+        for (parameter, argument) in definition.parameters.iter().zip(&block.token.arguments) {
+            let argument = self.bind_argument(block.start_pos, argument)?;
+
+            arguments.insert(parameter.as_str(), argument);
+        }
+
+        let mut expansion = Vec::with_capacity(definition.body.len());
+        for part in &definition.body {
+            expansion.push(self.expand_macro_part(part, &arguments)?);
+        }
+
+        for (i, replace_with) in expansion.into_iter().enumerate() {
+            let start_pos = if i == 0 {
+                block.start_pos
+            } else {
+                block.end_pos
+            };
+
+            self.replacements.push(ReplacementInstruction {
+                start_pos,
+                end_pos: block.end_pos,
+                replace_with,
+            });
+        }
+
+        // A macro with an empty body still removes the call block.
+        if definition.body.is_empty() {
+            self.replacements.push(ReplacementInstruction {
+                start_pos: block.start_pos,
+                end_pos: block.end_pos,
+                replace_with: ReplaceWith::Nothing,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Binds an `argument` of a macro call or `include` block. Identifiers naming variables
+    /// which haven't been declared are parameters passed to the render, other than `true` and
+    /// `false`, while any other arguments are resolved right away.
+    fn bind_argument(
+        &mut self,
+        pos: Position,
+        argument: &BalsaExpression,
+    ) -> BalsaResult<MacroArgument> {
+        Ok(match argument {
+            BalsaExpression::Identifier(identifier)
+                if !self
+                    .global_scope
+                    .variables
+                    .contains_key(path_root(identifier))
+                    && !self
+                        .upcoming_declarations
+                        .contains_key(path_root(identifier)) =>
+            {
+                MacroArgument::Parameter(identifier.clone())
+            }
+            _ => MacroArgument::Value(self.resolve_value(pos, argument)?),
+        })
+    }
+
+    /// Compiles one `part` of a macro's body with its parameters bound to `arguments`.
+    fn expand_macro_part(
+        &mut self,
+        part: &MacroPart,
+        arguments: &HashMap<&str, MacroArgument>,
+    ) -> BalsaResult<ReplaceWith> {
+        let token = match part {
+            MacroPart::Text(text) => return Ok(ReplaceWith::Markup(text.clone())),
+            MacroPart::Block(token) => token,
+        };
+
+        let substitute = |expression: &BalsaExpression| match expression {
+            BalsaExpression::Identifier(identifier) => match arguments.get(identifier.as_str()) {
+                Some(MacroArgument::Value(value)) => BalsaExpression::Value(value.clone()),
+                Some(MacroArgument::Parameter(name)) => BalsaExpression::Identifier(name.clone()),
+                None => expression.clone(),
+            },
+            _ => expression.clone(),
+        };
+
+        match token.as_ref() {
+            BalsaToken::ParameterBlock(block) => {
+                let name = block.token.variable_name.as_identifier();
+
+                if let Some(MacroArgument::Value(value)) =
+                    name.as_deref().and_then(|name| arguments.get(name))
+                {
+                    let type_ = block.token.variable_type.as_type().ok_or_else(|| {
+                        BalsaError::invalid_type_expression(
+                            block.start_pos,
+                            block.token.variable_type.clone(),
+                        )
+                    })?;
+                    let text =
+                        Self::value_text(self.cast(block.start_pos, value.clone(), type_)?);
+
+                    // The other options only matter for values passed to the render.
+                    let escape = block
+                        .token
+                        .options
+                        .iter()
+                        .flatten()
+                        .find_map(|(key, value)| (key == parameter_names::ESCAPE).then_some(value));
+
+                    return match escape {
+                        Some(escape)
+                            if !self.resolve_bool(
+                                block.start_pos,
+                                parameter_names::ESCAPE,
+                                escape,
+                            )? =>
+                        {
+                            if self.options.sandboxed {
+                                return Err(BalsaError::sandbox_violation(
+                                    block.start_pos,
+                                    "escape: false".to_string(),
+                                ));
+                            }
+
+                            Ok(ReplaceWith::Markup(text))
+                        }
+                        _ => Ok(ReplaceWith::Text(text)),
+                    };
+                }
+
+                let mut block = block.clone();
+                block.token.variable_name = substitute(&block.token.variable_name);
+                if let Some(options) = &mut block.token.options {
+                    for (_, value) in options.iter_mut() {
+                        *value = substitute(value);
+                    }
+                }
+
+                self.parse_param_block(&block)?;
+            }
+            BalsaToken::HelperBlock(block) => {
+                let mut block = block.clone();
+                block.token.arguments = block.token.arguments.iter().map(substitute).collect();
+                for (_, argument) in &mut block.token.named_arguments {
+                    *argument = substitute(argument);
+                }
+
+                self.parse_helper_block(&block)?;
+            }
+            _ => unreachable!("macro bodies only contain parameter and helper blocks"),
+        }
+
+        Ok(self
+            .replacements
+            .pop()
+            .expect("compiling a block pushes a replacement")
+            .replace_with)
+    }
+
+    /// Gives every use of a parameter the default value it was given in any of its blocks, so that
+    /// a default value given once applies wherever the parameter is used, including in helper
+    /// blocks, which can't set one.
+    fn spread_defaults(&mut self) {
+        for replacement in &mut self.replacements {
+            let Some(p) = replacement.replace_with.parameter_mut() else {
+                continue;
+            };
+
+            if let Some((_, description)) = self.parameters.get(&p.variable_name) {
+                p.default_value = description.default_value.clone();
+            }
+        }
+    }
+
+    fn parse_helper_block(&mut self, block: &Block<HelperCall>) -> BalsaResult<()> {
+        match block.token.named_arguments.first() {
+            Some((name, _)) if block.token.helper != Helper::Include => {
+                return Err(BalsaError::invalid_parameter(block.start_pos, name.clone()))
+            }
+            _ => {}
+        }
+
+        let replace_with = match block.token.helper {
+            Helper::Include => return self.parse_include_block(block),
+            Helper::Translate => {
+                let key = match block.token.arguments.as_slice() {
+                    [key] => self.resolve_string(block.start_pos, key)?,
+                    arguments => {
+                        return Err(BalsaError::invalid_helper_arguments(
+                            block.start_pos,
+                            Helper::Translate.name().to_string(),
+                            1,
+                            arguments.len(),
+                        ))
+                    }
+                };
+                let text = self.options.catalog.get(&key).map(str::to_string);
+
+                if text.is_none() && !self.options.allow_missing_messages {
+                    return Err(BalsaError::missing_message(block.start_pos, key));
+                }
+
+                ReplaceWith::Message(MessageDescription { key, text })
+            }
+            Helper::Plural => self.parse_plural(block)?,
+            Helper::Direction => match block.token.arguments.as_slice() {
+                [BalsaExpression::Identifier(variable_name)] => {
+                    let parameter = self.helper_parameter(block, variable_name, BalsaType::String);
+                    self.check_parameter_consistency(block.start_pos, &parameter)?;
+
+                    ReplaceWith::Direction(parameter)
+                }
+                [locale] => {
+                    let locale = Locale::new(self.resolve_string(block.start_pos, locale)?);
+
+                    ReplaceWith::Markup(direction_attribute(locale.direction()))
+                }
+                arguments => {
+                    return Err(BalsaError::invalid_helper_arguments(
+                        block.start_pos,
+                        Helper::Direction.name().to_string(),
+                        1,
+                        arguments.len(),
+                    ))
+                }
+            },
+        };
+
+        let instr = ReplacementInstruction {
+            start_pos: block.start_pos,
+            end_pos: block.end_pos,
+            replace_with,
+        };
+
+        self.replacements.push(instr);
+
+        Ok(())
+    }
+
+    /// Compiles the template loaded for an `include` block and inserts its output in place of
+    /// the block. Its parameters are the parameters of this template, so they must be
+    /// consistent with the rest of the template.
+    ///
+    /// Named arguments bind parameters of the included template, i.e. `title=postTitle` renders
+    /// the included `title` parameter from this template's `postTitle` parameter. Arguments
+    /// which are values are written in place of the parameter, as for the arguments of a macro.
+    fn parse_include_block(&mut self, block: &Block<HelperCall>) -> BalsaResult<()> {
+        let path = self.include_path(block)?;
+
+        let mut arguments = HashMap::new();
+        for (name, argument) in &block.token.named_arguments {
+            let argument = self.bind_argument(block.start_pos, argument)?;
+
+            if arguments.insert(name.as_str(), argument).is_some() {
+                return Err(BalsaError::duplicate_option(block.start_pos, name.clone()));
+            }
+        }
+
+        if self.includes.contains(&path) {
+            let mut cycle = self.includes.clone();
+            cycle.push(path.clone());
+
+            return Err(BalsaError::include_failed(
+                block.start_pos,
+                path,
+                IncludeFailure::Cycle(cycle),
+            ));
+        }
+
+        let mut included = self.compile_include(block.start_pos, &path)?;
+        self.collect_partials(&path, &mut included);
+
+        for mut c in included.computed {
+            let mut bindings = HashMap::new();
+            for name in c.expression.inputs() {
+                let expression = match arguments.get(name) {
+                    Some(MacroArgument::Parameter(name)) => Expression::Parameter(name.clone()),
+                    Some(MacroArgument::Value(value)) => {
+                        self.value_expression(block.start_pos, value.clone())?
+                    }
+                    None => continue,
+                };
+
+                bindings.insert(name.to_string(), expression);
+            }
+            c.expression = c.expression.bind_parameters(&bindings);
+
+            match self.computed.iter().find(|first| first.name == c.name) {
+                Some(first)
+                    if first.variable_type == c.variable_type
+                        && first.expression == c.expression => {}
+                Some(_) => return Err(BalsaError::duplicate_computed(block.start_pos, c.name)),
+                None => self.computed.push(c),
+            }
+        }
+
+        // The text of the included template is written as markup, as it isn't part of this
+        // template's raw template.
+        let mut expansion = Vec::with_capacity(included.segments.len() * 2);
+        let mut segments = included.segments.into_iter();
+        for replacement in included.replacements {
+            let segment = segments.next().unwrap_or_default();
+            if !segment.is_empty() {
+                expansion.push(ReplaceWith::Markup(segment));
+            }
+
+            let replace_with =
+                self.bind_included(block.start_pos, replacement.replace_with, &arguments)?;
+
+            if let Some(p) = replace_with.parameter() {
+                self.check_parameter_consistency(block.start_pos, p)?;
+            }
+
+            if replace_with != ReplaceWith::Nothing {
+                expansion.push(replace_with);
+            }
+        }
+
+        expansion.extend(
+            segments
+                .filter(|segment| !segment.is_empty())
+                .map(ReplaceWith::Markup),
+        );
+
+        if expansion.is_empty() {
+            expansion.push(ReplaceWith::Nothing);
+        }
+
+        for (i, replace_with) in expansion.into_iter().enumerate() {
+            let start_pos = if i == 0 {
+                block.start_pos
+            } else {
+                block.end_pos
+            };
 
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) struct ReplacementInstruction {
-    pub(crate) start_pos: usize,
-    pub(crate) end_pos: usize,
-    pub(crate) replace_with: ReplaceWith,
-}
+            self.replacements.push(ReplacementInstruction {
+                start_pos,
+                end_pos: block.end_pos,
+                replace_with,
+            });
+        }
 
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) enum ReplaceWith {
-    Parameter(ParameterDescription),
-    Nothing,
-}
+        Ok(())
+    }
 
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) struct ParameterDescription {
-    pub(crate) variable_name: String,
-    pub(crate) variable_type: BalsaType,
-    pub(crate) default_value: Option<BalsaValue>,
-}
+    /// Describes an `include` block in the body of an `#each` section, whose template is
+    /// rendered for every item rather than compiled into the section.
+    ///
+    /// Named arguments bind parameters of the included template to values of the item, i.e.
+    /// `items=children`, or to values. The template is rendered with only these parameters, so
+    /// it can include itself to render tree-shaped items, which it is compiled once for.
+    fn describe_partial(&mut self, block: &Block<HelperCall>) -> BalsaResult<PartialDescription> {
+        let path = self.include_path(block)?;
 
-/// Struct which provides compiler methods.
-pub(crate) struct Compiler {
-    pub(crate) global_scope: Scope,
-    pub(crate) replacements: Vec<ReplacementInstruction>,
-}
+        let mut arguments: Vec<(String, PartialArgument)> = Vec::new();
+        for (name, argument) in &block.token.named_arguments {
+            if arguments.iter().any(|(bound, _)| bound == name) {
+                return Err(BalsaError::duplicate_option(block.start_pos, name.clone()));
+            }
 
-impl Compiler {
-    /// Compiles a template from a list of tokens/AST from the parser.
-    pub(crate) fn compile_from_tokens(tokens: &[BalsaToken]) -> BalsaResult<CompiledTemplate> {
-        let mut compiler = Self {
-            global_scope: Scope::default(),
-            replacements: Vec::new(),
-        };
+            let argument = match self.bind_argument(block.start_pos, argument)? {
+                MacroArgument::Parameter(field) => PartialArgument::Field(field),
+                MacroArgument::Value(value) => PartialArgument::Value(value),
+            };
+            arguments.push((name.clone(), argument));
+        }
 
-        for token in tokens {
-            match token {
-                BalsaToken::ParameterBlock(p) => compiler.parse_param_block(p)?,
-                BalsaToken::DeclarationBlock(d) => compiler.parse_dec_block(d)?,
+        // A template which is already being compiled is kept once its compilation finishes.
+        if !self.includes.contains(&path) && !self.partials.contains_key(&path) {
+            let mut included = self.compile_include(block.start_pos, &path)?;
+            self.collect_partials(&path, &mut included);
+            self.partials.insert(path.clone(), included);
+        }
+
+        Ok(PartialDescription { path, arguments })
+    }
+
+    /// Returns the path of the template an `include` block includes. Fails if the template is
+    /// sandboxed, as included templates are read through the loader.
+    fn include_path(&mut self, block: &Block<HelperCall>) -> BalsaResult<String> {
+        if self.options.sandboxed {
+            return Err(BalsaError::sandbox_violation(
+                block.start_pos,
+                Helper::Include.name().to_string(),
+            ));
+        }
+
+        match block.token.arguments.as_slice() {
+            [path] => self.resolve_string(block.start_pos, path),
+            arguments => Err(BalsaError::invalid_helper_arguments(
+                block.start_pos,
+                Helper::Include.name().to_string(),
+                1,
+                arguments.len(),
+            )),
+        }
+    }
+
+    /// Loads and compiles the template at `path`, included by the `include` block at `pos`.
+    fn compile_include(&self, pos: Position, path: &str) -> BalsaResult<CompiledTemplate> {
+        let fail = |reason| BalsaError::include_failed(pos, path.to_string(), reason);
+
+        if let Some(max_depth) = self.options.max_include_depth {
+            if self.includes.len() >= max_depth {
+                return Err(BalsaError::include_too_deep(pos, max_depth));
             }
         }
 
-        Ok(CompiledTemplate {
-            global_scope: compiler.global_scope,
-            replacements: compiler.replacements,
-        })
+        let loader = self
+            .options
+            .loader
+            .as_ref()
+            .ok_or_else(|| fail(IncludeFailure::NoLoader))?;
+        let raw_template = loader
+            .0
+            .load(path)
+            .map_err(|e| fail(IncludeFailure::LoadFailed(e.to_string())))?;
+        let (raw_template, _) = self.options.normalize_template(raw_template);
+
+        let mut includes = self.includes.clone();
+        includes.push(path.to_string());
+        BalsaParser::parse(&raw_template, self.options)
+            .and_then(|tokens| {
+                Self::compile_included(&raw_template, &tokens, self.options, includes)
+            })
+            .map_err(|e| match e {
+                BalsaError::CompileError(e) => fail(IncludeFailure::CompileFailed(Box::new(e))),
+                BalsaError::MultipleCompileErrors(mut errors) => {
+                    fail(IncludeFailure::CompileFailed(Box::new(errors.remove(0))))
+                }
+                e => e,
+            })
     }
 
-    fn parse_param_block(&mut self, block: &Block<ParameterBlockIntermediate>) -> BalsaResult<()> {
-        let i = block.token.variable_name.as_identifier().ok_or_else(|| {
-            BalsaError::invalid_identifier_in_parameter_block(
-                block.start_pos as usize,
-                block.token.variable_name.clone(),
-            )
-        })?;
+    /// Keeps the templates included in the `#each` sections of `included`, the template at
+    /// `path`, along with `included` itself if one of them includes it, as it wasn't compiled
+    /// again for them.
+    fn collect_partials(&mut self, path: &str, included: &mut CompiledTemplate) {
+        let partials = std::mem::take(&mut included.partials);
+        let is_recursive =
+            included.includes_partial(path) || partials.values().any(|p| p.includes_partial(path));
 
-        let type_ = block.token.variable_type.as_type().ok_or_else(|| {
-            BalsaError::invalid_type_expression(
-                block.start_pos as usize,
-                block.token.variable_type.clone(),
-            )
-        })?;
+        for (partial_path, partial) in partials {
+            self.partials.entry(partial_path).or_insert(partial);
+        }
 
-        let mut param_description = ParameterDescription {
-            variable_name: i,
-            variable_type: type_.clone(),
-            default_value: None,
+        if is_recursive {
+            self.partials
+                .entry(path.to_string())
+                .or_insert_with(|| included.clone());
+        }
+    }
+
+    /// Binds the parameter `replace_with` is rendered from in an included template to the
+    /// `arguments` of the `include` block at `pos`. Parameters bound to another parameter are
+    /// renamed, while those bound to a value are resolved right away.
+    fn bind_included(
+        &self,
+        pos: Position,
+        mut replace_with: ReplaceWith,
+        arguments: &HashMap<&str, MacroArgument>,
+    ) -> BalsaResult<ReplaceWith> {
+        let argument = match replace_with.parameter_mut() {
+            Some(p) => match arguments.get(p.variable_name.as_str()) {
+                Some(MacroArgument::Parameter(name)) => {
+                    p.variable_name = name.clone();
+                    return Ok(replace_with);
+                }
+                Some(MacroArgument::Value(value)) => value.clone(),
+                None => return Ok(replace_with),
+            },
+            None => return Ok(replace_with),
         };
 
-        if let Some(map) = &block.token.options {
-            for (key, value) in map {
-                match key.as_str() {
-                    parameter_names::DEFAULT_VALUE => {
-                        let default_value = value
-                            .as_value()
-                            .ok_or_else(|| {
-                                BalsaError::invalid_expression(
-                                    block.start_pos as usize,
-                                    value.clone(),
-                                )
-                            })?
-                            .try_cast(type_.clone())
-                            .map_err(|error| {
-                                BalsaError::new_compile_error(BalsaCompileError::InvalidTypeCast(
-                                    TemplateErrorContext {
-                                        pos: block.start_pos as usize,
-                                        error,
-                                    },
-                                ))
-                            })?;
+        Ok(match replace_with {
+            ReplaceWith::Parameter(p) => {
+                let text = Self::value_text(self.cast(pos, argument, p.variable_type)?);
 
-                        param_description.default_value = Some(default_value);
-                    }
-                    _ => {
-                        return Err(BalsaError::invalid_parameter(
-                            block.start_pos as usize,
-                            key.clone(),
-                        ))
-                    }
+                if p.escape {
+                    ReplaceWith::Text(text)
+                } else {
+                    ReplaceWith::Markup(text)
+                }
+            }
+            ReplaceWith::Plural(p) => match self.cast(pos, argument, BalsaType::Integer)? {
+                BalsaValue::Integer(n) => ReplaceWith::Text(p.forms[p.rule.form_index(n)].clone()),
+                _ => unreachable!("value was cast to an integer"),
+            },
+            ReplaceWith::Direction(_) => match self.cast(pos, argument, BalsaType::String)? {
+                BalsaValue::String(locale) => {
+                    ReplaceWith::Markup(direction_attribute(Locale::new(locale).direction()))
                 }
+                _ => unreachable!("value was cast to a string"),
+            },
+            // Sections need a parameter to be rendered from.
+            _ => {
+                return Err(BalsaError::invalid_identifier_in_parameter_block(
+                    pos,
+                    BalsaExpression::Value(argument),
+                ))
             }
+        })
+    }
+
+    /// Describes the parameter `variable_name` of type `variable_type` which is passed to a
+    /// helper block. Its default value is set once the whole template has been compiled.
+    fn helper_parameter(
+        &self,
+        block: &Block<HelperCall>,
+        variable_name: &str,
+        variable_type: BalsaType,
+    ) -> ParameterDescription {
+        ParameterDescription {
+            variable_name: variable_name.to_string(),
+            variable_type,
+            default_value: None,
+            direction: None,
+            escape: true,
+            items: ItemsDescription::default(),
+            image: ImageDescription::default(),
+            one_of: Vec::new(),
+            format: None,
+            safe: false,
+            optional: false,
+            placeholder: None,
+            filters: Vec::new(),
+            custom_options: Vec::new(),
+            source: block.source.clone(),
+            source_start_pos: block.start_pos,
+            source_end_pos: block.end_pos,
         }
+    }
 
-        let instr = ReplacementInstruction {
-            start_pos: block.start_pos as usize,
-            end_pos: block.end_pos as usize,
-            replace_with: ReplaceWith::Parameter(param_description),
+    /// Compiles a `plural` helper block, i.e. a count followed by one form for each case of the
+    /// configured [`PluralRule`].
+    ///
+    /// A count given as an identifier names an integer parameter, and the form is chosen when
+    /// rendering. Any other count is resolved and the form is chosen right away.
+    fn parse_plural(&mut self, block: &Block<HelperCall>) -> BalsaResult<ReplaceWith> {
+        let rule = self.options.plural_rule;
+
+        let (count, forms) = match block.token.arguments.split_first() {
+            Some((count, forms)) if forms.len() == rule.form_count() => (count, forms),
+            _ => {
+                return Err(BalsaError::invalid_helper_arguments(
+                    block.start_pos,
+                    Helper::Plural.name().to_string(),
+                    rule.form_count() + 1,
+                    block.token.arguments.len(),
+                ))
+            }
         };
 
-        self.replacements.push(instr);
+        let forms = forms
+            .iter()
+            .map(|form| self.resolve_string(block.start_pos, form))
+            .collect::<BalsaResult<Vec<String>>>()?;
 
-        Ok(())
+        if let BalsaExpression::Identifier(variable_name) = count {
+            let parameter = self.helper_parameter(block, variable_name, BalsaType::Integer);
+
+            self.check_parameter_consistency(block.start_pos, &parameter)?;
+
+            return Ok(ReplaceWith::Plural(PluralDescription {
+                parameter,
+                rule,
+                forms,
+            }));
+        }
+
+        let count = self.resolve_value(block.start_pos, count)?;
+        match self.cast(block.start_pos, count, BalsaType::Integer)? {
+            BalsaValue::Integer(n) => Ok(ReplaceWith::Text(forms[rule.form_index(n)].clone())),
+            _ => unreachable!("value was cast to an integer"),
+        }
     }
 
-    fn parse_dec_block(&mut self, block: &Block<Vec<Declaration>>) -> BalsaResult<()> {
-        for declaration in &block.token {
-            let identifier = declaration.identifier.as_identifier().ok_or_else(|| {
-                BalsaError::invalid_identifier_in_declaration_block(
-                    block.start_pos as usize,
-                    declaration.identifier.clone(),
-                )
-            })?;
+    /// Checks that a parameter used more than once is always given the same type and default
+    /// value, recording its description the first time it is used. Uses without a default value
+    /// don't conflict with uses which have one, and a default value given after the first use is
+    /// recorded as well, to be given to every use. See [`Compiler::spread_defaults`].
+    fn check_parameter_consistency(
+        &mut self,
+        pos: Position,
+        description: &ParameterDescription,
+    ) -> BalsaResult<()> {
+        let (first_pos, first) = match self.parameters.get_mut(&description.variable_name) {
+            Some(first) => first,
+            None => {
+                self.parameters.insert(
+                    description.variable_name.clone(),
+                    (pos, description.clone()),
+                );
 
-            let type_ = declaration.variable_type.as_type().ok_or_else(|| {
-                BalsaError::invalid_type_expression(
-                    block.start_pos as usize,
-                    declaration.variable_type.clone(),
-                )
-            })?;
+                return Ok(());
+            }
+        };
 
-            let value = declaration
-                .value
-                .as_value()
-                .ok_or_else(|| {
-                    BalsaError::invalid_expression(
-                        block.start_pos as usize,
-                        declaration.value.clone(),
-                    )
-                })?
-                .try_cast(type_.clone())
-                .map_err(|error| {
-                    BalsaError::new_compile_error(BalsaCompileError::InvalidTypeCast(
-                        TemplateErrorContext {
-                            pos: block.start_pos as usize,
-                            error,
-                        },
-                    ))
-                })?;
+        let conflict = if first.variable_type != description.variable_type {
+            ParameterConflict::Type {
+                first: first.variable_type.clone(),
+                conflicting: description.variable_type.clone(),
+            }
+        } else {
+            match (&first.default_value, &description.default_value) {
+                (Some(a), Some(b)) if a != b => ParameterConflict::DefaultValue {
+                    first: a.clone(),
+                    conflicting: b.clone(),
+                },
+                (None, Some(b)) => {
+                    first.default_value = Some(b.clone());
+                    return Ok(());
+                }
+                _ => return Ok(()),
+            }
+        };
 
-            self.global_scope.variables.insert(identifier, value);
+        Err(BalsaError::conflicting_parameter(
+            pos,
+            description.variable_name.clone(),
+            *first_pos,
+            conflict,
+        ))
+    }
+
+    /// Casts a `value` written in the template to `type_`.
+    ///
+    /// With [`CompileOptions::strict_types`] enabled the value must already be of `type_`.
+    fn cast(&self, pos: Position, value: BalsaValue, type_: BalsaType) -> BalsaResult<BalsaValue> {
+        let from = value.get_type();
+
+        if self.options.strict_types && from != type_ {
+            return Err(BalsaError::invalid_type_cast(pos, value, from, type_));
         }
 
-        let instr = ReplacementInstruction {
-            start_pos: block.start_pos as usize,
-            end_pos: block.end_pos as usize,
-            replace_with: ReplaceWith::Nothing,
+        value
+            .try_cast(type_)
+            .map_err(|e| BalsaError::invalid_type_cast(pos, e.value, e.from, e.to))
+    }
+
+    /// Resolves the value of the boolean parameter option `option`, which is an expression
+    /// resolving to a boolean or to the string `"true"` or `"false"`.
+    fn resolve_bool(
+        &mut self,
+        pos: Position,
+        option: &str,
+        expression: &BalsaExpression,
+    ) -> BalsaResult<bool> {
+        let value = match self.resolve_value(pos, expression)? {
+            BalsaValue::Boolean(b) => return Ok(b),
+            value => value,
         };
 
-        self.replacements.push(instr);
+        match self.cast(pos, value, BalsaType::String)? {
+            BalsaValue::String(s) if s == "true" => Ok(true),
+            BalsaValue::String(s) if s == "false" => Ok(false),
+            BalsaValue::String(s) => Err(BalsaError::invalid_boolean(pos, option.to_string(), s)),
+            _ => unreachable!("value was cast to a string"),
+        }
+    }
 
-        Ok(())
+    /// Resolves an `expression` to a value like [`Compiler::resolve_value`] and casts it to a
+    /// string.
+    fn resolve_string(
+        &mut self,
+        pos: Position,
+        expression: &BalsaExpression,
+    ) -> BalsaResult<String> {
+        let value = self.resolve_value(pos, expression)?;
+
+        match self.cast(pos, value, BalsaType::String)? {
+            BalsaValue::String(s) => Ok(s),
+            _ => unreachable!("value was cast to a string"),
+        }
+    }
+
+    /// Resolves the `filters` option `expression` to the names of the filters it lists, or the
+    /// name of the single filter it names, each of which must be registered with
+    /// [`CompileOptions::filter`].
+    fn resolve_filters(
+        &mut self,
+        pos: Position,
+        expression: &BalsaExpression,
+    ) -> BalsaResult<Vec<String>> {
+        let names = match self.resolve_value(pos, expression)? {
+            BalsaValue::String(name) => vec![name],
+            value => match self.cast(pos, value, BalsaType::array_of(BalsaType::String))? {
+                BalsaValue::Array(names) => names.into_iter().map(Self::value_text).collect(),
+                _ => unreachable!("value was cast to an array of strings"),
+            },
+        };
+
+        for name in &names {
+            if !self.options.filters.contains(name) {
+                return Err(BalsaError::undefined_filter(pos, name.clone()));
+            }
+
+            if self.options.sandboxed && self.options.filters.is_unsafe(name) {
+                return Err(BalsaError::sandbox_violation(
+                    pos,
+                    format!("{}: [\"{}\"]", parameter_names::FILTERS, name),
+                ));
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Resolves an option or declaration `expression` to a value.
+    ///
+    /// Values are returned as-is and list literals become arrays, while identifiers are looked up
+    /// in the global scope so that blocks can refer to variables declared in a declaration block.
+    /// Only declarations which come before `pos` are visible, and the latest of them shadows any
+    /// earlier ones. Paths like `theme.primaryColor` look up values nested in a declared
+    /// dictionary.
+    fn resolve_value(
+        &mut self,
+        pos: Position,
+        expression: &BalsaExpression,
+    ) -> BalsaResult<BalsaValue> {
+        match expression {
+            BalsaExpression::Identifier(identifier) => {
+                let name = path_root(identifier);
+                if !self.global_scope.variables.contains_key(name) {
+                    return Err(match self.upcoming_declarations.get(name) {
+                        Some(declared_pos) => BalsaError::used_before_declaration(
+                            pos,
+                            identifier.clone(),
+                            *declared_pos,
+                        ),
+                        None => BalsaError::undefined_variable(pos, identifier.clone()),
+                    });
+                }
+
+                let value = lookup_path(identifier, |name| self.global_scope.variables.get(name))
+                    .cloned()
+                    .ok_or_else(|| BalsaError::undefined_variable(pos, identifier.clone()))?;
+
+                if let Some(index) = self.bindings.get(name) {
+                    self.used_declarations.insert(*index);
+                }
+
+                Ok(value)
+            }
+            BalsaExpression::List(values) => {
+                // The items keep their type if they all share one, and are otherwise cast to the
+                // type of the array the literal is cast to.
+                let type_ = BalsaType::common_type_of(values);
+                let array = Array::from_values(type_, values.iter().cloned())
+                    .expect("the items of a literal have the type of the array");
+
+                Ok(BalsaValue::Array(array))
+            }
+            _ => expression
+                .as_value()
+                .ok_or_else(|| BalsaError::invalid_expression(pos, expression.clone())),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::balsa_types::BalsaExpression;
+    use crate::errors::BalsaCompileError;
 
     use super::*;
 
+    /// Compiles `tokens` with the default [`CompileOptions`].
+    fn compile(tokens: &[BalsaToken]) -> BalsaResult<CompiledTemplate> {
+        Compiler::compile_from_tokens("", tokens, &CompileOptions::default())
+    }
+
     /// Converts a [`HashMap`] to a DeclarationBlock.
     fn map_to_declaration_block(
-        start_pos: i32,
-        end_pos: i32,
+        start_pos: Position,
+        end_pos: Position,
         map: HashMap<String, (BalsaType, BalsaValue)>,
     ) -> BalsaToken {
         let ds = map
@@ -198,12 +2189,14 @@ mod tests {
                 identifier: BalsaExpression::Identifier(identifier),
                 variable_type: BalsaExpression::Type(variable_type),
                 value: BalsaExpression::Value(value),
+                condition: None,
             })
             .collect::<Vec<Declaration>>();
 
         BalsaToken::DeclarationBlock(Block {
             start_pos,
             end_pos,
+            source: String::new(),
             token: ds,
         })
     }
@@ -211,8 +2204,8 @@ mod tests {
     #[test]
     fn test_compiler() {
         let dec_block = map_to_declaration_block(
-            0,
-            30,
+            Position::new(0, 1, 1),
+            Position::new(30, 1, 31),
             HashMap::from([
                 (
                     "helloWorld".to_string(),
@@ -226,22 +2219,36 @@ mod tests {
         );
 
         let param_block = BalsaToken::ParameterBlock(Block {
-            start_pos: 40,
-            end_pos: 80,
+            start_pos: Position::new(40, 2, 1),
+            end_pos: Position::new(80, 2, 41),
+            source: "{{ testInt : int, defaultValue: favoriteNumber }}".to_string(),
             token: ParameterBlockIntermediate {
                 variable_name: BalsaExpression::Identifier("testInt".to_string()),
                 variable_type: BalsaExpression::Type(BalsaType::Integer),
-                options: Some(HashMap::from([(
+                options: Some(Vec::from([(
+                    "defaultValue".to_string(),
+                    BalsaExpression::Identifier("favoriteNumber".to_string()),
+                )])),
+            },
+        });
+
+        let greeting_block = BalsaToken::ParameterBlock(Block {
+            start_pos: Position::new(90, 3, 1),
+            end_pos: Position::new(130, 3, 41),
+            source: "{{ greeting : string, defaultValue: helloWorld }}".to_string(),
+            token: ParameterBlockIntermediate {
+                variable_name: BalsaExpression::Identifier("greeting".to_string()),
+                variable_type: BalsaExpression::Type(BalsaType::String),
+                options: Some(Vec::from([(
                     "defaultValue".to_string(),
-                    BalsaExpression::Value(BalsaValue::Integer(1)),
+                    BalsaExpression::Identifier("helloWorld".to_string()),
                 )])),
             },
         });
 
-        let tokens = vec![dec_block, param_block];
+        let tokens = vec![dec_block, param_block, greeting_block];
 
-        let output =
-            Compiler::compile_from_tokens(&tokens).expect("failed to compile from token list");
+        let output = compile(&tokens).expect("failed to compile from token list");
 
         let values = [
             (
@@ -252,35 +2259,65 @@ mod tests {
         ];
 
         for (id, val) in values {
-            let item = output
-                .global_scope
-                .variables
-                .get("helloWorld")
-                .map(|x| x.clone());
+            let item = output.global_scope.variables.get(id).cloned();
 
             assert_eq!(
-                item,
-                Some(BalsaValue::String("goodbye".to_string())),
+                item, val,
                 "Global scope variable `{}` set incorrectly.\n\tExpected: `{:?}`\n\tGot: `{:?}`",
-                id,
-                val,
-                item
+                id, val, item
             );
         }
 
         let params = vec![
             ReplacementInstruction {
-                start_pos: 0,
-                end_pos: 30,
+                start_pos: Position::new(0, 1, 1),
+                end_pos: Position::new(30, 1, 31),
                 replace_with: ReplaceWith::Nothing,
             },
             ReplacementInstruction {
-                start_pos: 40,
-                end_pos: 80,
+                start_pos: Position::new(40, 2, 1),
+                end_pos: Position::new(80, 2, 41),
                 replace_with: ReplaceWith::Parameter(ParameterDescription {
                     variable_name: "testInt".to_string(),
                     variable_type: BalsaType::Integer,
                     default_value: Some(BalsaValue::Integer(1)),
+                    direction: None,
+                    escape: true,
+                    items: ItemsDescription::default(),
+                    image: ImageDescription::default(),
+                    one_of: Vec::new(),
+                    format: None,
+                    safe: false,
+                    optional: false,
+                    placeholder: None,
+                    filters: Vec::new(),
+                    custom_options: Vec::new(),
+                    source: "{{ testInt : int, defaultValue: favoriteNumber }}".to_string(),
+                    source_start_pos: Position::new(40, 2, 1),
+                    source_end_pos: Position::new(80, 2, 41),
+                }),
+            },
+            ReplacementInstruction {
+                start_pos: Position::new(90, 3, 1),
+                end_pos: Position::new(130, 3, 41),
+                replace_with: ReplaceWith::Parameter(ParameterDescription {
+                    variable_name: "greeting".to_string(),
+                    variable_type: BalsaType::String,
+                    default_value: Some(BalsaValue::String("goodbye".to_string())),
+                    direction: None,
+                    escape: true,
+                    items: ItemsDescription::default(),
+                    image: ImageDescription::default(),
+                    one_of: Vec::new(),
+                    format: None,
+                    safe: false,
+                    optional: false,
+                    placeholder: None,
+                    filters: Vec::new(),
+                    custom_options: Vec::new(),
+                    source: "{{ greeting : string, defaultValue: helloWorld }}".to_string(),
+                    source_start_pos: Position::new(90, 3, 1),
+                    source_end_pos: Position::new(130, 3, 41),
                 }),
             },
         ];
@@ -291,4 +2328,333 @@ mod tests {
             params, output.replacements
         );
     }
+
+    #[test]
+    fn test_default_value_from_declaration() {
+        let dec_block = map_to_declaration_block(
+            Position::new(0, 1, 1),
+            Position::new(30, 1, 31),
+            HashMap::from([(
+                "brandColor".to_string(),
+                (BalsaType::Color, BalsaValue::Color("#ff0000".to_string())),
+            )]),
+        );
+
+        let param_block = |identifier: &str| {
+            BalsaToken::ParameterBlock(Block {
+                start_pos: Position::new(40, 2, 1),
+                end_pos: Position::new(80, 2, 41),
+                source: String::new(),
+                token: ParameterBlockIntermediate {
+                    variable_name: BalsaExpression::Identifier("accent".to_string()),
+                    variable_type: BalsaExpression::Type(BalsaType::Color),
+                    options: Some(Vec::from([(
+                        "defaultValue".to_string(),
+                        BalsaExpression::Identifier(identifier.to_string()),
+                    )])),
+                },
+            })
+        };
+
+        let output = compile(&[dec_block.clone(), param_block("brandColor")])
+            .expect("failed to compile from token list");
+
+        assert_eq!(
+            output.replacements[1].replace_with,
+            ReplaceWith::Parameter(ParameterDescription {
+                variable_name: "accent".to_string(),
+                variable_type: BalsaType::Color,
+                default_value: Some(BalsaValue::Color("#ff0000".to_string())),
+                direction: None,
+                escape: true,
+                items: ItemsDescription::default(),
+                image: ImageDescription::default(),
+                one_of: Vec::new(),
+                format: None,
+                safe: false,
+                optional: false,
+                placeholder: None,
+                filters: Vec::new(),
+                custom_options: Vec::new(),
+                source: String::new(),
+                source_start_pos: Position::new(40, 2, 1),
+                source_end_pos: Position::new(80, 2, 41),
+            }),
+            "Default value referencing a declared variable was not resolved"
+        );
+
+        let err = compile(&[dec_block, param_block("otherColor")])
+            .expect_err("Default value referencing an undeclared variable should fail");
+
+        assert!(
+            matches!(
+                err,
+                BalsaError::CompileError(BalsaCompileError::UndefinedVariable(_))
+            ),
+            "Expected `UndefinedVariable` compile error, got: {:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_conflicting_parameters() {
+        let param_block = |line: usize, type_: BalsaType, default_value: Option<BalsaValue>| {
+            BalsaToken::ParameterBlock(Block {
+                start_pos: Position::new(line * 20, line, 1),
+                end_pos: Position::new(line * 20 + 10, line, 11),
+                source: String::new(),
+                token: ParameterBlockIntermediate {
+                    variable_name: BalsaExpression::Identifier("title".to_string()),
+                    variable_type: BalsaExpression::Type(type_),
+                    options: default_value.map(|v| {
+                        Vec::from([("defaultValue".to_string(), BalsaExpression::Value(v))])
+                    }),
+                },
+            })
+        };
+
+        let hello = || Some(BalsaValue::String("hello".to_string()));
+
+        compile(&[
+            param_block(1, BalsaType::String, hello()),
+            param_block(2, BalsaType::String, None),
+            param_block(3, BalsaType::String, hello()),
+        ])
+        .expect("Consistent uses of the same parameter should compile");
+
+        let err = compile(&[
+            param_block(1, BalsaType::String, None),
+            param_block(2, BalsaType::Integer, None),
+        ])
+        .expect_err("Parameter used with two different types should fail to compile");
+
+        match err {
+            BalsaError::CompileError(BalsaCompileError::ConflictingParameter(ctx)) => {
+                assert_eq!(
+                    (ctx.line, ctx.first_line, &ctx.conflict),
+                    (
+                        2,
+                        1,
+                        &ParameterConflict::Type {
+                            first: BalsaType::String,
+                            conflicting: BalsaType::Integer,
+                        }
+                    ),
+                    "Incorrect conflict reported for parameter with two different types"
+                );
+            }
+            e => panic!(
+                "Expected `ConflictingParameter` compile error, got: {:?}",
+                e
+            ),
+        }
+
+        let err = compile(&[
+            param_block(1, BalsaType::String, hello()),
+            param_block(
+                2,
+                BalsaType::String,
+                Some(BalsaValue::String("bye".to_string())),
+            ),
+        ])
+        .expect_err("Parameter used with two different defaults should fail to compile");
+
+        assert!(
+            matches!(
+                err,
+                BalsaError::CompileError(BalsaCompileError::ConflictingParameter(ref ctx))
+                    if matches!(ctx.conflict, ParameterConflict::DefaultValue { .. })
+            ),
+            "Expected default value `ConflictingParameter` compile error, got: {:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_unused_declarations() {
+        let dec_block = map_to_declaration_block(
+            Position::new(0, 1, 1),
+            Position::new(30, 1, 31),
+            HashMap::from([(
+                "brandColor".to_string(),
+                (BalsaType::Color, BalsaValue::Color("#ff0000".to_string())),
+            )]),
+        );
+
+        let output =
+            compile(&[dec_block]).expect("Template with unused declarations should compile");
+
+        assert!(
+            output.global_scope.variables.is_empty(),
+            "Unused declarations were not removed from the global scope: {:?}",
+            output.global_scope
+        );
+
+        assert_eq!(
+            output.warnings,
+            vec![BalsaWarning::unused_declaration(
+                Position::new(0, 1, 1),
+                "brandColor".to_string()
+            )],
+            "Unused declarations were not reported as warnings"
+        );
+    }
+
+    #[test]
+    fn test_declaration_ordering() {
+        let dec_block = |line: usize, value: &str| {
+            map_to_declaration_block(
+                Position::new(line * 20, line, 1),
+                Position::new(line * 20 + 10, line, 11),
+                HashMap::from([(
+                    "title".to_string(),
+                    (BalsaType::String, BalsaValue::String(value.to_string())),
+                )]),
+            )
+        };
+
+        let param_block = |line: usize, name: &str| {
+            BalsaToken::ParameterBlock(Block {
+                start_pos: Position::new(line * 20, line, 1),
+                end_pos: Position::new(line * 20 + 10, line, 11),
+                source: String::new(),
+                token: ParameterBlockIntermediate {
+                    variable_name: BalsaExpression::Identifier(name.to_string()),
+                    variable_type: BalsaExpression::Type(BalsaType::String),
+                    options: Some(Vec::from([(
+                        "defaultValue".to_string(),
+                        BalsaExpression::Identifier("title".to_string()),
+                    )])),
+                },
+            })
+        };
+
+        let output = compile(&[
+            dec_block(1, "first"),
+            param_block(2, "a"),
+            dec_block(3, "second"),
+            param_block(4, "b"),
+        ])
+        .expect("Template with shadowed declarations should compile");
+
+        let defaults = output
+            .replacements
+            .iter()
+            .filter_map(|r| match &r.replace_with {
+                ReplaceWith::Parameter(p) => p.default_value.clone(),
+                _ => None,
+            })
+            .collect::<Vec<BalsaValue>>();
+
+        assert_eq!(
+            defaults,
+            vec![
+                BalsaValue::String("first".to_string()),
+                BalsaValue::String("second".to_string())
+            ],
+            "Later declarations should only shadow earlier ones after their position"
+        );
+
+        let err = compile(&[param_block(1, "a"), dec_block(2, "first")])
+            .expect_err("Variable used before its declaration should fail to compile");
+
+        assert_eq!(
+            err.to_string(),
+            "compile error: variable `title` is used before it is declared (declared at line 2, column 1) at line 1, column 1",
+            "Only the error's own position should be reported with `at line`"
+        );
+
+        match err {
+            BalsaError::CompileError(BalsaCompileError::UsedBeforeDeclaration(ctx)) => {
+                assert_eq!(
+                    (ctx.line, ctx.declared_line),
+                    (1, 2),
+                    "Incorrect positions reported for variable used before its declaration"
+                );
+            }
+            e => panic!(
+                "Expected `UsedBeforeDeclaration` compile error, got: {:?}",
+                e
+            ),
+        }
+    }
+
+    #[test]
+    fn test_optimize_replacements() {
+        let nothing = |start: usize, end: usize| ReplacementInstruction {
+            start_pos: Position::new(start, 1, start + 1),
+            end_pos: Position::new(end, 1, end + 1),
+            replace_with: ReplaceWith::Nothing,
+        };
+        let parameter = |start: usize, end: usize| ReplacementInstruction {
+            replace_with: ReplaceWith::Parameter(ParameterDescription {
+                variable_name: "title".to_string(),
+                variable_type: BalsaType::String,
+                default_value: None,
+                direction: None,
+                escape: true,
+                items: ItemsDescription::default(),
+                image: ImageDescription::default(),
+                one_of: Vec::new(),
+                format: None,
+                safe: false,
+                optional: false,
+                placeholder: None,
+                filters: Vec::new(),
+                custom_options: Vec::new(),
+                source: String::new(),
+                source_start_pos: Position::new(start, 1, start + 1),
+                source_end_pos: Position::new(end, 1, end + 1),
+            }),
+            ..nothing(start, end)
+        };
+
+        let optimized = Compiler::optimize_replacements(vec![
+            nothing(30, 40),
+            parameter(10, 20),
+            nothing(20, 25),
+            nothing(0, 5),
+            nothing(5, 10),
+        ])
+        .expect("Non-overlapping replacements should be optimized");
+
+        let expected = vec![
+            nothing(0, 10),
+            ReplacementInstruction {
+                end_pos: Position::new(25, 1, 26),
+                ..parameter(10, 20)
+            },
+            nothing(30, 40),
+        ];
+
+        assert_eq!(
+            optimized, expected,
+            "Replacements were not sorted and merged correctly"
+        );
+        assert_eq!(
+            Compiler::split_segments("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRS", &optimized),
+            vec!["", "", "zABCD", "OPQRS"],
+            "Static segments between replacements computed incorrectly"
+        );
+
+        let err = Compiler::optimize_replacements(vec![nothing(0, 10), parameter(5, 15)])
+            .expect_err("Overlapping replacements should fail to compile");
+
+        assert_eq!(
+            err.to_string(),
+            "compile error: block overlaps the previous block (which ends at line 1, column 11) at line 1, column 6",
+            "Only the error's own position should be reported with `at line`"
+        );
+
+        match err {
+            BalsaError::CompileError(BalsaCompileError::OverlappingBlocks(ctx)) => {
+                assert_eq!(
+                    (ctx.pos, ctx.previous_end_pos),
+                    (5, 10),
+                    "Incorrect positions reported for overlapping blocks"
+                );
+            }
+            e => panic!("Expected `OverlappingBlocks` compile error, got: {:?}", e),
+        }
+    }
 }
@@ -1,42 +1,86 @@
-use std::collections::HashMap;
-
-use crate::balsa_types::{BalsaExpression, BalsaValue};
-use crate::converters::tuple_vec_to_map;
-use crate::errors::{BalsaCompileError, BalsaError, TemplateErrorContext, TemplateParseFail};
+use crate::balsa_types::{BalsaExpression, BalsaValue, Dictionary, PATH_SEPARATOR};
+use crate::errors::BalsaError;
+use crate::expression::{self, Expression, ExpressionError};
+use crate::options::{CompileOptions, Delimiters};
 use crate::parser::{
-    char_parser, delimited_list, fmap, fmap_chain, fmap_result, key_sep_value, many, middle,
-    optional, or, right, string_parser, take_until_char_parser, take_while_chars_parser,
-    ParseError, Parser, ParserB,
+    between, char_parser, choice, commit, delimited_list, fmap, fmap_chain, fmap_result,
+    key_sep_value, many, many_until, many_until_memo, memo_scope, middle, nested, one_to_many,
+    optional, right, satisfy, sep_by1, skip_string_parser, skip_while_chars_parser, string_parser,
+    take_until_str, take_while_chars_parser, with_source, DepthGuard, ParseError, Parser, ParserB,
+    Position, ScanMemo,
 };
+use crate::validators;
 use crate::BalsaType;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 /// Exposes methods for parsing Balsa templates.
 pub(crate) struct BalsaParser;
 
+/// The default maximum depth to which blocks and values may be nested.
+pub(crate) const DEFAULT_MAX_NESTING_DEPTH: usize = 32;
+
+/// The delimiters, `strict_blocks` setting and maximum nesting depth a grammar was built for.
+type GrammarKey = (Delimiters, bool, usize);
+
+thread_local! {
+    /// The grammars built by [`BalsaParser::parse`] on this thread. Building a grammar allocates
+    /// every parser in it, so each one is built once and reused for every template compiled with
+    /// the same options.
+    static GRAMMARS: RefCell<HashMap<GrammarKey, Rc<ParserB<Vec<BalsaToken>>>>> =
+        RefCell::new(HashMap::new());
+}
+
 impl BalsaParser {
-    /// Parses a string input to a list of [`BalsaToken`]s.
-    pub(crate) fn parse(input: String) -> Result<Vec<BalsaToken>, BalsaError> {
-        let p = balsa_p();
-
-        p.parse(0, &input).map(|(_, t)| t.token).map_err(|_| {
-            BalsaError::CompileError(BalsaCompileError::TemplateParseFail(TemplateErrorContext {
-                pos: 0, // TODO
-                error: TemplateParseFail::Generic,
+    /// Parses a string input to a list of [`BalsaToken`]s using the delimiters in `options`,
+    /// failing if blocks or values are nested deeper than its `max_nesting_depth`.
+    pub(crate) fn parse(
+        input: &str,
+        options: &CompileOptions,
+    ) -> Result<Vec<BalsaToken>, BalsaError> {
+        let delimiters = &options.delimiters;
+        if delimiters.open.is_empty() || delimiters.close.is_empty() {
+            return Err(BalsaError::empty_delimiter());
+        }
+
+        let max_nesting_depth = options.max_nesting_depth;
+        let key = (delimiters.clone(), options.strict_blocks, max_nesting_depth);
+        let p = GRAMMARS.with(|grammars| {
+            Rc::clone(grammars.borrow_mut().entry(key).or_insert_with(|| {
+                Rc::new(balsa_p(
+                    &DepthGuard::new(max_nesting_depth),
+                    &ScanMemo::new(),
+                    delimiters,
+                    options.strict_blocks,
+                ))
             }))
-        })
+        });
+
+        p.parse(Position::default(), input)
+            .map(|(_, t)| t.token)
+            .map_err(|e| match e {
+                ParseError::MalformedInput(pos) => BalsaError::generic_template_parse_fail(pos),
+                ParseError::MalformedBlock(pos) => BalsaError::malformed_block(pos),
+                ParseError::NotMatched => {
+                    BalsaError::generic_template_parse_fail(Position::default())
+                }
+                ParseError::TooDeep(pos) => BalsaError::template_too_deep(pos, max_nesting_depth),
+            })
     }
 }
 
-/// Represents a key-value set from a block.
+/// Represents the key-value pairs of a block, in the order they were written. Keys may be
+/// repeated, which the compiler reports as an error.
 ///
 /// i.e. `defaultValue: "test", type: string`
-pub(crate) type OptionsMap = HashMap<String, BalsaExpression>;
+pub(crate) type OptionsList = Vec<(String, BalsaExpression)>;
 
 /// Contains contextual information about a block.
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub(crate) struct Block<T> {
-    pub(crate) start_pos: i32,
-    pub(crate) end_pos: i32,
+    pub(crate) start_pos: Position,
+    pub(crate) end_pos: Position,
+    /// The block exactly as it was written in the template.
+    pub(crate) source: String,
     pub(crate) token: T,
 }
 
@@ -45,6 +89,69 @@ pub(crate) struct Declaration {
     pub(crate) identifier: BalsaExpression,
     pub(crate) variable_type: BalsaExpression,
     pub(crate) value: BalsaExpression,
+    /// The condition of a conditional declaration, i.e. `darkMode` in
+    /// `if darkMode: background: color = "#000"`. The declaration is skipped unless it is true.
+    pub(crate) condition: Option<BalsaExpression>,
+}
+
+/// A computed parameter, i.e. `readingTime: int = wordCount / 200`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ComputedDeclaration {
+    pub(crate) identifier: BalsaExpression,
+    pub(crate) variable_type: BalsaExpression,
+    pub(crate) expression: Expression,
+    /// The expression exactly as it was written in the template, without surrounding whitespace.
+    pub(crate) expression_source: String,
+}
+
+/// A macro defined in the template, i.e. `{{#macro button(label, href)}} ... {{/macro}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MacroDefinition {
+    pub(crate) name: String,
+    pub(crate) parameters: Vec<String>,
+    /// The text and blocks making up the body of the macro, in order.
+    pub(crate) body: Vec<MacroPart>,
+}
+
+/// A part of the body of a macro or `#each` block.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MacroPart {
+    Text(String),
+    /// A parameter or helper block in a macro body, or a parameter or `include` block in an
+    /// `#each` body. Other blocks are not allowed.
+    Block(Box<BalsaToken>),
+}
+
+/// A section repeated for every item of an array of dictionaries, i.e.
+/// `{{#each posts: array<dict>}} ... {{/each}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EachIntermediate {
+    /// The name of the parameter holding the items.
+    pub(crate) variable_name: BalsaExpression,
+    /// The type of the parameter holding the items.
+    pub(crate) variable_type: BalsaExpression,
+    /// A list of optional options, as in a parameter block.
+    pub(crate) options: Option<OptionsList>,
+    /// The text, parameter and `include` blocks rendered for every item, in order. The
+    /// parameter blocks, and the arguments of the `include` blocks, read the values of the item.
+    pub(crate) body: Vec<MacroPart>,
+}
+
+/// The start of a conditional section, i.e. `{{if showBanner}}`, which is rendered only if the
+/// `bool` parameter it names is `true`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConditionIntermediate {
+    /// The name of the parameter holding the condition.
+    pub(crate) variable_name: BalsaExpression,
+    /// A list of optional options, as in a parameter block.
+    pub(crate) options: Option<OptionsList>,
+}
+
+/// A call to a macro defined earlier in the template, i.e. `{{ button("Sign up", signupUrl) }}`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MacroCall {
+    pub(crate) name: String,
+    pub(crate) arguments: Vec<BalsaExpression>,
 }
 
 /// Intermediate representation for a parameter block.
@@ -55,199 +162,1115 @@ pub(crate) struct ParameterBlockIntermediate {
     /// The type of the variable expected.
     pub(crate) variable_type: BalsaExpression,
     /// A list of optional options.
-    pub(crate) options: Option<OptionsMap>,
+    pub(crate) options: Option<OptionsList>,
+}
+
+/// A helper which can be called from a helper block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Helper {
+    /// `t`, which looks a message up in the template's message catalog.
+    Translate,
+    /// `plural`, which chooses between several forms of a word based on a count.
+    Plural,
+    /// `dir`, which writes the `dir` attribute for a locale.
+    Direction,
+    /// `include`, which compiles another template loaded by the [`crate::TemplateLoader`] in
+    /// place of the block.
+    Include,
+}
+
+/// Intermediate representation for a helper block.
+///
+/// i.e. `{{ t "welcome.title" }}`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HelperCall {
+    /// The helper being called.
+    pub(crate) helper: Helper,
+    /// The whitespace-separated arguments passed to the helper.
+    pub(crate) arguments: Vec<BalsaExpression>,
+    /// The arguments passed to the helper by name, i.e. `title=postTitle`, in the order they
+    /// were written. Only `include` blocks take named arguments.
+    pub(crate) named_arguments: OptionsList,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum BalsaToken {
     DeclarationBlock(Block<Vec<Declaration>>),
+    ComputedBlock(Block<ComputedDeclaration>),
     ParameterBlock(Block<ParameterBlockIntermediate>),
+    HelperBlock(Block<HelperCall>),
+    MacroBlock(Block<MacroDefinition>),
+    CallBlock(Block<MacroCall>),
+    /// A slot filled by the application when rendering, i.e. `{{#slot "sidebar"}}`.
+    SlotBlock(Block<BalsaExpression>),
+    EachBlock(Block<EachIntermediate>),
+    /// The start of a conditional section, i.e. `{{if showBanner}}`.
+    IfBlock(Block<ConditionIntermediate>),
+    /// The start of the section rendered when the enclosing condition is `false`, i.e.
+    /// `{{else}}`.
+    ElseBlock(Block<()>),
+    /// The end of a conditional section, i.e. `{{endif}}`.
+    EndIfBlock(Block<()>),
+    /// Text which is written to the output as-is, i.e. `{{raw}}{{ title }}{{endraw}}`. Holds
+    /// the text between the `raw` and `endraw` blocks.
+    RawBlock(Block<String>),
+    /// A reference to a declared variable, i.e. `{{ $brandColor }}`, which is replaced with its
+    /// value. Holds the name, which may be a path like `theme.primary`.
+    SubstitutionBlock(Block<String>),
+}
+
+impl Helper {
+    /// The name the helper is called by in a template.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Translate => "t",
+            Self::Plural => "plural",
+            Self::Direction => "dir",
+            Self::Include => "include",
+        }
+    }
 }
 
+const DECLARATION_MARKER: char = '@';
+const SUBSTITUTION_MARKER: char = '$';
+const COMPUTED_KEYWORD: &str = "computed";
+const CONDITION_KEYWORD: &str = "if";
+const MACRO_START_MARKER: &str = "#macro";
+const MACRO_END_MARKER: &str = "/macro";
+const SLOT_MARKER: &str = "#slot";
+const EACH_START_MARKER: &str = "#each";
+const EACH_END_MARKER: &str = "/each";
+const ELSE_KEYWORD: &str = "else";
+const END_IF_KEYWORD: &str = "endif";
+const RAW_KEYWORD: &str = "raw";
+const END_RAW_KEYWORD: &str = "endraw";
+const TRUE_LITERAL: &str = "true";
+const FALSE_LITERAL: &str = "false";
+const ARRAY_TYPE: &str = "array";
+const DICTIONARY_TYPE: &str = "dict";
+const TYPE_ARGUMENT_OPEN: char = '<';
+const TYPE_ARGUMENT_CLOSE: char = '>';
+const ARGUMENTS_OPEN: char = '(';
+const ARGUMENTS_CLOSE: char = ')';
+const LIST_OPEN: char = '[';
+const LIST_CLOSE: char = ']';
+const DICTIONARY_OPEN: char = '{';
+const DICTIONARY_CLOSE: char = '}';
 const STR_LITERAL_QUOTE: char = '"';
+const ESCAPE_MARKER: char = '\\';
+const UNICODE_ESCAPE_MARKER: char = 'u';
+const UNICODE_ESCAPE_OPEN: char = '{';
+const UNICODE_ESCAPE_CLOSE: char = '}';
+const COLOR_LITERAL_MARKER: char = '#';
 const ALLOWED_VARIABLE_CHARACTERS: &str =
     "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ123456789-_";
 const DIGITS: &str = "1234567890";
+const PLUS_SIGN: char = '+';
+const MINUS_SIGN: char = '-';
+const DECIMAL_POINT: char = '.';
+/// The characters of a date-time literal other than digits, i.e. in `2024-03-01T09:30:00.5Z`.
+#[cfg(feature = "chrono")]
+const DATETIME_CHARACTERS: &str = "-:.+TZ";
 const KEY_VALUE_DELIMETER: char = ':';
 const LIST_ELEMENT_DELIMETER: char = ',';
 const DECLARATION_DELIMITER: char = '=';
 
-fn parameter_open_bracket_p<'a>() -> ParserB<'a, ()> {
-    fmap(string_parser("{{"), |_, _| ())
+fn parameter_open_bracket_p(delimiters: &Delimiters) -> ParserB<()> {
+    skip_string_parser(delimiters.open.as_str())
 }
 
-fn declaration_open_bracket_p<'a>() -> ParserB<'a, ()> {
-    fmap(string_parser("{{@"), |_, _| ())
+fn declaration_open_bracket_p(delimiters: &Delimiters) -> ParserB<()> {
+    skip_string_parser(format!("{}{}", delimiters.open, DECLARATION_MARKER))
 }
 
-fn closing_bracket_p<'a>() -> ParserB<'a, ()> {
-    fmap(string_parser("}}"), |_, _| ())
+fn closing_bracket_p(delimiters: &Delimiters) -> ParserB<()> {
+    skip_string_parser(delimiters.close.as_str())
 }
 
-fn ws_p<'a>() -> ParserB<'a, ()> {
+fn ws_p() -> ParserB<()> {
     let ws_chars = vec![' ', '\t', '\n'];
 
-    fmap(optional(take_while_chars_parser(ws_chars)), |_, _| ())
+    fmap(optional(skip_while_chars_parser(ws_chars)), |_, _| ())
 }
 
-fn ws_padded_p<'a, P, T: 'a>(parser: P) -> ParserB<'a, T>
+/// Parses whitespace which must be at least one char long.
+fn required_ws_p() -> ParserB<()> {
+    let ws_chars = vec![' ', '\t', '\n'];
+
+    skip_while_chars_parser(ws_chars)
+}
+
+fn ws_padded_p<P, T: 'static>(parser: P) -> ParserB<T>
 where
-    P: Parser<'a, T> + 'a,
+    P: Parser<T>,
 {
     middle(ws_p(), parser, ws_p())
 }
 
-fn variable_name_p<'a>() -> ParserB<'a, String> {
+fn variable_name_p() -> ParserB<String> {
     let allowed_chars = ALLOWED_VARIABLE_CHARACTERS.chars().collect::<Vec<char>>();
 
     take_while_chars_parser(allowed_chars)
 }
 
-fn variable_with_type_p<'a>() -> ParserB<'a, (BalsaExpression, BalsaExpression)> {
+fn variable_with_type_p() -> ParserB<(BalsaExpression, BalsaExpression)> {
     key_sep_value(balsa_expr_p(), key_value_delimiter_p(), balsa_expr_p())
 }
 
-fn string_literal_p<'a>() -> ParserB<'a, BalsaValue> {
+/// Parses a string literal, i.e. `"Hello"`. The escape sequences `\"`, `\\`, `\n`, `\t` and
+/// `\u{...}` are replaced with the characters they stand for.
+fn string_literal_p() -> ParserB<BalsaValue> {
+    let char_p = choice!(
+        escape_sequence_p(),
+        satisfy(|c| c != STR_LITERAL_QUOTE && c != ESCAPE_MARKER)
+    );
+
     fmap(
-        middle(
-            char_parser('"'),
-            take_until_char_parser('"'),
-            char_parser('"'),
+        right(
+            char_parser(STR_LITERAL_QUOTE),
+            many_until(char_p, char_parser(STR_LITERAL_QUOTE)),
         ),
-        |s, _| BalsaValue::String(s),
+        |chars, _| BalsaValue::String(chars.into_iter().collect()),
     )
 }
 
-fn int_literal_p<'a>() -> ParserB<'a, BalsaValue> {
+/// Parses an escape sequence in a string literal, returning the character it stands for. Fails
+/// with [`ParseError::MalformedInput`] if the escape sequence is unknown, or if a `\u{...}`
+/// escape isn't the hexadecimal code of a character, which fails the template only inside a
+/// block which has committed. See [`block_p`].
+fn escape_sequence_p() -> ParserB<char> {
+    let escaped_p = |escaped: char, c: char| fmap(char_parser(escaped), move |_, _| c);
+    let unicode_p = fmap_result(
+        right(
+            char_parser(UNICODE_ESCAPE_MARKER),
+            between(
+                char_parser(UNICODE_ESCAPE_OPEN),
+                char_parser(UNICODE_ESCAPE_CLOSE),
+                one_to_many(satisfy(|c| c.is_ascii_hexdigit())),
+            ),
+        ),
+        |digits, ctx| {
+            u32::from_str_radix(&digits.into_iter().collect::<String>(), 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(ParseError::MalformedInput(ctx.start_pos))
+        },
+    );
+
+    fmap_result(
+        right(
+            char_parser(ESCAPE_MARKER),
+            optional(choice!(
+                escaped_p(STR_LITERAL_QUOTE, STR_LITERAL_QUOTE),
+                escaped_p(ESCAPE_MARKER, ESCAPE_MARKER),
+                escaped_p('n', '\n'),
+                escaped_p('t', '\t'),
+                unicode_p
+            )),
+        ),
+        |c, ctx| c.ok_or(ParseError::MalformedInput(ctx.start_pos)),
+    )
+}
+
+fn int_literal_p() -> ParserB<BalsaValue> {
     let digits = DIGITS.chars().collect::<Vec<char>>();
     let digit_p = take_while_chars_parser(digits);
 
-    fmap_result(digit_p, |token, _| match token.parse::<i64>() {
+    fmap_result(digit_p, |token, ctx| match token.parse::<i64>() {
         Ok(val) => Ok(BalsaValue::Integer(val)),
-        Err(_) => Err(ParseError::MalformedInput(0)),
+        Err(_) => Err(ParseError::MalformedInput(ctx.start_pos)),
     })
 }
 
-fn balsa_type_p<'a>() -> ParserB<'a, BalsaType> {
-    // TODO: or macro or similar shortcut for scalability
-    or(
-        fmap(string_parser("string"), |_, _| BalsaType::String),
-        or(
-            fmap(string_parser("color"), |_, _| BalsaType::Color),
-            or(
-                fmap(string_parser("int"), |_, _| BalsaType::Integer),
-                fmap(string_parser("float"), |_, _| BalsaType::Float),
+/// Parses a float literal with an optional sign, i.e. `3.14` or `-0.5`. Literals without a
+/// decimal part are left to [`int_literal_p`].
+fn float_literal_p() -> ParserB<BalsaValue> {
+    let digit_p = || take_while_chars_parser(DIGITS.chars().collect::<Vec<char>>());
+    let sign_p = choice!(char_parser(PLUS_SIGN), char_parser(MINUS_SIGN));
+    let literal_p = fmap_chain(
+        fmap_chain(optional(sign_p), digit_p(), |_, _| ()),
+        right(char_parser(DECIMAL_POINT), digit_p()),
+        |_, _| (),
+    );
+
+    fmap_result(with_source(literal_p), |(_, source), ctx| {
+        match source.parse::<f64>() {
+            Ok(val) => Ok(BalsaValue::Float(val)),
+            Err(_) => Err(ParseError::MalformedInput(ctx.start_pos)),
+        }
+    })
+}
+
+/// Parses a hex color literal, i.e. `#ff0000`. Words which aren't made of hex digits, such as
+/// `#each`, aren't matched, while hex codes which aren't valid colors fail to parse.
+fn color_literal_p() -> ParserB<BalsaValue> {
+    let word_chars = ALLOWED_VARIABLE_CHARACTERS.chars().chain(DIGITS.chars());
+    let word_p = right(
+        char_parser(COLOR_LITERAL_MARKER),
+        take_while_chars_parser(word_chars.collect::<Vec<char>>()),
+    );
+
+    fmap_result(word_p, |hex, ctx| {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ParseError::NotMatched);
+        }
+
+        let color = format!("{}{}", COLOR_LITERAL_MARKER, hex);
+        if validators::is_valid_color(&color) {
+            Ok(BalsaValue::Color(color))
+        } else {
+            Err(ParseError::MalformedInput(ctx.start_pos))
+        }
+    })
+}
+
+/// Parses a type, i.e. `string`, `array<int>`, `dict<string>` or `dict`, which is a dictionary
+/// whose values may have different types. Arrays and dictionaries may be nested once, as in
+/// `array<dict>`.
+fn balsa_type_p() -> ParserB<BalsaType> {
+    let array_p = |element_p: ParserB<BalsaType>| {
+        fmap(
+            between(
+                string_parser(format!("{}{}", ARRAY_TYPE, TYPE_ARGUMENT_OPEN)),
+                char_parser(TYPE_ARGUMENT_CLOSE),
+                ws_padded_p(element_p),
             ),
+            |t, _| BalsaType::array_of(t),
+        )
+    };
+    let dict_p = || {
+        fmap_chain(
+            string_parser(DICTIONARY_TYPE),
+            optional(between(
+                char_parser(TYPE_ARGUMENT_OPEN),
+                char_parser(TYPE_ARGUMENT_CLOSE),
+                ws_padded_p(scalar_type_p()),
+            )),
+            |_, (t, _)| BalsaType::dictionary_of(t.unwrap_or(BalsaType::Any)),
+        )
+    };
+
+    choice!(
+        array_p(choice!(dict_p(), scalar_type_p())),
+        dict_p(),
+        scalar_type_p(),
+    )
+}
+
+fn scalar_type_p() -> ParserB<BalsaType> {
+    let type_p = choice!(
+        fmap(string_parser("string"), |_, _| BalsaType::String),
+        fmap(string_parser("color"), |_, _| BalsaType::Color),
+        fmap(string_parser("int"), |_, _| BalsaType::Integer),
+        fmap(string_parser("float"), |_, _| BalsaType::Float),
+        fmap(string_parser("bool"), |_, _| BalsaType::Boolean),
+        fmap(string_parser("currency"), |_, _| BalsaType::Currency),
+        fmap(string_parser("image"), |_, _| BalsaType::Image),
+        fmap(string_parser("url"), |_, _| BalsaType::Url),
+        fmap(string_parser("messageformat"), |_, _| {
+            BalsaType::MessageFormat
+        }),
+    );
+
+    #[cfg(feature = "chrono")]
+    let type_p = choice!(
+        fmap(string_parser("datetime"), |_, _| BalsaType::DateTime),
+        type_p
+    );
+
+    type_p
+}
+
+/// Parses a literal other than a dictionary, i.e. `"hello"`, `#ff0000`, `1.5`, `3` or `true`, or
+/// with the `chrono` feature, a date-time such as `2024-03-01`.
+fn scalar_literal_p() -> ParserB<BalsaValue> {
+    let number_p = choice!(float_literal_p(), int_literal_p());
+
+    #[cfg(feature = "chrono")]
+    let number_p = choice!(datetime_literal_p(), number_p);
+
+    choice!(
+        string_literal_p(),
+        color_literal_p(),
+        number_p,
+        bool_literal_p()
+    )
+}
+
+/// Parses a boolean literal, i.e. `true` or `false`. Names which only start with either, such as
+/// `trueColor`, are left to the identifiers.
+fn bool_literal_p() -> ParserB<BalsaValue> {
+    fmap_result(variable_name_p(), |token, _| match token.as_str() {
+        TRUE_LITERAL => Ok(BalsaValue::Boolean(true)),
+        FALSE_LITERAL => Ok(BalsaValue::Boolean(false)),
+        _ => Err(ParseError::NotMatched),
+    })
+}
+
+/// Parses an ISO 8601 date-time literal, i.e. `2024-03-01` or `2024-03-01T09:30:00+01:00`. Text
+/// which doesn't start with a four-digit year and a dash is left to the number literals, and
+/// text which does but isn't a valid date-time fails with [`ParseError::MalformedInput`], which
+/// fails the template only inside a block which has committed. See [`block_p`].
+#[cfg(feature = "chrono")]
+fn datetime_literal_p() -> ParserB<BalsaValue> {
+    let allowed_chars = DIGITS.chars().chain(DATETIME_CHARACTERS.chars()).collect();
+
+    fmap_result(take_while_chars_parser(allowed_chars), |token, ctx| {
+        let is_datetime = token.len() > 4
+            && token[..4].chars().all(|c| c.is_ascii_digit())
+            && token[4..].starts_with(MINUS_SIGN);
+        if !is_datetime {
+            return Err(ParseError::NotMatched);
+        }
+
+        crate::balsa_types::parse_datetime(&token)
+            .map(BalsaValue::DateTime)
+            .ok_or(ParseError::MalformedInput(ctx.start_pos))
+    })
+}
+
+/// Parses a braced, comma-separated list of entries, i.e. `{ primary: #ff0000, gap: 4 }`. The
+/// values keep their type if they all share one, and the dictionary is otherwise of any type.
+fn dictionary_literal_p() -> ParserB<BalsaValue> {
+    let entry_p = || {
+        key_sep_value(
+            variable_name_p(),
+            key_value_delimiter_p(),
+            scalar_literal_p(),
+        )
+    };
+
+    fmap(
+        between(
+            char_parser(DICTIONARY_OPEN),
+            char_parser(DICTIONARY_CLOSE),
+            ws_padded_p(delimited_list(entry_p, list_delimeter)),
         ),
+        |entries: Vec<(String, BalsaValue)>, _| {
+            let type_ = BalsaType::common_type_of(entries.iter().map(|(_, v)| v));
+            let dictionary = Dictionary::from_entries(type_, entries)
+                .expect("the values of a literal have the type of the dictionary");
+
+            BalsaValue::Dictionary(dictionary)
+        },
     )
 }
 
-fn balsa_value_p<'a>() -> ParserB<'a, BalsaValue> {
-    or(string_literal_p(), int_literal_p())
+fn balsa_value_p() -> ParserB<BalsaValue> {
+    choice!(dictionary_literal_p(), scalar_literal_p())
 }
 
-fn balsa_expr_p<'a>() -> ParserB<'a, BalsaExpression> {
-    or(
+/// Parses a name, which may be a path to a value nested in dictionaries, i.e.
+/// `theme.primaryColor`.
+fn path_p() -> ParserB<String> {
+    let separator_p = || char_parser(PATH_SEPARATOR);
+
+    fmap(sep_by1(variable_name_p, separator_p), |names, _| {
+        names.join(&PATH_SEPARATOR.to_string())
+    })
+}
+
+/// Parses a bracketed, comma-separated list of literals, i.e. `["left", "right"]`.
+fn list_literal_p() -> ParserB<Vec<BalsaValue>> {
+    between(
+        char_parser(LIST_OPEN),
+        char_parser(LIST_CLOSE),
+        ws_padded_p(delimited_list(balsa_value_p, list_delimeter)),
+    )
+}
+
+fn balsa_expr_p() -> ParserB<BalsaExpression> {
+    choice!(
+        fmap(list_literal_p(), |l, _| BalsaExpression::List(l)),
         fmap(balsa_value_p(), |v, _| BalsaExpression::Value(v)),
-        or(
-            fmap(balsa_type_p(), |t, _| BalsaExpression::Type(t)),
-            fmap(variable_name_p(), |v, _| BalsaExpression::Identifier(v)),
-        ),
+        fmap(balsa_type_p(), |t, _| BalsaExpression::Type(t)),
+        fmap(path_p(), |v, _| BalsaExpression::Identifier(v)),
     )
 }
 
-fn key_value_delimiter_p<'a>() -> ParserB<'a, ()> {
+fn key_value_delimiter_p() -> ParserB<()> {
     fmap(ws_padded_p(char_parser(KEY_VALUE_DELIMETER)), |_, _| ())
 }
 
-fn key_value_p<'a>() -> ParserB<'a, (String, BalsaExpression)> {
+fn key_value_p() -> ParserB<(String, BalsaExpression)> {
     key_sep_value(variable_name_p(), key_value_delimiter_p(), balsa_expr_p())
 }
 
-fn list_delimeter<'a>() -> ParserB<'a, ()> {
+fn list_delimeter() -> ParserB<()> {
     fmap(ws_padded_p(char_parser(LIST_ELEMENT_DELIMETER)), |_, _| ())
 }
 
-fn declaration_delimiter_p<'a>() -> ParserB<'a, ()> {
+fn declaration_delimiter_p() -> ParserB<()> {
     fmap(ws_padded_p(char_parser(DECLARATION_DELIMITER)), |_, _| ())
 }
 
-fn declaration_p<'a>() -> ParserB<'a, Declaration> {
+/// Parses the condition of a conditional declaration, i.e. `if darkMode:`.
+fn condition_p() -> ParserB<BalsaExpression> {
+    middle(
+        right(string_parser(CONDITION_KEYWORD), required_ws_p()),
+        balsa_expr_p(),
+        key_value_delimiter_p(),
+    )
+}
+
+fn declaration_p() -> ParserB<Declaration> {
     fmap_chain(
-        variable_with_type_p(),
-        right(declaration_delimiter_p(), balsa_expr_p()),
-        |((identifier, variable_type), _), (value, _)| Declaration {
+        optional(condition_p()),
+        fmap_chain(
+            variable_with_type_p(),
+            right(declaration_delimiter_p(), balsa_expr_p()),
+            |((identifier, variable_type), _), (value, _)| (identifier, variable_type, value),
+        ),
+        |(condition, _), ((identifier, variable_type, value), _)| Declaration {
             identifier,
             variable_type,
             value,
+            condition,
         },
     )
 }
 
-fn declaration_block_p<'a>() -> ParserB<'a, BalsaToken> {
-    fmap(
-        middle(
-            declaration_open_bracket_p(),
-            ws_padded_p(delimited_list(declaration_p, list_delimeter)),
-            closing_bracket_p(),
+fn declaration_block_p(delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    commit(
+        declaration_open_bracket_p(delimiters),
+        fmap(
+            with_source(between(
+                declaration_open_bracket_p(delimiters),
+                closing_bracket_p(delimiters),
+                ws_padded_p(delimited_list(declaration_p, list_delimeter)),
+            )),
+            |(d, source), ctx| {
+                BalsaToken::DeclarationBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: d,
+                })
+            },
         ),
-        |d, ctx| {
-            BalsaToken::DeclarationBlock(Block {
-                start_pos: ctx.start_pos,
-                end_pos: ctx.end_pos,
-                token: d,
-            })
+    )
+}
+
+/// Parses a computed block, i.e. `{{@computed readingTime: int = wordCount / 200 }}`. Like
+/// declaration blocks, the `computed` keyword may be separated from the `@` by whitespace.
+///
+/// The expression runs up to the closing delimiter and may nest parentheses and unary `-` up to
+/// the guard's maximum depth.
+fn computed_block_p(guard: &DepthGuard, delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    let max_depth = guard.max_depth();
+
+    let expression_p = fmap_result(
+        optional(take_until_str(delimiters.close.as_str())),
+        move |source, ctx| {
+            let source = source.unwrap_or_default().trim().to_string();
+
+            match expression::parse(&source, max_depth) {
+                Ok(expression) => Ok((expression, source)),
+                Err(ExpressionError::Malformed) => Err(ParseError::MalformedInput(ctx.start_pos)),
+                Err(ExpressionError::TooDeep) => Err(ParseError::TooDeep(ctx.start_pos)),
+            }
         },
+    );
+
+    commit(
+        declaration_open_bracket_p(delimiters),
+        fmap(
+            with_source(between(
+                right(
+                    declaration_open_bracket_p(delimiters),
+                    middle(ws_p(), string_parser(COMPUTED_KEYWORD), required_ws_p()),
+                ),
+                closing_bracket_p(delimiters),
+                fmap_chain(
+                    variable_with_type_p(),
+                    right(declaration_delimiter_p(), expression_p),
+                    |((identifier, variable_type), _), ((expression, expression_source), _)| {
+                        ComputedDeclaration {
+                            identifier,
+                            variable_type,
+                            expression,
+                            expression_source,
+                        }
+                    },
+                ),
+            )),
+            |(c, source), ctx| {
+                BalsaToken::ComputedBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: c,
+                })
+            },
+        ),
     )
 }
 
-fn parameter_block_p<'a>() -> ParserB<'a, BalsaToken> {
-    fmap(
-        middle(
-            parameter_open_bracket_p(),
+fn parameter_block_p(delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    commit(
+        parameter_start_p(delimiters),
+        fmap(
+            with_source(between(
+                parameter_open_bracket_p(delimiters),
+                closing_bracket_p(delimiters),
+                ws_padded_p(fmap_chain(
+                    variable_with_type_p(),
+                    optional(right(
+                        list_delimeter(),
+                        delimited_list(key_value_p, list_delimeter),
+                    )),
+                    |((variable_name, variable_type), _), (options, _)| {
+                        ParameterBlockIntermediate {
+                            variable_name,
+                            variable_type,
+                            options,
+                        }
+                    },
+                )),
+            )),
+            |(p, source), ctx| {
+                BalsaToken::ParameterBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: p,
+                })
+            },
+        ),
+    )
+}
+
+fn helper_name_p(helper: Helper) -> ParserB<Helper> {
+    fmap(string_parser(helper.name()), move |_, _| helper)
+}
+
+fn helper_p() -> ParserB<Helper> {
+    choice!(
+        helper_name_p(Helper::Translate),
+        helper_name_p(Helper::Plural),
+        helper_name_p(Helper::Direction),
+        helper_name_p(Helper::Include)
+    )
+}
+
+fn include_helper_p() -> ParserB<Helper> {
+    helper_name_p(Helper::Include)
+}
+
+/// Parses a helper block, i.e. the name of a known helper followed by one or more arguments.
+///
+/// Only known helpers are matched, so that text such as `{{ not a block }}` is still treated as
+/// raw template text.
+fn helper_block_p(delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    helper_call_block_p(delimiters, helper_p)
+}
+
+/// Parses a helper block as in [`helper_block_p`], matching only the helpers `helper_p` does.
+fn helper_call_block_p(
+    delimiters: &Delimiters,
+    helper_p: fn() -> ParserB<Helper>,
+) -> ParserB<BalsaToken> {
+    commit(
+        right(
+            parameter_open_bracket_p(delimiters),
+            right(ws_p(), right(helper_p(), required_ws_p())),
+        ),
+        fmap(
+            with_source(between(
+                parameter_open_bracket_p(delimiters),
+                closing_bracket_p(delimiters),
+                ws_padded_p(fmap_chain(
+                    helper_p(),
+                    one_to_many(right(required_ws_p(), helper_argument_p())),
+                    |(helper, _), (arguments, _)| {
+                        let mut call = HelperCall {
+                            helper,
+                            arguments: Vec::new(),
+                            named_arguments: Vec::new(),
+                        };
+                        for (name, argument) in arguments {
+                            match name {
+                                Some(name) => call.named_arguments.push((name, argument)),
+                                None => call.arguments.push(argument),
+                            }
+                        }
+
+                        call
+                    },
+                )),
+            )),
+            |(h, source), ctx| {
+                BalsaToken::HelperBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: h,
+                })
+            },
+        ),
+    )
+}
+
+/// Parses an argument of a helper block, along with its name if it is passed by name, i.e.
+/// `title=postTitle`.
+fn helper_argument_p() -> ParserB<(Option<String>, BalsaExpression)> {
+    choice!(
+        fmap(
+            key_sep_value(variable_name_p(), declaration_delimiter_p(), balsa_expr_p()),
+            |(name, argument), _| (Some(name), argument)
+        ),
+        fmap(balsa_expr_p(), |argument, _| (None, argument))
+    )
+}
+
+/// Parses a parenthesized, comma-separated list of `item`s, i.e. `(label, href)`.
+fn argument_list_p<P, T>(item: P) -> ParserB<Vec<T>>
+where
+    P: Fn() -> ParserB<T>,
+    T: Clone + 'static,
+{
+    between(
+        char_parser(ARGUMENTS_OPEN),
+        char_parser(ARGUMENTS_CLOSE),
+        ws_padded_p(delimited_list(item, list_delimeter)),
+    )
+}
+
+/// Parses a call to a macro, i.e. `{{ button("Sign up", signupUrl) }}`.
+fn call_block_p(delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    commit(
+        right(
+            parameter_open_bracket_p(delimiters),
+            right(ws_padded_p(variable_name_p()), char_parser(ARGUMENTS_OPEN)),
+        ),
+        fmap(
+            with_source(between(
+                parameter_open_bracket_p(delimiters),
+                closing_bracket_p(delimiters),
+                ws_padded_p(fmap_chain(
+                    variable_name_p(),
+                    right(ws_p(), argument_list_p(balsa_expr_p)),
+                    |(name, _), (arguments, _)| MacroCall { name, arguments },
+                )),
+            )),
+            |(c, source), ctx| {
+                BalsaToken::CallBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: c,
+                })
+            },
+        ),
+    )
+}
+
+/// Parses a macro definition, i.e. `{{#macro button(label, href)}} ... {{/macro}}`.
+///
+/// The body may contain parameter and helper blocks, which each count as one level of nesting
+/// in the provided `guard`. Opening delimiters which don't start one of these blocks are handled
+/// as in [`balsa_p`].
+fn macro_block_p(
+    guard: &DepthGuard,
+    memo: &ScanMemo,
+    delimiters: &Delimiters,
+    strict: bool,
+) -> ParserB<BalsaToken> {
+    let start_p = between(
+        string_parser(format!("{}{}", delimiters.open, MACRO_START_MARKER)),
+        closing_bracket_p(delimiters),
+        right(
+            required_ws_p(),
+            ws_padded_p(fmap_chain(
+                variable_name_p(),
+                right(ws_p(), argument_list_p(variable_name_p)),
+                |(name, _), (parameters, _)| (name, parameters),
+            )),
+        ),
+    );
+    let end_p = right(
+        string_parser(format!("{}{}", delimiters.open, MACRO_END_MARKER)),
+        ws_padded_p(closing_bracket_p(delimiters)),
+    );
+
+    let body_block_p = nested(
+        guard,
+        choice!(parameter_block_p(delimiters), helper_block_p(delimiters)),
+    );
+    let part_p = choice!(
+        fmap(body_block_p, |b, _| MacroPart::Block(Box::new(b))),
+        fmap(take_until_str(delimiters.open.as_str()), |t, _| {
+            MacroPart::Text(t)
+        }),
+        fmap(unmatched_open_p(delimiters, strict), |c, _| {
+            MacroPart::Text(c.to_string())
+        }),
+    );
+
+    commit(
+        string_parser(format!("{}{}", delimiters.open, MACRO_START_MARKER)),
+        fmap(
+            with_source(fmap_chain(
+                start_p,
+                many_until_memo(memo, part_p, end_p),
+                |((name, parameters), _), (parts, _)| MacroDefinition {
+                    name,
+                    parameters,
+                    body: merge_text(parts),
+                },
+            )),
+            |(m, source), ctx| {
+                BalsaToken::MacroBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: m,
+                })
+            },
+        ),
+    )
+}
+
+/// Parses a slot block, i.e. `{{#slot "sidebar"}}`.
+fn slot_block_p(delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    commit(
+        string_parser(format!("{}{}", delimiters.open, SLOT_MARKER)),
+        fmap(
+            with_source(between(
+                string_parser(format!("{}{}", delimiters.open, SLOT_MARKER)),
+                closing_bracket_p(delimiters),
+                right(required_ws_p(), ws_padded_p(balsa_expr_p())),
+            )),
+            |(name, source), ctx| {
+                BalsaToken::SlotBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: name,
+                })
+            },
+        ),
+    )
+}
+
+/// Parses a repeated section, i.e. `{{#each posts: array<dict>}} ... {{/each}}`, which takes the
+/// same options as a parameter block.
+///
+/// The body may contain parameter and `include` blocks, which each count as one level of nesting
+/// in the provided `guard`. Opening delimiters which don't start one of these blocks are handled
+/// as in [`balsa_p`].
+fn each_block_p(
+    guard: &DepthGuard,
+    memo: &ScanMemo,
+    delimiters: &Delimiters,
+    strict: bool,
+) -> ParserB<BalsaToken> {
+    let start_p = between(
+        string_parser(format!("{}{}", delimiters.open, EACH_START_MARKER)),
+        closing_bracket_p(delimiters),
+        right(
+            required_ws_p(),
             ws_padded_p(fmap_chain(
                 variable_with_type_p(),
                 optional(right(
                     list_delimeter(),
                     delimited_list(key_value_p, list_delimeter),
                 )),
-                |((variable_name, variable_type), _), (options_list, _)| {
-                    let options = options_list.map(tuple_vec_to_map);
-
-                    ParameterBlockIntermediate {
-                        variable_name,
-                        variable_type,
-                        options,
-                    }
+                |(variable, _), (options, _)| (variable, options),
+            )),
+        ),
+    );
+    let end_p = right(
+        string_parser(format!("{}{}", delimiters.open, EACH_END_MARKER)),
+        ws_padded_p(closing_bracket_p(delimiters)),
+    );
+
+    let body_block_p = nested(
+        guard,
+        choice!(
+            parameter_block_p(delimiters),
+            helper_call_block_p(delimiters, include_helper_p)
+        ),
+    );
+    let part_p = choice!(
+        fmap(body_block_p, |b, _| MacroPart::Block(Box::new(b))),
+        fmap(take_until_str(delimiters.open.as_str()), |t, _| {
+            MacroPart::Text(t)
+        }),
+        fmap(unmatched_open_p(delimiters, strict), |c, _| {
+            MacroPart::Text(c.to_string())
+        }),
+    );
+
+    commit(
+        string_parser(format!("{}{}", delimiters.open, EACH_START_MARKER)),
+        fmap(
+            with_source(fmap_chain(
+                start_p,
+                many_until_memo(memo, part_p, end_p),
+                |(((variable_name, variable_type), options), _), (parts, _)| EachIntermediate {
+                    variable_name,
+                    variable_type,
+                    options,
+                    body: merge_text(parts),
                 },
             )),
-            closing_bracket_p(),
+            |(e, source), ctx| {
+                BalsaToken::EachBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: e,
+                })
+            },
+        ),
+    )
+}
+
+/// Parses the start of a conditional section, i.e. `{{if showBanner}}`, which takes the same
+/// options as a parameter block, i.e. `{{if showBanner, defaultValue: false}}`.
+fn if_block_p(delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    commit(
+        right(
+            parameter_open_bracket_p(delimiters),
+            right(
+                ws_p(),
+                right(string_parser(CONDITION_KEYWORD), required_ws_p()),
+            ),
+        ),
+        fmap(
+            with_source(between(
+                parameter_open_bracket_p(delimiters),
+                closing_bracket_p(delimiters),
+                ws_padded_p(right(
+                    right(string_parser(CONDITION_KEYWORD), required_ws_p()),
+                    fmap_chain(
+                        balsa_expr_p(),
+                        optional(right(
+                            list_delimeter(),
+                            delimited_list(key_value_p, list_delimeter),
+                        )),
+                        |(variable_name, _), (options, _)| ConditionIntermediate {
+                            variable_name,
+                            options,
+                        },
+                    ),
+                )),
+            )),
+            |(c, source), ctx| {
+                BalsaToken::IfBlock(Block {
+                    start_pos: ctx.start_pos,
+                    end_pos: ctx.end_pos,
+                    source,
+                    token: c,
+                })
+            },
         ),
-        |p, ctx| {
-            BalsaToken::ParameterBlock(Block {
+    )
+}
+
+/// Parses a block made up of only the `keyword`, i.e. `{{else}}` or `{{endif}}`.
+fn keyword_p(delimiters: &Delimiters, keyword: &'static str) -> ParserB<String> {
+    between(
+        parameter_open_bracket_p(delimiters),
+        closing_bracket_p(delimiters),
+        ws_padded_p(string_parser(keyword)),
+    )
+}
+
+/// Parses a block made up of only the `keyword`, i.e. `{{else}}` or `{{endif}}`, into the token
+/// created by `token`.
+fn keyword_block_p(
+    delimiters: &Delimiters,
+    keyword: &'static str,
+    token: fn(Block<()>) -> BalsaToken,
+) -> ParserB<BalsaToken> {
+    fmap(
+        with_source(keyword_p(delimiters, keyword)),
+        move |(_, source), ctx| {
+            token(Block {
                 start_pos: ctx.start_pos,
                 end_pos: ctx.end_pos,
-                token: p,
+                source,
+                token: (),
             })
         },
     )
 }
 
-/// Parses any kind of block into a BalsaToken.
-fn block_p<'a>() -> ParserB<'a, BalsaToken> {
-    or(parameter_block_p(), declaration_block_p())
+/// Parses a raw section, i.e. `{{raw}}{{ title }}{{endraw}}`, whose text is written to the output
+/// as-is rather than parsed, so that templates can output the opening delimiter.
+fn raw_block_p(memo: &ScanMemo, delimiters: &Delimiters) -> ParserB<BalsaToken> {
+    fmap(
+        with_source(right(
+            keyword_p(delimiters, RAW_KEYWORD),
+            many_until_memo(
+                memo,
+                satisfy(|_| true),
+                keyword_p(delimiters, END_RAW_KEYWORD),
+            ),
+        )),
+        |(text, source), ctx| {
+            BalsaToken::RawBlock(Block {
+                start_pos: ctx.start_pos,
+                end_pos: ctx.end_pos,
+                source,
+                token: text.into_iter().collect(),
+            })
+        },
+    )
 }
 
-fn balsa_p<'a>() -> ParserB<'a, Vec<BalsaToken>> {
+/// Parses a substitution block, i.e. `{{ $brandColor }}` or `{{ $theme.primary }}`, which is
+/// replaced with the value of a declared variable.
+fn substitution_block_p(delimiters: &Delimiters) -> ParserB<BalsaToken> {
     fmap(
-        many(right(
-            take_until_char_parser('{'),
-            or(
-                fmap(block_p(), |v, _| Some(v)),
-                fmap(take_while_chars_parser(vec!['{']), |_, _| None),
-            ),
+        with_source(between(
+            parameter_open_bracket_p(delimiters),
+            closing_bracket_p(delimiters),
+            ws_padded_p(right(char_parser(SUBSTITUTION_MARKER), path_p())),
         )),
-        |v, _| v.into_iter().flatten().collect(),
+        |(name, source), ctx| {
+            BalsaToken::SubstitutionBlock(Block {
+                start_pos: ctx.start_pos,
+                end_pos: ctx.end_pos,
+                source,
+                token: name,
+            })
+        },
+    )
+}
+
+/// Joins adjacent text parts of a macro or `#each` body.
+fn merge_text(parts: Vec<MacroPart>) -> Vec<MacroPart> {
+    let mut merged: Vec<MacroPart> = Vec::with_capacity(parts.len());
+
+    for part in parts {
+        match (merged.last_mut(), part) {
+            (Some(MacroPart::Text(text)), MacroPart::Text(next)) => text.push_str(&next),
+            (_, part) => merged.push(part),
+        }
+    }
+
+    merged
+}
+
+/// Parses any kind of block into a BalsaToken.
+///
+/// Computed, declaration, macro and `#each` blocks are tried first as their `{{@` and `{{#`
+/// openings are strict extensions of the parameter block's `{{`, which lets the alternation commit
+/// after reading the prefix. Computed blocks come before declaration blocks, as `computed` is also
+/// a valid name. `raw`, `if`, `else` and `endif` blocks only match their keywords, so text such as
+/// `{{ if : string }}` is still a parameter block. Helper blocks are tried last, as a helper's name
+/// is also a valid parameter name. Each block counts as one level of nesting in the provided
+/// `guard`.
+///
+/// Blocks [`commit`] once the text starts like them, i.e. `{{ title :` for a parameter block or
+/// `{{@` for a declaration block. Malformed literals fail the template only after that, so text
+/// such as `{{ "C:\path" }}` is still written as-is.
+///
+/// Alternatives which don't match fail within their opening, or at the first token which rules
+/// their block out, other than unterminated macro, `#each` and `raw` blocks, which fail once they
+/// reach the end of the template. Their bodies are scanned with [`many_until_memo`] using the
+/// provided `memo`, so later unterminated blocks stop where an earlier scan failed. Those blocks
+/// are only parsed here, so their bodies always run at the same depth and the memoized results
+/// don't depend on where they were found. No other alternative runs twice at the same position.
+fn block_p(
+    guard: &DepthGuard,
+    memo: &ScanMemo,
+    delimiters: &Delimiters,
+    strict: bool,
+) -> ParserB<BalsaToken> {
+    nested(
+        guard,
+        choice!(
+            computed_block_p(guard, delimiters),
+            declaration_block_p(delimiters),
+            macro_block_p(guard, memo, delimiters, strict),
+            each_block_p(guard, memo, delimiters, strict),
+            slot_block_p(delimiters),
+            raw_block_p(memo, delimiters),
+            if_block_p(delimiters),
+            keyword_block_p(delimiters, ELSE_KEYWORD, BalsaToken::ElseBlock),
+            keyword_block_p(delimiters, END_IF_KEYWORD, BalsaToken::EndIfBlock),
+            substitution_block_p(delimiters),
+            parameter_block_p(delimiters),
+            call_block_p(delimiters),
+            helper_block_p(delimiters)
+        ),
+    )
+}
+
+/// Parses the first char of an opening delimiter which doesn't start a block, so that text such
+/// as `{{{ x : string }}` still finds the block that follows. Fails with
+/// [`ParseError::MalformedBlock`] if the text starts like a parameter or declaration block, i.e.
+/// `{{ title : strnig }`, as it can only be a typo, and with `strict` set, whenever the opening
+/// delimiter doesn't start a block.
+fn unmatched_open_p(delimiters: &Delimiters, strict: bool) -> ParserB<char> {
+    if strict {
+        fmap_result(string_parser(delimiters.open.as_str()), |_, ctx| {
+            Err(ParseError::MalformedBlock(ctx.start_pos))
+        })
+    } else {
+        let open_first_char = delimiters.open.chars().next().unwrap_or_default();
+
+        choice!(
+            fmap_result(block_start_p(delimiters), |_, ctx| {
+                Err(ParseError::MalformedBlock(ctx.start_pos))
+            }),
+            char_parser(open_first_char),
+        )
+    }
+}
+
+/// Parses the start of a parameter block up to the colon after its name, i.e. `{{ title :`, or
+/// the opening of a declaration block, which text only contains when it is meant as a block.
+fn block_start_p(delimiters: &Delimiters) -> ParserB<()> {
+    choice!(
+        declaration_open_bracket_p(delimiters),
+        parameter_start_p(delimiters)
+    )
+}
+
+/// Parses the start of a parameter block up to the colon after its name, i.e. `{{ title :`.
+fn parameter_start_p(delimiters: &Delimiters) -> ParserB<()> {
+    right(
+        parameter_open_bracket_p(delimiters),
+        right(
+            ws_padded_p(path_p()),
+            fmap(char_parser(KEY_VALUE_DELIMETER), |_, _| ()),
+        ),
+    )
+}
+
+/// Parses a whole template into a list of blocks.
+///
+/// Raw HTML is skipped up to the next opening delimiter. If no block can be parsed there, a
+/// single char is skipped so that text such as `{{{ x : string }}` still finds the block that
+/// follows. With `strict` set, an opening delimiter which doesn't start a block is a
+/// [`ParseError::MalformedBlock`] instead. The opening delimiter must not be empty. The provided
+/// `memo` is cleared at the start of each parse.
+fn balsa_p(
+    guard: &DepthGuard,
+    memo: &ScanMemo,
+    delimiters: &Delimiters,
+    strict: bool,
+) -> ParserB<Vec<BalsaToken>> {
+    let fallback = fmap(unmatched_open_p(delimiters, strict), |_, _| None);
+
+    memo_scope(
+        memo,
+        fmap(
+            many(right(
+                optional(take_until_str(delimiters.open.as_str())),
+                choice!(
+                    fmap(block_p(guard, memo, delimiters, strict), |v, _| Some(v)),
+                    fallback,
+                ),
+            )),
+            |v, _| v.into_iter().flatten().collect(),
+        ),
     )
 }
 
@@ -260,14 +1283,14 @@ mod tests {
     #[test]
     fn test_parameter_block_p() {
         let valid_input = r#"{{ helloWorld: color, defaultValue: "hello world" }}"#;
-        let mut valid_options = HashMap::new();
-        valid_options.insert(
+        let valid_options = vec![(
             "defaultValue".to_string(),
             BalsaExpression::Value(BalsaValue::String("hello world".to_string())),
-        );
+        )];
         let valid_output = BalsaToken::ParameterBlock(Block {
-            start_pos: 0,
-            end_pos: 52,
+            start_pos: Position::default(),
+            end_pos: Position::new(52, 1, 53),
+            source: valid_input.to_string(),
             token: ParameterBlockIntermediate {
                 variable_name: BalsaExpression::Identifier("helloWorld".to_string()),
                 variable_type: BalsaExpression::Type(BalsaType::Color),
@@ -275,12 +1298,16 @@ mod tests {
             },
         });
 
-        let p = parameter_block_p();
+        let p = parameter_block_p(&Delimiters::default());
 
-        let (_, parsed) = p.parse(0, valid_input).expect(&format!(
-            "Parameter block parser should successfully parse input `{}`",
-            valid_input
-        ));
+        let (_, parsed) = p
+            .parse(Position::default(), valid_input)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Parameter block parser should successfully parse input `{}`",
+                    valid_input
+                )
+            });
 
         assert!(
             PartialEq::eq(&parsed.token, &valid_output),
@@ -294,23 +1321,28 @@ mod tests {
     #[test]
     fn test_declaration_block_p() {
         let valid_input = r#"{{@ hello: string     = "world" }}"#;
-        let mut valid_declarations = Vec::new();
-        valid_declarations.push(Declaration {
+        let valid_declarations = vec![Declaration {
             identifier: BalsaExpression::Identifier("hello".to_string()),
             variable_type: BalsaExpression::Type(BalsaType::String),
             value: BalsaExpression::Value(BalsaValue::String("world".to_string())),
-        });
+            condition: None,
+        }];
         let valid_output = BalsaToken::DeclarationBlock(Block {
-            start_pos: 0,
-            end_pos: valid_input.len() as i32,
+            start_pos: Position::default(),
+            end_pos: Position::default().advance(valid_input),
+            source: valid_input.to_string(),
             token: valid_declarations,
         });
-        let p = declaration_block_p();
+        let p = declaration_block_p(&Delimiters::default());
 
-        let (_, parsed) = p.parse(0, valid_input).expect(&format!(
-            "Declaration block parser should successfully parse input `{}`",
-            valid_input
-        ));
+        let (_, parsed) = p
+            .parse(Position::default(), valid_input)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Declaration block parser should successfully parse input `{}`",
+                    valid_input
+                )
+            });
 
         assert!(
             PartialEq::eq(&parsed.token, &valid_output),
@@ -321,6 +1353,462 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_conditional_declaration_p() {
+        let cases = [
+            (
+                r##"if darkMode: background: color = "#000""##,
+                Some("darkMode"),
+                "background",
+            ),
+            (r#"if : string = "x""#, None, "if"),
+            ("iffy: int = 1", None, "iffy"),
+        ];
+
+        for (input, condition, identifier) in cases {
+            let (_, parsed) = declaration_p()
+                .parse(Position::default(), input)
+                .unwrap_or_else(|_| panic!("Declaration `{}` should be parsed", input));
+
+            assert_eq!(
+                (parsed.token.condition, parsed.token.identifier),
+                (
+                    condition.map(|c| BalsaExpression::Identifier(c.to_string())),
+                    BalsaExpression::Identifier(identifier.to_string())
+                ),
+                "Declaration `{}` parsed incorrectly",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_slot_block_p() {
+        let input = r#"{{#slot "sidebar" }}"#;
+
+        let (_, parsed) = slot_block_p(&Delimiters::default())
+            .parse(Position::default(), input)
+            .expect("Slot block should be parsed");
+
+        assert_eq!(
+            parsed.token,
+            BalsaToken::SlotBlock(Block {
+                start_pos: Position::default(),
+                end_pos: Position::default().advance(input),
+                source: input.to_string(),
+                token: BalsaExpression::Value(BalsaValue::String("sidebar".to_string())),
+            })
+        );
+    }
+
+    #[test]
+    fn test_substitution_block_p() {
+        let input = "{{ $theme.primary }}";
+
+        let (_, parsed) = substitution_block_p(&Delimiters::default())
+            .parse(Position::default(), input)
+            .expect("Substitution block should be parsed");
+
+        assert_eq!(
+            parsed.token,
+            BalsaToken::SubstitutionBlock(Block {
+                start_pos: Position::default(),
+                end_pos: Position::default().advance(input),
+                source: input.to_string(),
+                token: "theme.primary".to_string(),
+            })
+        );
+
+        assert!(substitution_block_p(&Delimiters::default())
+            .parse(Position::default(), "{{ brandColor }}")
+            .is_err());
+    }
+
+    #[test]
+    fn test_macro_block_p() {
+        let input = r#"{{#macro button(label, href)}}<a href="{{ href : string }}">{ {{ label : string }}</a>{{/macro}}"#;
+
+        let p = macro_block_p(
+            &DepthGuard::new(DEFAULT_MAX_NESTING_DEPTH),
+            &ScanMemo::new(),
+            &Delimiters::default(),
+            false,
+        );
+
+        let (_, parsed) = p
+            .parse(Position::default(), input)
+            .expect("Macro block should be parsed");
+
+        let definition = match parsed.token {
+            BalsaToken::MacroBlock(block) => block.token,
+            token => panic!("Expected a macro block, got {:?}", token),
+        };
+
+        assert_eq!(definition.name, "button");
+        assert_eq!(definition.parameters, vec!["label", "href"]);
+        let body: Vec<Option<&str>> = definition
+            .body
+            .iter()
+            .map(|part| match part {
+                MacroPart::Text(text) => Some(text.as_str()),
+                MacroPart::Block(block) => {
+                    assert!(matches!(**block, BalsaToken::ParameterBlock(_)));
+                    None
+                }
+            })
+            .collect();
+
+        assert_eq!(
+            body,
+            vec![Some("<a href=\""), None, Some("\">{ "), None, Some("</a>")],
+            "Macro body parsed incorrectly"
+        );
+
+        let call = call_block_p(&Delimiters::default())
+            .parse(Position::default(), r#"{{ button("Sign up", signupUrl) }}"#)
+            .map(|(_, parsed)| parsed.token);
+
+        assert!(
+            matches!(
+                call,
+                Ok(BalsaToken::CallBlock(Block { token: MacroCall { ref name, ref arguments }, .. }))
+                    if name == "button" && arguments.len() == 2
+            ),
+            "Macro call should be parsed"
+        );
+    }
+
+    #[test]
+    fn test_conditional_block_p() {
+        let p = block_p(
+            &DepthGuard::new(DEFAULT_MAX_NESTING_DEPTH),
+            &ScanMemo::new(),
+            &Delimiters::default(),
+            false,
+        );
+        let parse = |input| {
+            p.parse(Position::default(), input)
+                .map(|(_, parsed)| parsed.token)
+        };
+
+        match parse("{{ if showBanner, defaultValue: false }}") {
+            Ok(BalsaToken::IfBlock(block)) => {
+                assert_eq!(
+                    block.token.variable_name,
+                    BalsaExpression::Identifier("showBanner".to_string())
+                );
+                assert_eq!(block.token.options.map(|o| o.len()), Some(1));
+            }
+            token => panic!("Expected an if block, got {:?}", token),
+        }
+
+        assert!(matches!(parse("{{else}}"), Ok(BalsaToken::ElseBlock(_))));
+        assert!(matches!(
+            parse("{{ endif }}"),
+            Ok(BalsaToken::EndIfBlock(_))
+        ));
+        assert!(
+            matches!(
+                parse("{{ if : string }}"),
+                Ok(BalsaToken::ParameterBlock(_))
+            ),
+            "`if` should still be a valid parameter name"
+        );
+        assert!(parse("{{ifBanner}}").is_err());
+    }
+
+    #[test]
+    fn test_each_block_p() {
+        let input = "{{#each posts: array<dict>}}<h2>{{ title: string }}</h2>{{/each}}";
+
+        let p = each_block_p(
+            &DepthGuard::new(DEFAULT_MAX_NESTING_DEPTH),
+            &ScanMemo::new(),
+            &Delimiters::default(),
+            false,
+        );
+
+        let (_, parsed) = p
+            .parse(Position::default(), input)
+            .expect("Each block should be parsed");
+
+        let section = match parsed.token {
+            BalsaToken::EachBlock(block) => block.token,
+            token => panic!("Expected an each block, got {:?}", token),
+        };
+
+        assert_eq!(
+            section.variable_type,
+            BalsaExpression::Type(BalsaType::array_of(BalsaType::dictionary_of(
+                BalsaType::Any
+            )))
+        );
+        assert_eq!(section.body.len(), 3, "Each body parsed incorrectly");
+
+        for (input, expected) in [
+            ("array<int>", BalsaType::array_of(BalsaType::Integer)),
+            ("dict<color>", BalsaType::dictionary_of(BalsaType::Color)),
+            (
+                "array< dict<string> >",
+                BalsaType::array_of(BalsaType::dictionary_of(BalsaType::String)),
+            ),
+        ] {
+            let parsed = balsa_type_p()
+                .parse(Position::default(), input)
+                .map(|(_, parsed)| parsed.token);
+
+            assert_eq!(parsed, Ok(expected), "Type `{}` parsed incorrectly", input);
+        }
+    }
+
+    #[test]
+    fn test_balsa_value_p() {
+        for (input, expected) in [
+            ("1.25", BalsaValue::Float(1.25)),
+            ("-0.5", BalsaValue::Float(-0.5)),
+            ("+2.0", BalsaValue::Float(2.0)),
+            ("42", BalsaValue::Integer(42)),
+            ("#ff0000", BalsaValue::Color("#ff0000".to_string())),
+            ("#0af", BalsaValue::Color("#0af".to_string())),
+            ("true", BalsaValue::Boolean(true)),
+            ("false", BalsaValue::Boolean(false)),
+            (
+                "{ primary: #ff0000, gap : 4 }",
+                BalsaValue::Dictionary(
+                    Dictionary::from_entries(
+                        BalsaType::Any,
+                        [
+                            (
+                                "primary".to_string(),
+                                BalsaValue::Color("#ff0000".to_string()),
+                            ),
+                            ("gap".to_string(), BalsaValue::Integer(4)),
+                        ],
+                    )
+                    .unwrap(),
+                ),
+            ),
+            (r#""1.5""#, BalsaValue::String("1.5".to_string())),
+            (r#""""#, BalsaValue::String(String::new())),
+            (
+                r#""Say \"hi\"\\\n\tto \u{1F600}""#,
+                BalsaValue::String("Say \"hi\"\\\n\tto \u{1F600}".to_string()),
+            ),
+        ] {
+            let parsed = balsa_value_p()
+                .parse(Position::default(), input)
+                .map(|(remainder, parsed)| (remainder, parsed.token));
+
+            assert_eq!(
+                parsed,
+                Ok(("", expected)),
+                "Value `{}` parsed incorrectly",
+                input
+            );
+        }
+
+        let parsed = path_p()
+            .parse(Position::default(), "theme.colors.primary }}")
+            .map(|(remainder, parsed)| (remainder, parsed.token));
+        assert_eq!(
+            parsed,
+            Ok((" }}", "theme.colors.primary".to_string())),
+            "Paths should be parsed with their separators"
+        );
+
+        let parsed = balsa_expr_p()
+            .parse(Position::default(), "trueColor }}")
+            .map(|(remainder, parsed)| (remainder, parsed.token));
+        assert_eq!(
+            parsed,
+            Ok((" }}", BalsaExpression::Identifier("trueColor".to_string()))),
+            "Names which start with a boolean literal should be parsed as identifiers"
+        );
+
+        for invalid_input in [
+            "-",
+            "-.5",
+            "+x",
+            "#ff00f",
+            "#FF0000",
+            "#each",
+            "{ a: { b: 1 } }",
+            r#""\q""#,
+            r#""\u{110000}""#,
+            r#""\u{}""#,
+            r#""unterminated \""#,
+        ] {
+            assert!(
+                balsa_value_p()
+                    .parse(Position::default(), invalid_input)
+                    .is_err(),
+                "Value parser should not parse `{}`",
+                invalid_input
+            );
+        }
+    }
+
+    #[test]
+    fn test_helper_block_p() {
+        let valid_input = r#"{{ t "welcome.title" }}"#;
+        let valid_output = BalsaToken::HelperBlock(Block {
+            start_pos: Position::default(),
+            end_pos: Position::default().advance(valid_input),
+            source: valid_input.to_string(),
+            token: HelperCall {
+                helper: Helper::Translate,
+                arguments: vec![BalsaExpression::Value(BalsaValue::String(
+                    "welcome.title".to_string(),
+                ))],
+                named_arguments: Vec::new(),
+            },
+        });
+
+        let p = helper_block_p(&Delimiters::default());
+
+        let (_, parsed) = p
+            .parse(Position::default(), valid_input)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Helper block parser should successfully parse input `{}`",
+                    valid_input
+                )
+            });
+
+        assert_eq!(
+            parsed.token, valid_output,
+            "Helper block parser failed to parse `{}`",
+            valid_input
+        );
+
+        let named_input = r#"{{ include "card.html" title=postTitle size = 2 }}"#;
+        let (_, parsed) = p
+            .parse(Position::default(), named_input)
+            .expect("Helper block parser should parse named arguments");
+
+        assert_eq!(
+            parsed.token,
+            BalsaToken::HelperBlock(Block {
+                start_pos: Position::default(),
+                end_pos: Position::default().advance(named_input),
+                source: named_input.to_string(),
+                token: HelperCall {
+                    helper: Helper::Include,
+                    arguments: vec![BalsaExpression::Value(BalsaValue::String(
+                        "card.html".to_string(),
+                    ))],
+                    named_arguments: vec![
+                        (
+                            "title".to_string(),
+                            BalsaExpression::Identifier("postTitle".to_string()),
+                        ),
+                        (
+                            "size".to_string(),
+                            BalsaExpression::Value(BalsaValue::Integer(2)),
+                        ),
+                    ],
+                },
+            }),
+            "Helper block parser failed to parse `{}`",
+            named_input
+        );
+
+        for invalid_input in [r#"{{ t }}"#, r#"{{ tr "x" }}"#, r#"{{ t"x" }}"#] {
+            assert!(
+                p.parse(Position::default(), invalid_input).is_err(),
+                "Helper block parser should not parse `{}`",
+                invalid_input
+            );
+        }
+    }
+
+    #[test]
+    fn test_balsa_p_single_braces() {
+        let valid_input = r#"{{ a: string }}<style>body { color: red; }</style>{{{ b: int }}{{ c: color }}<script>if (x) { f({ y: 1 }); }</script>"#;
+
+        let p = balsa_p(
+            &DepthGuard::new(DEFAULT_MAX_NESTING_DEPTH),
+            &ScanMemo::new(),
+            &Delimiters::default(),
+            false,
+        );
+
+        let (_, parsed) = p
+            .parse(Position::default(), valid_input)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Balsa parser should successfully parse input `{}`",
+                    valid_input
+                )
+            });
+
+        let names = parsed
+            .token
+            .iter()
+            .filter_map(|t| match t {
+                BalsaToken::ParameterBlock(b) => b.token.variable_name.as_identifier(),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            names,
+            vec!["a", "b", "c"],
+            "Balsa parser failed to find every block in input `{}`",
+            valid_input
+        );
+    }
+
+    #[test]
+    fn test_balsa_p_unterminated_blocks() {
+        // Each unterminated macro, `#each` and raw block would scan the rest of the template for
+        // its closing block before it's skipped, taking quadratic time in the number of them.
+        // Their bodies are memoized, so each offset is scanned at most once per kind of block.
+        let input =
+            "{{#macro m(a)}}{{#each items: array<dict>}}{{raw}}{{ a : string }}".repeat(100);
+
+        let memo = ScanMemo::new();
+        let p = balsa_p(
+            &DepthGuard::new(DEFAULT_MAX_NESTING_DEPTH),
+            &memo,
+            &Delimiters::default(),
+            false,
+        );
+
+        let (_, parsed) = p
+            .parse(Position::default(), &input)
+            .expect("Balsa parser should skip unterminated blocks");
+
+        assert_eq!(parsed.token.len(), 100);
+        assert!(
+            memo.steps() <= 3 * input.len(),
+            "Balsa parser scanned unterminated blocks {} times for {} bytes of input",
+            memo.steps(),
+            input.len()
+        );
+    }
+
+    #[test]
+    fn test_balsa_p_strict() {
+        let input = r#"{{ a: string }}<style>body { color: red; }</style>{{{ b: int }}"#;
+
+        let p = balsa_p(
+            &DepthGuard::new(DEFAULT_MAX_NESTING_DEPTH),
+            &ScanMemo::new(),
+            &Delimiters::default(),
+            true,
+        );
+
+        assert!(
+            matches!(
+                p.parse(Position::default(), input),
+                Err(ParseError::MalformedBlock(pos)) if pos.offset == 50
+            ),
+            "Strict Balsa parser should reject the stray delimiter in input `{}`",
+            input
+        );
+    }
+
     #[test]
     fn test_balsa_p() {
         let valid_input = r#"
@@ -336,28 +1824,30 @@ mod tests {
         </html>
         "#;
 
-        let mut valid_declarations = Vec::new();
-        valid_declarations.push(Declaration {
+        let valid_declarations = vec![Declaration {
             identifier: BalsaExpression::Identifier("test".to_string()),
             variable_type: BalsaExpression::Type(BalsaType::String),
             value: BalsaExpression::Value(BalsaValue::String("hello".to_string())),
-        });
+            condition: None,
+        }];
 
         let valid_declaration_output = BalsaToken::DeclarationBlock(Block {
-            start_pos: 51,
-            end_pos: 116,
+            start_pos: Position::new(51, 4, 17),
+            end_pos: Position::new(116, 6, 19),
+            source: "{{@\n                    test: string = \"hello\"\n                }}"
+                .to_string(),
             token: valid_declarations,
         });
 
-        let mut valid_parameter_options = HashMap::new();
-        valid_parameter_options.insert(
+        let valid_parameter_options = vec![(
             "defaultValue".to_string(),
             BalsaExpression::Value(BalsaValue::String("test".to_string())),
-        );
+        )];
 
         let valid_parameter_output = BalsaToken::ParameterBlock(Block {
-            start_pos: 178,
-            end_pos: 224,
+            start_pos: Position::new(178, 9, 23),
+            end_pos: Position::new(224, 9, 69),
+            source: r#"{{ helloWorld: string, defaultValue: "test" }}"#.to_string(),
             token: ParameterBlockIntermediate {
                 variable_name: BalsaExpression::Identifier("helloWorld".to_string()),
                 variable_type: BalsaExpression::Type(BalsaType::String),
@@ -367,12 +1857,21 @@ mod tests {
 
         let valid_output = vec![valid_declaration_output, valid_parameter_output];
 
-        let p = balsa_p();
+        let p = balsa_p(
+            &DepthGuard::new(DEFAULT_MAX_NESTING_DEPTH),
+            &ScanMemo::new(),
+            &Delimiters::default(),
+            false,
+        );
 
-        let (_, parsed) = p.parse(0, valid_input).expect(&format!(
-            "Balsa parser should successfully parse input `{}`",
-            valid_input
-        ));
+        let (_, parsed) = p
+            .parse(Position::default(), valid_input)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Balsa parser should successfully parse input `{}`",
+                    valid_input
+                )
+            });
 
         assert!(
             PartialEq::eq(&parsed.token, &valid_output),
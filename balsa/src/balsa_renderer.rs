@@ -1,96 +1,483 @@
-use std::str::Chars;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    iter::Zip,
+    slice::Iter,
+    time::Instant,
+};
 
 use crate::{
-    balsa_compiler::{CompiledTemplate, ReplaceWith, ReplacementInstruction},
-    errors::BalsaError,
-    BalsaParameters, BalsaResult, BalsaValue,
+    balsa_compiler::{
+        direction_attribute, CompiledTemplate, ComputedDescription, EachPart, ItemsDescription,
+        ParameterDescription, PartialArgument, PartialDescription, ReplaceWith,
+        ReplacementInstruction,
+    },
+    balsa_types::lookup_path,
+    errors::{BalsaError, ComputationError, RenderAborted},
+    expression::Expression,
+    message_format::{self, MessagePart},
+    options::{EscapePolicy, HydrationMarkers, RenderOptions},
+    parser::Position,
+    validators::is_safe_url,
+    Array, BalsaParameters, BalsaResult, BalsaType, BalsaValue, Dictionary, Image, Locale,
 };
 
+/// The default maximum depth to which templates included in `#each` sections are rendered
+/// within each other.
+const DEFAULT_MAX_INCLUDE_DEPTH: usize = 32;
+
 /// Provides methods for rendering a compiled template.
 ///
 /// Renderers are meant to be used a single timk
 pub(crate) struct Renderer<'a> {
-    raw_template: &'a str,
     compiled_template: &'a CompiledTemplate,
 }
 
 /// Holds state for a currently rendering template.
 struct RenderContext<'a> {
     output: String,
-    chars_written: usize,
-    chars: Chars<'a>,
+    compiled_template: &'a CompiledTemplate,
     parameters: &'a BalsaParameters,
+    /// The values of the computed parameters which have been evaluated so far.
+    computed_values: HashMap<String, BalsaValue>,
+    escape_policy: EscapePolicy,
+    locale: &'a Locale,
+    bidi_isolation: bool,
+    hydration_markers: HydrationMarkers,
+    max_output_size: Option<usize>,
+    max_loop_iterations: Option<usize>,
+    /// The number of items the `#each` sections have repeated their body for so far.
+    iterations: usize,
+    /// The HTML each slot is filled with.
+    slots: &'a HashMap<String, String>,
+    usage: Option<ParameterUsage>,
+    /// The number of bytes of output which were already taken by [`RenderContext::take_output`].
+    taken: usize,
+    /// Whether the current part of each enclosing conditional section is rendered, innermost
+    /// last.
+    conditions: Vec<bool>,
+    options: &'a RenderOptions,
+    /// The templates included in `#each` sections of the template being rendered, which
+    /// templates rendered for its items share.
+    partials: &'a BTreeMap<String, CompiledTemplate>,
+    /// The number of templates included in `#each` sections the render is within.
+    include_depth: usize,
+}
+
+/// A render in progress, which processes one replacement at a time so that the output can be
+/// taken as it is produced.
+pub(crate) struct RenderSteps<'a> {
+    ctx: RenderContext<'a>,
+    steps: Zip<Iter<'a, String>, Iter<'a, ReplacementInstruction>>,
+    /// The static text after the last replacement.
+    trailing: &'a str,
+    options: &'a RenderOptions,
+    end_pos: Position,
+    done: bool,
+}
+
+/// The names of the parameters a render looked up, in the order they were first used.
+#[derive(Debug, Default)]
+pub(crate) struct ParameterUsage {
+    /// Parameters whose values were passed to the render.
+    pub(crate) used: Vec<String>,
+    /// Parameters which fell back to their default values.
+    pub(crate) defaulted: Vec<String>,
 }
 
 impl<'a> Renderer<'a> {
     /// Creates a new [`Renderer`] for the given template.
-    pub(crate) fn new(raw_template: &'a str, compiled_template: &'a CompiledTemplate) -> Self {
-        let p = BalsaParameters::default();
-
-        Self {
-            raw_template,
-            compiled_template,
-        }
+    pub(crate) fn new(compiled_template: &'a CompiledTemplate) -> Self {
+        Self { compiled_template }
     }
 
-    /// Renders the template with the given [`BalsaParameters`].
+    /// Renders the template with the given [`BalsaParameters`] and [`RenderOptions`].
     pub(crate) fn render_with_parameters(
         &self,
         parameters: &'a BalsaParameters,
+        options: &'a RenderOptions,
     ) -> BalsaResult<String> {
-        let mut ctx = RenderContext::new(self.raw_template, parameters);
+        self.render(parameters, options, None)
+            .map(|(output, _)| output)
+    }
+
+    /// Renders the template like [`Renderer::render_with_parameters`], also recording which
+    /// parameters were used and which fell back to their default values.
+    pub(crate) fn render_with_usage(
+        &self,
+        parameters: &'a BalsaParameters,
+        options: &'a RenderOptions,
+    ) -> BalsaResult<(String, ParameterUsage)> {
+        self.render(parameters, options, Some(ParameterUsage::default()))
+            .map(|(output, usage)| (output, usage.unwrap_or_default()))
+    }
+
+    /// Renders the template into `writer`, writing the output of each replacement as soon as it
+    /// is rendered rather than collecting the whole document first.
+    pub(crate) fn render_to_writer(
+        &self,
+        parameters: &'a BalsaParameters,
+        options: &'a RenderOptions,
+        writer: &mut dyn io::Write,
+    ) -> BalsaResult<()> {
+        for chunk in self.start(parameters, options, None)? {
+            writer
+                .write_all(chunk?.as_bytes())
+                .map_err(BalsaError::write_output_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts rendering the template one replacement at a time. See [`RenderSteps`].
+    #[cfg(feature = "tokio")]
+    pub(crate) fn render_steps(
+        &self,
+        parameters: &'a BalsaParameters,
+        options: &'a RenderOptions,
+    ) -> BalsaResult<RenderSteps<'a>> {
+        self.start(parameters, options, None)
+    }
+
+    /// Renders the template, recording parameter usage into `usage` if it is provided.
+    fn render(
+        &self,
+        parameters: &'a BalsaParameters,
+        options: &'a RenderOptions,
+        usage: Option<ParameterUsage>,
+    ) -> BalsaResult<(String, Option<ParameterUsage>)> {
+        let mut steps = self.start(parameters, options, usage)?;
+        while let Some(step) = steps.advance() {
+            step?;
+        }
+
+        Ok(steps.ctx.output())
+    }
+
+    /// Renders every replacement which only depends on `parameters`, returning a compiled
+    /// template with only the replacements which remain, in which the output of the others is
+    /// part of the static text.
+    ///
+    /// Values are written as with the default [`RenderOptions`]. Computed parameters which read
+    /// both `parameters` and other parameters keep their expressions, with the values from
+    /// `parameters` written into them.
+    pub(crate) fn partially_render(
+        &self,
+        parameters: &BalsaParameters,
+    ) -> BalsaResult<CompiledTemplate> {
+        let options = RenderOptions::default();
+        let mut ctx = RenderContext::new(self.compiled_template, parameters, &options, None);
+
+        let is_supplied = |name: &str| parameters.get(name).is_some();
+        let mut static_computed = HashSet::new();
+        for c in &self.compiled_template.computed {
+            let is_static = c.expression.inputs().into_iter().all(|input| {
+                static_computed.contains(input)
+                    || (self.compiled_template.computed(input).is_none() && is_supplied(input))
+            });
+
+            if is_static {
+                static_computed.insert(c.name.as_str());
+            }
+        }
+
+        let mut computed = Vec::with_capacity(self.compiled_template.computed.len());
+        for c in &self.compiled_template.computed {
+            let expression = if static_computed.contains(c.name.as_str()) {
+                match ctx.computed_value(c.source_start_pos, c)? {
+                    BalsaValue::Integer(i) => Expression::Integer(i),
+                    BalsaValue::Float(f) => Expression::Float(f),
+                    _ => unreachable!("computed parameters are numbers"),
+                }
+            } else {
+                ctx.bake_expression(c.source_start_pos, &c.expression, &is_supplied)?
+            };
+
+            computed.push(ComputedDescription {
+                expression,
+                ..c.clone()
+            });
+        }
+
+        let is_baked = |name: &str| {
+            static_computed.contains(name)
+                || (self.compiled_template.computed(name).is_none() && is_supplied(name))
+        };
+
+        let mut replacements = Vec::new();
+        let mut segments = Vec::new();
+        // Whether each enclosing conditional section's condition was supplied, innermost last.
+        // Sections inside a skipped section are always resolved, as they are skipped as well.
+        let mut resolved_conditions = Vec::new();
+
+        for (segment, replacement) in self
+            .compiled_template
+            .segments
+            .iter()
+            .zip(&self.compiled_template.replacements)
+        {
+            ctx.push_segment(segment);
+
+            let is_static = match &replacement.replace_with {
+                ReplaceWith::If(p) => {
+                    let resolved = ctx.is_skipping() || is_baked(&p.variable_name);
+                    resolved_conditions.push(resolved);
+
+                    resolved
+                }
+                ReplaceWith::Else => resolved_conditions.last() == Some(&true),
+                ReplaceWith::EndIf => resolved_conditions.pop() == Some(true),
+                _ if ctx.is_skipping() => true,
+                ReplaceWith::Slot(_) => false,
+                replace_with => replace_with
+                    .parameter()
+                    .is_none_or(|p| is_baked(&p.variable_name)),
+            };
+
+            if is_static {
+                ctx.push_replacement(replacement)?;
+            } else {
+                segments.push(std::mem::take(&mut ctx.output));
+                replacements.push(replacement.clone());
+            }
+        }
+
+        ctx.push_segment(self.compiled_template.trailing());
+        segments.push(ctx.output);
+
+        Ok(CompiledTemplate {
+            replacements,
+            segments,
+            computed,
+            ..self.compiled_template.clone()
+        })
+    }
+
+    /// Checks the parameters against the render's limits and prepares the render.
+    fn start(
+        &self,
+        parameters: &'a BalsaParameters,
+        options: &'a RenderOptions,
+        usage: Option<ParameterUsage>,
+    ) -> BalsaResult<RenderSteps<'a>> {
+        parameters
+            .check_limits(options.max_parameters, options.max_value_size)
+            .map_err(BalsaError::limit_exceeded)?;
+
+        let ctx = RenderContext::new(self.compiled_template, parameters, options, usage);
+
+        Ok(RenderSteps {
+            ctx,
+            steps: self
+                .compiled_template
+                .segments
+                .iter()
+                .zip(&self.compiled_template.replacements),
+            trailing: self.compiled_template.trailing(),
+            options,
+            end_pos: self.compiled_template.end_pos,
+            done: false,
+        })
+    }
+
+    /// Fails if the render's deadline has passed or its cancellation token was cancelled,
+    /// attributing the error to the block at `pos`.
+    fn check_aborted(pos: Position, options: &RenderOptions) -> BalsaResult<()> {
+        if options.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(BalsaError::render_aborted(
+                pos,
+                RenderAborted::DeadlineExceeded,
+            ));
+        }
+
+        if options
+            .cancellation
+            .as_ref()
+            .is_some_and(|c| c.is_cancelled())
+        {
+            return Err(BalsaError::render_aborted(pos, RenderAborted::Cancelled));
+        }
+
+        Ok(())
+    }
+}
+
+impl RenderSteps<'_> {
+    /// Processes the next replacement, or the static text after the last one, returning `None`
+    /// once the render has finished or failed.
+    fn advance(&mut self) -> Option<BalsaResult<()>> {
+        if self.done {
+            return None;
+        }
+
+        let result = match self.steps.next() {
+            Some((segment, replacement)) => {
+                Renderer::check_aborted(replacement.start_pos, self.options)
+                    .and_then(|_| self.ctx.next(segment, replacement))
+                    .and_then(|_| self.ctx.check_output_size(replacement.start_pos))
+            }
+            None => {
+                self.done = true;
+                self.ctx.push_segment(self.trailing);
+                self.ctx.check_output_size(self.end_pos)
+            }
+        };
 
-        for replacement in &self.compiled_template.replacements {
-            ctx.next(replacement)?;
+        if result.is_err() {
+            self.done = true;
         }
 
-        Ok(ctx.output())
+        Some(result)
+    }
+}
+
+/// Yields the output of each replacement, along with the static text preceding it, as it is
+/// rendered. The render stops after the first error.
+impl Iterator for RenderSteps<'_> {
+    type Item = BalsaResult<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance()
+            .map(|step| step.map(|_| self.ctx.take_output()))
     }
 }
 
 impl<'a> RenderContext<'a> {
     /// Creates a new [`RenderContext`] from the supplied raw template source.
-    fn new(raw_template: &'a str, parameters: &'a BalsaParameters) -> Self {
+    fn new(
+        compiled_template: &'a CompiledTemplate,
+        parameters: &'a BalsaParameters,
+        options: &'a RenderOptions,
+        usage: Option<ParameterUsage>,
+    ) -> Self {
         Self {
             output: String::new(),
-            chars_written: 0,
-            chars: raw_template.chars(),
+            compiled_template,
             parameters,
+            computed_values: HashMap::new(),
+            escape_policy: compiled_template.escape_policy,
+            locale: &options.locale,
+            bidi_isolation: options.bidi_isolation,
+            hydration_markers: options.hydration_markers,
+            max_output_size: options.max_output_size,
+            max_loop_iterations: options.max_loop_iterations,
+            iterations: 0,
+            slots: &options.slots,
+            usage,
+            taken: 0,
+            conditions: Vec::new(),
+            options,
+            partials: &compiled_template.partials,
+            include_depth: 0,
         }
     }
 
-    /// Processes the next ReplacementInstruction.
-    fn next(&mut self, replacement: &ReplacementInstruction) -> BalsaResult<()> {
-        self.prepend_missing_chars(replacement);
+    /// Processes the next ReplacementInstruction, along with the static `segment` preceding it.
+    fn next(&mut self, segment: &str, replacement: &ReplacementInstruction) -> BalsaResult<()> {
+        self.push_segment(segment);
+        self.push_replacement(replacement)
+    }
 
+    /// Appends the output of a single `replacement` to the output.
+    fn push_replacement(&mut self, replacement: &ReplacementInstruction) -> BalsaResult<()> {
         match &replacement.replace_with {
+            // Conditions inside a skipped section are never looked up, so their parameters
+            // don't need to be passed.
+            ReplaceWith::If(_) if self.is_skipping() => self.conditions.push(false),
+            ReplaceWith::If(p) => match self.parameter_value(replacement.start_pos, p)? {
+                BalsaValue::Boolean(condition) => self.conditions.push(condition),
+                // Optional conditions which were left out are false.
+                BalsaValue::Null => self.conditions.push(false),
+                _ => unreachable!("conditions are bool parameters"),
+            },
+            ReplaceWith::Else => {
+                if let Some(condition) = self.conditions.last_mut() {
+                    *condition = !*condition;
+                }
+            }
+            ReplaceWith::EndIf => {
+                self.conditions.pop();
+            }
+            _ if self.is_skipping() => {}
             ReplaceWith::Parameter(p) => {
-                let value = self
-                    .parameters
-                    .get(&p.variable_name)
-                    .or_else(|| p.default_value.clone());
-
-                match value {
-                    None => return Err(BalsaError::missing_parameter(p.variable_name.clone())),
-                    Some(v) => {
-                        let v = v.try_cast(p.variable_type.clone()).map_err(|_| {
-                            BalsaError::invalid_parameter_type(
-                                p.variable_name.clone(),
-                                v.clone(),
-                                v.get_type(),
-                                p.variable_type.clone(),
-                            )
-                        })?;
-
-                        match &v {
-                            BalsaValue::String(s) => self.output.push_str(s),
-                            BalsaValue::Color(s) => self.output.push_str(s),
-                            BalsaValue::Integer(i) => self.output.push_str(&i.to_string()),
-                            BalsaValue::Float(f) => self.output.push_str(&f.to_string()),
-                            _ => todo!(),
+                let value = self.parameter_value(replacement.start_pos, p)?;
+
+                self.push_marker_open(&p.variable_name);
+                self.push_described(replacement.start_pos, p, &value)?;
+                self.push_marker_close();
+            }
+            ReplaceWith::Plural(p) => {
+                match self.parameter_value(replacement.start_pos, &p.parameter)? {
+                    BalsaValue::Integer(n) => {
+                        let form = &p.forms[p.rule.form_index(n)];
+                        self.push_marker_open(&p.parameter.variable_name);
+                        self.push_value(form);
+                        self.push_marker_close();
+                    }
+                    _ => unreachable!("plural counts are integer parameters"),
+                }
+            }
+            ReplaceWith::Direction(p) => match self.parameter_value(replacement.start_pos, p)? {
+                BalsaValue::String(tag) => {
+                    let direction = Locale::new(tag).direction();
+                    self.output.push_str(&direction_attribute(direction));
+                }
+                _ => unreachable!("locales are string parameters"),
+            },
+            ReplaceWith::Message(m) => self.push_value(m.text.as_ref().unwrap_or(&m.key)),
+            ReplaceWith::Text(text) => self.push_value(text),
+            ReplaceWith::Markup(markup) => self.output.push_str(markup),
+            ReplaceWith::Slot(name) => {
+                if let Some(html) = self.slots.get(name) {
+                    self.output.push_str(html);
+                }
+            }
+            ReplaceWith::Each(e) => {
+                let items = match self.parameter_value(replacement.start_pos, &e.parameter)? {
+                    BalsaValue::Array(items) => items,
+                    // Optional sections which were left out have no items.
+                    BalsaValue::Null => Array::new(BalsaType::Any),
+                    _ => unreachable!("sections are array parameters"),
+                };
+
+                self.push_marker_open(&e.parameter.variable_name);
+                for item in &items {
+                    self.iterations += 1;
+                    if let Some(max_iterations) = self.max_loop_iterations {
+                        if self.iterations > max_iterations {
+                            return Err(BalsaError::too_many_iterations(
+                                replacement.start_pos,
+                                max_iterations,
+                            ));
                         }
                     }
+
+                    let item = match item {
+                        BalsaValue::Dictionary(item) => item,
+                        _ => unreachable!("sections are arrays of dictionaries"),
+                    };
+
+                    for part in &e.body {
+                        match part {
+                            EachPart::Markup(markup) => self.output.push_str(markup),
+                            EachPart::Field(p) => {
+                                let value = Self::field_value(
+                                    replacement.start_pos,
+                                    &e.parameter.variable_name,
+                                    item,
+                                    p,
+                                )?;
+                                self.push_described(replacement.start_pos, p, &value)?;
+                            }
+                            EachPart::Include(partial) => {
+                                self.push_partial(replacement.start_pos, partial, item)?
+                            }
+                        }
+                    }
+
+                    self.check_output_size(replacement.start_pos)?;
                 }
+                self.push_marker_close();
             }
             ReplaceWith::Nothing => {}
         }
@@ -98,32 +485,650 @@ impl<'a> RenderContext<'a> {
         Ok(())
     }
 
-    /// Prepends chars that come before a replacement block that haven't previously been prepended
-    /// and drops chars up to the replacement's `end_pos`.
-    fn prepend_missing_chars(&mut self, replacement: &ReplacementInstruction) {
-        if self.chars_written < replacement.start_pos {
-            let n = replacement.start_pos - self.chars_written;
-            self.output
-                .push_str(&(&mut self.chars).take(n).collect::<String>());
+    /// Appends the template included by the `#each` section at `pos` for the `item`, rendered
+    /// with only the parameters bound by the `partial`'s arguments. Fields which the item
+    /// doesn't have leave their parameters to their default values.
+    fn push_partial(
+        &mut self,
+        pos: Position,
+        partial: &PartialDescription,
+        item: &Dictionary,
+    ) -> BalsaResult<()> {
+        let max_depth = self
+            .options
+            .max_include_depth
+            .unwrap_or(DEFAULT_MAX_INCLUDE_DEPTH);
+        if self.include_depth >= max_depth {
+            return Err(BalsaError::partial_too_deep(pos, max_depth));
+        }
+
+        let mut parameters = BalsaParameters::new();
+        for (name, argument) in &partial.arguments {
+            let value = match argument {
+                PartialArgument::Field(field) => match lookup_path(field, |key| item.get(key)) {
+                    Some(value) => value.clone(),
+                    None => continue,
+                },
+                PartialArgument::Value(value) => value.clone(),
+            };
+
+            parameters = parameters.insert(name.clone(), value);
+        }
+
+        let Some(template) = self.partials.get(&partial.path) else {
+            unreachable!("included templates are compiled along with the template")
+        };
+
+        let mut ctx = RenderContext::new(template, &parameters, self.options, None);
+        ctx.partials = self.partials;
+        ctx.include_depth = self.include_depth + 1;
+        ctx.iterations = self.iterations;
+        for (segment, replacement) in template.segments.iter().zip(&template.replacements) {
+            ctx.next(segment, replacement)?;
+        }
+        ctx.push_segment(template.trailing());
+
+        self.iterations = ctx.iterations;
+        self.output.push_str(&ctx.output);
+
+        Ok(())
+    }
+
+    /// Appends the `value` of the parameter `p` to the output, after passing it through the
+    /// parameter's `filters`. Parameters marked `escape: false` are written as-is, and optional
+    /// parameters which were left out are written as their `placeholder`, if they have one.
+    fn push_described(
+        &mut self,
+        pos: Position,
+        p: &ParameterDescription,
+        value: &BalsaValue,
+    ) -> BalsaResult<()> {
+        let filtered;
+        let value = match value {
+            BalsaValue::Null => value,
+            _ if p.filters.is_empty() => value,
+            _ => {
+                filtered = self.compiled_template.filters.apply(
+                    pos,
+                    &p.variable_name,
+                    &p.filters,
+                    value.clone(),
+                )?;
+                &filtered
+            }
+        };
+
+        let escape_policy = self.escape_policy;
+        if !p.escape {
+            self.escape_policy = EscapePolicy::None;
+        }
+
+        let pushed = match (value, &p.format) {
+            (BalsaValue::Null, _) => {
+                if let Some(placeholder) = &p.placeholder {
+                    self.push_value(placeholder);
+                }
+                Ok(())
+            }
+            #[cfg(feature = "chrono")]
+            (BalsaValue::DateTime(d), Some(format)) => {
+                let formatted = self.locale.format_datetime(d, Some(format));
+                self.push_value(&formatted);
+                Ok(())
+            }
+            _ => self.push_parameter(pos, value),
+        };
+        self.escape_policy = escape_policy;
+
+        pushed
+    }
+
+    /// Looks up the value of the field `p` in an `item` of the section `section`, falling back to
+    /// the field's default value, or `null` if the field is optional, and casts it to the field's
+    /// type, which must be one of its `oneOf` values. Errors name the field as `section.field`.
+    fn field_value(
+        pos: Position,
+        section: &str,
+        item: &Dictionary,
+        p: &ParameterDescription,
+    ) -> BalsaResult<BalsaValue> {
+        let name = || format!("{}.{}", section, p.variable_name);
+
+        let value = match lookup_path(&p.variable_name, |key| item.get(key))
+            .filter(|value| **value != BalsaValue::Null)
+            .or(p.default_value.as_ref())
+        {
+            Some(value) => value,
+            None if p.optional => return Ok(BalsaValue::Null),
+            None => return Err(BalsaError::missing_parameter(pos, name())),
+        };
+
+        let value = value.try_cast(p.variable_type.clone()).map_err(|_| {
+            BalsaError::invalid_parameter_type(
+                pos,
+                name(),
+                value.clone(),
+                value.get_type(),
+                p.variable_type.clone(),
+            )
+        })?;
+
+        Self::check_allowed(pos, name, p, &value)?;
+
+        Ok(value)
+    }
+
+    /// Appends the value of a parameter to the output.
+    fn push_parameter(&mut self, pos: Position, value: &BalsaValue) -> BalsaResult<()> {
+        match value {
+            BalsaValue::String(s) => self.push_string(s),
+            BalsaValue::Color(s) | BalsaValue::Url(s) => self.push_value(s),
+            BalsaValue::MessageFormat(pattern) => {
+                let parts = message_format::parse(pattern)
+                    .expect("message formats are checked when they are casted");
+
+                let mut message = String::new();
+                self.format_message(pos, &parts, None, &mut message)?;
+                self.push_value(&message);
+            }
+            BalsaValue::Integer(i) => self.push_number(&i.to_string()),
+            BalsaValue::Float(f) => self.push_number(&f.to_string()),
+            BalsaValue::Boolean(b) => self.push_value(&b.to_string()),
+            BalsaValue::Currency(c) => {
+                let currency = self.locale.format_currency(c);
+                self.push_value(&currency);
+            }
+            BalsaValue::Image(image) => self.push_image(image),
+            #[cfg(feature = "chrono")]
+            BalsaValue::DateTime(d) => {
+                let formatted = self.locale.format_datetime(d, None);
+                self.push_value(&formatted);
+            }
+            BalsaValue::Array(_) | BalsaValue::Dictionary(_) => self.push_value(&value.to_string()),
+            BalsaValue::Null => {}
+        }
+
+        Ok(())
+    }
+
+    /// Appends an `<img>` tag for `image` to the output. Its attributes are always escaped, as
+    /// they are part of the tag.
+    fn push_image(&mut self, image: &Image) {
+        let escape_policy = self.escape_policy;
+        self.escape_policy = EscapePolicy::Html;
+
+        self.output.push_str("<img");
+        for (name, value) in image.attributes() {
+            self.output.push_str(&format!(" {}=\"", name));
+            self.push_value(&value);
+            self.output.push('"');
+        }
+        self.output.push('>');
+
+        self.escape_policy = escape_policy;
+    }
+
+    /// Looks up the value of the parameter `p`, falling back to its default value, and casts it
+    /// to the parameter's type. Computed parameters are evaluated instead. Fails if an array has
+    /// fewer items than its `minItems` or more than its `maxItems`, or if the value isn't one of
+    /// its `oneOf` values.
+    fn parameter_value(
+        &mut self,
+        pos: Position,
+        p: &ParameterDescription,
+    ) -> BalsaResult<BalsaValue> {
+        let compiled_template = self.compiled_template;
+
+        let value = match compiled_template.computed(&p.variable_name) {
+            Some(c) => self.computed_value(pos, c)?,
+            None => self.supplied_value(pos, p)?,
+        };
+        if value == BalsaValue::Null {
+            return Ok(value);
+        }
+
+        let value = value.try_cast(p.variable_type.clone()).map_err(|_| {
+            BalsaError::invalid_parameter_type(
+                pos,
+                p.variable_name.clone(),
+                value.clone(),
+                value.get_type(),
+                p.variable_type.clone(),
+            )
+        })?;
+
+        if let BalsaValue::Array(items) = &value {
+            let ItemsDescription {
+                min_items,
+                max_items,
+                ..
+            } = p.items;
+
+            if min_items.is_some_and(|min| items.len() < min)
+                || max_items.is_some_and(|max| items.len() > max)
+            {
+                return Err(BalsaError::invalid_item_count(
+                    pos,
+                    p.variable_name.clone(),
+                    items.len(),
+                    min_items,
+                    max_items,
+                ));
+            }
+        }
+
+        Self::check_allowed(pos, || p.variable_name.clone(), p, &value)?;
+
+        Ok(value)
+    }
+
+    /// Fails if `value` isn't one of the `oneOf` values of the parameter `p`, or if `p` is marked
+    /// `safe: true` and `value` is a URL which isn't safe to link to, naming the parameter `name`
+    /// in the error.
+    fn check_allowed(
+        pos: Position,
+        name: impl FnOnce() -> String,
+        p: &ParameterDescription,
+        value: &BalsaValue,
+    ) -> BalsaResult<()> {
+        if let BalsaValue::Url(url) = value {
+            if p.safe && !is_safe_url(url) {
+                return Err(BalsaError::unsafe_url(pos, name(), url.clone()));
+            }
+        }
+
+        if p.one_of.is_empty() || p.one_of.contains(value) {
+            return Ok(());
+        }
+
+        Err(BalsaError::disallowed_value(
+            pos,
+            name(),
+            value.clone(),
+            p.one_of.clone(),
+        ))
+    }
+
+    /// Looks up the value passed to the render for the parameter `p`, falling back to its
+    /// default value, or `null` if the parameter is optional. Parameters passed `null` are
+    /// treated as if they weren't passed.
+    fn supplied_value(
+        &mut self,
+        pos: Position,
+        p: &ParameterDescription,
+    ) -> BalsaResult<BalsaValue> {
+        match self
+            .parameters
+            .get_localized(&p.variable_name, self.locale)
+            .filter(|value| *value != BalsaValue::Null)
+        {
+            Some(value) => {
+                self.record_usage(&p.variable_name, false);
+                Ok(value)
+            }
+            None => {
+                let value = match &p.default_value {
+                    Some(value) => value.clone(),
+                    None if p.optional => BalsaValue::Null,
+                    None => {
+                        return Err(BalsaError::missing_parameter(pos, p.variable_name.clone()))
+                    }
+                };
+                self.record_usage(&p.variable_name, true);
+                Ok(value)
+            }
+        }
+    }
+
+    /// Evaluates the computed parameter `c`, reusing its value if it was already evaluated
+    /// during this render.
+    ///
+    /// A float result is truncated towards zero when the parameter is an integer.
+    fn computed_value(
+        &mut self,
+        pos: Position,
+        c: &'a ComputedDescription,
+    ) -> BalsaResult<BalsaValue> {
+        if let Some(value) = self.computed_values.get(&c.name) {
+            return Ok(value.clone());
+        }
+
+        let compiled_template = self.compiled_template;
+        let failed = |reason| BalsaError::computation_failed(pos, c.name.clone(), reason);
+
+        let value = c.expression.evaluate(
+            &mut |input| match input {
+                Expression::Parameter(name) => self.computed_input(pos, name),
+                Expression::Computed(name) => {
+                    let c = compiled_template
+                        .computed(name)
+                        .expect("computed parameters only read earlier computed parameters");
+
+                    self.computed_value(pos, c)
+                }
+                _ => unreachable!("only names are looked up"),
+            },
+            &failed,
+        )?;
+
+        let value = match (value, &c.variable_type) {
+            (BalsaValue::Float(f), BalsaType::Integer) => {
+                // The upper bound is exclusive, as `i64::MAX` rounds up to 2^63 as a float.
+                if f.is_finite() && f >= i64::MIN as f64 && f < i64::MAX as f64 {
+                    BalsaValue::Integer(f as i64)
+                } else {
+                    return Err(failed(ComputationError::Overflow));
+                }
+            }
+            (BalsaValue::Integer(i), BalsaType::Float) => BalsaValue::Float(i as f64),
+            (value, _) => value,
+        };
+
+        self.computed_values.insert(c.name.clone(), value.clone());
+
+        Ok(value)
+    }
+
+    /// Replaces the parameters in `expression` for which `is_supplied` returns `true` with their
+    /// values.
+    fn bake_expression(
+        &mut self,
+        pos: Position,
+        expression: &Expression,
+        is_supplied: &impl Fn(&str) -> bool,
+    ) -> BalsaResult<Expression> {
+        Ok(match expression {
+            Expression::Parameter(name) if is_supplied(name) => {
+                match self.computed_input(pos, name)? {
+                    BalsaValue::Integer(i) => Expression::Integer(i),
+                    BalsaValue::Float(f) => Expression::Float(f),
+                    _ => unreachable!("computed inputs are looked up as numbers"),
+                }
+            }
+            Expression::Negate(operand) => {
+                Expression::Negate(Box::new(self.bake_expression(pos, operand, is_supplied)?))
+            }
+            Expression::Chain { first, rest } => Expression::Chain {
+                first: Box::new(self.bake_expression(pos, first, is_supplied)?),
+                rest: rest
+                    .iter()
+                    .map(|(operator, operand)| {
+                        Ok((*operator, self.bake_expression(pos, operand, is_supplied)?))
+                    })
+                    .collect::<BalsaResult<_>>()?,
+            },
+            _ => expression.clone(),
+        })
+    }
+
+    /// Looks up the parameter `name` read by a computed parameter, falling back to the default
+    /// value given to it by any of its blocks. Values which aren't numbers are cast to floats.
+    fn computed_input(&mut self, pos: Position, name: &str) -> BalsaResult<BalsaValue> {
+        match self.supplied_or_default(pos, name)? {
+            value @ (BalsaValue::Integer(_) | BalsaValue::Float(_)) => Ok(value),
+            value => value.try_cast(BalsaType::Float).map_err(|_| {
+                BalsaError::invalid_parameter_type(
+                    pos,
+                    name.to_string(),
+                    value.clone(),
+                    value.get_type(),
+                    BalsaType::Float,
+                )
+            }),
+        }
+    }
+
+    /// Formats the parsed message pattern `parts` into `output`, looking up its arguments in the
+    /// render's parameters. `count` is the count of the innermost plural argument, if any.
+    fn format_message(
+        &mut self,
+        pos: Position,
+        parts: &[MessagePart],
+        count: Option<i64>,
+        output: &mut String,
+    ) -> BalsaResult<()> {
+        for part in parts {
+            match part {
+                MessagePart::Text(text) => output.push_str(text),
+                MessagePart::Count => {
+                    let count = count.expect("`#` is only parsed inside plural arguments");
+                    output.push_str(&self.locale.format_number(&count.to_string()));
+                }
+                MessagePart::Argument(name) => match self.message_argument(pos, name)? {
+                    BalsaValue::String(s)
+                    | BalsaValue::Color(s)
+                    | BalsaValue::MessageFormat(s)
+                    | BalsaValue::Url(s) => output.push_str(&s),
+                    BalsaValue::Null => {}
+                    BalsaValue::Integer(i) => {
+                        output.push_str(&self.locale.format_number(&i.to_string()))
+                    }
+                    BalsaValue::Float(f) => {
+                        output.push_str(&self.locale.format_number(&f.to_string()))
+                    }
+                    BalsaValue::Currency(c) => output.push_str(&self.locale.format_currency(&c)),
+                    #[cfg(feature = "chrono")]
+                    BalsaValue::DateTime(d) => {
+                        output.push_str(&self.locale.format_datetime(&d, None))
+                    }
+                    value @ (BalsaValue::Boolean(_)
+                    | BalsaValue::Image(_)
+                    | BalsaValue::Array(_)
+                    | BalsaValue::Dictionary(_)) => output.push_str(&value.to_string()),
+                },
+                MessagePart::Plural { name, cases } => {
+                    let n = match self.message_argument_as(pos, name, BalsaType::Integer)? {
+                        BalsaValue::Integer(n) => n,
+                        _ => unreachable!("values are casted to integers"),
+                    };
+
+                    let case = message_format::plural_case(cases, n, self.locale.plurals());
+                    self.format_message(pos, case, Some(n), output)?;
+                }
+                MessagePart::Select { name, cases } => {
+                    let value = match self.message_argument_as(pos, name, BalsaType::String)? {
+                        BalsaValue::String(s) => s,
+                        _ => unreachable!("values are casted to strings"),
+                    };
+
+                    let case = message_format::select_case(cases, &value);
+                    self.format_message(pos, case, count, output)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Looks up the value of the parameter `name` used as an argument of a message pattern,
+    /// falling back to the default value given to it by any of its blocks.
+    fn message_argument(&mut self, pos: Position, name: &str) -> BalsaResult<BalsaValue> {
+        if let Some(c) = self.compiled_template.computed(name) {
+            return self.computed_value(pos, c);
+        }
+
+        self.supplied_or_default(pos, name)
+    }
+
+    /// Looks up the parameter `name`, falling back to the default value given to it by any of its
+    /// blocks, and records whether it was defaulted.
+    fn supplied_or_default(&mut self, pos: Position, name: &str) -> BalsaResult<BalsaValue> {
+        match self.parameters.get_localized(name, self.locale) {
+            Some(value) => {
+                self.record_usage(name, false);
+                Ok(value)
+            }
+            None => {
+                let value = self
+                    .compiled_template
+                    .default_value(name)
+                    .cloned()
+                    .ok_or_else(|| BalsaError::missing_parameter(pos, name.to_string()))?;
+                self.record_usage(name, true);
+                Ok(value)
+            }
+        }
+    }
+
+    /// Records that the parameter `name` was looked up, if the render is recording usage.
+    fn record_usage(&mut self, name: &str, defaulted: bool) {
+        if let Some(usage) = &mut self.usage {
+            let names = if defaulted {
+                &mut usage.defaulted
+            } else {
+                &mut usage.used
+            };
 
-            self.chars_written += n;
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
         }
+    }
 
-        if self.chars_written < replacement.end_pos {
-            // Drop the remaining characters from the block.
-            let n = replacement.end_pos - self.chars_written;
-            (&mut self.chars).take(n).for_each(drop);
+    /// Looks up the value of the parameter `name` used as an argument of a message pattern and
+    /// casts it to `type_`.
+    fn message_argument_as(
+        &mut self,
+        pos: Position,
+        name: &str,
+        type_: BalsaType,
+    ) -> BalsaResult<BalsaValue> {
+        let value = self.message_argument(pos, name)?;
+
+        value.try_cast(type_.clone()).map_err(|_| {
+            BalsaError::invalid_parameter_type(
+                pos,
+                name.to_string(),
+                value.clone(),
+                value.get_type(),
+                type_,
+            )
+        })
+    }
 
-            self.chars_written += n;
+    /// Appends the static text `segment` to the output, unless it is in a skipped part of a
+    /// conditional section.
+    fn push_segment(&mut self, segment: &str) {
+        if !self.is_skipping() {
+            self.output.push_str(segment);
         }
     }
 
-    /// Flushes the char buffer and returns the output of the render, consuming `self`.
-    fn output(mut self) -> String {
-        // Flush remaining chars.
-        self.output.push_str(&(&mut self.chars).collect::<String>());
+    /// Returns whether the render is in a part of a conditional section which is skipped.
+    fn is_skipping(&self) -> bool {
+        self.conditions.contains(&false)
+    }
+
+    /// Appends a parameter value or resolved text to the output, escaping it according to the
+    /// template's [`EscapePolicy`].
+    fn push_value(&mut self, value: &str) {
+        match self.escape_policy {
+            EscapePolicy::None => self.output.push_str(value),
+            EscapePolicy::Html => {
+                for c in value.chars() {
+                    match c {
+                        '&' => self.output.push_str("&amp;"),
+                        '<' => self.output.push_str("&lt;"),
+                        '>' => self.output.push_str("&gt;"),
+                        '"' => self.output.push_str("&quot;"),
+                        '\'' => self.output.push_str("&#39;"),
+                        c => self.output.push(c),
+                    }
+                }
+            }
+        }
+    }
 
-        self.output
+    /// Appends the hydration marker which opens the value of the parameter `name`, if markers
+    /// are enabled.
+    fn push_marker_open(&mut self, name: &str) {
+        match self.hydration_markers {
+            HydrationMarkers::None => {}
+            HydrationMarkers::Span => self
+                .output
+                .push_str(&format!("<span data-balsa=\"{}\">", name)),
+            HydrationMarkers::Comment => self.output.push_str(&format!("<!--balsa:{}-->", name)),
+        }
+    }
+
+    /// Appends the hydration marker which closes the value of a parameter, if markers are
+    /// enabled.
+    fn push_marker_close(&mut self) {
+        match self.hydration_markers {
+            HydrationMarkers::None => {}
+            HydrationMarkers::Span => self.output.push_str("</span>"),
+            HydrationMarkers::Comment => self.output.push_str("<!--/balsa-->"),
+        }
+    }
+
+    /// Appends the value of a string parameter to the output, isolating it from the surrounding
+    /// text if bidi isolation is enabled.
+    fn push_string(&mut self, value: &str) {
+        if !self.bidi_isolation {
+            return self.push_value(value);
+        }
+
+        let (open, close) = match self.escape_policy {
+            EscapePolicy::None => ("\u{2068}", "\u{2069}"),
+            EscapePolicy::Html => ("<bdi>", "</bdi>"),
+        };
+
+        self.output.push_str(open);
+        self.push_value(value);
+        self.output.push_str(close);
+    }
+
+    /// Appends a formatted integer or float to the output, written in the render's [`Locale`].
+    fn push_number(&mut self, number: &str) {
+        let number = self.locale.format_number(number);
+        self.push_value(&number);
+    }
+
+    /// Fails if the output has grown larger than the render's maximum output size, attributing the
+    /// error to the block at `pos`.
+    fn check_output_size(&self, pos: Position) -> BalsaResult<()> {
+        match self.max_output_size {
+            Some(max_size) if self.taken + self.output.len() > max_size => {
+                Err(BalsaError::output_too_large(pos, max_size))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Takes the output rendered since the last call, leaving the output empty.
+    fn take_output(&mut self) -> String {
+        self.taken += self.output.len();
+        let output = std::mem::take(&mut self.output);
+
+        self.line_endings(output)
+    }
+
+    /// Returns the output of the render along with the recorded parameter usage, consuming
+    /// `self`.
+    fn output(mut self) -> (String, Option<ParameterUsage>) {
+        let output = std::mem::take(&mut self.output);
+
+        (self.line_endings(output), self.usage)
+    }
+
+    /// Converts the `\n` line endings of `output` back to `\r\n` if the template had them.
+    fn line_endings(&self, output: String) -> String {
+        if !self.compiled_template.restore_crlf {
+            return output;
+        }
+
+        let mut restored = String::with_capacity(output.len());
+        let mut previous = None;
+        for c in output.chars() {
+            if c == '\n' && previous != Some('\r') {
+                restored.push('\r');
+            }
+
+            restored.push(c);
+            previous = Some(c);
+        }
+
+        restored
     }
 }
 
@@ -132,8 +1137,11 @@ mod tests {
     use std::collections::HashMap;
 
     use crate::{
-        balsa_compiler::{self, ParameterDescription, Scope},
-        balsa_parser, BalsaType,
+        balsa_compiler::{self, ImageDescription, Scope},
+        balsa_parser,
+        filters::Filters,
+        options::CompileOptions,
+        BalsaType, BalsaWarning,
     };
 
     use super::*;
@@ -151,37 +1159,70 @@ mod tests {
             </html>
         "#;
 
-        let c = balsa_compiler::Compiler::compile_from_tokens(
-            &balsa_parser::BalsaParser::parse(template.to_string()).unwrap(),
-        )
-        .unwrap();
+        let options = CompileOptions::default();
+        let tokens = balsa_parser::BalsaParser::parse(template, &options).unwrap();
+        let compiled =
+            balsa_compiler::Compiler::compile_from_tokens(template, &tokens, &options).unwrap();
 
         // Correct output from the template compiler.
         let compiled_template = CompiledTemplate {
+            // `defaultSubtitle` is never used, so it is eliminated from the global scope.
             global_scope: Scope {
-                variables: HashMap::from([(
-                    "defaultSubtitle".to_string(),
-                    BalsaValue::String("subtitle here".to_string()),
-                )]),
+                variables: HashMap::new(),
             },
+            warnings: vec![BalsaWarning::unused_declaration(
+                Position::new(36, 3, 17),
+                "defaultSubtitle".to_string(),
+            )],
             replacements: vec![
                 ReplacementInstruction {
-                    start_pos: 36,
-                    end_pos: 121,
+                    start_pos: Position::new(36, 3, 17),
+                    end_pos: Position::new(121, 5, 19),
                     replace_with: ReplaceWith::Nothing,
                 },
                 ReplacementInstruction {
-                    start_pos: 169,
-                    end_pos: 189,
+                    start_pos: Position::new(169, 7, 25),
+                    end_pos: Position::new(189, 7, 45),
                     replace_with: ReplaceWith::Parameter(ParameterDescription {
                         variable_name: "title".to_string(),
                         variable_type: BalsaType::String,
                         default_value: None,
+                        direction: None,
+                        escape: true,
+                        items: ItemsDescription::default(),
+                        image: ImageDescription::default(),
+                        one_of: Vec::new(),
+                        format: None,
+                        safe: false,
+                        optional: false,
+                        placeholder: None,
+                        filters: Vec::new(),
+                        custom_options: Vec::new(),
+                        source: "{{ title : string }}".to_string(),
+                        source_start_pos: Position::new(169, 7, 25),
+                        source_end_pos: Position::new(189, 7, 45),
                     }),
                 },
             ],
+            segments: vec![
+                template[..36].to_string(),
+                template[121..169].to_string(),
+                template[189..].to_string(),
+            ],
+            end_pos: Position::default().advance(template),
+            escape_policy: EscapePolicy::None,
+            restore_crlf: false,
+            computed: Vec::new(),
+            filters: Filters::default(),
+            partials: BTreeMap::new(),
         };
 
+        assert_eq!(
+            compiled, compiled_template,
+            "Template compiler produced incorrect output\n\tExpected: {:?}\n\tGot: {:?}",
+            compiled_template, compiled
+        );
+
         let expected_output = r#"
             <html>
                 
@@ -193,8 +1234,8 @@ mod tests {
 
         let params = BalsaParameters::new().string("title", "this is a title");
 
-        let output = Renderer::new(template, &compiled_template)
-            .render_with_parameters(&params)
+        let output = Renderer::new(&compiled_template)
+            .render_with_parameters(&params, &RenderOptions::default())
             .expect("Renderer should render with no errors.");
 
         assert_eq!(
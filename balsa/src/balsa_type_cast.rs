@@ -2,11 +2,15 @@
 //! Balsa values to be casted from one [`BalsaType`] to another.
 
 use crate::{
-    balsa_types::{BalsaType, BalsaValue},
+    balsa_types::{Array, BalsaType, BalsaValue, Currency, Dictionary, Image},
     errors::InvalidTypeCast,
-    validators::is_valid_color,
+    message_format,
+    validators::{is_valid_color, is_valid_url},
 };
 
+#[cfg(feature = "chrono")]
+use crate::balsa_types::parse_datetime;
+
 impl BalsaValue {
     /// Attempts to cast the [`BalsaValue`] from its [`BalsaType`] to the `target` [`BalsaType`].
     ///
@@ -20,6 +24,8 @@ impl BalsaValue {
         });
 
         match self {
+            // Every value can be stored where any type is expected.
+            _ if target_type == BalsaType::Any => Ok(self.clone()),
             BalsaValue::String(value) => match &target_type {
                 BalsaType::String => Ok(self.clone()),
                 BalsaType::Color => {
@@ -30,6 +36,30 @@ impl BalsaValue {
                         err
                     }
                 }
+                // Strings can be casted to message formats only if they are well-formed patterns.
+                BalsaType::MessageFormat => match message_format::parse(value) {
+                    Some(_) => Ok(BalsaValue::MessageFormat(value.clone())),
+                    None => err,
+                },
+                // Strings can be casted to URLs only if they are well-formed.
+                BalsaType::Url if is_valid_url(value) => Ok(BalsaValue::Url(value.clone())),
+                // Strings such as `12.50 USD` can be casted to currencies.
+                BalsaType::Currency => match Currency::parse(value) {
+                    Some(currency) => Ok(BalsaValue::Currency(currency)),
+                    None => err,
+                },
+                // ISO 8601 strings such as `2024-03-01T09:30:00Z` can be casted to date-times.
+                #[cfg(feature = "chrono")]
+                BalsaType::DateTime => match parse_datetime(value) {
+                    Some(datetime) => Ok(BalsaValue::DateTime(datetime)),
+                    None => err,
+                },
+                // Only the strings `true` and `false` can be casted to booleans.
+                BalsaType::Boolean => match value.as_str() {
+                    "true" => Ok(BalsaValue::Boolean(true)),
+                    "false" => Ok(BalsaValue::Boolean(false)),
+                    _ => err,
+                },
                 _ => err,
             },
             BalsaValue::Color(value) => match &target_type {
@@ -37,6 +67,11 @@ impl BalsaValue {
                 BalsaType::Color => Ok(self.clone()),
                 _ => err,
             },
+            BalsaValue::MessageFormat(value) => match &target_type {
+                BalsaType::String => Ok(BalsaValue::String(value.clone())),
+                BalsaType::MessageFormat => Ok(self.clone()),
+                _ => err,
+            },
             BalsaValue::Integer(value) => match &target_type {
                 BalsaType::Integer => Ok(self.clone()),
                 BalsaType::Float => {
@@ -52,7 +87,51 @@ impl BalsaValue {
                 BalsaType::Float => Ok(self.clone()),
                 _ => err,
             },
-            _ => todo!(),
+            BalsaValue::Boolean(_value) => match &target_type {
+                BalsaType::Boolean => Ok(self.clone()),
+                _ => err,
+            },
+            BalsaValue::Currency(value) => match &target_type {
+                BalsaType::String => Ok(BalsaValue::String(value.to_string())),
+                BalsaType::Float => Ok(BalsaValue::Float(value.amount())),
+                BalsaType::Currency => Ok(self.clone()),
+                _ => err,
+            },
+            // Arrays and dictionaries are casted by casting each of their values.
+            BalsaValue::Array(array) => match &target_type {
+                BalsaType::Array(t) => Array::from_values((**t).clone(), array.iter().cloned())
+                    .map_or(err, |a| Ok(BalsaValue::Array(a))),
+                _ => err,
+            },
+            BalsaValue::Image(_) => match &target_type {
+                BalsaType::Image => Ok(self.clone()),
+                _ => err,
+            },
+            BalsaValue::Url(value) => match &target_type {
+                BalsaType::String => Ok(BalsaValue::String(value.clone())),
+                // URLs passed to the render are checked as they aren't checked when created.
+                BalsaType::Url if is_valid_url(value) => Ok(self.clone()),
+                _ => err,
+            },
+            #[cfg(feature = "chrono")]
+            BalsaValue::DateTime(value) => match &target_type {
+                BalsaType::String => Ok(BalsaValue::String(value.to_rfc3339())),
+                BalsaType::DateTime => Ok(self.clone()),
+                _ => err,
+            },
+            BalsaValue::Dictionary(dictionary) => match &target_type {
+                // Dictionaries with the keys of an image can be casted to images.
+                BalsaType::Image => {
+                    Image::from_dictionary(dictionary).map_or(err, |i| Ok(BalsaValue::Image(i)))
+                }
+                BalsaType::Dictionary(t) => Dictionary::from_entries(
+                    (**t).clone(),
+                    dictionary.iter().map(|(k, v)| (k.clone(), v.clone())),
+                )
+                .map_or(err, |d| Ok(BalsaValue::Dictionary(d))),
+                _ => err,
+            },
+            BalsaValue::Null => err,
         }
     }
 }
@@ -61,15 +140,42 @@ impl BalsaValue {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_container_cast() {
+        let item = Dictionary::from_entries(
+            BalsaType::Any,
+            [
+                ("title", BalsaValue::String("Hello".to_string())),
+                ("views", BalsaValue::Integer(3)),
+            ],
+        )
+        .expect("Any value should be inserted");
+        let sections = BalsaValue::Array(Array::of_ints([1, 2]));
+
+        assert_eq!(
+            BalsaValue::Dictionary(item.clone()).try_cast(BalsaType::Any),
+            Ok(BalsaValue::Dictionary(item.clone()))
+        );
+        assert_eq!(
+            sections.try_cast(BalsaType::array_of(BalsaType::Float)),
+            Ok(BalsaValue::Array(Array::of_floats([1.0, 2.0])))
+        );
+        assert!(BalsaValue::Dictionary(item)
+            .try_cast(BalsaType::dictionary_of(BalsaType::String))
+            .is_err());
+    }
+
     #[test]
     fn test_balsa_integer_to_float_cast() {
         let integer = BalsaValue::Integer(80000);
 
-        integer.try_cast(BalsaType::Float).expect(&format!(
+        integer.try_cast(BalsaType::Float).unwrap_or_else(|_| {
+            panic!(
             "`BalsaValue::try_cast` should correctly cast value `{}` from type `{}` to type `{}`",
             integer,
             integer.get_type(),
             BalsaType::Float
-        ));
+        )
+        });
     }
 }
@@ -1,9 +1,27 @@
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, Index};
 
 use super::{BalsaType, BalsaValue};
+use crate::errors::InvalidTypeCast;
 
-/// An array of BalsaValues.
+/// An array of BalsaValues which all have the same type.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let mut tags = Array::of_strings(["news", "rust"]);
+/// tags.push(BalsaValue::String("web".to_string()))?;
+///
+/// assert_eq!(tags.len(), 3);
+/// assert_eq!(tags[2], BalsaValue::String("web".to_string()));
+/// assert!(tags.push(BalsaValue::Integer(1)).is_err());
+/// # Ok::<(), balsa::errors::InvalidTypeCast>(())
+/// ```
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerializedArray", into = "SerializedArray")
+)]
 pub struct Array {
     vec: Vec<BalsaValue>,
     type_: BalsaType,
@@ -17,15 +35,164 @@ impl Deref for Array {
     }
 }
 
-impl DerefMut for Array {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.vec
+impl Array {
+    /// Creates a new empty array of values of type `type_`.
+    pub fn new(type_: BalsaType) -> Self {
+        Self {
+            vec: Vec::new(),
+            type_,
+        }
+    }
+
+    /// Creates a new array of values of type `type_`, casting each of `values` to it.
+    ///
+    /// Fails if any of the values can't be cast to `type_`.
+    pub fn from_values(
+        type_: BalsaType,
+        values: impl IntoIterator<Item = BalsaValue>,
+    ) -> Result<Self, InvalidTypeCast> {
+        let mut array = Self::new(type_);
+        for value in values {
+            array.push(value)?;
+        }
+
+        Ok(array)
+    }
+
+    /// Creates a new array of strings.
+    pub fn of_strings(values: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            vec: values
+                .into_iter()
+                .map(|v| BalsaValue::String(v.into()))
+                .collect(),
+            type_: BalsaType::String,
+        }
+    }
+
+    /// Creates a new array of integers.
+    pub fn of_ints(values: impl IntoIterator<Item = impl Into<i64>>) -> Self {
+        Self {
+            vec: values
+                .into_iter()
+                .map(|v| BalsaValue::Integer(v.into()))
+                .collect(),
+            type_: BalsaType::Integer,
+        }
+    }
+
+    /// Creates a new array of floats.
+    pub fn of_floats(values: impl IntoIterator<Item = impl Into<f64>>) -> Self {
+        Self {
+            vec: values
+                .into_iter()
+                .map(|v| BalsaValue::Float(v.into()))
+                .collect(),
+            type_: BalsaType::Float,
+        }
     }
-}
 
-impl Array {
     /// Returns the type of the Array elements.
     pub fn get_type(&self) -> BalsaType {
         self.type_.clone()
     }
+
+    /// Appends `value` to the end of the array, casting it to the type of the elements.
+    ///
+    /// Fails without changing the array if `value` can't be cast to the type of the elements.
+    pub fn push(&mut self, value: BalsaValue) -> Result<(), InvalidTypeCast> {
+        let value = value.try_cast(self.type_.clone())?;
+        self.vec.push(value);
+
+        Ok(())
+    }
+}
+
+impl Index<usize> for Array {
+    type Output = BalsaValue;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.vec[index]
+    }
+}
+
+impl IntoIterator for Array {
+    type Item = BalsaValue;
+    type IntoIter = std::vec::IntoIter<BalsaValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vec.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Array {
+    type Item = &'a BalsaValue;
+    type IntoIter = std::slice::Iter<'a, BalsaValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vec.iter()
+    }
+}
+
+/// The form an [`Array`] is serialized in. Its values are cast to its type again when it is
+/// deserialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedArray {
+    #[serde(rename = "type")]
+    type_: BalsaType,
+    values: Vec<BalsaValue>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Array> for SerializedArray {
+    fn from(array: Array) -> Self {
+        Self {
+            type_: array.type_,
+            values: array.vec,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerializedArray> for Array {
+    type Error = InvalidTypeCast;
+
+    fn try_from(array: SerializedArray) -> Result<Self, Self::Error> {
+        Self::from_values(array.type_, array.values)
+    }
+}
+
+impl From<Array> for BalsaValue {
+    fn from(array: Array) -> Self {
+        BalsaValue::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_push() {
+        let mut floats = Array::from_values(
+            BalsaType::Float,
+            [BalsaValue::Float(0.5), BalsaValue::Integer(2)],
+        )
+        .expect("Integers should be cast to floats");
+
+        assert_eq!(
+            floats.iter().cloned().collect::<Vec<_>>(),
+            vec![BalsaValue::Float(0.5), BalsaValue::Float(2.0)]
+        );
+
+        assert!(floats
+            .push(BalsaValue::String("three".to_string()))
+            .is_err());
+        assert_eq!(
+            floats.len(),
+            2,
+            "A failed push should leave the array as-is"
+        );
+    }
 }
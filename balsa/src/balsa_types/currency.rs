@@ -0,0 +1,156 @@
+use std::fmt::Display;
+
+/// An amount of money in a currency identified by its ISO 4217 code, e.g. `USD`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerializedCurrency", into = "SerializedCurrency")
+)]
+pub struct Currency {
+    amount: f64,
+    code: String,
+}
+
+impl Currency {
+    /// Creates a new [`Currency`] value of `amount` in the currency with the ISO 4217 `code`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `amount` is not finite or `code` is not three ASCII letters.
+    pub fn new(amount: f64, code: impl Into<String>) -> Self {
+        Self::checked(amount, &code.into())
+            .expect("currency amounts must be finite and codes must be three ASCII letters")
+    }
+
+    /// Creates a new [`Currency`], or `None` if `amount` is not finite or `code` is not three
+    /// ASCII letters.
+    pub(crate) fn checked(amount: f64, code: &str) -> Option<Self> {
+        let valid =
+            amount.is_finite() && code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic());
+
+        valid.then(|| Self {
+            amount,
+            code: code.to_ascii_uppercase(),
+        })
+    }
+
+    /// Returns the amount of money.
+    pub fn amount(&self) -> f64 {
+        self.amount
+    }
+
+    /// Returns the uppercased ISO 4217 code of the currency.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Returns the number of digits written after the decimal separator for the currency.
+    pub(crate) fn minor_digits(&self) -> usize {
+        match self.code.as_str() {
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF"
+            | "UGX" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+
+    /// Returns the symbol of the currency, or its code if it has no widely recognized symbol.
+    pub(crate) fn symbol(&self) -> &str {
+        match self.code.as_str() {
+            "USD" => "$",
+            "EUR" => "€",
+            "GBP" => "£",
+            "JPY" | "CNY" => "¥",
+            "INR" => "₹",
+            "KRW" => "₩",
+            "ILS" => "₪",
+            "RUB" => "₽",
+            "TRY" => "₺",
+            "UAH" => "₴",
+            code => code,
+        }
+    }
+
+    /// Formats the amount with the currency's number of minor digits, e.g. `12.50`.
+    pub(crate) fn format_amount(&self) -> String {
+        format!("{:.*}", self.minor_digits(), self.amount)
+    }
+
+    /// Parses a currency written as an amount followed by its code, e.g. `12.50 USD`.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let (amount, code) = value.trim().rsplit_once(' ')?;
+        let amount = amount.trim_end().parse::<f64>().ok()?;
+
+        Self::checked(amount, code)
+    }
+}
+
+/// The form a [`Currency`] is serialized in, which is checked when it is deserialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedCurrency {
+    amount: f64,
+    code: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<Currency> for SerializedCurrency {
+    fn from(currency: Currency) -> Self {
+        Self {
+            amount: currency.amount,
+            code: currency.code,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerializedCurrency> for Currency {
+    type Error = String;
+
+    fn try_from(currency: SerializedCurrency) -> Result<Self, Self::Error> {
+        Self::checked(currency.amount, &currency.code).ok_or_else(|| {
+            format!(
+                "invalid currency `{} {}`; amounts must be finite and codes must be three ASCII letters",
+                currency.amount, currency.code
+            )
+        })
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.format_amount(), self.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_parse() {
+        let cases = [
+            ("12.5 USD", Some(("12.50 USD", "$"))),
+            ("  -3 jpy ", Some(("-3 JPY", "¥"))),
+            ("1.2346 KWD", Some(("1.235 KWD", "KWD"))),
+            ("12.5", None),
+            ("12.5 US", None),
+            ("inf EUR", None),
+            ("twelve EUR", None),
+        ];
+
+        for (input, expected) in cases {
+            let parsed = Currency::parse(input);
+
+            assert_eq!(
+                parsed
+                    .as_ref()
+                    .map(|c| (c.to_string(), c.symbol().to_string())),
+                expected.map(|(s, symbol)| (s.to_string(), symbol.to_string())),
+                "Currency `{}` parsed incorrectly",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime,
+};
+
+/// Parses an ISO 8601 date-time, i.e. `2024-03-01T09:30:00+01:00`. Date-times without an offset
+/// are in UTC, and dates without a time, i.e. `2024-03-01`, are at midnight UTC.
+pub(crate) fn parse_datetime(s: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(datetime);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_time(NaiveTime::MIN)))
+        .ok()?;
+
+    Some(naive.and_utc().fixed_offset())
+}
+
+/// Returns whether `format` is a valid `strftime`-style format, i.e. `%Y-%m-%d`, which date-times
+/// can be written with.
+pub(crate) fn is_valid_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| !matches!(item, Item::Error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_datetime() {
+        for (input, expected) in [
+            ("2024-03-01T09:30:00+01:00", "2024-03-01T09:30:00+01:00"),
+            ("2024-03-01T09:30:00.5Z", "2024-03-01T09:30:00.500+00:00"),
+            ("2024-03-01T09:30:00", "2024-03-01T09:30:00+00:00"),
+            ("2024-03-01T09:30", "2024-03-01T09:30:00+00:00"),
+            ("2024-03-01", "2024-03-01T00:00:00+00:00"),
+        ] {
+            assert_eq!(
+                parse_datetime(input).map(|d| d.to_rfc3339()).as_deref(),
+                Some(expected),
+                "Date-time `{}` parsed incorrectly",
+                input
+            );
+        }
+
+        for invalid_input in ["2024-13-01", "2024-03-01T25:00", "01/03/2024", "2024"] {
+            assert_eq!(parse_datetime(invalid_input), None);
+        }
+
+        assert!(is_valid_format("%Y-%m-%d %H:%M"));
+        assert!(!is_valid_format("%Y-%"));
+    }
+}
@@ -1,53 +1,268 @@
-use std::{
-    collections::HashMap,
-    ops::{Deref, DerefMut},
-};
+use std::{collections::HashMap, iter, ops::Index, slice};
 
 use super::{BalsaType, BalsaValue};
+use crate::errors::InvalidTypeCast;
 
-/// A dictionary of String-indexed values.
+/// A dictionary of String-indexed values which all have the same type.
+///
+/// Entries are kept in the order their keys were first inserted, so that they render and
+/// iterate in the order the author wrote them, e.g. the links of a navigation menu. Two
+/// dictionaries are only equal if their entries are in the same order.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let mut links = Dictionary::of_strings([("home", "/"), ("blog", "/blog")]);
+/// links.insert("about", BalsaValue::String("/about".to_string()))?;
+///
+/// assert_eq!(links["about"], BalsaValue::String("/about".to_string()));
+/// assert!(links.insert("count", BalsaValue::Integer(3)).is_err());
+/// # Ok::<(), balsa::errors::InvalidTypeCast>(())
+/// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerializedDictionary", into = "SerializedDictionary")
+)]
 pub struct Dictionary {
-    map: HashMap<String, BalsaValue>,
+    /// The entries in insertion order.
+    entries: Vec<(String, BalsaValue)>,
+    /// The index into `entries` of each key.
+    indices: HashMap<String, usize>,
     type_: BalsaType,
 }
 
-impl Deref for Dictionary {
-    type Target = HashMap<String, BalsaValue>;
+/// An iterator over the entries of a [`Dictionary`] in insertion order.
+pub type DictionaryIter<'a> = iter::Map<
+    slice::Iter<'a, (String, BalsaValue)>,
+    fn(&(String, BalsaValue)) -> (&String, &BalsaValue),
+>;
 
-    fn deref(&self) -> &Self::Target {
-        &self.map
+/// The form a [`Dictionary`] is serialized in. Its values are cast to its type again when it is
+/// deserialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedDictionary {
+    #[serde(rename = "type")]
+    type_: BalsaType,
+    entries: Vec<(String, BalsaValue)>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Dictionary> for SerializedDictionary {
+    fn from(dictionary: Dictionary) -> Self {
+        Self {
+            type_: dictionary.type_,
+            entries: dictionary.entries,
+        }
     }
 }
 
-impl DerefMut for Dictionary {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.map
+#[cfg(feature = "serde")]
+impl TryFrom<SerializedDictionary> for Dictionary {
+    type Error = InvalidTypeCast;
+
+    fn try_from(dictionary: SerializedDictionary) -> Result<Self, Self::Error> {
+        Self::from_entries(dictionary.type_, dictionary.entries)
     }
 }
 
 impl PartialEq for Dictionary {
     fn eq(&self, other: &Self) -> bool {
-        if self.len() != other.len() {
-            return false;
+        self.type_ == other.type_ && self.entries == other.entries
+    }
+}
+
+impl Dictionary {
+    /// Creates a new empty dictionary of values of type `type_`.
+    pub fn new(type_: BalsaType) -> Self {
+        Self {
+            entries: Vec::new(),
+            indices: HashMap::new(),
+            type_,
         }
+    }
 
-        for (key, value) in &self.map {
-            if let Some(other_value) = other.get(key) {
-                if value != other_value {
-                    return false;
-                }
-            } else {
-                return false;
-            }
+    /// Creates a new dictionary of values of type `type_`, casting each of the values in
+    /// `entries` to it.
+    ///
+    /// Fails if any of the values can't be cast to `type_`.
+    pub fn from_entries(
+        type_: BalsaType,
+        entries: impl IntoIterator<Item = (impl Into<String>, BalsaValue)>,
+    ) -> Result<Self, InvalidTypeCast> {
+        let mut dictionary = Self::new(type_);
+        for (key, value) in entries {
+            dictionary.insert(key, value)?;
         }
 
-        true
+        Ok(dictionary)
     }
-}
 
-impl Dictionary {
+    /// Creates a new dictionary of strings.
+    pub fn of_strings(
+        entries: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        let mut dictionary = Self::new(BalsaType::String);
+        for (key, value) in entries {
+            dictionary.insert_unchecked(key.into(), BalsaValue::String(value.into()));
+        }
+
+        dictionary
+    }
+
+    /// Creates a new dictionary of integers.
+    pub fn of_ints(entries: impl IntoIterator<Item = (impl Into<String>, impl Into<i64>)>) -> Self {
+        let mut dictionary = Self::new(BalsaType::Integer);
+        for (key, value) in entries {
+            dictionary.insert_unchecked(key.into(), BalsaValue::Integer(value.into()));
+        }
+
+        dictionary
+    }
+
+    /// Creates a new dictionary of floats.
+    pub fn of_floats(
+        entries: impl IntoIterator<Item = (impl Into<String>, impl Into<f64>)>,
+    ) -> Self {
+        let mut dictionary = Self::new(BalsaType::Float);
+        for (key, value) in entries {
+            dictionary.insert_unchecked(key.into(), BalsaValue::Float(value.into()));
+        }
+
+        dictionary
+    }
+
+    /// Returns the type of the Dictionary values.
     pub fn get_type(&self) -> BalsaType {
         self.type_.clone()
     }
+
+    /// Inserts `value` under `key`, casting it to the type of the values and returning the
+    /// value it replaced, if any.
+    ///
+    /// Fails without changing the dictionary if `value` can't be cast to the type of the values.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: BalsaValue,
+    ) -> Result<Option<BalsaValue>, InvalidTypeCast> {
+        let value = value.try_cast(self.type_.clone())?;
+
+        Ok(self.insert_unchecked(key.into(), value))
+    }
+
+    /// Inserts `value` under `key`, which must already be of the type of the values. A key
+    /// which is already present keeps its position.
+    fn insert_unchecked(&mut self, key: String, value: BalsaValue) -> Option<BalsaValue> {
+        match self.indices.get(&key) {
+            Some(&index) => Some(std::mem::replace(&mut self.entries[index].1, value)),
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+
+                None
+            }
+        }
+    }
+
+    /// Returns the value stored under `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&BalsaValue> {
+        self.indices.get(key).map(|&index| &self.entries[index].1)
+    }
+
+    /// Returns whether a value is stored under `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// Returns the number of entries in the dictionary.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the dictionary has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries in insertion order.
+    pub fn iter(&self) -> DictionaryIter<'_> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Returns an iterator over the keys in insertion order.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    /// Returns an iterator over the values in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = &BalsaValue> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl Index<&str> for Dictionary {
+    type Output = BalsaValue;
+
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key)
+            .expect("key should be present in the dictionary")
+    }
+}
+
+impl IntoIterator for Dictionary {
+    type Item = (String, BalsaValue);
+    type IntoIter = std::vec::IntoIter<(String, BalsaValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Dictionary {
+    type Item = (&'a String, &'a BalsaValue);
+    type IntoIter = DictionaryIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl From<Dictionary> for BalsaValue {
+    fn from(dictionary: Dictionary) -> Self {
+        BalsaValue::Dictionary(dictionary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insertion_order() {
+        let mut menu =
+            Dictionary::of_strings([("home", "/"), ("blog", "/blog"), ("about", "/about")]);
+        let replaced = menu
+            .insert("blog", BalsaValue::String("/posts".to_string()))
+            .expect("Strings should be inserted");
+
+        assert_eq!(replaced, Some(BalsaValue::String("/blog".to_string())));
+        assert_eq!(
+            menu.keys().collect::<Vec<_>>(),
+            vec!["home", "blog", "about"]
+        );
+        assert_eq!(
+            BalsaValue::Dictionary(menu.clone()).to_string(),
+            r#"{"home": "/", "blog": "/posts", "about": "/about"}"#
+        );
+
+        let reordered =
+            Dictionary::of_strings([("blog", "/posts"), ("home", "/"), ("about", "/about")]);
+        assert_ne!(
+            menu, reordered,
+            "Dictionaries in a different order should not be equal"
+        );
+    }
 }
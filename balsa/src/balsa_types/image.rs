@@ -0,0 +1,129 @@
+use std::fmt::Display;
+
+use super::{BalsaValue, Dictionary};
+
+/// An image along with the metadata needed to display it accessibly.
+///
+/// Parameter blocks of type `image` render a complete `<img>` tag. A dictionary with the keys
+/// `src` and `alt`, and optionally `width` and `height`, can be cast to an image.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let hero = Image::new("/hero.png", "A lighthouse at dusk").with_size(1200, 630);
+///
+/// assert_eq!(hero.alt(), "A lighthouse at dusk");
+/// assert_eq!(hero.width(), Some(1200));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Image {
+    src: String,
+    alt: String,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+impl Image {
+    /// Creates a new [`Image`] loaded from `src` and described by `alt`. Decorative images
+    /// should have an empty `alt`.
+    pub fn new(src: impl Into<String>, alt: impl Into<String>) -> Self {
+        Self {
+            src: src.into(),
+            alt: alt.into(),
+            width: None,
+            height: None,
+        }
+    }
+
+    /// Sets the intrinsic width and height of the image in pixels, which lets browsers reserve
+    /// space for it before it loads.
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+
+        self
+    }
+
+    /// Sets the width and height of the image, either of which may be unknown.
+    pub(crate) fn with_dimensions(mut self, width: Option<u32>, height: Option<u32>) -> Self {
+        self.width = width;
+        self.height = height;
+
+        self
+    }
+
+    /// Returns the URL the image is loaded from.
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// Returns the text alternative of the image.
+    pub fn alt(&self) -> &str {
+        &self.alt
+    }
+
+    /// Returns the width of the image in pixels, if it is known.
+    pub fn width(&self) -> Option<u32> {
+        self.width
+    }
+
+    /// Returns the height of the image in pixels, if it is known.
+    pub fn height(&self) -> Option<u32> {
+        self.height
+    }
+
+    /// Creates a new [`Image`] from a dictionary with the string keys `src` and `alt`, and the
+    /// optional integer keys `width` and `height`. Returns `None` if any key is missing, has a
+    /// value of the wrong type or isn't one of these.
+    pub(crate) fn from_dictionary(dictionary: &Dictionary) -> Option<Self> {
+        let string = |key| match dictionary.get(key)? {
+            BalsaValue::String(s) => Some(s.clone()),
+            _ => None,
+        };
+        let dimension = |key| match dictionary.get(key) {
+            None => Some(None),
+            Some(BalsaValue::Integer(i)) => u32::try_from(*i).ok().map(Some),
+            Some(_) => None,
+        };
+
+        let known = ["src", "alt", "width", "height"];
+        if dictionary.keys().any(|key| !known.contains(&key.as_str())) {
+            return None;
+        }
+
+        Some(Self {
+            src: string("src")?,
+            alt: string("alt")?,
+            width: dimension("width")?,
+            height: dimension("height")?,
+        })
+    }
+
+    /// Returns the attributes of the `<img>` tag for the image, in order, before they are
+    /// escaped.
+    pub(crate) fn attributes(&self) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![("src", self.src.clone()), ("alt", self.alt.clone())];
+        attributes.extend(self.width.map(|w| ("width", w.to_string())));
+        attributes.extend(self.height.map(|h| ("height", h.to_string())));
+
+        attributes
+    }
+}
+
+impl Display for Image {
+    /// Writes the image like a dictionary, i.e. `{"src": "/a.png", "alt": "A"}`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{\"src\": {:?}, \"alt\": {:?}", self.src, self.alt)?;
+
+        if let Some(width) = self.width {
+            write!(f, ", \"width\": {}", width)?;
+        }
+
+        if let Some(height) = self.height {
+            write!(f, ", \"height\": {}", height)?;
+        }
+
+        write!(f, "}}")
+    }
+}
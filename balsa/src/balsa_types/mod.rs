@@ -1,18 +1,57 @@
 mod dictionary;
-pub(crate) use dictionary::Dictionary;
+pub use dictionary::{Dictionary, DictionaryIter};
 
 mod array;
-pub(crate) use array::Array;
+pub use array::Array;
+
+mod currency;
+pub use currency::Currency;
+
+mod image;
+pub use image::Image;
+
+#[cfg(feature = "chrono")]
+mod datetime;
+#[cfg(feature = "chrono")]
+pub(crate) use datetime::{is_valid_format, parse_datetime};
 
 use std::{
-    collections::HashMap,
     fmt::Display,
-    ops::{Deref, DerefMut},
+    hash::{Hash, Hasher},
+    ops::Deref,
 };
 
 /// Represents a reference to a variable or key by name without any preceding characters like `$`.
 pub(crate) type BalsaIdentifier = String;
 
+/// Separates the keys of a path to a value nested in dictionaries, i.e. `theme.primaryColor`.
+pub(crate) const PATH_SEPARATOR: char = '.';
+
+/// Returns the first key of `path`, i.e. `theme` for `theme.primaryColor`.
+pub(crate) fn path_root(path: &str) -> &str {
+    path.split(PATH_SEPARATOR).next().unwrap_or(path)
+}
+
+/// Looks up the value at `path`, i.e. `theme.primaryColor`, by looking up its first key with
+/// `root` and each following key in the dictionary found before it. Returns `None` if a key is
+/// missing or a value along the path isn't a dictionary.
+pub(crate) fn lookup_path<'a>(
+    path: &str,
+    root: impl FnOnce(&str) -> Option<&'a BalsaValue>,
+) -> Option<&'a BalsaValue> {
+    let mut keys = path.split(PATH_SEPARATOR);
+    let mut value = root(keys.next()?)?;
+
+    for key in keys {
+        value = match value {
+            BalsaValue::Dictionary(dictionary) => dictionary.get(key)?,
+            _ => return None,
+        };
+    }
+
+    Some(value)
+}
+
 /// Represents a low-level parsed expression in a Balsa template.
 ///
 /// Should only be used for error-checking.
@@ -21,26 +60,47 @@ pub enum BalsaExpression {
     Identifier(BalsaIdentifier),
     Type(BalsaType),
     Value(BalsaValue),
+    /// A list of literals, i.e. `["left", "right"]`.
+    List(Vec<BalsaValue>),
 }
 
 /// Represents a typed value in a Balsa template.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BalsaValue {
     /// A basic string.
     String(String),
     /// Can be either a hex code or an RGB value.
     Color(String),
+    /// A string holding an ICU MessageFormat pattern, resolved against the other parameters when
+    /// rendering.
+    MessageFormat(String),
     /// A 64-bit integer.
     Integer(i64),
     /// A 64-bit float.
     Float(f64),
+    /// Either `true` or `false`.
+    Boolean(bool),
+    /// An amount of money in a specific currency.
+    Currency(Currency),
+    /// An image along with its text alternative and dimensions.
+    Image(Image),
+    /// An absolute or relative URL, i.e. `https://example.com` or `/posts/1`.
+    Url(String),
+    /// A date and time along with the UTC offset it was given in.
+    #[cfg(feature = "chrono")]
+    DateTime(chrono::DateTime<chrono::FixedOffset>),
     /// An array of values.
     Array(Array),
     /// A dictionary of values indexed by a String.
     Dictionary(Dictionary),
+    /// The absence of a value. Parameters passed `null` are treated as if they weren't passed,
+    /// so optional parameters are left empty.
+    Null,
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecursiveBalsaType(Box<BalsaType>);
 
 impl Deref for RecursiveBalsaType {
@@ -53,19 +113,65 @@ impl Deref for RecursiveBalsaType {
 
 /// Represents a type in a Balsa template.
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BalsaType {
     /// A basic string.
     String,
     /// Can be either a hex code or an RGB value.
     Color,
+    /// A string holding an ICU MessageFormat pattern, resolved against the other parameters when
+    /// rendering.
+    MessageFormat,
     /// A 64-bit integer.
     Integer,
     /// A 64-bit float.
     Float,
+    /// Either `true` or `false`.
+    Boolean,
+    /// An amount of money in a specific currency.
+    Currency,
+    /// An image along with its text alternative and dimensions.
+    Image,
+    /// An absolute or relative URL, i.e. `https://example.com` or `/posts/1`.
+    Url,
+    /// A date and time, written as an ISO 8601 literal such as `2024-03-01T09:30:00Z`.
+    #[cfg(feature = "chrono")]
+    DateTime,
     /// An array of the specified type.
     Array(RecursiveBalsaType),
     /// A String-indexed dictionary of the specified type.
     Dictionary(RecursiveBalsaType),
+    /// Any type. Only used as the type of the values of a dictionary whose values may have
+    /// different types, such as the items of a section, which is written as `dict`.
+    Any,
+}
+
+impl BalsaType {
+    /// Returns the type of an array of values of type `type_`.
+    pub(crate) fn array_of(type_: BalsaType) -> Self {
+        Self::Array(RecursiveBalsaType(Box::new(type_)))
+    }
+
+    /// Returns the type of a dictionary of values of type `type_`.
+    pub(crate) fn dictionary_of(type_: BalsaType) -> Self {
+        Self::Dictionary(RecursiveBalsaType(Box::new(type_)))
+    }
+
+    /// Returns the type shared by all of `values`, or [`BalsaType::Any`] if their types differ
+    /// or there are none, such as for the items of an array literal.
+    pub(crate) fn common_type_of<'a>(values: impl IntoIterator<Item = &'a BalsaValue>) -> Self {
+        let mut values = values.into_iter();
+        let type_ = match values.next() {
+            Some(first) => first.get_type(),
+            None => return Self::Any,
+        };
+
+        if values.all(|v| v.get_type() == type_) {
+            type_
+        } else {
+            Self::Any
+        }
+    }
 }
 
 impl BalsaExpression {
@@ -103,16 +209,80 @@ impl BalsaValue {
         match self {
             BalsaValue::String(_) => BalsaType::String,
             BalsaValue::Color(_) => BalsaType::Color,
+            BalsaValue::MessageFormat(_) => BalsaType::MessageFormat,
             BalsaValue::Integer(_) => BalsaType::Integer,
             BalsaValue::Float(_) => BalsaType::Float,
-            BalsaValue::Array(_) => todo!(),
-            BalsaValue::Dictionary(_) => todo!(),
+            BalsaValue::Boolean(_) => BalsaType::Boolean,
+            BalsaValue::Currency(_) => BalsaType::Currency,
+            BalsaValue::Image(_) => BalsaType::Image,
+            BalsaValue::Url(_) => BalsaType::Url,
+            #[cfg(feature = "chrono")]
+            BalsaValue::DateTime(_) => BalsaType::DateTime,
+            BalsaValue::Array(a) => BalsaType::array_of(a.get_type()),
+            BalsaValue::Dictionary(d) => BalsaType::dictionary_of(d.get_type()),
+            // `null` can only be stored where any type is expected.
+            BalsaValue::Null => BalsaType::Any,
+        }
+    }
+
+    /// Returns the approximate size of the value in bytes, i.e. the length of strings and the
+    /// in-memory size of numbers, summed over the elements of arrays and dictionaries.
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            BalsaValue::String(s)
+            | BalsaValue::Color(s)
+            | BalsaValue::MessageFormat(s)
+            | BalsaValue::Url(s) => s.len(),
+            BalsaValue::Integer(_) | BalsaValue::Float(_) => 8,
+            BalsaValue::Boolean(_) => 1,
+            BalsaValue::Currency(c) => 8 + c.code().len(),
+            BalsaValue::Image(i) => i.src().len() + i.alt().len() + 8,
+            #[cfg(feature = "chrono")]
+            BalsaValue::DateTime(_) => 12,
+            BalsaValue::Array(a) => a.iter().map(BalsaValue::size).sum(),
+            BalsaValue::Dictionary(d) => d.iter().map(|(k, v)| k.len() + v.size()).sum(),
+            BalsaValue::Null => 0,
         }
     }
 
-    /// Checks if a [`BalsaValue`] is the provided [`BalsaType`] `type_`.
-    pub(crate) fn is_type(&self, type_: BalsaType) -> bool {
-        self.get_type() == type_
+    /// Feeds the value into `state`. Floats are hashed by their bits, so equal values hash
+    /// equally.
+    pub(crate) fn hash_into<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        match self {
+            BalsaValue::String(s)
+            | BalsaValue::Color(s)
+            | BalsaValue::MessageFormat(s)
+            | BalsaValue::Url(s) => s.hash(state),
+            BalsaValue::Integer(i) => i.hash(state),
+            BalsaValue::Float(f) => f.to_bits().hash(state),
+            BalsaValue::Boolean(b) => b.hash(state),
+            BalsaValue::Currency(c) => {
+                c.amount().to_bits().hash(state);
+                c.code().hash(state);
+            }
+            BalsaValue::Image(i) => {
+                i.src().hash(state);
+                i.alt().hash(state);
+                i.width().hash(state);
+                i.height().hash(state);
+            }
+            #[cfg(feature = "chrono")]
+            BalsaValue::DateTime(d) => d.hash(state),
+            BalsaValue::Array(a) => {
+                a.len().hash(state);
+                a.iter().for_each(|v| v.hash_into(state));
+            }
+            BalsaValue::Dictionary(d) => {
+                d.len().hash(state);
+                for (k, v) in d {
+                    k.hash(state);
+                    v.hash_into(state);
+                }
+            }
+            BalsaValue::Null => {}
+        }
     }
 }
 
@@ -122,6 +292,11 @@ impl Display for BalsaExpression {
             BalsaExpression::Identifier(i) => write!(f, "{}", i),
             BalsaExpression::Type(t) => t.fmt(f),
             BalsaExpression::Value(v) => v.fmt(f),
+            BalsaExpression::List(l) => {
+                let values = l.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+
+                write!(f, "[{}]", values.join(", "))
+            }
         }
     }
 }
@@ -131,23 +306,51 @@ impl Display for BalsaValue {
         match self {
             BalsaValue::String(s) => write!(f, r#""{}""#, s),
             BalsaValue::Color(c) => write!(f, r#"{}"#, c),
+            BalsaValue::MessageFormat(m) => write!(f, r#""{}""#, m),
             BalsaValue::Integer(i) => write!(f, r#"{:?}"#, i),
             BalsaValue::Float(f_) => write!(f, r#"{}"#, f_),
-            BalsaValue::Array(_) => todo!(),
-            BalsaValue::Dictionary(_) => todo!(),
+            BalsaValue::Boolean(b) => write!(f, "{}", b),
+            BalsaValue::Currency(c) => write!(f, r#""{}""#, c),
+            BalsaValue::Image(i) => i.fmt(f),
+            BalsaValue::Url(u) => write!(f, r#""{}""#, u),
+            #[cfg(feature = "chrono")]
+            BalsaValue::DateTime(d) => write!(f, "{}", d.to_rfc3339()),
+            BalsaValue::Array(a) => {
+                let values = a.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+
+                write!(f, "[{}]", values.join(", "))
+            }
+            BalsaValue::Dictionary(d) => {
+                let entries = d
+                    .iter()
+                    .map(|(k, v)| format!("{:?}: {}", k, v))
+                    .collect::<Vec<String>>();
+
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            BalsaValue::Null => write!(f, "null"),
         }
     }
 }
 
 impl Display for BalsaType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
+        match self {
             BalsaType::String => write!(f, "string"),
             BalsaType::Color => write!(f, "color"),
+            BalsaType::MessageFormat => write!(f, "messageformat"),
             BalsaType::Integer => write!(f, "int"),
             BalsaType::Float => write!(f, "float"),
-            BalsaType::Array(_) => todo!(),
-            BalsaType::Dictionary(_) => todo!(),
+            BalsaType::Boolean => write!(f, "bool"),
+            BalsaType::Currency => write!(f, "currency"),
+            BalsaType::Image => write!(f, "image"),
+            BalsaType::Url => write!(f, "url"),
+            #[cfg(feature = "chrono")]
+            BalsaType::DateTime => write!(f, "datetime"),
+            BalsaType::Array(t) => write!(f, "array<{}>", **t),
+            BalsaType::Dictionary(t) if **t == BalsaType::Any => write!(f, "dict"),
+            BalsaType::Dictionary(t) => write!(f, "dict<{}>", **t),
+            BalsaType::Any => write!(f, "any"),
         }
     }
 }
@@ -0,0 +1,284 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    AsParameters, Balsa, BalsaResult, BalsaTemplate, CompileOptions, RenderOptions, Template,
+};
+
+/// A cache of compiled templates keyed by a hash of their source, so that services which receive
+/// the same template text repeatedly only compile it once.
+///
+/// Every template in the cache is compiled with the same [`CompileOptions`]. Once the cache holds
+/// `capacity` templates, the least recently used one is evicted to make room. The cache can be
+/// shared between threads, although two threads compiling the same new source at once may both
+/// compile it.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// # use std::sync::Arc;
+/// let cache = CompileCache::new(64).with_options(CompileOptions::new().strict_types(true));
+///
+/// let first = cache.get_or_compile("<h1>{{ title : string }}</h1>")?;
+/// let second = cache.get_or_compile("<h1>{{ title : string }}</h1>")?;
+/// assert!(Arc::ptr_eq(&first, &second));
+/// # Ok::<(), BalsaError>(())
+/// ```
+#[derive(Debug)]
+pub struct CompileCache {
+    options: CompileOptions,
+    templates: Mutex<LruMap<u64, CachedTemplate>>,
+}
+
+/// A cache of rendered output keyed by the template and a hash of the parameters and
+/// [`RenderOptions`] it was rendered with, so that identical renders, e.g. of a page between
+/// edits to its content, are only rendered once.
+///
+/// Once the cache holds `capacity` outputs, the least recently used one is evicted to make room.
+/// Outputs can also be given a time to live with [`RenderCache::ttl`], after which they are
+/// rendered again. Renders which fail aren't cached. Parameters are compared by a 64-bit hash,
+/// so the cache assumes that different parameter sets never collide.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// # use std::time::Duration;
+/// let cache = RenderCache::new(256).ttl(Duration::from_secs(60));
+/// let template = Balsa::from_string("<h1>{{ title : string }}</h1>").build()?;
+/// let params = BalsaParameters::new().string("title", "Home");
+///
+/// let output = cache.render(&template, &params, &RenderOptions::new())?;
+/// assert_eq!(&*output, "<h1>Home</h1>");
+/// assert_eq!(cache.len(), 1);
+/// # Ok::<(), BalsaError>(())
+/// ```
+#[derive(Debug)]
+pub struct RenderCache {
+    ttl: Option<Duration>,
+    outputs: Mutex<LruMap<(u64, u64), CachedOutput>>,
+}
+
+/// A template held by a [`CompileCache`], along with the source it was compiled from, which
+/// templates don't keep.
+#[derive(Debug)]
+struct CachedTemplate {
+    source: Box<str>,
+    template: Arc<Template>,
+}
+
+/// An output held by a [`RenderCache`].
+#[derive(Debug)]
+struct CachedOutput {
+    output: Arc<str>,
+    rendered_at: Instant,
+}
+
+/// A map holding up to `capacity` entries which evicts the least recently used entry when full.
+#[derive(Debug)]
+struct LruMap<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    /// Incremented on every lookup to track how recently each entry was used.
+    clock: u64,
+}
+
+impl CompileCache {
+    /// Creates an empty cache which holds up to `capacity` templates compiled with the default
+    /// [`CompileOptions`]. A cache with a capacity of 0 compiles every template it is given.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            options: CompileOptions::default(),
+            templates: Mutex::new(LruMap::new(capacity)),
+        }
+    }
+
+    /// Sets the [`CompileOptions`] used to compile templates, clearing the cache.
+    pub fn with_options(mut self, options: CompileOptions) -> Self {
+        self.options = options;
+        self.clear();
+        self
+    }
+
+    /// Returns the compiled template for `source`, compiling and caching it if it isn't cached
+    /// yet. Templates which fail to compile aren't cached.
+    pub fn get_or_compile(&self, source: &str) -> BalsaResult<Arc<Template>> {
+        let key = hash(&source);
+
+        // Hash collisions are resolved by compiling the new source in place of the old.
+        if let Some(cached) = lock(&self.templates).get(&key) {
+            if &*cached.source == source {
+                return Ok(cached.template.clone());
+            }
+        }
+
+        // Compile without holding the lock so that other templates can be looked up meanwhile.
+        let template = Arc::new(
+            Balsa::from_string(source)
+                .with_options(self.options.clone())
+                .build_once()?,
+        );
+        lock(&self.templates).insert(
+            key,
+            CachedTemplate {
+                source: source.into(),
+                template: template.clone(),
+            },
+        );
+
+        Ok(template)
+    }
+
+    /// Returns the number of templates in the cache.
+    pub fn len(&self) -> usize {
+        lock(&self.templates).entries.len()
+    }
+
+    /// Returns `true` if the cache holds no templates.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every template from the cache.
+    pub fn clear(&self) {
+        lock(&self.templates).entries.clear();
+    }
+}
+
+impl RenderCache {
+    /// Creates an empty cache which holds up to `capacity` rendered outputs, which never expire.
+    /// A cache with a capacity of 0 renders every template it is given.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            ttl: None,
+            outputs: Mutex::new(LruMap::new(capacity)),
+        }
+    }
+
+    /// Sets how long an output is cached for before the template is rendered again.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Renders `template` with `params` and `options`, returning the cached output if the same
+    /// template was already rendered with equal parameters and options.
+    ///
+    /// Only the options which change the output, such as the locale and slots, are part of the
+    /// key. Limits, deadlines and cancellation only apply when the template is actually
+    /// rendered.
+    pub fn render<T: AsParameters>(
+        &self,
+        template: &Template,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<Arc<str>> {
+        let params = params.as_parameters();
+
+        let mut hasher = DefaultHasher::new();
+        params.hash_into(&mut hasher);
+        options.locale.hash(&mut hasher);
+        options.bidi_isolation.hash(&mut hasher);
+        options.hydration_markers.hash(&mut hasher);
+        let mut slots: Vec<_> = options.slots.iter().collect();
+        slots.sort();
+        slots.hash(&mut hasher);
+        let key = (template.id, hasher.finish());
+
+        if let Some(cached) = lock(&self.outputs).get(&key) {
+            if self
+                .ttl
+                .is_none_or(|ttl| cached.rendered_at.elapsed() < ttl)
+            {
+                return Ok(cached.output.clone());
+            }
+        }
+
+        let output: Arc<str> = template
+            .render_html_string_with_options(&params, options)?
+            .into();
+        lock(&self.outputs).insert(
+            key,
+            CachedOutput {
+                output: output.clone(),
+                rendered_at: Instant::now(),
+            },
+        );
+
+        Ok(output)
+    }
+
+    /// Returns the number of outputs in the cache, including any which have expired.
+    pub fn len(&self) -> usize {
+        lock(&self.outputs).entries.len()
+    }
+
+    /// Returns `true` if the cache holds no outputs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every output from the cache.
+    pub fn clear(&self) {
+        lock(&self.outputs).entries.clear();
+    }
+}
+
+impl<K: Hash + Eq + Copy, V> LruMap<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Returns the entry for `key`, marking it as the most recently used.
+    fn get(&mut self, key: &K) -> Option<&V> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        self.entries.get_mut(key).map(|(value, last_used)| {
+            *last_used = clock;
+            &*value
+        })
+    }
+
+    /// Inserts `value` under `key`, evicting the least recently used entry if the map is full.
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key);
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.entries.insert(key, (value, self.clock));
+    }
+}
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // The caches are always left consistent, so a poisoned lock can be recovered.
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{Balsa, BalsaError, BalsaResult, Template};
+
+/// Resolves the paths of `{{include "..."}}` blocks to the templates they include, which are
+/// compiled into the including template.
+///
+/// Set with [`crate::BalsaBuilder::loader`] or [`crate::CompileOptions::loader`]. Included
+/// templates share the parameters of the template including them, and are compiled with the
+/// same options. Named arguments bind parameters of the included template to other parameters
+/// or to values, i.e. `{{include "card.html" title=postTitle}}` renders the card's `title` from
+/// `postTitle`.
+///
+/// Templates can't include themselves, directly or through other templates, outside of `#each`
+/// sections. Included templates are compiled into the including template rather than rendered
+/// on their own, so a recursive include would never end, and fails with
+/// [`crate::errors::IncludeFailure::Cycle`].
+///
+/// Within an `#each` section, an `include` block is instead rendered for every item, with only
+/// the parameters its arguments bind, which read the item's fields. This lets a template include
+/// itself for nested items, such as a menu with submenus, up to
+/// [`crate::RenderOptions::max_include_depth`] levels deep. Fields an item doesn't have leave
+/// their parameters to their default values:
+/// `{{#each items: array<dict>}}{{include "menu.html" items=children}}{{/each}}`.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// # use std::collections::HashMap;
+/// let partials = HashMap::from([(
+///     "partials/footer.html".to_string(),
+///     "<footer>{{ siteName : string }}</footer>".to_string(),
+/// )]);
+///
+/// let template = Balsa::from_string("<main>Hi</main>{{include \"partials/footer.html\"}}")
+///     .loader(partials)
+///     .build()?;
+///
+/// let params = BalsaParameters::new().string("siteName", "Balsa");
+/// assert_eq!(
+///     template.render_html_string(&params)?,
+///     "<main>Hi</main><footer>Balsa</footer>"
+/// );
+/// # Ok::<(), BalsaError>(())
+/// ```
+pub trait TemplateLoader: Send + Sync {
+    /// Returns the raw template at `path`, exactly as it was written in the `include` block.
+    fn load(&self, path: &str) -> io::Result<String>;
+}
+
+/// A [`TemplateLoader`] which reads included templates from files, resolving their paths
+/// relative to a root directory.
+///
+/// Paths which are absolute or contain `..` are rejected, so that templates can't include files
+/// outside of the root directory.
+#[derive(Debug, Clone)]
+pub struct FileLoader {
+    root: PathBuf,
+}
+
+/// A [`TemplateLoader`] shared by the options it was set on and their clones.
+#[derive(Clone)]
+pub(crate) struct SharedTemplateLoader(pub(crate) Arc<dyn TemplateLoader>);
+
+impl FileLoader {
+    /// Creates a new [`FileLoader`] which reads templates from the directory at `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl TemplateLoader for FileLoader {
+    fn load(&self, path: &str) -> io::Result<String> {
+        let relative = Path::new(path);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "path must be relative and stay within the template directory",
+            ));
+        }
+
+        fs::read_to_string(self.root.join(relative))
+    }
+}
+
+/// Loads templates from memory, keyed by their paths.
+impl TemplateLoader for HashMap<String, String> {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "template not found"))
+    }
+}
+
+impl fmt::Debug for SharedTemplateLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedTemplateLoader")
+    }
+}
+
+/// Loaders are equal if they are the same loader.
+impl PartialEq for SharedTemplateLoader {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A set of compiled template fragments stored by name, which pages can be assembled from with
+/// [`TemplateSet::compose`].
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let mut fragments = TemplateSet::new();
+/// fragments.insert("header", Balsa::from_string("<h1>{{ title : string }}</h1>").build()?);
+/// fragments.insert("body", Balsa::from_string("<p>{{ body : string }}</p>").build()?);
+/// fragments.insert("footer", Balsa::from_string("<footer>{{ title : string }}</footer>").build()?);
+///
+/// let page = fragments.compose(["header", "body", "footer"])?;
+/// let params = BalsaParameters::new().string("title", "Balsa").string("body", "Hello");
+/// assert_eq!(
+///     page.render_html_string(&params)?,
+///     "<h1>Balsa</h1><p>Hello</p><footer>Balsa</footer>"
+/// );
+/// # Ok::<(), BalsaError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TemplateSet {
+    templates: HashMap<String, Template>,
+}
+
+impl TemplateSet {
+    /// Creates a new empty [`TemplateSet`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `template` to the set under `name`, returning the template it replaced, if any.
+    pub fn insert(&mut self, name: impl Into<String>, template: Template) -> Option<Template> {
+        self.templates.insert(name.into(), template)
+    }
+
+    /// Returns the template named `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    /// Returns the number of templates in the set.
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    /// Returns `true` if the set has no templates.
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Combines the templates named `names` into a single template, in order, as with
+    /// [`Template::concat`]. A template may be used more than once.
+    ///
+    /// Fails with a [`crate::errors::BalsaCompileError::UndefinedTemplate`] error if any of the
+    /// names isn't in the set. Composing no templates returns an empty template.
+    pub fn compose<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> BalsaResult<Template> {
+        let mut composed: Option<Template> = None;
+
+        for name in names {
+            let template = self
+                .get(name)
+                .ok_or_else(|| BalsaError::undefined_template(name.to_string()))?;
+
+            composed = Some(match composed {
+                Some(composed) => composed.concat(template)?,
+                None => template.clone(),
+            });
+        }
+
+        match composed {
+            Some(composed) => Ok(composed),
+            None => Balsa::from_string("").build_once(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::BalsaCompileError, Balsa, BalsaError, BalsaParameters, BalsaTemplate};
+
+    use super::*;
+
+    #[test]
+    fn test_compose() {
+        let mut fragments = TemplateSet::new();
+        fragments.insert(
+            "header",
+            Balsa::from_string("<h1>{{ title : string }}</h1>\n")
+                .build()
+                .unwrap(),
+        );
+        fragments.insert(
+            "item",
+            Balsa::from_string("<li>{{ item : string, defaultValue: \"?\" }}</li>")
+                .build()
+                .unwrap(),
+        );
+
+        let page = fragments.compose(["header", "item", "item"]).unwrap();
+        assert_eq!(
+            page.render_html_string(&BalsaParameters::new().string("title", "List"))
+                .unwrap(),
+            "<h1>List</h1>\n<li>?</li><li>?</li>"
+        );
+
+        assert!(matches!(
+            fragments.compose(["header", "missing"]),
+            Err(BalsaError::CompileError(BalsaCompileError::UndefinedTemplate(e)))
+                if e.error.template_name == "missing"
+        ));
+        assert_eq!(
+            fragments
+                .compose([])
+                .unwrap()
+                .render_html_string(&BalsaParameters::new())
+                .unwrap(),
+            ""
+        );
+    }
+}
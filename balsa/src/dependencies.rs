@@ -0,0 +1,212 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use crate::balsa_compiler::CompiledTemplate;
+
+/// Describes which values of a template are derived from which parameters.
+///
+/// Created with [`crate::Template::dependency_graph`]. Every parameter and computed parameter of
+/// the template is a node, and each computed parameter depends on the nodes its expression
+/// reads. Declared variables are resolved when the template is compiled, so they never appear.
+/// The arguments of `messageformat` patterns are only known when rendering, so they aren't
+/// included either.
+///
+/// The fields are public so that tooling can merge the graphs of several templates, which is
+/// why [`DependencyGraph::evaluation_order`] checks for cycles.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DependencyGraph {
+    /// The nodes, sorted by name.
+    pub nodes: Vec<DependencyNode>,
+}
+
+/// A parameter or computed parameter in a [`DependencyGraph`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DependencyNode {
+    /// The name of the parameter.
+    pub name: String,
+    /// Whether the value is computed from other nodes rather than passed to the render.
+    pub computed: bool,
+    /// Whether the value is written to the output by one of the template's blocks, rather than
+    /// only being read by computed parameters.
+    pub rendered: bool,
+    /// The names of the nodes the value is computed from, in the order they are first read.
+    /// Parameters which aren't computed have no dependencies.
+    pub dependencies: Vec<String>,
+}
+
+/// The nodes of a [`DependencyGraph`] which couldn't be ordered because they are part of, or
+/// depend on, a cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle {
+    /// The names of the nodes, sorted by name.
+    pub names: Vec<String>,
+}
+
+impl DependencyGraph {
+    /// Creates a new [`DependencyGraph`] describing the provided [`CompiledTemplate`].
+    pub(crate) fn new(compiled_template: &CompiledTemplate) -> Self {
+        let mut nodes = BTreeMap::new();
+
+        for p in compiled_template
+            .replacements
+            .iter()
+            .filter_map(|r| r.replace_with.parameter())
+        {
+            node_mut(&mut nodes, &p.variable_name).rendered = true;
+        }
+
+        for c in &compiled_template.computed {
+            let inputs = c.expression.inputs();
+
+            for input in &inputs {
+                node_mut(&mut nodes, input);
+            }
+
+            let computed = node_mut(&mut nodes, &c.name);
+            computed.computed = true;
+            computed.dependencies = inputs.into_iter().map(str::to_string).collect();
+        }
+
+        Self {
+            nodes: nodes.into_values().collect(),
+        }
+    }
+
+    /// Returns the node named `name`, if there is one.
+    pub fn node(&self, name: &str) -> Option<&DependencyNode> {
+        self.nodes.iter().find(|n| n.name == name)
+    }
+
+    /// Returns the names of every node which depends on `name`, directly or through other
+    /// computed parameters, sorted by name. These are the values which must be recomputed when
+    /// `name` changes.
+    pub fn dependents(&self, name: &str) -> Vec<&str> {
+        let mut dependents = BTreeSet::new();
+        let mut pending = vec![name];
+
+        while let Some(changed) = pending.pop() {
+            for node in &self.nodes {
+                if node.dependencies.iter().any(|d| d == changed) && dependents.insert(&*node.name)
+                {
+                    pending.push(&node.name);
+                }
+            }
+        }
+
+        dependents.into_iter().collect()
+    }
+
+    /// Returns the names of every node, ordered so that each node comes after all of its
+    /// dependencies. Nodes which don't depend on each other are ordered by name.
+    ///
+    /// Dependencies which aren't nodes of the graph are ignored. Fails if any nodes are part of,
+    /// or depend on, a cycle.
+    pub fn evaluation_order(&self) -> Result<Vec<&str>, DependencyCycle> {
+        let mut order: Vec<&str> = Vec::with_capacity(self.nodes.len());
+        let mut remaining: Vec<&DependencyNode> = self.nodes.iter().collect();
+
+        // Repeatedly takes every node whose dependencies have all been ordered, which is
+        // quadratic but keeps the order stable.
+        loop {
+            let (ready, blocked): (Vec<&DependencyNode>, Vec<&DependencyNode>) =
+                remaining.into_iter().partition(|node| {
+                    node.dependencies
+                        .iter()
+                        .all(|d| order.contains(&d.as_str()) || self.node(d).is_none())
+                });
+
+            if ready.is_empty() {
+                remaining = blocked;
+                break;
+            }
+
+            let mut ready: Vec<&str> = ready.iter().map(|n| n.name.as_str()).collect();
+            ready.sort_unstable();
+            order.extend(ready);
+            remaining = blocked;
+        }
+
+        if remaining.is_empty() {
+            return Ok(order);
+        }
+
+        let mut names: Vec<String> = remaining.iter().map(|n| n.name.clone()).collect();
+        names.sort();
+
+        Err(DependencyCycle { names })
+    }
+}
+
+/// Returns the node named `name`, inserting a parameter which isn't rendered if there is none.
+fn node_mut<'a>(
+    nodes: &'a mut BTreeMap<String, DependencyNode>,
+    name: &str,
+) -> &'a mut DependencyNode {
+    nodes
+        .entry(name.to_string())
+        .or_insert_with(|| DependencyNode {
+            name: name.to_string(),
+            ..Default::default()
+        })
+}
+
+impl Display for DependencyCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "dependency cycle involving `{}`",
+            self.names.join("`, `")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, dependencies: &[&str]) -> DependencyNode {
+        DependencyNode {
+            name: name.to_string(),
+            computed: !dependencies.is_empty(),
+            rendered: true,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let graph = DependencyGraph {
+            nodes: vec![
+                node("a", &["c"]),
+                node("b", &["c", "missing"]),
+                node("c", &[]),
+                node("d", &["a", "b"]),
+            ],
+        };
+
+        assert_eq!(graph.evaluation_order(), Ok(vec!["c", "a", "b", "d"]));
+        assert_eq!(graph.dependents("c"), vec!["a", "b", "d"]);
+        assert_eq!(graph.dependents("d"), Vec::<&str>::new());
+
+        let cyclic = DependencyGraph {
+            nodes: vec![
+                node("a", &["b"]),
+                node("b", &["a"]),
+                node("c", &[]),
+                node("d", &["a"]),
+            ],
+        };
+
+        assert_eq!(
+            cyclic.evaluation_order(),
+            Err(DependencyCycle {
+                names: vec!["a".to_string(), "b".to_string(), "d".to_string()]
+            })
+        );
+        assert_eq!(cyclic.dependents("a"), vec!["a", "b", "d"]);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    registry::collect_files, Balsa, BalsaBuilder, BalsaError, BalsaResult, CompileOptions,
+    FileSource, Template,
+};
+
+/// The templates in a directory compiled by [`Balsa::compile_dir_parallel`], along with the
+/// errors of those which failed to compile.
+///
+/// Both maps are keyed by the path of each file relative to the directory.
+#[derive(Debug)]
+pub struct CompiledDirectory {
+    /// The templates which compiled successfully. Their warnings are available through
+    /// [`Template::warnings`].
+    pub templates: BTreeMap<PathBuf, Template>,
+    /// The errors of the files which couldn't be read or compiled.
+    pub errors: BTreeMap<PathBuf, BalsaError>,
+}
+
+impl CompiledDirectory {
+    /// Returns `true` if every file in the directory compiled successfully.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl Balsa {
+    /// Compiles every file in the directory at `path` and its subdirectories concurrently with
+    /// the default [`CompileOptions`], collecting the templates and errors of every file rather
+    /// than stopping at the first error.
+    ///
+    /// Fails with a [`BalsaError::ReadTemplateError`] only if the directory itself can't be read.
+    pub fn compile_dir_parallel(path: impl AsRef<Path>) -> BalsaResult<CompiledDirectory> {
+        Self::compile_dir_parallel_with_options(path, &CompileOptions::default())
+    }
+
+    /// Compiles every file in the directory at `path` and its subdirectories concurrently with
+    /// the provided [`CompileOptions`]. See [`Balsa::compile_dir_parallel`].
+    pub fn compile_dir_parallel_with_options(
+        path: impl AsRef<Path>,
+        options: &CompileOptions,
+    ) -> BalsaResult<CompiledDirectory> {
+        let root = path.as_ref();
+        let mut files = Vec::new();
+        collect_files(root, &mut files).map_err(BalsaError::read_template_error)?;
+
+        let results: Vec<(PathBuf, BalsaResult<Template>)> = files
+            .into_par_iter()
+            .map(|file| {
+                let template = BalsaBuilder {
+                    template_source: FileSource { path: file.clone() },
+                    options: options.clone(),
+                }
+                .build_once();
+                let relative = file.strip_prefix(root).unwrap_or(&file).to_path_buf();
+
+                (relative, template)
+            })
+            .collect();
+
+        let mut compiled = CompiledDirectory {
+            templates: BTreeMap::new(),
+            errors: BTreeMap::new(),
+        };
+        for (path, template) in results {
+            match template {
+                Ok(template) => {
+                    compiled.templates.insert(path, template);
+                }
+                Err(e) => {
+                    compiled.errors.insert(path, e);
+                }
+            }
+        }
+
+        Ok(compiled)
+    }
+}
@@ -1,6 +1,10 @@
-use std::{fmt::Display, io, ops::Deref};
+use std::{error::Error, fmt::Display, io, ops::Deref};
 
-use crate::balsa_types::{BalsaExpression, BalsaType, BalsaValue};
+use crate::{
+    balsa_types::{BalsaExpression, BalsaType, BalsaValue},
+    parser::Position,
+    EscapePolicy,
+};
 
 /// Represents all Balsa errors.
 #[derive(Debug)]
@@ -9,8 +13,14 @@ pub enum BalsaError {
     ReadTemplateError(io::Error),
     /// Represents a failure that occurred during template compilation, before being rendered.
     CompileError(BalsaCompileError),
+    /// Represents several failures that occurred during template compilation, in the order they
+    /// occur in the template. Templates which fail to compile with a single error return a
+    /// [`BalsaError::CompileError`] instead.
+    MultipleCompileErrors(Vec<BalsaCompileError>),
     /// Represents a failure that occurred while rendering a template.
     RenderError(BalsaRenderError),
+    /// Failed to write rendered output (IO error).
+    WriteOutputError(io::Error),
 }
 
 /// Represents an error in compiling a file.
@@ -19,7 +29,7 @@ pub enum BalsaCompileError {
     /// A failure occurred while trying to parse and tokenize the raw template.
     TemplateParseFail(TemplateErrorContext<TemplateParseFail>),
     /// A failure occurred while attempting to cast a value from one type to another.
-    InvalidTypeCast(TemplateErrorContext<InvalidTypeCast>),
+    InvalidTypeCast(Box<TemplateErrorContext<InvalidTypeCast>>),
     /// A provided type expression was malformed or didn't match a valid type.
     InvalidTypeExpression(TemplateErrorContext<InvalidTypeExpression>),
     /// The variant of the provided expression was invalid.
@@ -34,16 +44,68 @@ pub enum BalsaCompileError {
     ),
     /// Unexpected parameter was provided to a parameter block.
     InvalidParameter(TemplateErrorContext<InvalidParameter>),
+    /// A parameter block was given the same option more than once.
+    DuplicateOption(TemplateErrorContext<DuplicateOption>),
+    /// An identifier referenced a variable which has not been declared.
+    UndefinedVariable(TemplateErrorContext<UndefinedVariable>),
+    /// A parameter was used more than once with a different type or default value.
+    ConflictingParameter(Box<TemplateErrorContext<ConflictingParameter>>),
+    /// A variable was referenced before the declaration block declaring it.
+    UsedBeforeDeclaration(TemplateErrorContext<UsedBeforeDeclaration>),
+    /// Two blocks in the template overlap one another.
+    OverlappingBlocks(TemplateErrorContext<OverlappingBlocks>),
+    /// A helper block was called with the wrong number of arguments.
+    InvalidHelperArguments(TemplateErrorContext<InvalidHelperArguments>),
+    /// A `t` helper block referenced a message which is not in the message catalog.
+    MissingMessage(TemplateErrorContext<MissingMessage>),
+    /// A parameter block was given a direction other than `ltr`, `rtl` or `auto`.
+    InvalidDirection(TemplateErrorContext<InvalidDirection>),
+    /// A parameter block option which expects `true` or `false` was given another value.
+    InvalidBoolean(TemplateErrorContext<InvalidBoolean>),
+    /// A sandboxed template used a feature which is not allowed in sandbox mode.
+    SandboxViolation(TemplateErrorContext<SandboxViolation>),
+    /// A computed parameter was declared with a type other than `int` or `float`.
+    InvalidComputedType(TemplateErrorContext<InvalidComputedType>),
+    /// A computed parameter was declared more than once.
+    DuplicateComputed(TemplateErrorContext<DuplicateComputed>),
+    /// A macro was called which has not been defined earlier in the template.
+    UndefinedMacro(TemplateErrorContext<UndefinedMacro>),
+    /// A macro was defined more than once.
+    DuplicateMacro(TemplateErrorContext<DuplicateMacro>),
+    /// An `#each` block was given a parameter whose type isn't an array of dictionaries.
+    InvalidSectionType(TemplateErrorContext<InvalidSectionType>),
+    /// An array parameter was given a negative `minItems` or `maxItems`, or a `minItems` greater
+    /// than its `maxItems`.
+    InvalidItemLimits(TemplateErrorContext<InvalidItemLimits>),
+    /// Templates compiled with different escape policies were composed into one template.
+    ConflictingEscapePolicy(TemplateErrorContext<ConflictingEscapePolicy>),
+    /// A template was composed from a fragment which is not in the template set.
+    UndefinedTemplate(TemplateErrorContext<UndefinedTemplate>),
+    /// An `if`, `else` or `endif` block didn't form a complete conditional section.
+    UnbalancedConditional(TemplateErrorContext<UnbalancedConditional>),
+    /// An `include` block's template couldn't be loaded or compiled.
+    IncludeFailed(TemplateErrorContext<IncludeFailed>),
+    /// A parameter was passed through a filter which isn't registered.
+    UndefinedFilter(TemplateErrorContext<UndefinedFilter>),
+    /// A template of a [`crate::BalsaRegistry`] failed to compile.
+    TemplateFailed(TemplateErrorContext<TemplateFailed>),
+    /// The template exceeded a limit set in [`crate::CompileOptions`].
+    LimitExceeded(TemplateErrorContext<LimitExceeded>),
 }
 
 /// Wraps an error and provides file context.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TemplateErrorContext<T>
 where
     T: Display,
 {
     /// The character position within the raw template at which the failure occurred.
     pub pos: usize,
+    /// The line (starting at 1) within the raw template at which the failure occurred.
+    pub line: usize,
+    /// The column (starting at 1) within the raw template at which the failure occurred.
+    pub column: usize,
     /// The wrapped error that occurred.
     pub error: T,
 }
@@ -54,6 +116,24 @@ pub enum TemplateParseFail {
     /// Represents a generic parser fail.
     // TODO: more descriptive variants
     Generic,
+    /// The template's blocks or values were nested deeper than the configured maximum depth.
+    TooDeep {
+        /// The maximum nesting depth that was exceeded.
+        max_depth: usize,
+    },
+    /// The template was larger than the configured maximum size.
+    TooLarge {
+        /// The size of the template in bytes.
+        size: usize,
+        /// The maximum size in bytes that was exceeded.
+        max_size: usize,
+    },
+    /// An opening delimiter was not followed by a valid block. Unless the text started like a
+    /// parameter or declaration block, this is only an error with
+    /// [`crate::CompileOptions::strict_blocks`] enabled.
+    MalformedBlock,
+    /// The opening or closing delimiter set with [`crate::CompileOptions::delimiters`] was empty.
+    EmptyDelimiter,
 }
 
 /// Represents an invalid or failed attempt to cast [`BalsaValue`] `value` from [`BalsaType`] `from` to [`BalsaType`] `to`.
@@ -104,13 +184,265 @@ pub struct InvalidParameter {
     pub parameter_name: String,
 }
 
+/// Represents an option which was given more than once in the same parameter block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateOption {
+    /// The name of the repeated option.
+    pub option_name: String,
+}
+
+/// Represents a reference to a variable which has not been declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndefinedVariable {
+    /// The name of the undefined variable.
+    pub variable_name: String,
+}
+
+/// Represents a reference to a variable which is only declared later in the template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsedBeforeDeclaration {
+    /// The name of the variable.
+    pub variable_name: String,
+    /// The character position of the variable's declaration block.
+    pub declared_pos: usize,
+    /// The line of the variable's declaration block.
+    pub declared_line: usize,
+    /// The column of the variable's declaration block.
+    pub declared_column: usize,
+}
+
+/// Represents a block which begins before the previous block has ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlappingBlocks {
+    /// The character position where the previous block ends.
+    pub previous_end_pos: usize,
+    /// The line where the previous block ends.
+    pub previous_end_line: usize,
+    /// The column where the previous block ends.
+    pub previous_end_column: usize,
+}
+
+/// Represents a helper block called with the wrong number of arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidHelperArguments {
+    /// The name of the helper.
+    pub helper_name: String,
+    /// The number of arguments the helper expects.
+    pub expected: usize,
+    /// The number of arguments the helper was called with.
+    pub received: usize,
+}
+
+/// Represents a reference to a message which is not in the message catalog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingMessage {
+    /// The ID of the missing message.
+    pub key: String,
+}
+
+/// Represents a text direction which is not `ltr`, `rtl` or `auto`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidDirection {
+    /// The invalid direction.
+    pub direction: String,
+}
+
+/// Represents a value other than `true` or `false` given to a boolean parameter block option.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidBoolean {
+    /// The name of the option.
+    pub option: String,
+    /// The invalid value.
+    pub value: String,
+}
+
+/// Represents a feature which a sandboxed template is not allowed to use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxViolation {
+    /// The feature, as it is written in templates.
+    pub feature: String,
+}
+
+/// Represents a computed parameter declared with a type which isn't numeric.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidComputedType {
+    /// The name of the computed parameter.
+    pub computed_name: String,
+    /// The type the computed parameter was declared with.
+    pub computed_type: BalsaType,
+}
+
+/// Represents a computed parameter which was declared more than once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateComputed {
+    /// The name of the computed parameter.
+    pub computed_name: String,
+}
+
+/// Represents a call to a macro which has not been defined earlier in the template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndefinedMacro {
+    /// The name of the macro.
+    pub macro_name: String,
+}
+
+/// Represents a macro which was defined more than once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateMacro {
+    /// The name of the macro.
+    pub macro_name: String,
+}
+
+/// Represents the `minItems` and `maxItems` of an array parameter which can't both be met.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidItemLimits {
+    /// The name of the parameter.
+    pub parameter_name: String,
+    /// The minimum number of items, if one was given.
+    pub min_items: Option<i64>,
+    /// The maximum number of items, if one was given.
+    pub max_items: Option<i64>,
+}
+
+/// Represents a template fragment whose escape policy differs from the fragments before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictingEscapePolicy {
+    /// The escape policy of the fragments before it.
+    pub first: EscapePolicy,
+    /// The escape policy of the conflicting fragment.
+    pub conflicting: EscapePolicy,
+}
+
+/// Represents a fragment which was composed by name but is not in the template set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndefinedTemplate {
+    /// The name of the fragment.
+    pub template_name: String,
+}
+
+/// Represents an `if`, `else` or `endif` block which doesn't form a complete conditional
+/// section.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnbalancedConditional {
+    /// An `else` block which isn't inside a conditional section.
+    UnexpectedElse,
+    /// A second `else` block in the same conditional section.
+    DuplicateElse,
+    /// An `endif` block which doesn't close a conditional section.
+    UnexpectedEndIf,
+    /// An `if` block which is never closed by an `endif` block.
+    Unclosed,
+}
+
+/// Represents a parameter passed through a filter which wasn't registered with
+/// [`crate::BalsaBuilder::with_filter`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndefinedFilter {
+    /// The name of the filter.
+    pub filter_name: String,
+}
+
+/// Represents a template of a [`crate::BalsaRegistry`] which failed to compile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateFailed {
+    /// The name of the template in the registry, i.e. its path relative to the registry's
+    /// directory.
+    pub template_name: String,
+    /// The first error the template failed to compile with.
+    pub error: Box<BalsaCompileError>,
+}
+
+/// Represents an `include` block whose template couldn't be included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeFailed {
+    /// The path of the included template, as written in the block.
+    pub path: String,
+    /// Why the template couldn't be included.
+    pub reason: IncludeFailure,
+}
+
+/// The reason an `include` block's template couldn't be included.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncludeFailure {
+    /// No [`crate::TemplateLoader`] was set to load included templates with.
+    NoLoader,
+    /// The [`crate::TemplateLoader`] failed to load the template, with the error it returned.
+    LoadFailed(String),
+    /// The template includes itself outside of an `#each` section, directly or through other
+    /// templates. Holds the paths of the templates being included, outermost first.
+    Cycle(Vec<String>),
+    /// The template failed to compile.
+    CompileFailed(Box<BalsaCompileError>),
+}
+
+/// Represents an `#each` block whose parameter isn't an array of dictionaries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidSectionType {
+    /// The name of the parameter.
+    pub parameter_name: String,
+    /// The type the parameter was declared with.
+    pub parameter_type: BalsaType,
+}
+
+/// Represents a parameter which was used more than once with conflicting descriptions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictingParameter {
+    /// The name of the conflicting parameter.
+    pub parameter_name: String,
+    /// The character position at which the parameter was first used.
+    pub first_pos: usize,
+    /// The line at which the parameter was first used.
+    pub first_line: usize,
+    /// The column at which the parameter was first used.
+    pub first_column: usize,
+    /// The part of the parameter's description that conflicts with its first use.
+    pub conflict: ParameterConflict,
+}
+
+/// Describes how two uses of the same parameter conflict.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterConflict {
+    /// The parameter was given two different types.
+    Type {
+        /// The type the parameter was first given.
+        first: BalsaType,
+        /// The conflicting type.
+        conflicting: BalsaType,
+    },
+    /// The parameter was given two different default values.
+    DefaultValue {
+        /// The default value the parameter was first given.
+        first: BalsaValue,
+        /// The conflicting default value.
+        conflicting: BalsaValue,
+    },
+}
+
 /// Represents an error in compiling a file.
 #[derive(Debug, Clone, PartialEq)]
 pub enum BalsaRenderError {
     /// A parameter was expected and no default value was provided.
-    MissingParameter(MissingParameter),
+    MissingParameter(TemplateErrorContext<MissingParameter>),
     /// A parameter's value could not be casted to the specified type.
-    InvalidParameterType(InvalidParameterType),
+    InvalidParameterType(Box<TemplateErrorContext<InvalidParameterType>>),
+    /// A template was rendered with only its defaults, but some parameters have no default value.
+    MissingDefaults(TemplateErrorContext<MissingDefaults>),
+    /// The render exceeded one of the limits set in its [`crate::RenderOptions`].
+    LimitExceeded(TemplateErrorContext<LimitExceeded>),
+    /// The render was aborted by its deadline or cancellation token.
+    Aborted(TemplateErrorContext<RenderAborted>),
+    /// The expression of a computed parameter couldn't be evaluated.
+    ComputationFailed(TemplateErrorContext<ComputationFailed>),
+    /// A [`crate::DataLoader`] failed to load the value of a parameter.
+    LoadFailed(TemplateErrorContext<LoadFailed>),
+    /// An array parameter had fewer items than its `minItems` or more than its `maxItems`.
+    InvalidItemCount(TemplateErrorContext<InvalidItemCount>),
+    /// A parameter had a value which isn't one of the values given with `oneOf`.
+    DisallowedValue(Box<TemplateErrorContext<DisallowedValue>>),
+    /// A URL parameter marked `safe: true` had a URL which isn't safe to link to.
+    UnsafeUrl(TemplateErrorContext<UnsafeUrl>),
+    /// A filter failed to transform the value of a parameter.
+    FilterFailed(TemplateErrorContext<FilterFailed>),
 }
 
 /// A parameter was expected and no default value was provided.
@@ -120,6 +452,130 @@ pub struct MissingParameter {
     pub parameter_name: String,
 }
 
+/// A limit set in [`crate::RenderOptions`] which a render exceeded, or in
+/// [`crate::CompileOptions`] which a template exceeded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitExceeded {
+    /// The output grew larger than the configured maximum size.
+    OutputSize {
+        /// The maximum size in bytes that was exceeded.
+        max_size: usize,
+    },
+    /// More parameters were passed than the configured maximum, counting every localized layer.
+    ParameterCount {
+        /// The maximum number of parameters that was exceeded.
+        max_count: usize,
+    },
+    /// The value of a parameter was larger than the configured maximum size.
+    ValueSize {
+        /// The name of the parameter.
+        parameter_name: String,
+        /// The maximum size in bytes that was exceeded.
+        max_size: usize,
+    },
+    /// The `#each` sections repeated their body for more items than the configured maximum.
+    LoopIterations {
+        /// The maximum number of items that was exceeded.
+        max_iterations: usize,
+    },
+    /// Templates were included deeper than the configured maximum depth, when compiling or, for
+    /// templates included in `#each` sections, when rendering.
+    IncludeDepth {
+        /// The maximum include depth that was exceeded.
+        max_depth: usize,
+    },
+}
+
+/// The expression of a computed parameter couldn't be evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputationFailed {
+    /// The name of the computed parameter.
+    pub computed_name: String,
+    /// The reason the expression couldn't be evaluated.
+    pub reason: ComputationError,
+}
+
+/// A [`crate::DataLoader`] failed to load the value of a parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadFailed {
+    /// The name of the parameter.
+    pub parameter_name: String,
+    /// The reason given by the loader.
+    pub reason: String,
+}
+
+/// An array parameter had fewer items than its `minItems` or more than its `maxItems`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidItemCount {
+    /// The name of the parameter.
+    pub parameter_name: String,
+    /// The number of items the parameter had.
+    pub count: usize,
+    /// The minimum number of items, if there is one.
+    pub min_items: Option<usize>,
+    /// The maximum number of items, if there is one.
+    pub max_items: Option<usize>,
+}
+
+/// A parameter had a value which isn't one of the values given with `oneOf`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisallowedValue {
+    /// The name of the parameter.
+    pub parameter_name: String,
+    /// The value the parameter had.
+    pub value: BalsaValue,
+    /// The values the parameter may have.
+    pub allowed: Vec<BalsaValue>,
+}
+
+/// A URL parameter marked `safe: true` had a URL which isn't safe to link to, such as
+/// `javascript:alert(1)`. Only relative URLs and `http`, `https`, `mailto` and `tel` URLs are
+/// safe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsafeUrl {
+    /// The name of the parameter.
+    pub parameter_name: String,
+    /// The URL the parameter had.
+    pub url: String,
+}
+
+/// A filter failed to transform the value of a parameter, or wasn't registered with the
+/// template being rendered, e.g. one loaded with [`crate::Template::from_precompiled`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterFailed {
+    /// The name of the parameter.
+    pub parameter_name: String,
+    /// The name of the filter.
+    pub filter_name: String,
+    /// The reason given by the filter.
+    pub reason: String,
+}
+
+/// An arithmetic error in the expression of a computed parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputationError {
+    /// A value was divided by zero, or the remainder of dividing by zero was taken.
+    DivisionByZero,
+    /// The result was too large to be represented.
+    Overflow,
+}
+
+/// The reason a render was aborted before it finished.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderAborted {
+    /// The deadline set with [`crate::RenderOptions::deadline`] passed.
+    DeadlineExceeded,
+    /// The token set with [`crate::RenderOptions::cancellation`] was cancelled.
+    Cancelled,
+}
+
+/// A template was rendered with only its defaults, but some parameters have no default value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingDefaults {
+    /// The names of every parameter without a default value, in the order they appear.
+    pub parameter_names: Vec<String>,
+}
+
 /// A parameter's value could not be casted to the specified type.
 #[derive(Debug, Clone, PartialEq)]
 pub struct InvalidParameterType {
@@ -138,7 +594,16 @@ impl Display for BalsaError {
         match self {
             BalsaError::ReadTemplateError(e) => write!(f, "failed to read template file: {}", e),
             BalsaError::CompileError(e) => write!(f, "compile error: {}", e),
+            BalsaError::MultipleCompileErrors(errors) => {
+                write!(f, "{} compile errors:", errors.len())?;
+                for e in errors {
+                    write!(f, "\n  {}", e)?;
+                }
+
+                Ok(())
+            }
             BalsaError::RenderError(e) => write!(f, "render error: {}", e),
+            BalsaError::WriteOutputError(e) => write!(f, "failed to write output: {}", e),
         }
     }
 }
@@ -153,8 +618,54 @@ impl Display for BalsaCompileError {
             Self::InvalidIdentifierForParameterBlock(e) => e.fmt(f),
             Self::InvalidIdentifierForDeclarationBlock(e) => e.fmt(f),
             Self::InvalidParameter(e) => e.fmt(f),
+            Self::DuplicateOption(e) => e.fmt(f),
+            Self::UndefinedVariable(e) => e.fmt(f),
+            Self::ConflictingParameter(e) => e.fmt(f),
+            Self::UsedBeforeDeclaration(e) => e.fmt(f),
+            Self::OverlappingBlocks(e) => e.fmt(f),
+            Self::InvalidHelperArguments(e) => e.fmt(f),
+            Self::MissingMessage(e) => e.fmt(f),
+            Self::InvalidDirection(e) => e.fmt(f),
+            Self::InvalidBoolean(e) => e.fmt(f),
+            Self::SandboxViolation(e) => e.fmt(f),
+            Self::InvalidComputedType(e) => e.fmt(f),
+            Self::DuplicateComputed(e) => e.fmt(f),
+            Self::UndefinedMacro(e) => e.fmt(f),
+            Self::DuplicateMacro(e) => e.fmt(f),
+            Self::InvalidSectionType(e) => e.fmt(f),
+            Self::InvalidItemLimits(e) => e.fmt(f),
+            Self::ConflictingEscapePolicy(e) => e.fmt(f),
+            Self::UndefinedTemplate(e) => e.fmt(f),
+            Self::UnbalancedConditional(e) => e.fmt(f),
+            Self::IncludeFailed(e) => e.fmt(f),
+            Self::UndefinedFilter(e) => e.fmt(f),
+            Self::TemplateFailed(e) => e.fmt(f),
+            Self::LimitExceeded(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<T> TemplateErrorContext<T>
+where
+    T: Display,
+{
+    /// Makes a [`TemplateErrorContext<T>`] with the provided `pos` and `error` of type `T`.
+    pub(crate) fn new(pos: Position, error: T) -> Self {
+        Self {
+            pos: pos.offset,
+            line: pos.line,
+            column: pos.column,
+            error,
         }
     }
+
+    /// Returns the context moved to where it is in an input which `base` is the position of the
+    /// start of.
+    pub(crate) fn rebase(self, base: Position) -> Self {
+        let pos = Position::new(self.pos, self.line, self.column).rebase(base);
+
+        Self::new(pos, self.error)
+    }
 }
 
 // Allow [`TemplateErrorContext`]s to be deref'd to their wrapped error types.
@@ -174,13 +685,31 @@ where
     T: Display,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} at position {}", self.error, self.pos)
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.error, self.line, self.column
+        )
     }
 }
 
 impl Display for TemplateParseFail {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "parser failed")
+        match self {
+            Self::Generic => write!(f, "parser failed"),
+            Self::TooDeep { max_depth } => write!(
+                f,
+                "template exceeds the maximum nesting depth of {}",
+                max_depth
+            ),
+            Self::TooLarge { size, max_size } => write!(
+                f,
+                "template of {} bytes exceeds the maximum size of {} bytes",
+                size, max_size
+            ),
+            Self::MalformedBlock => write!(f, "malformed or unterminated block"),
+            Self::EmptyDelimiter => write!(f, "block delimiters must not be empty"),
+        }
     }
 }
 
@@ -234,6 +763,12 @@ impl Display for InvalidIdentifierForDeclarationBlock {
     }
 }
 
+impl Display for DuplicateOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "option `{}` provided more than once", self.option_name)
+    }
+}
+
 impl Display for InvalidParameter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -244,149 +779,1286 @@ impl Display for InvalidParameter {
     }
 }
 
-impl Display for BalsaRenderError {
+impl Display for UndefinedVariable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::MissingParameter(e) => e.fmt(f),
-            Self::InvalidParameterType(e) => e.fmt(f),
-        }
+        write!(f, "variable `{}` has not been declared", self.variable_name)
     }
 }
 
-impl Display for MissingParameter {
+impl Display for UsedBeforeDeclaration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "expected parameter `{}` but no parameter was found and no default value was provided",
-            self.parameter_name
+            "variable `{}` is used before it is declared (declared at line {}, column {})",
+            self.variable_name, self.declared_line, self.declared_column
         )
     }
 }
 
-impl Display for InvalidParameterType {
+impl Display for OverlappingBlocks {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "parameter `{}` but no parameter was found and no default value was provided",
-            self.parameter_name
+            "block overlaps the previous block (which ends at line {}, column {})",
+            self.previous_end_line, self.previous_end_column
         )
     }
 }
-// Error constructor functions.
-impl BalsaError {
-    /// Creates a [`BalsaError::CompileError`] with the provided [`BalsaCompileError`].
-    pub(crate) fn new_compile_error(error: BalsaCompileError) -> Self {
-        Self::CompileError(error)
-    }
 
-    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::TemlateParseFail`]
-    /// which wraps a [`ParseFail::Generic`].
-    pub(crate) fn generic_template_parse_fail(pos: usize) -> Self {
-        Self::new_compile_error(BalsaCompileError::TemplateParseFail(
-            Self::template_context(pos, TemplateParseFail::Generic),
-        ))
+impl Display for InvalidHelperArguments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "helper `{}` expects {} argument(s) but received {}",
+            self.helper_name, self.expected, self.received
+        )
     }
+}
 
-    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::InvalidTypeCast`]
-    /// which wraps a [`InvalidTypeCast`] with the provided arguments.
-    pub(crate) fn invalid_type_cast(
-        pos: usize,
-        value: BalsaValue,
-        from_type: BalsaType,
-        to_type: BalsaType,
-    ) -> Self {
-        Self::new_compile_error(BalsaCompileError::InvalidTypeCast(Self::template_context(
-            pos,
-            InvalidTypeCast {
-                value,
-                from: from_type,
-                to: to_type,
-            },
-        )))
+impl Display for MissingMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "message `{}` was not found in the message catalog",
+            self.key
+        )
     }
+}
 
-    /// Creates a new [`BalsaError::CompileError`] which wraps a
-    /// [`CompileError::InvalidTypeExpression`] which wraps a [`InvalidTypeExpression`] with the
-    /// provided expression.
-    pub(crate) fn invalid_type_expression(pos: usize, expression: BalsaExpression) -> Self {
-        Self::new_compile_error(BalsaCompileError::InvalidTypeExpression(
-            Self::template_context(pos, InvalidTypeExpression { expression }),
-        ))
+impl Display for InvalidDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid direction `{}`, expected `ltr`, `rtl` or `auto`",
+            self.direction
+        )
     }
+}
 
-    /// Creates a new [`BalsaError::CompileError`] which wraps a
-    /// [`CompileError::InvalidExpression`] which wraps a [`InvalidExpression`] with the
-    /// provided expression.
-    pub(crate) fn invalid_expression(pos: usize, expression: BalsaExpression) -> Self {
-        Self::new_compile_error(BalsaCompileError::InvalidExpression(
-            Self::template_context(pos, InvalidExpression { expression }),
-        ))
+impl Display for InvalidBoolean {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid value `{}` for option `{}`, expected `true` or `false`",
+            self.value, self.option
+        )
     }
+}
 
-    /// Creates a new [`BalsaError::CompileError`] which wraps a
-    /// [`CompileError::InvalidIdentifierForParameterBlock`] which wraps a
-    /// [`InvalidIdentifierForParameterBlock`] with the provided arguments.
-    pub(crate) fn invalid_identifier_in_parameter_block(
-        pos: usize,
-        expression: BalsaExpression,
-    ) -> Self {
-        Self::new_compile_error(BalsaCompileError::InvalidIdentifierForParameterBlock(
-            Self::template_context(pos, InvalidIdentifierForParameterBlock { expression }),
-        ))
+impl Display for SandboxViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` is not allowed in sandboxed templates",
+            self.feature
+        )
     }
+}
 
-    /// Creates a new [`BalsaError::CompileError`] which wraps a
-    /// [`CompileError::InvalidIdentifierForDeclarationBlock`] which wraps a
-    /// [`InvalidIdentifierForDeclarationBlock`] with the provided arguments.
-    pub(crate) fn invalid_identifier_in_declaration_block(
-        pos: usize,
-        expression: BalsaExpression,
-    ) -> Self {
-        Self::new_compile_error(BalsaCompileError::InvalidIdentifierForDeclarationBlock(
-            Self::template_context(pos, InvalidIdentifierForDeclarationBlock { expression }),
-        ))
+impl Display for InvalidComputedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "computed parameter `{}` must be of type int or float, not {}",
+            self.computed_name, self.computed_type
+        )
     }
+}
 
-    /// Creates a new [`BalsaError::CompileError`] which wraps a
-    /// [`CompileError::InvalidParameter`] which wraps a [`InvalidParameter`] with the provided
-    /// parameter name.
-    pub(crate) fn invalid_parameter(pos: usize, parameter_name: String) -> Self {
-        Self::new_compile_error(BalsaCompileError::InvalidParameter(Self::template_context(
-            pos,
-            InvalidParameter { parameter_name },
-        )))
+impl Display for DuplicateComputed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "computed parameter `{}` declared more than once",
+            self.computed_name
+        )
     }
+}
 
-    pub(crate) fn new_render_error(error: BalsaRenderError) -> Self {
-        Self::RenderError(error)
+impl Display for UndefinedMacro {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "macro `{}` has not been defined", self.macro_name)
     }
+}
 
-    /// Creates a new [`BalsaError::RenderError`] which wraps a
-    /// [`RenderError::MissingParameter`] which wraps a [`MissingParameter`] with the provided
-    /// parameter name.
-    pub(crate) fn missing_parameter(parameter_name: String) -> Self {
-        Self::new_render_error(BalsaRenderError::MissingParameter(MissingParameter {
-            parameter_name,
-        }))
+impl Display for DuplicateMacro {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "macro `{}` defined more than once", self.macro_name)
     }
+}
 
-    /// Creates a new [`BalsaError::RenderError`] which wraps a
-    /// [`RenderError::InvalidParameterType`] which wraps a [`InvalidParameterType`] with the provided
-    /// parameter name, parameter_value.
-    pub(crate) fn invalid_parameter_type(
+impl Display for InvalidItemLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let limit = |limit: Option<i64>| limit.map_or("none".to_string(), |l| l.to_string());
+
+        write!(
+            f,
+            "parameter `{}` has invalid item limits (minItems: {}, maxItems: {}); limits must not be negative and minItems must not exceed maxItems",
+            self.parameter_name,
+            limit(self.min_items),
+            limit(self.max_items)
+        )
+    }
+}
+
+impl Display for ConflictingEscapePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "template compiled with escape policy {:?} can't be composed with templates compiled with escape policy {:?}",
+            self.conflicting, self.first
+        )
+    }
+}
+
+impl Display for UndefinedTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "template `{}` is not in the template set",
+            self.template_name
+        )
+    }
+}
+
+impl Display for UnbalancedConditional {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedElse => write!(f, "`else` block outside of an `if` block"),
+            Self::DuplicateElse => write!(f, "`if` block has more than one `else` block"),
+            Self::UnexpectedEndIf => write!(f, "`endif` block without a matching `if` block"),
+            Self::Unclosed => write!(f, "`if` block is never closed by an `endif` block"),
+        }
+    }
+}
+
+impl Display for UndefinedFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "filter `{}` is not registered", self.filter_name)
+    }
+}
+
+impl Display for TemplateFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "in template `{}`: {}", self.template_name, self.error)
+    }
+}
+
+impl Display for IncludeFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.reason {
+            IncludeFailure::NoLoader => write!(
+                f,
+                "can't include `{}` as no template loader was set",
+                self.path
+            ),
+            IncludeFailure::LoadFailed(e) => {
+                write!(f, "failed to load included template `{}`: {}", self.path, e)
+            }
+            IncludeFailure::Cycle(paths) => write!(
+                f,
+                "template `{}` includes itself through `{}`",
+                self.path,
+                paths.join("` -> `")
+            ),
+            IncludeFailure::CompileFailed(e) => {
+                write!(f, "in included template `{}`: {}", self.path, e)
+            }
+        }
+    }
+}
+
+impl Display for InvalidSectionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`#each` parameter `{}` must be an array of dictionaries, not {}",
+            self.parameter_name, self.parameter_type
+        )
+    }
+}
+
+impl Display for ConflictingParameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.conflict {
+            ParameterConflict::Type { first, conflicting } => write!(
+                f,
+                "parameter `{}` has type `{}` but was first used with type `{}` (first used at line {}, column {})",
+                self.parameter_name, conflicting, first, self.first_line, self.first_column
+            ),
+            ParameterConflict::DefaultValue { first, conflicting } => write!(
+                f,
+                "parameter `{}` has default value `{}` but was first used with default value `{}` (first used at line {}, column {})",
+                self.parameter_name, conflicting, first, self.first_line, self.first_column
+            ),
+        }
+    }
+}
+
+impl Display for BalsaRenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingParameter(e) => e.fmt(f),
+            Self::InvalidParameterType(e) => e.fmt(f),
+            Self::MissingDefaults(e) => e.fmt(f),
+            Self::LimitExceeded(e) => e.fmt(f),
+            Self::Aborted(e) => e.fmt(f),
+            Self::ComputationFailed(e) => e.fmt(f),
+            Self::LoadFailed(e) => e.fmt(f),
+            Self::InvalidItemCount(e) => e.fmt(f),
+            Self::DisallowedValue(e) => e.fmt(f),
+            Self::UnsafeUrl(e) => e.fmt(f),
+            Self::FilterFailed(e) => e.fmt(f),
+        }
+    }
+}
+
+impl BalsaError {
+    /// Returns a stable, machine-readable code identifying the kind of error, i.e. `B0402` for a
+    /// parameter with a value of the wrong type, which applications can match on without
+    /// depending on error messages.
+    ///
+    /// Codes starting with `B00` are IO errors or several errors at once, `B01` template parse
+    /// failures, `B02` other compile errors and `B04` render errors. A code never changes its
+    /// meaning once released.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let error = Balsa::from_string("<h1>{{ title : string, defaultValue: missing }}</h1>")
+    ///     .build()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(error.code(), "B0208");
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ReadTemplateError(_) => "B0001",
+            Self::WriteOutputError(_) => "B0002",
+            Self::MultipleCompileErrors(_) => "B0003",
+            Self::CompileError(e) => e.code(),
+            Self::RenderError(e) => e.code(),
+        }
+    }
+
+    /// Creates an error for a filter registered with [`crate::BalsaBuilder::with_filter`] to
+    /// return when it can't transform a value. Rendering fails with a
+    /// [`BalsaRenderError::FilterFailed`] error naming the filter and parameter, with `reason`
+    /// as its reason.
+    pub fn filter_failed(reason: impl Into<String>) -> Self {
+        Self::filter_failed_at(
+            Position::default(),
+            String::new(),
+            String::new(),
+            reason.into(),
+        )
+    }
+}
+
+impl BalsaCompileError {
+    /// Returns a stable, machine-readable code identifying the kind of error. See
+    /// [`BalsaError::code`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TemplateParseFail(e) => match e.error {
+                TemplateParseFail::Generic => "B0101",
+                TemplateParseFail::TooDeep { .. } => "B0102",
+                TemplateParseFail::TooLarge { .. } => "B0103",
+                TemplateParseFail::MalformedBlock => "B0104",
+                TemplateParseFail::EmptyDelimiter => "B0105",
+            },
+            Self::InvalidTypeCast(_) => "B0201",
+            Self::InvalidTypeExpression(_) => "B0202",
+            Self::InvalidExpression(_) => "B0203",
+            Self::InvalidIdentifierForParameterBlock(_) => "B0204",
+            Self::InvalidIdentifierForDeclarationBlock(_) => "B0205",
+            Self::InvalidParameter(_) => "B0206",
+            Self::DuplicateOption(_) => "B0207",
+            Self::UndefinedVariable(_) => "B0208",
+            Self::ConflictingParameter(_) => "B0209",
+            Self::UsedBeforeDeclaration(_) => "B0210",
+            Self::OverlappingBlocks(_) => "B0211",
+            Self::InvalidHelperArguments(_) => "B0212",
+            Self::MissingMessage(_) => "B0213",
+            Self::InvalidDirection(_) => "B0214",
+            Self::InvalidBoolean(_) => "B0215",
+            Self::SandboxViolation(_) => "B0216",
+            Self::InvalidComputedType(_) => "B0217",
+            Self::DuplicateComputed(_) => "B0218",
+            Self::UndefinedMacro(_) => "B0219",
+            Self::DuplicateMacro(_) => "B0220",
+            Self::InvalidSectionType(_) => "B0221",
+            Self::InvalidItemLimits(_) => "B0222",
+            Self::ConflictingEscapePolicy(_) => "B0223",
+            Self::UndefinedTemplate(_) => "B0224",
+            Self::UnbalancedConditional(_) => "B0225",
+            Self::IncludeFailed(_) => "B0226",
+            Self::UndefinedFilter(_) => "B0227",
+            Self::TemplateFailed(_) => "B0228",
+            Self::LimitExceeded(_) => "B0229",
+        }
+    }
+
+    /// Returns the [`TemplateErrorContext`] the error wraps.
+    fn context(&self) -> &(dyn Error + 'static) {
+        match self {
+            Self::TemplateParseFail(e) => e,
+            Self::InvalidTypeCast(e) => e,
+            Self::InvalidTypeExpression(e) => e,
+            Self::InvalidExpression(e) => e,
+            Self::InvalidIdentifierForParameterBlock(e) => e,
+            Self::InvalidIdentifierForDeclarationBlock(e) => e,
+            Self::InvalidParameter(e) => e,
+            Self::DuplicateOption(e) => e,
+            Self::UndefinedVariable(e) => e,
+            Self::ConflictingParameter(e) => e,
+            Self::UsedBeforeDeclaration(e) => e,
+            Self::OverlappingBlocks(e) => e,
+            Self::InvalidHelperArguments(e) => e,
+            Self::MissingMessage(e) => e,
+            Self::InvalidDirection(e) => e,
+            Self::InvalidBoolean(e) => e,
+            Self::SandboxViolation(e) => e,
+            Self::InvalidComputedType(e) => e,
+            Self::DuplicateComputed(e) => e,
+            Self::UndefinedMacro(e) => e,
+            Self::DuplicateMacro(e) => e,
+            Self::InvalidSectionType(e) => e,
+            Self::InvalidItemLimits(e) => e,
+            Self::ConflictingEscapePolicy(e) => e,
+            Self::UndefinedTemplate(e) => e,
+            Self::UnbalancedConditional(e) => e,
+            Self::IncludeFailed(e) => e,
+            Self::UndefinedFilter(e) => e,
+            Self::TemplateFailed(e) => e,
+            Self::LimitExceeded(e) => e,
+        }
+    }
+}
+
+impl BalsaRenderError {
+    /// Returns a stable, machine-readable code identifying the kind of error. See
+    /// [`BalsaError::code`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingParameter(_) => "B0401",
+            Self::InvalidParameterType(_) => "B0402",
+            Self::MissingDefaults(_) => "B0403",
+            Self::LimitExceeded(_) => "B0404",
+            Self::Aborted(_) => "B0405",
+            Self::ComputationFailed(_) => "B0406",
+            Self::LoadFailed(_) => "B0407",
+            Self::InvalidItemCount(_) => "B0408",
+            Self::DisallowedValue(_) => "B0409",
+            Self::UnsafeUrl(_) => "B0410",
+            Self::FilterFailed(_) => "B0411",
+        }
+    }
+
+    /// Returns the [`TemplateErrorContext`] the error wraps.
+    fn context(&self) -> &(dyn Error + 'static) {
+        match self {
+            Self::MissingParameter(e) => e,
+            Self::InvalidParameterType(e) => e,
+            Self::MissingDefaults(e) => e,
+            Self::LimitExceeded(e) => e,
+            Self::Aborted(e) => e,
+            Self::ComputationFailed(e) => e,
+            Self::LoadFailed(e) => e,
+            Self::InvalidItemCount(e) => e,
+            Self::DisallowedValue(e) => e,
+            Self::UnsafeUrl(e) => e,
+            Self::FilterFailed(e) => e,
+        }
+    }
+}
+
+impl Error for BalsaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ReadTemplateError(e) | Self::WriteOutputError(e) => Some(e),
+            Self::CompileError(e) => Some(e),
+            Self::MultipleCompileErrors(errors) => errors.first().map(|e| e as _),
+            Self::RenderError(e) => Some(e),
+        }
+    }
+}
+
+// The message of a compile or render error is the message of its context, so the chain skips
+// straight to the error the context wraps.
+impl Error for BalsaCompileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.context().source()
+    }
+}
+
+impl Error for BalsaRenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.context().source()
+    }
+}
+
+impl<T> Error for TemplateErrorContext<T>
+where
+    T: Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl Error for TemplateFailed {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl Error for IncludeFailed {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.reason {
+            IncludeFailure::CompileFailed(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Implements [`Error`] for errors which don't wrap another error.
+macro_rules! impl_error {
+    ($($error:ty),* $(,)?) => {
+        $(impl Error for $error {})*
+    };
+}
+
+impl_error!(
+    ComputationFailed,
+    ConflictingEscapePolicy,
+    ConflictingParameter,
+    DisallowedValue,
+    DuplicateComputed,
+    DuplicateMacro,
+    DuplicateOption,
+    FilterFailed,
+    InvalidBoolean,
+    InvalidComputedType,
+    InvalidDirection,
+    InvalidExpression,
+    InvalidHelperArguments,
+    InvalidIdentifierForDeclarationBlock,
+    InvalidIdentifierForParameterBlock,
+    InvalidItemCount,
+    InvalidItemLimits,
+    InvalidParameter,
+    InvalidParameterType,
+    InvalidSectionType,
+    InvalidTypeCast,
+    InvalidTypeExpression,
+    LimitExceeded,
+    LoadFailed,
+    MissingDefaults,
+    MissingMessage,
+    MissingParameter,
+    OverlappingBlocks,
+    RenderAborted,
+    SandboxViolation,
+    TemplateParseFail,
+    UnbalancedConditional,
+    UndefinedFilter,
+    UndefinedMacro,
+    UndefinedTemplate,
+    UndefinedVariable,
+    UnsafeUrl,
+    UsedBeforeDeclaration,
+);
+
+impl Display for ComputationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to compute parameter `{}`: {}",
+            self.computed_name, self.reason
+        )
+    }
+}
+
+impl Display for InvalidItemCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "parameter `{}` has {} items",
+            self.parameter_name, self.count
+        )?;
+
+        match (self.min_items, self.max_items) {
+            (Some(min), Some(max)) => write!(f, " but must have between {} and {}", min, max),
+            (Some(min), None) => write!(f, " but must have at least {}", min),
+            (None, Some(max)) => write!(f, " but must have at most {}", max),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl Display for DisallowedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let allowed = self
+            .allowed
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>();
+
+        write!(
+            f,
+            "parameter `{}` has value {} but must be one of {}",
+            self.parameter_name,
+            self.value,
+            allowed.join(", ")
+        )
+    }
+}
+
+impl Display for UnsafeUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "parameter `{}` has URL {:?} which isn't safe to link to",
+            self.parameter_name, self.url
+        )
+    }
+}
+
+impl Display for FilterFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "filter `{}` failed on parameter `{}`: {}",
+            self.filter_name, self.parameter_name, self.reason
+        )
+    }
+}
+
+impl Display for LoadFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to load parameter `{}`: {}",
+            self.parameter_name, self.reason
+        )
+    }
+}
+
+impl Display for ComputationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::Overflow => write!(f, "result is out of range"),
+        }
+    }
+}
+
+impl Display for RenderAborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DeadlineExceeded => write!(f, "render exceeded its deadline"),
+            Self::Cancelled => write!(f, "render was cancelled"),
+        }
+    }
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutputSize { max_size } => write!(
+                f,
+                "rendered output exceeds the maximum size of {} bytes",
+                max_size
+            ),
+            Self::ParameterCount { max_count } => {
+                write!(f, "parameters exceed the maximum count of {}", max_count)
+            }
+            Self::ValueSize {
+                parameter_name,
+                max_size,
+            } => write!(
+                f,
+                "value of parameter `{}` exceeds the maximum size of {} bytes",
+                parameter_name, max_size
+            ),
+            Self::LoopIterations { max_iterations } => write!(
+                f,
+                "sections exceed the maximum of {} iterations",
+                max_iterations
+            ),
+            Self::IncludeDepth { max_depth } => {
+                write!(f, "includes exceed the maximum depth of {}", max_depth)
+            }
+        }
+    }
+}
+
+impl Display for MissingDefaults {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self
+            .parameter_names
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<String>>();
+
+        write!(f, "parameters {} have no default value", names.join(", "))
+    }
+}
+
+impl Display for MissingParameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected parameter `{}` but no parameter was found and no default value was provided",
+            self.parameter_name
+        )
+    }
+}
+
+impl Display for InvalidParameterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "parameter `{}` but no parameter was found and no default value was provided",
+            self.parameter_name
+        )
+    }
+}
+// Error constructor functions.
+impl BalsaError {
+    /// Creates a [`BalsaError::CompileError`] with the provided [`BalsaCompileError`].
+    pub(crate) fn new_compile_error(error: BalsaCompileError) -> Self {
+        Self::CompileError(error)
+    }
+
+    /// Creates a [`BalsaError::CompileError`] if `errors` holds a single [`BalsaCompileError`],
+    /// and otherwise a [`BalsaError::MultipleCompileErrors`].
+    pub(crate) fn compile_errors(mut errors: Vec<BalsaCompileError>) -> Self {
+        match errors.len() {
+            1 => Self::new_compile_error(errors.remove(0)),
+            _ => Self::MultipleCompileErrors(errors),
+        }
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::TemlateParseFail`]
+    /// which wraps a [`ParseFail::Generic`].
+    pub(crate) fn generic_template_parse_fail(pos: Position) -> Self {
+        Self::new_compile_error(BalsaCompileError::TemplateParseFail(
+            Self::template_context(pos, TemplateParseFail::Generic),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::TemlateParseFail`]
+    /// which wraps a [`ParseFail::TooDeep`] with the provided maximum depth.
+    pub(crate) fn template_too_deep(pos: Position, max_depth: usize) -> Self {
+        Self::new_compile_error(BalsaCompileError::TemplateParseFail(
+            Self::template_context(pos, TemplateParseFail::TooDeep { max_depth }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::TemlateParseFail`]
+    /// which wraps a [`ParseFail::TooLarge`] with the provided sizes, located at the start of the
+    /// template.
+    pub(crate) fn template_too_large(size: usize, max_size: usize) -> Self {
+        Self::new_compile_error(BalsaCompileError::TemplateParseFail(
+            Self::template_context(
+                Position::default(),
+                TemplateParseFail::TooLarge { size, max_size },
+            ),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::TemlateParseFail`]
+    /// which wraps a [`ParseFail::MalformedBlock`].
+    pub(crate) fn malformed_block(pos: Position) -> Self {
+        Self::new_compile_error(BalsaCompileError::TemplateParseFail(
+            Self::template_context(pos, TemplateParseFail::MalformedBlock),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::TemlateParseFail`]
+    /// which wraps a [`ParseFail::EmptyDelimiter`], located at the start of the template.
+    pub(crate) fn empty_delimiter() -> Self {
+        Self::new_compile_error(BalsaCompileError::TemplateParseFail(
+            Self::template_context(Position::default(), TemplateParseFail::EmptyDelimiter),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a [`CompileError::InvalidTypeCast`]
+    /// which wraps a [`InvalidTypeCast`] with the provided arguments.
+    pub(crate) fn invalid_type_cast(
+        pos: Position,
+        value: BalsaValue,
+        from_type: BalsaType,
+        to_type: BalsaType,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidTypeCast(Box::new(
+            Self::template_context(
+                pos,
+                InvalidTypeCast {
+                    value,
+                    from: from_type,
+                    to: to_type,
+                },
+            ),
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidTypeExpression`] which wraps a [`InvalidTypeExpression`] with the
+    /// provided expression.
+    pub(crate) fn invalid_type_expression(pos: Position, expression: BalsaExpression) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidTypeExpression(
+            Self::template_context(pos, InvalidTypeExpression { expression }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidExpression`] which wraps a [`InvalidExpression`] with the
+    /// provided expression.
+    pub(crate) fn invalid_expression(pos: Position, expression: BalsaExpression) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidExpression(
+            Self::template_context(pos, InvalidExpression { expression }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidIdentifierForParameterBlock`] which wraps a
+    /// [`InvalidIdentifierForParameterBlock`] with the provided arguments.
+    pub(crate) fn invalid_identifier_in_parameter_block(
+        pos: Position,
+        expression: BalsaExpression,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidIdentifierForParameterBlock(
+            Self::template_context(pos, InvalidIdentifierForParameterBlock { expression }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidIdentifierForDeclarationBlock`] which wraps a
+    /// [`InvalidIdentifierForDeclarationBlock`] with the provided arguments.
+    pub(crate) fn invalid_identifier_in_declaration_block(
+        pos: Position,
+        expression: BalsaExpression,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidIdentifierForDeclarationBlock(
+            Self::template_context(pos, InvalidIdentifierForDeclarationBlock { expression }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidParameter`] which wraps a [`InvalidParameter`] with the provided
+    /// parameter name.
+    pub(crate) fn invalid_parameter(pos: Position, parameter_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidParameter(Self::template_context(
+            pos,
+            InvalidParameter { parameter_name },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::DuplicateOption`] which wraps a [`DuplicateOption`] with the provided
+    /// option name.
+    pub(crate) fn duplicate_option(pos: Position, option_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::DuplicateOption(Self::template_context(
+            pos,
+            DuplicateOption { option_name },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::UndefinedVariable`] which wraps a [`UndefinedVariable`] with the provided
+    /// variable name.
+    pub(crate) fn undefined_variable(pos: Position, variable_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::UndefinedVariable(
+            Self::template_context(pos, UndefinedVariable { variable_name }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::UsedBeforeDeclaration`] which wraps a [`UsedBeforeDeclaration`] with the
+    /// provided arguments.
+    pub(crate) fn used_before_declaration(
+        pos: Position,
+        variable_name: String,
+        declared_pos: Position,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::UsedBeforeDeclaration(
+            Self::template_context(
+                pos,
+                UsedBeforeDeclaration {
+                    variable_name,
+                    declared_pos: declared_pos.offset,
+                    declared_line: declared_pos.line,
+                    declared_column: declared_pos.column,
+                },
+            ),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::OverlappingBlocks`] which wraps a [`OverlappingBlocks`] with the provided
+    /// arguments.
+    pub(crate) fn overlapping_blocks(pos: Position, previous_end_pos: Position) -> Self {
+        Self::new_compile_error(BalsaCompileError::OverlappingBlocks(
+            Self::template_context(
+                pos,
+                OverlappingBlocks {
+                    previous_end_pos: previous_end_pos.offset,
+                    previous_end_line: previous_end_pos.line,
+                    previous_end_column: previous_end_pos.column,
+                },
+            ),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidHelperArguments`] which wraps a [`InvalidHelperArguments`] with the
+    /// provided arguments.
+    pub(crate) fn invalid_helper_arguments(
+        pos: Position,
+        helper_name: String,
+        expected: usize,
+        received: usize,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidHelperArguments(
+            Self::template_context(
+                pos,
+                InvalidHelperArguments {
+                    helper_name,
+                    expected,
+                    received,
+                },
+            ),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::MissingMessage`] which wraps a [`MissingMessage`] with the provided
+    /// message ID.
+    pub(crate) fn missing_message(pos: Position, key: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::MissingMessage(Self::template_context(
+            pos,
+            MissingMessage { key },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidDirection`] which wraps a [`InvalidDirection`] with the provided
+    /// direction.
+    pub(crate) fn invalid_direction(pos: Position, direction: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidDirection(Self::template_context(
+            pos,
+            InvalidDirection { direction },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidBoolean`] which wraps a [`InvalidBoolean`] with the provided option
+    /// name and value.
+    pub(crate) fn invalid_boolean(pos: Position, option: String, value: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidBoolean(Self::template_context(
+            pos,
+            InvalidBoolean { option, value },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::SandboxViolation`] which wraps a [`SandboxViolation`] with the provided
+    /// feature.
+    pub(crate) fn sandbox_violation(pos: Position, feature: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::SandboxViolation(Self::template_context(
+            pos,
+            SandboxViolation { feature },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidComputedType`] which wraps a [`InvalidComputedType`] with the
+    /// provided computed parameter name and type.
+    pub(crate) fn invalid_computed_type(
+        pos: Position,
+        computed_name: String,
+        computed_type: BalsaType,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidComputedType(
+            Self::template_context(
+                pos,
+                InvalidComputedType {
+                    computed_name,
+                    computed_type,
+                },
+            ),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::DuplicateComputed`] which wraps a [`DuplicateComputed`] with the provided
+    /// computed parameter name.
+    pub(crate) fn duplicate_computed(pos: Position, computed_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::DuplicateComputed(
+            Self::template_context(pos, DuplicateComputed { computed_name }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::UndefinedMacro`] which wraps a [`UndefinedMacro`] with the provided macro
+    /// name.
+    pub(crate) fn undefined_macro(pos: Position, macro_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::UndefinedMacro(Self::template_context(
+            pos,
+            UndefinedMacro { macro_name },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::DuplicateMacro`] which wraps a [`DuplicateMacro`] with the provided macro
+    /// name.
+    pub(crate) fn duplicate_macro(pos: Position, macro_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::DuplicateMacro(Self::template_context(
+            pos,
+            DuplicateMacro { macro_name },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidItemLimits`] which wraps a [`InvalidItemLimits`] with the provided
+    /// parameter name and limits.
+    pub(crate) fn invalid_item_limits(
+        pos: Position,
+        parameter_name: String,
+        min_items: Option<i64>,
+        max_items: Option<i64>,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidItemLimits(
+            Self::template_context(
+                pos,
+                InvalidItemLimits {
+                    parameter_name,
+                    min_items,
+                    max_items,
+                },
+            ),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::ConflictingEscapePolicy`] which wraps a [`ConflictingEscapePolicy`] with
+    /// the provided escape policies.
+    pub(crate) fn conflicting_escape_policy(
+        pos: Position,
+        first: EscapePolicy,
+        conflicting: EscapePolicy,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::ConflictingEscapePolicy(
+            Self::template_context(pos, ConflictingEscapePolicy { first, conflicting }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::UndefinedTemplate`] which wraps a [`UndefinedTemplate`] with the provided
+    /// template name, located at the start of the template.
+    pub(crate) fn undefined_template(template_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::UndefinedTemplate(
+            Self::template_context(Position::default(), UndefinedTemplate { template_name }),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::TemplateFailed`] which wraps a [`TemplateFailed`] with the provided
+    /// template name and the first compile error of `error`, located at the start of the
+    /// template. Errors reading the template are returned as-is.
+    pub(crate) fn template_failed(template_name: String, error: BalsaError) -> Self {
+        let error = match error {
+            Self::CompileError(e) => e,
+            Self::MultipleCompileErrors(mut errors) if !errors.is_empty() => errors.remove(0),
+            e => return e,
+        };
+
+        Self::new_compile_error(BalsaCompileError::TemplateFailed(Self::template_context(
+            Position::default(),
+            TemplateFailed {
+                template_name,
+                error: Box::new(error),
+            },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::UnbalancedConditional`] which wraps the provided [`UnbalancedConditional`].
+    pub(crate) fn unbalanced_conditional(pos: Position, error: UnbalancedConditional) -> Self {
+        Self::new_compile_error(BalsaCompileError::UnbalancedConditional(
+            Self::template_context(pos, error),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::IncludeFailed`] which wraps a [`IncludeFailed`] with the provided path and
+    /// reason.
+    pub(crate) fn include_failed(pos: Position, path: String, reason: IncludeFailure) -> Self {
+        Self::new_compile_error(BalsaCompileError::IncludeFailed(Self::template_context(
+            pos,
+            IncludeFailed { path, reason },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::LimitExceeded`] which wraps a [`LimitExceeded::IncludeDepth`] with the
+    /// provided maximum depth.
+    pub(crate) fn include_too_deep(pos: Position, max_depth: usize) -> Self {
+        Self::new_compile_error(BalsaCompileError::LimitExceeded(Self::template_context(
+            pos,
+            LimitExceeded::IncludeDepth { max_depth },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::UndefinedFilter`] which wraps a [`UndefinedFilter`] with the provided
+    /// filter name.
+    pub(crate) fn undefined_filter(pos: Position, filter_name: String) -> Self {
+        Self::new_compile_error(BalsaCompileError::UndefinedFilter(Self::template_context(
+            pos,
+            UndefinedFilter { filter_name },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::InvalidSectionType`] which wraps a [`InvalidSectionType`] with the provided
+    /// parameter name and type.
+    pub(crate) fn invalid_section_type(
+        pos: Position,
+        parameter_name: String,
+        parameter_type: BalsaType,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::InvalidSectionType(
+            Self::template_context(
+                pos,
+                InvalidSectionType {
+                    parameter_name,
+                    parameter_type,
+                },
+            ),
+        ))
+    }
+
+    /// Creates a new [`BalsaError::CompileError`] which wraps a
+    /// [`CompileError::ConflictingParameter`] which wraps a [`ConflictingParameter`] with the
+    /// provided arguments.
+    pub(crate) fn conflicting_parameter(
+        pos: Position,
+        parameter_name: String,
+        first_pos: Position,
+        conflict: ParameterConflict,
+    ) -> Self {
+        Self::new_compile_error(BalsaCompileError::ConflictingParameter(Box::new(
+            Self::template_context(
+                pos,
+                ConflictingParameter {
+                    parameter_name,
+                    first_pos: first_pos.offset,
+                    first_line: first_pos.line,
+                    first_column: first_pos.column,
+                    conflict,
+                },
+            ),
+        )))
+    }
+
+    pub(crate) fn new_render_error(error: BalsaRenderError) -> Self {
+        Self::RenderError(error)
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::MissingParameter`] which wraps a [`MissingParameter`] with the provided
+    /// parameter name.
+    pub(crate) fn missing_parameter(pos: Position, parameter_name: String) -> Self {
+        Self::new_render_error(BalsaRenderError::MissingParameter(Self::template_context(
+            pos,
+            MissingParameter { parameter_name },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::MissingDefaults`] which wraps a [`MissingDefaults`] with the provided
+    /// parameter names, located at the first parameter without a default value.
+    pub(crate) fn missing_defaults(pos: Position, parameter_names: Vec<String>) -> Self {
+        Self::new_render_error(BalsaRenderError::MissingDefaults(Self::template_context(
+            pos,
+            MissingDefaults { parameter_names },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::LimitExceeded`] which wraps a [`LimitExceeded::OutputSize`] with the
+    /// provided maximum size.
+    pub(crate) fn output_too_large(pos: Position, max_size: usize) -> Self {
+        Self::new_render_error(BalsaRenderError::LimitExceeded(Self::template_context(
+            pos,
+            LimitExceeded::OutputSize { max_size },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::LimitExceeded`] which wraps a [`LimitExceeded::LoopIterations`] with the
+    /// provided maximum number of iterations.
+    pub(crate) fn too_many_iterations(pos: Position, max_iterations: usize) -> Self {
+        Self::new_render_error(BalsaRenderError::LimitExceeded(Self::template_context(
+            pos,
+            LimitExceeded::LoopIterations { max_iterations },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::LimitExceeded`] which wraps a [`LimitExceeded::IncludeDepth`] with the
+    /// provided maximum depth.
+    pub(crate) fn partial_too_deep(pos: Position, max_depth: usize) -> Self {
+        Self::new_render_error(BalsaRenderError::LimitExceeded(Self::template_context(
+            pos,
+            LimitExceeded::IncludeDepth { max_depth },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::LimitExceeded`] which wraps the provided [`LimitExceeded`] limit, located at
+    /// the start of the template.
+    pub(crate) fn limit_exceeded(limit: LimitExceeded) -> Self {
+        Self::new_render_error(BalsaRenderError::LimitExceeded(Self::template_context(
+            Position::default(),
+            limit,
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::ComputationFailed`] which wraps a [`ComputationFailed`] with the provided
+    /// computed parameter name and reason.
+    pub(crate) fn computation_failed(
+        pos: Position,
+        computed_name: String,
+        reason: ComputationError,
+    ) -> Self {
+        Self::new_render_error(BalsaRenderError::ComputationFailed(Self::template_context(
+            pos,
+            ComputationFailed {
+                computed_name,
+                reason,
+            },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a [`RenderError::LoadFailed`] which
+    /// wraps a [`LoadFailed`] with the provided parameter name and reason.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn load_failed(pos: Position, parameter_name: String, reason: String) -> Self {
+        Self::new_render_error(BalsaRenderError::LoadFailed(Self::template_context(
+            pos,
+            LoadFailed {
+                parameter_name,
+                reason,
+            },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a [`RenderError::InvalidItemCount`]
+    /// which wraps a [`InvalidItemCount`] with the provided parameter name, count and limits.
+    pub(crate) fn invalid_item_count(
+        pos: Position,
+        parameter_name: String,
+        count: usize,
+        min_items: Option<usize>,
+        max_items: Option<usize>,
+    ) -> Self {
+        Self::new_render_error(BalsaRenderError::InvalidItemCount(Self::template_context(
+            pos,
+            InvalidItemCount {
+                parameter_name,
+                count,
+                min_items,
+                max_items,
+            },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a [`RenderError::DisallowedValue`]
+    /// which wraps a [`DisallowedValue`] with the provided parameter name, value and allowed
+    /// values.
+    pub(crate) fn disallowed_value(
+        pos: Position,
+        parameter_name: String,
+        value: BalsaValue,
+        allowed: Vec<BalsaValue>,
+    ) -> Self {
+        Self::new_render_error(BalsaRenderError::DisallowedValue(Box::new(
+            Self::template_context(
+                pos,
+                DisallowedValue {
+                    parameter_name,
+                    value,
+                    allowed,
+                },
+            ),
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a [`RenderError::UnsafeUrl`] which
+    /// wraps a [`UnsafeUrl`] with the provided parameter name and URL.
+    pub(crate) fn unsafe_url(pos: Position, parameter_name: String, url: String) -> Self {
+        Self::new_render_error(BalsaRenderError::UnsafeUrl(Self::template_context(
+            pos,
+            UnsafeUrl {
+                parameter_name,
+                url,
+            },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a [`RenderError::FilterFailed`]
+    /// which wraps a [`FilterFailed`] with the provided parameter name, filter name and reason.
+    pub(crate) fn filter_failed_at(
+        pos: Position,
+        parameter_name: String,
+        filter_name: String,
+        reason: String,
+    ) -> Self {
+        Self::new_render_error(BalsaRenderError::FilterFailed(Self::template_context(
+            pos,
+            FilterFailed {
+                parameter_name,
+                filter_name,
+                reason,
+            },
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a [`RenderError::Aborted`] which
+    /// wraps the provided [`RenderAborted`] reason.
+    pub(crate) fn render_aborted(pos: Position, reason: RenderAborted) -> Self {
+        Self::new_render_error(BalsaRenderError::Aborted(Self::template_context(
+            pos, reason,
+        )))
+    }
+
+    /// Creates a new [`BalsaError::RenderError`] which wraps a
+    /// [`RenderError::InvalidParameterType`] which wraps a [`InvalidParameterType`] with the provided
+    /// parameter name, parameter_value.
+    pub(crate) fn invalid_parameter_type(
+        pos: Position,
         parameter_name: String,
         received_value: BalsaValue,
         received_type: BalsaType,
         expected_type: BalsaType,
     ) -> Self {
-        Self::new_render_error(BalsaRenderError::InvalidParameterType(
-            InvalidParameterType {
-                parameter_name,
-                received_value,
-                received_type,
-                expected_type,
-            },
-        ))
+        Self::new_render_error(BalsaRenderError::InvalidParameterType(Box::new(
+            Self::template_context(
+                pos,
+                InvalidParameterType {
+                    parameter_name,
+                    received_value,
+                    received_type,
+                    expected_type,
+                },
+            ),
+        )))
     }
 
     /// Creates a new [`BalsaError::ReadTemplateError`] from the provided [`std::io::Error`].
@@ -394,8 +2066,27 @@ impl BalsaError {
         Self::ReadTemplateError(error)
     }
 
+    /// Creates a new [`BalsaError::WriteOutputError`] from the provided [`std::io::Error`].
+    pub(crate) fn write_output_error(error: io::Error) -> Self {
+        Self::WriteOutputError(error)
+    }
+
+    /// Returns a copy of the error. IO errors are copied with their kind and message, as
+    /// [`io::Error`] can't be cloned.
+    pub(crate) fn duplicate(&self) -> Self {
+        let copy_io = |e: &io::Error| io::Error::new(e.kind(), e.to_string());
+
+        match self {
+            Self::ReadTemplateError(e) => Self::ReadTemplateError(copy_io(e)),
+            Self::CompileError(e) => Self::CompileError(e.clone()),
+            Self::MultipleCompileErrors(errors) => Self::MultipleCompileErrors(errors.clone()),
+            Self::RenderError(e) => Self::RenderError(e.clone()),
+            Self::WriteOutputError(e) => Self::WriteOutputError(copy_io(e)),
+        }
+    }
+
     /// Makes a [`TemplateErrorContext<T>`] with the provided `pos` and `error` of type `T`.
-    fn template_context<T: Display>(pos: usize, error: T) -> TemplateErrorContext<T> {
-        TemplateErrorContext { pos, error }
+    fn template_context<T: Display>(pos: Position, error: T) -> TemplateErrorContext<T> {
+        TemplateErrorContext::new(pos, error)
     }
 }
@@ -0,0 +1,626 @@
+use std::fmt::Display;
+
+use crate::{
+    balsa_compiler::{
+        CompiledTemplate, EachPart, ImageDescription, ItemsDescription, ReplaceWith,
+        ReplacementInstruction,
+    },
+    parser::Position,
+};
+
+/// A human-readable description of everything the compiler produced for a template.
+///
+/// Created with [`crate::Template::explain`]. The [`Display`] implementation renders a stable,
+/// line-oriented dump which is suitable for attaching to bug reports.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TemplateExplanation {
+    /// The variables in the global scope, sorted by name.
+    pub variables: Vec<ExplainedVariable>,
+    /// The replacement instructions in the order they are applied.
+    pub replacements: Vec<ExplainedReplacement>,
+    /// The computed parameters in the order they are declared. They are derived from other
+    /// parameters when rendering, so editors can display them but not edit them.
+    pub computed: Vec<ExplainedComputed>,
+}
+
+/// A parameter which is computed from other parameters when rendering.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExplainedComputed {
+    /// The name of the computed parameter.
+    pub name: String,
+    /// The type of the computed parameter, which is `int` or `float`.
+    pub parameter_type: String,
+    /// The expression the parameter is computed from, exactly as it was written in the template.
+    pub expression: String,
+    /// The names of the parameters and computed parameters the expression reads, in the order
+    /// they are first read. Declared variables are not included.
+    pub inputs: Vec<String>,
+}
+
+/// A variable in the global scope of a compiled template.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExplainedVariable {
+    /// The name of the variable.
+    pub name: String,
+    /// The type of the variable.
+    pub variable_type: String,
+    /// The value of the variable, formatted as it would be written in a template.
+    pub value: String,
+}
+
+/// A position in the raw template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExplainedPosition {
+    /// The character offset from the start of the template.
+    pub offset: usize,
+    /// The line, starting at 1.
+    pub line: usize,
+    /// The column, starting at 1.
+    pub column: usize,
+}
+
+/// A single replacement instruction of a compiled template.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExplainedReplacement {
+    /// The position where the replaced block starts.
+    pub start: ExplainedPosition,
+    /// The position where the replaced block ends.
+    pub end: ExplainedPosition,
+    /// What the block is replaced with when rendering.
+    pub replace_with: ExplainedReplaceWith,
+}
+
+/// What a replaced block is replaced with when rendering.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ExplainedReplaceWith {
+    /// The block is removed from the output.
+    Nothing,
+    /// The block is replaced with the value of a parameter.
+    Parameter {
+        /// The name of the parameter.
+        name: String,
+        /// The type of the parameter.
+        parameter_type: String,
+        /// The default value of the parameter, formatted as it would be written in a template.
+        default_value: Option<String>,
+        /// The direction the value of the parameter is written in, if one was given.
+        direction: Option<String>,
+        /// Whether the value of the parameter is escaped, which is `false` for parameters marked
+        /// `escape: false`.
+        escaped: bool,
+        /// The limits on the number of items, if the parameter is an array.
+        items: ExplainedItems,
+        /// The constraints on the file, if the parameter is an image.
+        image: ExplainedImage,
+        /// The values the parameter may have, formatted as they would be written in a template.
+        /// Any value is allowed if empty.
+        one_of: Vec<String>,
+        /// The format the value is written with, if the parameter is a date-time given a
+        /// `format`.
+        format: Option<String>,
+        /// Whether the value must be a URL which is safe to link to, if the parameter is a URL
+        /// marked `safe: true`.
+        safe: bool,
+        /// Whether the parameter may be left out of a render, given with `optional: true`.
+        optional: bool,
+        /// The text written in place of the parameter when it is left out, if it is optional.
+        placeholder: Option<String>,
+        /// The names of the filters the value is passed through, in order.
+        filters: Vec<String>,
+        /// The block the parameter was described in, exactly as it was written in the template.
+        source: String,
+    },
+    /// The block is replaced with one of several forms, chosen by the value of an integer
+    /// parameter.
+    Plural {
+        /// The name of the parameter holding the count.
+        name: String,
+        /// The default value of the parameter, formatted as it would be written in a template.
+        default_value: Option<String>,
+        /// The forms to choose between.
+        forms: Vec<String>,
+        /// The block the plural was described in, exactly as it was written in the template.
+        source: String,
+    },
+    /// The block is replaced with a `dir` attribute for the locale held by a string parameter.
+    Direction {
+        /// The name of the parameter holding the locale.
+        name: String,
+        /// The default value of the parameter, formatted as it would be written in a template.
+        default_value: Option<String>,
+        /// The block the helper was called in, exactly as it was written in the template.
+        source: String,
+    },
+    /// The block is replaced with a message from the message catalog.
+    Message {
+        /// The ID of the message.
+        key: String,
+        /// The translated message, or `None` if it is missing from the catalog.
+        text: Option<String>,
+    },
+    /// The block is replaced with text which was resolved at compile time, such as a chosen
+    /// plural form.
+    Text {
+        /// The text, before it is escaped.
+        text: String,
+    },
+    /// The block is replaced with markup which was resolved at compile time and is never
+    /// escaped, such as a `dir` attribute.
+    Markup {
+        /// The markup.
+        markup: String,
+    },
+    /// The block is replaced with the HTML the application fills a slot with, if any.
+    Slot {
+        /// The name of the slot.
+        name: String,
+    },
+    /// The block is replaced with its body, repeated for every item of an array parameter.
+    Each {
+        /// The name of the parameter holding the items.
+        name: String,
+        /// The type of the parameter holding the items.
+        parameter_type: String,
+        /// The limits on the number of items.
+        items: ExplainedItems,
+        /// The values read from each item, in the order they are rendered.
+        fields: Vec<ExplainedField>,
+        /// The block exactly as it was written in the template.
+        source: String,
+    },
+    /// The block starts a section which is only rendered if a `bool` parameter is `true`.
+    If {
+        /// The name of the parameter holding the condition.
+        name: String,
+        /// The default value of the parameter, formatted as it would be written in a template.
+        default_value: Option<String>,
+        /// The block exactly as it was written in the template.
+        source: String,
+    },
+    /// The block starts the part of a conditional section which is only rendered if its
+    /// condition is `false`.
+    Else,
+    /// The block ends a conditional section.
+    EndIf,
+}
+
+/// The `minItems`, `maxItems` and `itemLabel` options of an array parameter, which editors can
+/// use to display an editor which adds and removes items.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExplainedItems {
+    /// The fewest items the array may have.
+    pub min_items: Option<usize>,
+    /// The most items the array may have.
+    pub max_items: Option<usize>,
+    /// What a single item is called.
+    pub item_label: Option<String>,
+}
+
+/// The `accept`, `maxWidth` and `maxHeight` options of an image parameter, which editors can
+/// use to check an upload before accepting it.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExplainedImage {
+    /// The MIME types the image may have. Any type is accepted if empty.
+    pub accept: Vec<String>,
+    /// The widest the image may be, in pixels.
+    pub max_width: Option<u32>,
+    /// The tallest the image may be, in pixels.
+    pub max_height: Option<u32>,
+}
+
+/// A value read from each item of a section.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExplainedField {
+    /// The key of the value in the item.
+    pub name: String,
+    /// The type of the value.
+    pub field_type: String,
+    /// The default value, formatted as it would be written in a template.
+    pub default_value: Option<String>,
+}
+
+impl TemplateExplanation {
+    /// Creates a new [`TemplateExplanation`] describing the provided [`CompiledTemplate`].
+    pub(crate) fn new(compiled_template: &CompiledTemplate) -> Self {
+        let mut variables = compiled_template
+            .global_scope
+            .variables
+            .iter()
+            .map(|(name, value)| ExplainedVariable {
+                name: name.clone(),
+                variable_type: value.get_type().to_string(),
+                value: value.to_string(),
+            })
+            .collect::<Vec<ExplainedVariable>>();
+
+        variables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            variables,
+            replacements: compiled_template
+                .replacements
+                .iter()
+                .map(ExplainedReplacement::new)
+                .collect(),
+            computed: compiled_template
+                .computed
+                .iter()
+                .map(|c| ExplainedComputed {
+                    name: c.name.clone(),
+                    parameter_type: c.variable_type.to_string(),
+                    expression: c.source.clone(),
+                    inputs: c
+                        .expression
+                        .inputs()
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ExplainedReplacement {
+    /// Creates a new [`ExplainedReplacement`] describing the provided [`ReplacementInstruction`].
+    fn new(replacement: &ReplacementInstruction) -> Self {
+        let replace_with = match &replacement.replace_with {
+            ReplaceWith::Nothing => ExplainedReplaceWith::Nothing,
+            ReplaceWith::Plural(p) => ExplainedReplaceWith::Plural {
+                name: p.parameter.variable_name.clone(),
+                default_value: p.parameter.default_value.as_ref().map(|v| v.to_string()),
+                forms: p.forms.clone(),
+                source: p.parameter.source.clone(),
+            },
+            ReplaceWith::Message(m) => ExplainedReplaceWith::Message {
+                key: m.key.clone(),
+                text: m.text.clone(),
+            },
+            ReplaceWith::Text(text) => ExplainedReplaceWith::Text { text: text.clone() },
+            ReplaceWith::Markup(markup) => ExplainedReplaceWith::Markup {
+                markup: markup.clone(),
+            },
+            ReplaceWith::Slot(name) => ExplainedReplaceWith::Slot { name: name.clone() },
+            ReplaceWith::Each(e) => ExplainedReplaceWith::Each {
+                name: e.parameter.variable_name.clone(),
+                parameter_type: e.parameter.variable_type.to_string(),
+                items: ExplainedItems::new(&e.parameter.items),
+                fields: e
+                    .body
+                    .iter()
+                    .filter_map(|part| match part {
+                        EachPart::Field(p) => Some(ExplainedField {
+                            name: p.variable_name.clone(),
+                            field_type: p.variable_type.to_string(),
+                            default_value: p.default_value.as_ref().map(|v| v.to_string()),
+                        }),
+                        EachPart::Markup(_) | EachPart::Include(_) => None,
+                    })
+                    .collect(),
+                source: e.parameter.source.clone(),
+            },
+            ReplaceWith::Parameter(p) => ExplainedReplaceWith::Parameter {
+                name: p.variable_name.clone(),
+                parameter_type: p.variable_type.to_string(),
+                default_value: p.default_value.as_ref().map(|v| v.to_string()),
+                direction: p.direction.map(|d| d.to_string()),
+                escaped: p.escape,
+                items: ExplainedItems::new(&p.items),
+                image: ExplainedImage::new(&p.image),
+                one_of: p.one_of.iter().map(|v| v.to_string()).collect(),
+                format: p.format.clone(),
+                safe: p.safe,
+                optional: p.optional,
+                placeholder: p.placeholder.clone(),
+                filters: p.filters.clone(),
+                source: p.source.clone(),
+            },
+            ReplaceWith::Direction(p) => ExplainedReplaceWith::Direction {
+                name: p.variable_name.clone(),
+                default_value: p.default_value.as_ref().map(|v| v.to_string()),
+                source: p.source.clone(),
+            },
+            ReplaceWith::If(p) => ExplainedReplaceWith::If {
+                name: p.variable_name.clone(),
+                default_value: p.default_value.as_ref().map(|v| v.to_string()),
+                source: p.source.clone(),
+            },
+            ReplaceWith::Else => ExplainedReplaceWith::Else,
+            ReplaceWith::EndIf => ExplainedReplaceWith::EndIf,
+        };
+
+        Self {
+            start: replacement.start_pos.into(),
+            end: replacement.end_pos.into(),
+            replace_with,
+        }
+    }
+}
+
+impl ExplainedItems {
+    /// Creates a new [`ExplainedItems`] describing the provided [`ItemsDescription`].
+    fn new(items: &ItemsDescription) -> Self {
+        Self {
+            min_items: items.min_items,
+            max_items: items.max_items,
+            item_label: items.item_label.clone(),
+        }
+    }
+}
+
+impl From<Position> for ExplainedPosition {
+    fn from(pos: Position) -> Self {
+        Self {
+            offset: pos.offset,
+            line: pos.line,
+            column: pos.column,
+        }
+    }
+}
+
+impl Display for TemplateExplanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "global scope:")?;
+        for variable in &self.variables {
+            writeln!(
+                f,
+                "  {}: {} = {}",
+                variable.name, variable.variable_type, variable.value
+            )?;
+        }
+
+        writeln!(f, "replacements:")?;
+        for replacement in &self.replacements {
+            writeln!(f, "  {}", replacement)?;
+        }
+
+        if !self.computed.is_empty() {
+            writeln!(f, "computed:")?;
+            for c in &self.computed {
+                writeln!(f, "  {}: {} = {}", c.name, c.parameter_type, c.expression)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ExplainedItems {
+    /// Writes the options which were given, i.e. ` (minItems: 1, itemLabel: "Post")`, or nothing.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let options = [
+            self.min_items.map(|n| format!("minItems: {}", n)),
+            self.max_items.map(|n| format!("maxItems: {}", n)),
+            self.item_label
+                .as_ref()
+                .map(|label| format!("itemLabel: {:?}", label)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>();
+
+        if options.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, " ({})", options.join(", "))
+    }
+}
+
+impl ExplainedImage {
+    /// Creates a new [`ExplainedImage`] describing the provided [`ImageDescription`].
+    fn new(image: &ImageDescription) -> Self {
+        Self {
+            accept: image.accept.clone(),
+            max_width: image.max_width,
+            max_height: image.max_height,
+        }
+    }
+}
+
+impl Display for ExplainedImage {
+    /// Writes the options which were given, i.e. ` (accept: "image/png", maxWidth: 800)`, or
+    /// nothing.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let options = [
+            (!self.accept.is_empty()).then(|| format!("accept: {:?}", self.accept.join(", "))),
+            self.max_width.map(|n| format!("maxWidth: {}", n)),
+            self.max_height.map(|n| format!("maxHeight: {}", n)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>();
+
+        if options.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, " ({})", options.join(", "))
+    }
+}
+
+impl Display for ExplainedReplacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{} {}", self.start, self.end, self.replace_with)
+    }
+}
+
+impl Display for ExplainedPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{} ({})", self.line, self.column, self.offset)
+    }
+}
+
+impl Display for ExplainedReplaceWith {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nothing => write!(f, "nothing"),
+            Self::Plural {
+                name,
+                default_value,
+                forms,
+                ..
+            } => {
+                write!(f, "plural {}", name)?;
+
+                if let Some(default_value) = default_value {
+                    write!(f, " = {}", default_value)?;
+                }
+
+                write!(f, " {:?}", forms)
+            }
+            Self::Direction {
+                name,
+                default_value,
+                ..
+            } => {
+                write!(f, "direction {}", name)?;
+
+                if let Some(default_value) = default_value {
+                    write!(f, " = {}", default_value)?;
+                }
+
+                Ok(())
+            }
+            Self::Message { key, text } => match text {
+                Some(text) => write!(f, "message {} {:?}", key, text),
+                None => write!(f, "message {} (missing)", key),
+            },
+            Self::Text { text } => write!(f, "text {:?}", text),
+            Self::Markup { markup } => write!(f, "markup {:?}", markup),
+            Self::Slot { name } => write!(f, "slot {:?}", name),
+            Self::If {
+                name,
+                default_value,
+                ..
+            } => {
+                write!(f, "if {}", name)?;
+
+                if let Some(default_value) = default_value {
+                    write!(f, " = {}", default_value)?;
+                }
+
+                Ok(())
+            }
+            Self::Else => write!(f, "else"),
+            Self::EndIf => write!(f, "endif"),
+            Self::Each {
+                name,
+                parameter_type,
+                items,
+                fields,
+                ..
+            } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| match &field.default_value {
+                        Some(default_value) => {
+                            format!("{}: {} = {}", field.name, field.field_type, default_value)
+                        }
+                        None => format!("{}: {}", field.name, field.field_type),
+                    })
+                    .collect::<Vec<String>>();
+
+                write!(
+                    f,
+                    "each {}: {}{} [{}]",
+                    name,
+                    parameter_type,
+                    items,
+                    fields.join(", ")
+                )
+            }
+            Self::Parameter {
+                name,
+                parameter_type,
+                default_value,
+                direction,
+                escaped,
+                items,
+                image,
+                one_of,
+                format,
+                safe,
+                optional,
+                placeholder,
+                filters,
+                ..
+            } => {
+                write!(
+                    f,
+                    "parameter {}: {}{}{}",
+                    name, parameter_type, items, image
+                )?;
+
+                if !one_of.is_empty() {
+                    write!(f, " (oneOf: [{}])", one_of.join(", "))?;
+                }
+
+                if let Some(format) = format {
+                    write!(f, " (format: {:?})", format)?;
+                }
+
+                if *safe {
+                    write!(f, " (safe)")?;
+                }
+
+                match placeholder {
+                    Some(placeholder) => write!(f, " (optional, placeholder: {:?})", placeholder)?,
+                    None if *optional => write!(f, " (optional)")?,
+                    None => {}
+                }
+
+                if let Some(default_value) = default_value {
+                    write!(f, " = {}", default_value)?;
+                }
+
+                if !filters.is_empty() {
+                    write!(f, " (filters: [{}])", filters.join(", "))?;
+                }
+
+                if let Some(direction) = direction {
+                    write!(f, " ({})", direction)?;
+                }
+
+                if !escaped {
+                    write!(f, " (unescaped)")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{balsa_compiler::Compiler, balsa_parser, options::CompileOptions};
+
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let template = "{{@ title : string = \"hello\", unused : int = 1 }}\n<h1>{{ heading : string, defaultValue: title }}</h1>";
+
+        let options = CompileOptions::default();
+        let tokens = balsa_parser::BalsaParser::parse(template, &options).unwrap();
+        let compiled = Compiler::compile_from_tokens(template, &tokens, &options).unwrap();
+
+        let explanation = TemplateExplanation::new(&compiled).to_string();
+        let expected = "global scope:\n  title: string = \"hello\"\nreplacements:\n  1:1 (0)..1:50 (49) nothing\n  2:5 (54)..2:48 (97) parameter heading: string = \"hello\"\n";
+
+        assert_eq!(
+            explanation, expected,
+            "Template explanation rendered incorrectly"
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! Contains a parser and evaluator for the arithmetic expressions of computed parameters.
+//!
+//! Expressions are made up of integer and float literals, names, the binary operators `+`, `-`,
+//! `*`, `/` and `%`, unary `-` and parentheses. `*`, `/` and `%` bind tighter than `+` and `-`,
+//! and operators of the same precedence are applied from left to right. Names are made up of
+//! letters, digits and `_`.
+//!
+//! Arithmetic on two integers produces an integer, with `/` rounding towards zero. Arithmetic
+//! involving a float produces a float.
+
+use std::{collections::HashMap, iter::Peekable, str::Chars};
+
+use crate::{errors::ComputationError, BalsaValue};
+
+/// A parsed arithmetic expression.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Expression {
+    Integer(i64),
+    Float(f64),
+    /// The value of a parameter passed to the render.
+    Parameter(String),
+    /// The value of a computed parameter declared earlier in the template.
+    Computed(String),
+    Negate(Box<Expression>),
+    /// Operators of the same precedence applied from left to right, which keeps long sums from
+    /// nesting deeply.
+    Chain {
+        first: Box<Expression>,
+        rest: Vec<(Operator, Expression)>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+/// The reason an expression couldn't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExpressionError {
+    Malformed,
+    /// Parentheses or unary `-` were nested deeper than the maximum depth.
+    TooDeep,
+}
+
+/// The result of evaluating an expression, or one of its terms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+/// Parses an expression, allowing parentheses and unary `-` to be nested up to `max_depth`
+/// levels deep.
+pub(crate) fn parse(input: &str, max_depth: usize) -> Result<Expression, ExpressionError> {
+    let mut parser = ExpressionParser {
+        chars: input.chars().peekable(),
+        depth: 0,
+        max_depth,
+    };
+
+    let expression = parser.sum()?;
+    parser.skip_ws();
+
+    match parser.chars.peek() {
+        None => Ok(expression),
+        Some(_) => Err(ExpressionError::Malformed),
+    }
+}
+
+impl Expression {
+    /// Returns the names of the parameters and computed parameters the expression reads, in
+    /// the order they are first read.
+    pub(crate) fn inputs(&self) -> Vec<&str> {
+        let mut inputs = Vec::new();
+        self.collect_inputs(&mut inputs);
+
+        inputs
+    }
+
+    fn collect_inputs<'a>(&'a self, inputs: &mut Vec<&'a str>) {
+        match self {
+            Self::Integer(_) | Self::Float(_) => {}
+            Self::Parameter(name) | Self::Computed(name) => {
+                if !inputs.contains(&name.as_str()) {
+                    inputs.push(name);
+                }
+            }
+            Self::Negate(operand) => operand.collect_inputs(inputs),
+            Self::Chain { first, rest } => {
+                first.collect_inputs(inputs);
+                rest.iter().for_each(|(_, e)| e.collect_inputs(inputs));
+            }
+        }
+    }
+
+    /// Returns the expression with the parameters named in `bindings` replaced with the
+    /// expressions they are bound to.
+    pub(crate) fn bind_parameters(&self, bindings: &HashMap<String, Expression>) -> Expression {
+        match self {
+            Self::Parameter(name) => bindings.get(name).unwrap_or(self).clone(),
+            Self::Negate(operand) => Self::Negate(Box::new(operand.bind_parameters(bindings))),
+            Self::Chain { first, rest } => Self::Chain {
+                first: Box::new(first.bind_parameters(bindings)),
+                rest: rest
+                    .iter()
+                    .map(|(operator, operand)| (*operator, operand.bind_parameters(bindings)))
+                    .collect(),
+            },
+            Self::Integer(_) | Self::Float(_) | Self::Computed(_) => self.clone(),
+        }
+    }
+
+    /// Evaluates the expression, looking up the value of every parameter and computed parameter
+    /// it reads with `lookup`, which must return an integer or float. Arithmetic errors are
+    /// converted with `error`.
+    pub(crate) fn evaluate<E>(
+        &self,
+        lookup: &mut impl FnMut(&Expression) -> Result<BalsaValue, E>,
+        error: &impl Fn(ComputationError) -> E,
+    ) -> Result<BalsaValue, E> {
+        Ok(match self.number(lookup, error)? {
+            Number::Integer(i) => BalsaValue::Integer(i),
+            Number::Float(f) => BalsaValue::Float(f),
+        })
+    }
+
+    fn number<E>(
+        &self,
+        lookup: &mut impl FnMut(&Expression) -> Result<BalsaValue, E>,
+        error: &impl Fn(ComputationError) -> E,
+    ) -> Result<Number, E> {
+        match self {
+            Self::Integer(i) => Ok(Number::Integer(*i)),
+            Self::Float(f) => Ok(Number::Float(*f)),
+            Self::Parameter(_) | Self::Computed(_) => match lookup(self)? {
+                BalsaValue::Integer(i) => Ok(Number::Integer(i)),
+                BalsaValue::Float(f) => Ok(Number::Float(f)),
+                _ => unreachable!("computed inputs are looked up as numbers"),
+            },
+            Self::Negate(operand) => match operand.number(lookup, error)? {
+                Number::Integer(i) => i
+                    .checked_neg()
+                    .map(Number::Integer)
+                    .ok_or_else(|| error(ComputationError::Overflow)),
+                Number::Float(f) => Ok(Number::Float(-f)),
+            },
+            Self::Chain { first, rest } => {
+                let mut number = first.number(lookup, error)?;
+
+                for (operator, operand) in rest {
+                    let operand = operand.number(lookup, error)?;
+                    number = operator.apply(number, operand).map_err(error)?;
+                }
+
+                Ok(number)
+            }
+        }
+    }
+}
+
+impl Operator {
+    fn apply(self, left: Number, right: Number) -> Result<Number, ComputationError> {
+        match (left, right) {
+            (Number::Integer(l), Number::Integer(r)) => {
+                let result = match self {
+                    Self::Add => l.checked_add(r),
+                    Self::Subtract => l.checked_sub(r),
+                    Self::Multiply => l.checked_mul(r),
+                    Self::Divide | Self::Remainder if r == 0 => {
+                        return Err(ComputationError::DivisionByZero)
+                    }
+                    Self::Divide => l.checked_div(r),
+                    Self::Remainder => l.checked_rem(r),
+                };
+
+                result
+                    .map(Number::Integer)
+                    .ok_or(ComputationError::Overflow)
+            }
+            (l, r) => {
+                let (l, r) = (l.as_float(), r.as_float());
+                let result = match self {
+                    Self::Add => l + r,
+                    Self::Subtract => l - r,
+                    Self::Multiply => l * r,
+                    Self::Divide | Self::Remainder if r == 0.0 => {
+                        return Err(ComputationError::DivisionByZero)
+                    }
+                    Self::Divide => l / r,
+                    Self::Remainder => l % r,
+                };
+
+                if result.is_finite() {
+                    Ok(Number::Float(result))
+                } else {
+                    Err(ComputationError::Overflow)
+                }
+            }
+        }
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Self::Add => '+',
+            Self::Subtract => '-',
+            Self::Multiply => '*',
+            Self::Divide => '/',
+            Self::Remainder => '%',
+        }
+    }
+
+    fn from_symbol(c: char) -> Option<Self> {
+        [
+            Self::Add,
+            Self::Subtract,
+            Self::Multiply,
+            Self::Divide,
+            Self::Remainder,
+        ]
+        .into_iter()
+        .find(|operator| operator.symbol() == c)
+    }
+
+    fn is_additive(self) -> bool {
+        matches!(self, Self::Add | Self::Subtract)
+    }
+}
+
+impl Number {
+    fn as_float(self) -> f64 {
+        match self {
+            Self::Integer(i) => i as f64,
+            Self::Float(f) => f,
+        }
+    }
+}
+
+struct ExpressionParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl ExpressionParser<'_> {
+    /// Parses terms joined by `+` and `-`.
+    fn sum(&mut self) -> Result<Expression, ExpressionError> {
+        self.chain(true)
+    }
+
+    /// Parses factors joined by `*`, `/` and `%`.
+    fn product(&mut self) -> Result<Expression, ExpressionError> {
+        self.chain(false)
+    }
+
+    fn chain(&mut self, additive: bool) -> Result<Expression, ExpressionError> {
+        let operand = |parser: &mut Self| {
+            if additive {
+                parser.product()
+            } else {
+                parser.factor()
+            }
+        };
+
+        let first = operand(self)?;
+        let mut rest = Vec::new();
+
+        loop {
+            self.skip_ws();
+
+            let operator = match self.chars.peek().copied().and_then(Operator::from_symbol) {
+                Some(operator) if operator.is_additive() == additive => operator,
+                _ => break,
+            };
+            self.chars.next();
+
+            rest.push((operator, operand(self)?));
+        }
+
+        Ok(if rest.is_empty() {
+            first
+        } else {
+            Expression::Chain {
+                first: Box::new(first),
+                rest,
+            }
+        })
+    }
+
+    /// Parses a literal, a name, a negated factor or a parenthesized expression.
+    fn factor(&mut self) -> Result<Expression, ExpressionError> {
+        self.skip_ws();
+
+        match self.chars.peek() {
+            Some('(') => {
+                self.chars.next();
+                let expression = self.nested(Self::sum)?;
+                self.skip_ws();
+
+                match self.chars.next() {
+                    Some(')') => Ok(expression),
+                    _ => Err(ExpressionError::Malformed),
+                }
+            }
+            Some('-') => {
+                self.chars.next();
+                let operand = self.nested(Self::factor)?;
+
+                Ok(Expression::Negate(Box::new(operand)))
+            }
+            Some(c) if c.is_ascii_digit() || *c == '.' => self.number(),
+            Some(c) if c.is_alphanumeric() || *c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+
+                Ok(Expression::Parameter(name))
+            }
+            _ => Err(ExpressionError::Malformed),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expression, ExpressionError> {
+        let mut number = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+            number.push(c);
+        }
+
+        if number.contains('.') {
+            number
+                .parse::<f64>()
+                .map(Expression::Float)
+                .map_err(|_| ExpressionError::Malformed)
+        } else {
+            number
+                .parse::<i64>()
+                .map(Expression::Integer)
+                .map_err(|_| ExpressionError::Malformed)
+        }
+    }
+
+    /// Runs `parse` one level of nesting deeper, failing if the maximum depth is exceeded.
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Expression, ExpressionError>,
+    ) -> Result<Expression, ExpressionError> {
+        if self.depth >= self.max_depth {
+            return Err(ExpressionError::TooDeep);
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn skip_ws(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(input: &str) -> Result<BalsaValue, ComputationError> {
+        let expression = parse(input, 8).expect("Expression should be parsed");
+
+        expression.evaluate(
+            &mut |e| match e {
+                Expression::Parameter(name) if name == "words" => Ok(BalsaValue::Integer(1000)),
+                _ => panic!("Unexpected input {:?}", e),
+            },
+            &|e| e,
+        )
+    }
+
+    #[test]
+    fn test_expression() {
+        assert_eq!(evaluate("words / 200"), Ok(BalsaValue::Integer(5)));
+        assert_eq!(evaluate("1 + 2 * 3 - 4 % 3"), Ok(BalsaValue::Integer(6)));
+        assert_eq!(evaluate("(1 + 2) * -3"), Ok(BalsaValue::Integer(-9)));
+        assert_eq!(evaluate("words / 400.0"), Ok(BalsaValue::Float(2.5)));
+        assert_eq!(
+            evaluate("words / (3 - 3)"),
+            Err(ComputationError::DivisionByZero)
+        );
+        assert_eq!(
+            evaluate("9223372036854775807 + words"),
+            Err(ComputationError::Overflow)
+        );
+
+        assert_eq!(
+            parse("a + b * a", 8).map(|e| e.inputs().len()),
+            Ok(2),
+            "Inputs should be listed once"
+        );
+
+        for malformed in ["", "1 +", "(1", "1 2", "a b", "1..2", "a $ b"] {
+            assert_eq!(
+                parse(malformed, 8),
+                Err(ExpressionError::Malformed),
+                "Expression `{}` should be rejected",
+                malformed
+            );
+        }
+
+        assert_eq!(parse("((((1))))", 3), Err(ExpressionError::TooDeep));
+        assert_eq!(parse("----1", 3), Err(ExpressionError::TooDeep));
+    }
+}
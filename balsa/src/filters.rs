@@ -0,0 +1,161 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use crate::{errors::BalsaRenderError, parser::Position, BalsaError, BalsaResult, BalsaValue};
+
+/// A transform registered with [`crate::BalsaBuilder::with_filter`], which parameters are passed
+/// through with the `filters` option.
+pub(crate) type FilterFn = dyn Fn(BalsaValue) -> BalsaResult<BalsaValue> + Send + Sync;
+
+/// A filter registered with a template.
+#[derive(Clone)]
+struct Filter {
+    function: Arc<FilterFn>,
+    /// Whether the filter's output could compromise the page it is rendered into, so that
+    /// sandboxed templates can't use it.
+    is_unsafe: bool,
+}
+
+/// The filters registered with a template, keyed by their names. Clones share the same filters.
+#[derive(Clone, Default)]
+pub(crate) struct Filters(HashMap<String, Filter>);
+
+impl Filters {
+    /// Registers `filter` as `name`, replacing any filter previously registered as `name`.
+    pub(crate) fn insert(&mut self, name: String, filter: Arc<FilterFn>) {
+        self.insert_with_safety(name, filter, false);
+    }
+
+    /// Registers `filter` as `name` like [`Filters::insert`], marking it as unsafe if `is_unsafe`
+    /// is set.
+    pub(crate) fn insert_with_safety(
+        &mut self,
+        name: String,
+        function: Arc<FilterFn>,
+        is_unsafe: bool,
+    ) {
+        self.0.insert(
+            name,
+            Filter {
+                function,
+                is_unsafe,
+            },
+        );
+    }
+
+    /// Whether a filter is registered as `name`.
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Whether the filter registered as `name` is marked as unsafe.
+    pub(crate) fn is_unsafe(&self, name: &str) -> bool {
+        self.0.get(name).is_some_and(|f| f.is_unsafe)
+    }
+
+    /// Registers every filter of `other`, replacing filters registered with the same names.
+    pub(crate) fn extend(&mut self, other: &Filters) {
+        self.0
+            .extend(other.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    /// Passes the `value` of the parameter `parameter_name` through the filters named `names`
+    /// in order. Filters which fail, or aren't registered, fail with a
+    /// [`BalsaRenderError::FilterFailed`] error.
+    pub(crate) fn apply(
+        &self,
+        pos: Position,
+        parameter_name: &str,
+        names: &[String],
+        value: BalsaValue,
+    ) -> BalsaResult<BalsaValue> {
+        names.iter().try_fold(value, |value, name| {
+            let failed = |reason: String| {
+                BalsaError::filter_failed_at(pos, parameter_name.to_string(), name.clone(), reason)
+            };
+
+            let filter = self
+                .0
+                .get(name)
+                .ok_or_else(|| failed("filter is not registered".to_string()))?;
+
+            (filter.function)(value).map_err(|e| match e {
+                BalsaError::RenderError(BalsaRenderError::FilterFailed(e)) => {
+                    failed(e.error.reason)
+                }
+                e => failed(e.to_string()),
+            })
+        })
+    }
+}
+
+impl fmt::Debug for Filters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.0.keys().collect();
+        names.sort();
+
+        f.debug_tuple("Filters").field(&names).finish()
+    }
+}
+
+/// Filters are equal if they register the same filters under the same names, marked the same
+/// way.
+impl PartialEq for Filters {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self.0.iter().all(|(name, f)| {
+                other.0.get(name).is_some_and(|g| {
+                    Arc::ptr_eq(&f.function, &g.function) && f.is_unsafe == g.is_unsafe
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_in_order() {
+        let mut filters = Filters::default();
+        filters.insert(
+            "upper".to_string(),
+            Arc::new(|v: BalsaValue| Ok(BalsaValue::String(v.to_string().to_uppercase()))),
+        );
+        filters.insert(
+            "twice".to_string(),
+            Arc::new(|v: BalsaValue| match v {
+                BalsaValue::String(s) => Ok(BalsaValue::String(s.repeat(2))),
+                _ => Err(BalsaError::filter_failed("expected a string")),
+            }),
+        );
+
+        let names = ["upper".to_string(), "twice".to_string()];
+        let value = filters
+            .apply(Position::default(), "x", &names, BalsaValue::Integer(1))
+            .unwrap();
+        assert_eq!(value, BalsaValue::String("11".to_string()));
+
+        let err = filters
+            .apply(
+                Position::default(),
+                "x",
+                &names[1..],
+                BalsaValue::Integer(1),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "render error: filter `twice` failed on parameter `x`: expected a string at line 1, column 1"
+        );
+
+        let err = filters
+            .apply(
+                Position::default(),
+                "x",
+                &["missing".to_string()],
+                BalsaValue::Integer(1),
+            )
+            .unwrap_err();
+        assert_eq!(err.code(), "B0411");
+    }
+}
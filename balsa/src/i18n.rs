@@ -0,0 +1,836 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::Currency;
+
+/// Languages which are written right-to-left unless a script subtag says otherwise.
+const RTL_LANGUAGES: &[&str] = &[
+    "ar", "ckb", "dv", "fa", "he", "iw", "ps", "sd", "ug", "ur", "yi",
+];
+/// Script subtags, lowercased, of scripts which are written right-to-left.
+const RTL_SCRIPTS: &[&str] = &["adlm", "arab", "hebr", "nkoo", "rohg", "syrc", "thaa"];
+
+/// A use of a message in a template, returned by [`crate::Template::extract_messages`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExtractedMessage {
+    /// The ID of the message.
+    pub key: String,
+    /// The message from the template's catalog, or `None` if it hasn't been translated.
+    pub translation: Option<String>,
+    /// The line (starting at 1) of the block using the message.
+    pub line: usize,
+    /// The column (starting at 1) of the block using the message.
+    pub column: usize,
+}
+
+/// The output of [`crate::Template::render_localized`], along with the locale it was rendered
+/// in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalizedRender {
+    /// The rendered template.
+    pub output: String,
+    /// The locale the template was rendered in, which is the default locale if none of the
+    /// accepted locales were available.
+    pub locale: Locale,
+}
+
+/// Languages which write a currency's symbol before the amount rather than after it.
+const SYMBOL_FIRST_LANGUAGES: &[&str] = &["en", "ja", "ko", "zh"];
+
+/// A set of translated messages, keyed by message ID, which templates look up with the `t`
+/// helper.
+///
+/// Register a catalog with [`crate::CompileOptions::catalog`] (or
+/// [`crate::BalsaBuilder::catalog`]) and each `{{ t "welcome.title" }}` block is replaced with
+/// the message for `welcome.title` when the template is compiled. Multilingual sites build one
+/// template per locale, each with that locale's catalog.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let catalog = MessageCatalog::new().message("welcome.title", "Bienvenue");
+///
+/// let template = Balsa::from_string("<h1>{{ t \"welcome.title\" }}</h1>")
+///     .catalog(catalog)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(
+///     template.render_html_string(&BalsaParameters::new()).unwrap(),
+///     "<h1>Bienvenue</h1>"
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MessageCatalog {
+    messages: HashMap<String, String>,
+}
+
+impl MessageCatalog {
+    /// Creates a new, empty [`MessageCatalog`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the message `text` with the ID `key`, replacing any existing message with that ID.
+    pub fn message(mut self, key: impl Into<String>, text: impl Into<String>) -> Self {
+        self.insert(key, text);
+        self
+    }
+
+    /// Adds the message `text` with the ID `key`, returning the message it replaced, if any.
+    pub fn insert(&mut self, key: impl Into<String>, text: impl Into<String>) -> Option<String> {
+        self.messages.insert(key.into(), text.into())
+    }
+
+    /// Returns the message with the ID `key`, if there is one.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(String::as_str)
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for MessageCatalog {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            messages: iter
+                .into_iter()
+                .map(|(key, text)| (key.into(), text.into()))
+                .collect(),
+        }
+    }
+}
+
+/// The rule used to pick between the forms passed to a `plural` helper block based on a count.
+///
+/// Each rule expects a fixed number of forms, given in the order they are listed on its variant.
+/// The rules follow the CLDR cardinal plural rules for whole numbers.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let template = Balsa::from_string("{{ itemCount : int }} {{ plural itemCount \"item\" \"items\" }}")
+///     .build()
+///     .unwrap();
+///
+/// let params = BalsaParameters::new().int("itemCount", 1);
+/// assert_eq!(template.render_html_string(&params).unwrap(), "1 item");
+///
+/// let params = BalsaParameters::new().int("itemCount", 3);
+/// assert_eq!(template.render_html_string(&params).unwrap(), "3 items");
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PluralRule {
+    /// `one` when the count is 1, otherwise `other`, e.g. English, German and Spanish.
+    #[default]
+    OneOther,
+    /// `one` when the count is 0 or 1, otherwise `other`, e.g. French and Portuguese.
+    ZeroOneOther,
+    /// A single form used for every count, e.g. Chinese, Japanese and Korean.
+    Invariant,
+    /// `one`, `few` and `many`, chosen by the last digits of the count, e.g. Russian and
+    /// Ukrainian.
+    OneFewMany,
+}
+
+impl PluralRule {
+    /// The number of forms the rule chooses between.
+    pub fn form_count(self) -> usize {
+        match self {
+            Self::OneOther | Self::ZeroOneOther => 2,
+            Self::Invariant => 1,
+            Self::OneFewMany => 3,
+        }
+    }
+
+    /// Returns the index of the form to use for `count`, which is always less than
+    /// [`PluralRule::form_count`].
+    pub fn form_index(self, count: i64) -> usize {
+        let n = count.unsigned_abs();
+
+        match self {
+            Self::OneOther => usize::from(n != 1),
+            Self::ZeroOneOther => usize::from(n > 1),
+            Self::Invariant => 0,
+            Self::OneFewMany => match (n % 10, n % 100) {
+                (1, r) if r != 11 => 0,
+                (2..=4, r) if !(12..=14).contains(&r) => 1,
+                _ => 2,
+            },
+        }
+    }
+
+    /// Returns the CLDR plural category of `count`, which `messageformat` parameters use to pick
+    /// a plural case, e.g. `one` or `other`.
+    pub fn category(self, count: i64) -> &'static str {
+        match (self, self.form_index(count)) {
+            (Self::OneOther, 0) | (Self::ZeroOneOther, 0) | (Self::OneFewMany, 0) => "one",
+            (Self::OneFewMany, 1) => "few",
+            (Self::OneFewMany, _) => "many",
+            _ => "other",
+        }
+    }
+}
+
+/// Conventions for writing numbers and dates in a language, used when rendering integer, float
+/// and date-time values and when picking the plural case of a `messageformat` parameter.
+///
+/// Passed to [`crate::BalsaTemplate::render_html_string_with_options`] through
+/// [`crate::RenderOptions::locale`], which lets one template render correctly for several
+/// markets. The default locale writes numbers exactly as Rust's [`Display`](std::fmt::Display)
+/// implementations do.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let template = Balsa::from_string("{{ price : float }}").build().unwrap();
+/// let params = BalsaParameters::new().float("price", 1234.5);
+///
+/// let options = RenderOptions::new().locale(Locale::new("de-DE"));
+/// assert_eq!(
+///     template.render_html_string_with_options(&params, &options).unwrap(),
+///     "1.234,5"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Locale {
+    tag: String,
+    decimal_separator: char,
+    group_separator: Option<char>,
+    plural_rule: PluralRule,
+}
+
+impl Locale {
+    /// Creates a [`Locale`] for the BCP 47 language tag `tag`, e.g. `en-US` or `fr`.
+    ///
+    /// The separators are chosen by the tag's language. Languages Balsa doesn't know use a `.`
+    /// decimal separator and a `,` group separator, which can be changed with
+    /// [`Locale::decimal_separator`] and [`Locale::group_separator`]. The plural rule is also
+    /// chosen by the tag's language, defaulting to [`PluralRule::OneOther`].
+    pub fn new(tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        let language = language(&tag);
+
+        let (decimal_separator, group_separator) = match language.as_str() {
+            "de" | "es" | "id" | "it" | "nl" | "pt" | "tr" => (',', '.'),
+            "cs" | "fi" | "nb" | "no" | "pl" | "ru" | "sv" | "uk" => (',', '\u{a0}'),
+            "fr" => (',', '\u{202f}'),
+            _ => ('.', ','),
+        };
+
+        let plural_rule = match language.as_str() {
+            "fr" | "pt" => PluralRule::ZeroOneOther,
+            "id" | "ja" | "ko" | "th" | "vi" | "zh" => PluralRule::Invariant,
+            "be" | "bs" | "hr" | "ru" | "sr" | "uk" => PluralRule::OneFewMany,
+            _ => PluralRule::OneOther,
+        };
+
+        Self {
+            tag,
+            decimal_separator,
+            group_separator: Some(group_separator),
+            plural_rule,
+        }
+    }
+
+    /// Sets the char written between the whole and fractional parts of a float.
+    pub fn decimal_separator(mut self, decimal_separator: char) -> Self {
+        self.decimal_separator = decimal_separator;
+        self
+    }
+
+    /// Sets the char written between each group of three digits, or `None` to not group digits.
+    pub fn group_separator(mut self, group_separator: Option<char>) -> Self {
+        self.group_separator = group_separator;
+        self
+    }
+
+    /// Sets the rule used to pick the plural case of a `messageformat` parameter.
+    pub fn plural_rule(mut self, plural_rule: PluralRule) -> Self {
+        self.plural_rule = plural_rule;
+        self
+    }
+
+    /// Returns the rule used to pick the plural case of a `messageformat` parameter.
+    pub(crate) fn plurals(&self) -> PluralRule {
+        self.plural_rule
+    }
+
+    /// Returns the language tag the locale was created with, which is empty for the default
+    /// locale.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns the normalized tag of the locale followed by each of its prefixes, from the most
+    /// to the least specific, e.g. `fr-ca` and then `fr`. Empty for the default locale.
+    pub(crate) fn fallback_tags(&self) -> Vec<String> {
+        let tag = normalize_tag(&self.tag);
+        let mut tags = Vec::new();
+
+        let mut prefix = tag.as_str();
+        while !prefix.is_empty() {
+            tags.push(prefix.to_string());
+            prefix = prefix.rsplit_once('-').map_or("", |(prefix, _)| prefix);
+        }
+
+        tags
+    }
+
+    /// Returns the direction text is written in for the locale, taken from its script subtag if
+    /// it has one (e.g. `az-Arab`) and otherwise from its language.
+    pub fn direction(&self) -> TextDirection {
+        let script = self
+            .tag
+            .split(['-', '_'])
+            .skip(1)
+            .find(|subtag| subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+            .map(|subtag| subtag.to_ascii_lowercase());
+
+        let rtl = match script.as_deref() {
+            Some(script) => RTL_SCRIPTS.contains(&script),
+            None => RTL_LANGUAGES.contains(&language(&self.tag).as_str()),
+        };
+
+        if rtl {
+            TextDirection::Rtl
+        } else {
+            TextDirection::Ltr
+        }
+    }
+
+    /// Formats `currency` with the locale's separators and the currency's symbol, e.g. `$1,234.50`
+    /// or `1.234,50 €`. The default locale writes the amount followed by the currency's code,
+    /// e.g. `1234.50 USD`.
+    pub(crate) fn format_currency(&self, currency: &Currency) -> String {
+        let amount = self.format_number(&currency.format_amount());
+
+        if self.tag.is_empty() {
+            return format!("{} {}", amount, currency.code());
+        }
+
+        if !SYMBOL_FIRST_LANGUAGES.contains(&language(&self.tag).as_str()) {
+            return format!("{}\u{a0}{}", amount, currency.symbol());
+        }
+
+        match amount.strip_prefix('-') {
+            Some(amount) => format!("-{}{}", currency.symbol(), amount),
+            None => format!("{}{}", currency.symbol(), amount),
+        }
+    }
+
+    /// Rewrites `number`, formatted by Rust's [`Display`](std::fmt::Display) implementations for
+    /// integers and floats, using the locale's separators. Non-finite floats are returned as-is.
+    pub(crate) fn format_number(&self, number: &str) -> String {
+        let (sign, unsigned) = match number.strip_prefix('-') {
+            Some(unsigned) => ("-", unsigned),
+            None => ("", number),
+        };
+        let (whole, fraction) = match unsigned.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (unsigned, None),
+        };
+
+        if !whole.chars().all(|c| c.is_ascii_digit()) {
+            return number.to_string();
+        }
+
+        let mut output = sign.to_string();
+        for (i, c) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                output.extend(self.group_separator);
+            }
+            output.push(c);
+        }
+
+        if let Some(fraction) = fraction {
+            output.push(self.decimal_separator);
+            output.push_str(fraction);
+        }
+
+        output
+    }
+
+    /// Formats `datetime` with the strftime `format`, writing the names of months and weekdays
+    /// (`%B`, `%b`, `%A` and `%a`) in the locale's language. Without a `format`, the locale's
+    /// default format is used, and the default locale writes RFC 3339 date-times. Languages
+    /// Balsa doesn't know have their names written in English and default to `%Y-%m-%d %H:%M`.
+    #[cfg(feature = "chrono")]
+    pub(crate) fn format_datetime(
+        &self,
+        datetime: &chrono::DateTime<chrono::FixedOffset>,
+        format: Option<&str>,
+    ) -> String {
+        use chrono::Datelike;
+
+        let names = date_names(&language(&self.tag));
+        let format = match (format, names) {
+            (Some(format), _) => format,
+            (None, _) if self.tag.is_empty() => return datetime.to_rfc3339(),
+            (None, Some(names)) => names.default_format,
+            (None, None) => "%Y-%m-%d %H:%M",
+        };
+
+        let Some(names) = names else {
+            return datetime.format(format).to_string();
+        };
+
+        let month = datetime.month0() as usize;
+        let weekday = datetime.weekday().num_days_from_monday() as usize;
+
+        // Names are written into the format before it is passed to chrono, which only knows the
+        // English names. `%%` is kept as-is, so that `%%B` still writes a literal `%B`.
+        let mut localized = String::with_capacity(format.len());
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                localized.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('B') => localized.push_str(names.months[month]),
+                Some('b') | Some('h') => localized.push_str(names.short_months[month]),
+                Some('A') => localized.push_str(names.weekdays[weekday]),
+                Some('a') => localized.push_str(names.short_weekdays[weekday]),
+                Some(c) => {
+                    localized.push('%');
+                    localized.push(c);
+                }
+                None => localized.push('%'),
+            }
+        }
+
+        datetime.format(&localized).to_string()
+    }
+}
+
+impl From<&str> for Locale {
+    fn from(tag: &str) -> Self {
+        Self::new(tag)
+    }
+}
+
+impl From<String> for Locale {
+    fn from(tag: String) -> Self {
+        Self::new(tag)
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            tag: String::new(),
+            decimal_separator: '.',
+            group_separator: None,
+            plural_rule: PluralRule::default(),
+        }
+    }
+}
+
+/// The direction in which text is written, as used by the HTML `dir` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextDirection {
+    /// Left-to-right, e.g. English.
+    Ltr,
+    /// Right-to-left, e.g. Arabic and Hebrew.
+    Rtl,
+    /// Determined by the browser from the text itself.
+    Auto,
+}
+
+impl TextDirection {
+    /// Returns the value of the HTML `dir` attribute for the direction.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ltr => "ltr",
+            Self::Rtl => "rtl",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// Parses the value of an HTML `dir` attribute, i.e. `ltr`, `rtl` or `auto`.
+    pub(crate) fn from_attribute(value: &str) -> Option<Self> {
+        match value {
+            "ltr" => Some(Self::Ltr),
+            "rtl" => Some(Self::Rtl),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
+impl Display for TextDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The names of months and weekdays in a language, and the format date-times are written in by
+/// default.
+#[cfg(feature = "chrono")]
+struct DateNames {
+    months: [&'static str; 12],
+    short_months: [&'static str; 12],
+    /// Starting on Monday.
+    weekdays: [&'static str; 7],
+    short_weekdays: [&'static str; 7],
+    default_format: &'static str,
+}
+
+/// Returns the [`DateNames`] of the lowercased language subtag `language`, if Balsa knows them.
+#[cfg(feature = "chrono")]
+fn date_names(language: &str) -> Option<&'static DateNames> {
+    const EN: DateNames = DateNames {
+        months: [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+        short_months: [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ],
+        weekdays: [
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+            "Sunday",
+        ],
+        short_weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+        default_format: "%B %-d, %Y, %-I:%M %p",
+    };
+    const DE: DateNames = DateNames {
+        months: [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        short_months: [
+            "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.",
+            "Dez.",
+        ],
+        weekdays: [
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+            "Sonntag",
+        ],
+        short_weekdays: ["Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa.", "So."],
+        default_format: "%-d. %B %Y, %H:%M",
+    };
+    const ES: DateNames = DateNames {
+        months: [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        short_months: [
+            "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+        ],
+        weekdays: [
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+            "domingo",
+        ],
+        short_weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+        default_format: "%-d de %B de %Y, %H:%M",
+    };
+    const FR: DateNames = DateNames {
+        months: [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        short_months: [
+            "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+            "nov.", "déc.",
+        ],
+        weekdays: [
+            "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+        ],
+        short_weekdays: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+        default_format: "%-d %B %Y à %H:%M",
+    };
+    const IT: DateNames = DateNames {
+        months: [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+        short_months: [
+            "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+        ],
+        weekdays: [
+            "lunedì",
+            "martedì",
+            "mercoledì",
+            "giovedì",
+            "venerdì",
+            "sabato",
+            "domenica",
+        ],
+        short_weekdays: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+        default_format: "%-d %B %Y, %H:%M",
+    };
+    const NL: DateNames = DateNames {
+        months: [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+        short_months: [
+            "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+        ],
+        weekdays: [
+            "maandag",
+            "dinsdag",
+            "woensdag",
+            "donderdag",
+            "vrijdag",
+            "zaterdag",
+            "zondag",
+        ],
+        short_weekdays: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+        default_format: "%-d %B %Y %H:%M",
+    };
+    const PT: DateNames = DateNames {
+        months: [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+        short_months: [
+            "jan.", "fev.", "mar.", "abr.", "mai.", "jun.", "jul.", "ago.", "set.", "out.", "nov.",
+            "dez.",
+        ],
+        weekdays: [
+            "segunda-feira",
+            "terça-feira",
+            "quarta-feira",
+            "quinta-feira",
+            "sexta-feira",
+            "sábado",
+            "domingo",
+        ],
+        short_weekdays: ["seg.", "ter.", "qua.", "qui.", "sex.", "sáb.", "dom."],
+        default_format: "%-d de %B de %Y, %H:%M",
+    };
+
+    match language {
+        "en" => Some(&EN),
+        "de" => Some(&DE),
+        "es" => Some(&ES),
+        "fr" => Some(&FR),
+        "it" => Some(&IT),
+        "nl" => Some(&NL),
+        "pt" => Some(&PT),
+        _ => None,
+    }
+}
+
+/// Normalizes a BCP 47 language tag for comparison, e.g. `fr_CA` to `fr-ca`.
+pub(crate) fn normalize_tag(tag: &str) -> String {
+    tag.replace('_', "-").to_ascii_lowercase()
+}
+
+/// Returns the lowercased language subtag of the BCP 47 language tag `tag`.
+fn language(tag: &str) -> String {
+    tag.split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plural_rule_form_index() {
+        let counts = [0, 1, 2, 5, 11, 21, 22, 112, -1];
+
+        let cases = [
+            (PluralRule::OneOther, [1, 0, 1, 1, 1, 1, 1, 1, 0]),
+            (PluralRule::ZeroOneOther, [0, 0, 1, 1, 1, 1, 1, 1, 0]),
+            (PluralRule::Invariant, [0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            (PluralRule::OneFewMany, [2, 0, 1, 2, 2, 0, 1, 2, 0]),
+        ];
+
+        for (rule, expected) in cases {
+            let forms = counts
+                .iter()
+                .map(|count| rule.form_index(*count))
+                .collect::<Vec<usize>>();
+
+            assert_eq!(
+                forms, expected,
+                "Plural rule {:?} chose the wrong forms for counts {:?}",
+                rule, counts
+            );
+        }
+    }
+
+    #[test]
+    fn test_locale_direction() {
+        let cases = [
+            ("", TextDirection::Ltr),
+            ("en-US", TextDirection::Ltr),
+            ("ar", TextDirection::Rtl),
+            ("he-IL", TextDirection::Rtl),
+            ("FA_ir", TextDirection::Rtl),
+            ("az-Arab", TextDirection::Rtl),
+            ("ku-Latn-TR", TextDirection::Ltr),
+            ("ar-Latn", TextDirection::Ltr),
+        ];
+
+        for (tag, expected) in cases {
+            assert_eq!(
+                Locale::new(tag).direction(),
+                expected,
+                "Locale `{}` has the wrong direction",
+                tag
+            );
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_locale_format_datetime() {
+        use chrono::{FixedOffset, TimeZone};
+
+        let datetime = FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2024, 3, 1, 9, 30, 0)
+            .unwrap();
+
+        let cases = [
+            (Locale::default(), None, "2024-03-01T09:30:00+00:00"),
+            (Locale::new("de-DE"), None, "1. März 2024, 09:30"),
+            (Locale::new("pt-BR"), None, "1 de março de 2024, 09:30"),
+            (Locale::new("es"), Some("%a %-d %b"), "vie 1 mar"),
+            (Locale::new("nl"), Some("%%B %B"), "%B maart"),
+            (Locale::default(), Some("%A %B"), "Friday March"),
+        ];
+
+        for (locale, format, expected) in cases {
+            assert_eq!(
+                locale.format_datetime(&datetime, format),
+                expected,
+                "Locale `{}` formatted a date-time incorrectly with format {:?}",
+                locale.tag(),
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn test_locale_format_number() {
+        let numbers = ["0", "-12", "1234567", "-1234.5", "123.25", "inf", "NaN"];
+
+        let cases = [
+            (
+                Locale::default(),
+                ["0", "-12", "1234567", "-1234.5", "123.25", "inf", "NaN"],
+            ),
+            (
+                Locale::new("en-US"),
+                ["0", "-12", "1,234,567", "-1,234.5", "123.25", "inf", "NaN"],
+            ),
+            (
+                Locale::new("de_DE"),
+                ["0", "-12", "1.234.567", "-1.234,5", "123,25", "inf", "NaN"],
+            ),
+            (
+                Locale::new("fr").group_separator(Some(' ')),
+                ["0", "-12", "1 234 567", "-1 234,5", "123,25", "inf", "NaN"],
+            ),
+        ];
+
+        for (locale, expected) in cases {
+            let formatted = numbers
+                .iter()
+                .map(|number| locale.format_number(number))
+                .collect::<Vec<String>>();
+
+            assert_eq!(
+                formatted,
+                expected,
+                "Locale `{}` formatted numbers incorrectly",
+                locale.tag()
+            );
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    balsa_compiler::{CompiledTemplate, EachPart, ParameterDescription, ReplaceWith},
+    BalsaType, BalsaValue, TextDirection,
+};
+
+/// A parameter a template expects, returned by [`crate::Template::parameters`], with everything
+/// a CMS needs to generate a field for it in an edit form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterInfo {
+    /// The name of the parameter.
+    pub name: String,
+    /// The type of the parameter.
+    pub parameter_type: BalsaType,
+    /// The default value given by any of the parameter's blocks, which is used when no value is
+    /// passed. Parameters without one must be passed to every render unless they are optional.
+    pub default_value: Option<BalsaValue>,
+    /// The direction the value is written in, if one was given with `direction`.
+    pub direction: Option<TextDirection>,
+    /// Whether the value is escaped, which is `false` for parameters marked `escape: false`.
+    pub escaped: bool,
+    /// The fewest items an array parameter may have, given with `minItems`.
+    pub min_items: Option<usize>,
+    /// The most items an array parameter may have, given with `maxItems`.
+    pub max_items: Option<usize>,
+    /// What a single item of an array parameter is called, given with `itemLabel`.
+    pub item_label: Option<String>,
+    /// The MIME types an image parameter may have, given with `accept`. Any type is accepted if
+    /// empty.
+    pub accept: Vec<String>,
+    /// The widest an image parameter may be in pixels, given with `maxWidth`.
+    pub max_width: Option<u32>,
+    /// The tallest an image parameter may be in pixels, given with `maxHeight`.
+    pub max_height: Option<u32>,
+    /// The values the parameter may have, given with `oneOf`, which a CMS can offer as a
+    /// dropdown. Any value is allowed if empty.
+    pub one_of: Vec<BalsaValue>,
+    /// The `strftime`-style format a date-time parameter is written with, given with `format`.
+    pub format: Option<String>,
+    /// Whether a URL parameter only accepts URLs which are safe to link to, given with `safe`.
+    pub safe: bool,
+    /// Whether the parameter may be left out of a render, given with `optional: true`. A CMS can
+    /// leave the field blank instead of requiring it.
+    pub optional: bool,
+    /// The text written in place of an optional parameter which was left out, given with
+    /// `placeholder`.
+    pub placeholder: Option<String>,
+    /// The names of the filters the value is passed through before it is written, in order,
+    /// given with `filters`.
+    pub filters: Vec<String>,
+    /// The options allowed with [`crate::CompileOptions::allow_parameter_option`], e.g. a
+    /// `friendlyName` to label the field with, along with their values.
+    pub options: Vec<(String, BalsaValue)>,
+    /// The values read from each item, if the parameter holds the items of an `#each` section.
+    pub fields: Vec<ParameterInfo>,
+}
+
+impl ParameterInfo {
+    /// Returns every parameter of `compiled_template` in the order they are first used, followed
+    /// by the parameters which are only read by computed parameters. Computed parameters are
+    /// left out, as their values are never passed to a render.
+    pub(crate) fn collect(compiled_template: &CompiledTemplate) -> Vec<Self> {
+        let mut parameters: Vec<Self> = Vec::new();
+
+        for replacement in &compiled_template.replacements {
+            let p = match replacement.replace_with.parameter() {
+                Some(p) => p,
+                None => continue,
+            };
+
+            if compiled_template.computed(&p.variable_name).is_some()
+                || parameters.iter().any(|info| info.name == p.variable_name)
+            {
+                continue;
+            }
+
+            let mut info = Self::new(p);
+            info.default_value = compiled_template.default_value(&p.variable_name).cloned();
+            if let ReplaceWith::Each(e) = &replacement.replace_with {
+                info.fields = e
+                    .body
+                    .iter()
+                    .filter_map(|part| match part {
+                        EachPart::Field(field) => Some(Self::new(field)),
+                        EachPart::Markup(_) | EachPart::Include(_) => None,
+                    })
+                    .collect();
+            }
+
+            parameters.push(info);
+        }
+
+        for c in &compiled_template.computed {
+            for input in c.expression.inputs() {
+                if compiled_template.computed(input).is_some()
+                    || parameters.iter().any(|info| info.name == input)
+                {
+                    continue;
+                }
+
+                parameters.push(Self {
+                    name: input.to_string(),
+                    parameter_type: BalsaType::Float,
+                    default_value: None,
+                    direction: None,
+                    escaped: true,
+                    min_items: None,
+                    max_items: None,
+                    item_label: None,
+                    accept: Vec::new(),
+                    max_width: None,
+                    max_height: None,
+                    one_of: Vec::new(),
+                    format: None,
+                    safe: false,
+                    optional: false,
+                    placeholder: None,
+                    filters: Vec::new(),
+                    options: Vec::new(),
+                    fields: Vec::new(),
+                });
+            }
+        }
+
+        parameters
+    }
+
+    /// Creates a new [`ParameterInfo`] describing the provided [`ParameterDescription`].
+    fn new(p: &ParameterDescription) -> Self {
+        Self {
+            name: p.variable_name.clone(),
+            parameter_type: p.variable_type.clone(),
+            default_value: p.default_value.clone(),
+            direction: p.direction,
+            escaped: p.escape,
+            min_items: p.items.min_items,
+            max_items: p.items.max_items,
+            item_label: p.items.item_label.clone(),
+            accept: p.image.accept.clone(),
+            max_width: p.image.max_width,
+            max_height: p.image.max_height,
+            one_of: p.one_of.clone(),
+            format: p.format.clone(),
+            safe: p.safe,
+            optional: p.optional,
+            placeholder: p.placeholder.clone(),
+            filters: p.filters.clone(),
+            options: p.custom_options.clone(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Returns the value of the option `name` allowed with
+    /// [`crate::CompileOptions::allow_parameter_option`], if the parameter was given it.
+    pub fn option(&self, name: &str) -> Option<&BalsaValue> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value)
+    }
+}
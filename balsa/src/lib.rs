@@ -55,24 +55,97 @@ pub(crate) mod balsa_renderer;
 pub(crate) mod balsa_type_cast;
 /// Types supported in Balsa templates.
 pub(crate) mod balsa_types;
+/// Composition of templates from stored fragments.
+pub mod composition;
+pub use composition::{FileLoader, TemplateLoader, TemplateSet};
+/// Caching of compiled templates by their source.
+pub mod cache;
+pub use cache::{CompileCache, RenderCache};
+/// Dependency graphs of parameters and computed parameters.
+pub mod dependencies;
+pub use dependencies::DependencyGraph;
+/// Parallel compilation of template directories.
+#[cfg(feature = "rayon")]
+pub mod directory;
+#[cfg(feature = "rayon")]
+pub use directory::CompiledDirectory;
 /// Error types for Balsa compilation.
 pub mod errors;
 pub use errors::BalsaError;
+/// Human-readable descriptions of compiled templates.
+pub mod explain;
+pub use explain::TemplateExplanation;
+/// Arithmetic expressions for computed parameters.
+pub(crate) mod expression;
+/// Custom filters which transform parameter values when rendering.
+pub(crate) mod filters;
+/// Descriptions of the parameters templates expect, for generating edit forms.
+pub mod introspection;
+pub use introspection::ParameterInfo;
+/// Lazy loading of parameter values from async sources.
+#[cfg(feature = "tokio")]
+pub mod loader;
+#[cfg(feature = "tokio")]
+pub use loader::{DataLoader, LoadFuture};
+/// Message catalogs for translating templates.
+pub mod i18n;
+pub use i18n::{
+    ExtractedMessage, Locale, LocalizedRender, MessageCatalog, PluralRule, TextDirection,
+};
+/// Options for compiling templates.
+pub mod options;
+pub use options::{
+    CancellationToken, CompileOptions, EscapePolicy, HydrationMarkers, RenderOptions,
+};
+/// Parser for ICU MessageFormat patterns.
+pub(crate) mod message_format;
 /// Name constants for parameters.
 pub(crate) mod parameter_names;
+/// Binary encoding of precompiled templates.
+pub(crate) mod precompiled;
+/// RFC 7807 problem details for errors.
+#[cfg(feature = "serde")]
+pub mod problem;
+#[cfg(feature = "serde")]
+pub use problem::ProblemDetails;
+/// Registries of templates compiled from a directory and looked up by name.
+pub mod registry;
+pub use registry::BalsaRegistry;
+/// Timing and usage metrics for renders.
+pub mod report;
+pub use report::RenderReport;
+/// Streaming renders for async consumers.
+#[cfg(feature = "tokio")]
+pub mod stream;
+#[cfg(feature = "tokio")]
+pub use stream::RenderStream;
+/// Assertions, snapshots and placeholder parameters for testing templates.
+pub mod testing;
+/// Warning types for Balsa compilation.
+pub mod warnings;
+pub use warnings::BalsaWarning;
+/// Bindings for compiling and rendering templates in the browser with WebAssembly.
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
+#[cfg(feature = "wasm-bindgen")]
+pub use wasm::WasmTemplate;
 
 use std::{
-    fmt,
-    fs::{self, File},
+    borrow::Cow,
+    fmt, fs, io,
     marker::PhantomData,
     path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 
 use balsa_compiler::CompiledTemplate;
-pub use balsa_types::{BalsaType, BalsaValue};
+pub use balsa_types::{Array, BalsaType, BalsaValue, Currency, Dictionary, DictionaryIter, Image};
 
-/// Internal type converters.
-pub(crate) mod converters;
 /// Internal types for the compiler, etc.
 pub(crate) mod types;
 /// Validators for color formats etc.
@@ -91,13 +164,30 @@ pub use parameters_builder::{AsParameters, BalsaParameters};
 pub struct Balsa;
 
 /// A trait for loading a raw template document as a String.
-trait TemplateSource: fmt::Debug {
+pub trait TemplateSource: fmt::Debug {
+    /// Reads the raw template. Called every time a [`BalsaBuilder`] builds a template.
     fn read_template(&self) -> BalsaResult<String>;
+
+    /// Returns the size of the raw template in bytes if it can be found without reading the
+    /// template, which lets a [`BalsaBuilder`] with a maximum template size reject an oversized
+    /// template before it's loaded into memory. Returns `None` by default.
+    fn template_size(&self) -> BalsaResult<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Consumes the source and returns the raw template, which lets sources that already own the
+    /// template avoid copying it. Used by [`BalsaBuilder::build_once`].
+    fn into_template(self) -> BalsaResult<String>
+    where
+        Self: Sized,
+    {
+        self.read_template()
+    }
 }
 
 /// Loads raw template from a file.
-#[derive(Debug)]
-struct FileSource {
+#[derive(Debug, Clone)]
+pub struct FileSource {
     path: PathBuf,
 }
 
@@ -105,11 +195,17 @@ impl TemplateSource for FileSource {
     fn read_template(&self) -> BalsaResult<String> {
         fs::read_to_string(&self.path).map_err(BalsaError::read_template_error)
     }
+
+    fn template_size(&self) -> BalsaResult<Option<usize>> {
+        let metadata = fs::metadata(&self.path).map_err(BalsaError::read_template_error)?;
+
+        Ok(Some(usize::try_from(metadata.len()).unwrap_or(usize::MAX)))
+    }
 }
 
 /// Loads raw template from a string.
 #[derive(Debug, Clone)]
-struct StringSource {
+pub struct StringSource {
     raw_template: String,
 }
 
@@ -117,18 +213,81 @@ impl TemplateSource for StringSource {
     fn read_template(&self) -> BalsaResult<String> {
         Ok(self.raw_template.clone())
     }
+
+    fn into_template(self) -> BalsaResult<String> {
+        Ok(self.raw_template)
+    }
+}
+
+impl TemplateSource for Box<dyn TemplateSource> {
+    fn read_template(&self) -> BalsaResult<String> {
+        (**self).read_template()
+    }
+
+    fn template_size(&self) -> BalsaResult<Option<usize>> {
+        (**self).template_size()
+    }
 }
 
 /// A struct for building a Balsa template from a static HTML document.
+///
+/// The builder is generic over its [`TemplateSource`] `S`, which defaults to a boxed source so
+/// builders with different sources can be stored together (see [`BalsaBuilder::boxed`]).
 #[derive(Debug)]
-pub struct BalsaBuilder {
-    template_source: Box<dyn TemplateSource>,
+pub struct BalsaBuilder<S: TemplateSource = Box<dyn TemplateSource>> {
+    template_source: S,
+    options: CompileOptions,
 }
 
 /// A compiled template that can be rendered with the specified `T`.
 pub trait BalsaTemplate<T>: Sync + Send {
     /// Renders the template with the specified `params` argument.
-    fn render_html_string(&self, params: &T) -> BalsaResult<String>;
+    fn render_html_string(&self, params: &T) -> BalsaResult<String> {
+        self.render_html_string_with_options(params, &RenderOptions::default())
+    }
+
+    /// Renders the template with the specified `params` argument and [`RenderOptions`].
+    fn render_html_string_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<String>;
+
+    /// Renders the template with the specified `params` argument straight into `w`, e.g. a file
+    /// or an HTTP response, without collecting the whole document in a `String` first.
+    ///
+    /// Output is written in many small pieces, so slow writers should be wrapped in a
+    /// [`std::io::BufWriter`]. Anything rendered before an error has already been written.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string("<h1>{{ title : string }}</h1>").build()?;
+    ///
+    /// let mut output = Vec::new();
+    /// template.render_to_writer(&BalsaParameters::new().string("title", "Hi"), &mut output)?;
+    /// assert_eq!(output, b"<h1>Hi</h1>");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    fn render_to_writer(&self, params: &T, w: &mut dyn io::Write) -> BalsaResult<()> {
+        self.render_to_writer_with_options(params, &RenderOptions::default(), w)
+    }
+
+    /// Renders the template with the specified `params` argument and [`RenderOptions`] straight
+    /// into `w`. See [`BalsaTemplate::render_to_writer`].
+    ///
+    /// The default implementation renders the whole document before writing it.
+    fn render_to_writer_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+        w: &mut dyn io::Write,
+    ) -> BalsaResult<()> {
+        let output = self.render_html_string_with_options(params, options)?;
+
+        w.write_all(output.as_bytes())
+            .map_err(BalsaError::write_output_error)
+    }
 }
 
 /// A compiled template that can be rendered with any type implementing [`AsParameters`].
@@ -136,8 +295,29 @@ pub trait BalsaTemplate<T>: Sync + Send {
 /// Can be built with any object that implements [`AsParameters`].
 #[derive(Debug, Clone)]
 pub struct Template {
-    raw_template: String, // TODO: more memory-efficient way of loading raw templates
     compiled_template: CompiledTemplate,
+    /// Identifies the compiled template, which is shared by its clones, for [`RenderCache`].
+    id: u64,
+}
+
+/// Serializes the compiled template, leaving out its id.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Template {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.compiled_template.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Template {
+    /// Deserializes a template, checking it exactly like [`Template::from_precompiled`] so that
+    /// malformed input can't produce a template which fails to render.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let compiled_template = CompiledTemplate::deserialize(deserializer)?;
+        let bytes = precompiled::encode(&compiled_template);
+
+        Self::from_precompiled(&bytes).map_err(serde::de::Error::custom)
+    }
 }
 
 /// A compiled template that is pinned to the parameters type `T`. This is meant to provide a sort
@@ -147,63 +327,743 @@ pub struct Template {
 #[derive(Debug, Clone)]
 pub struct TypedTemplate<T: AsParameters> {
     template: Template,
-    _type: PhantomData<T>,
+    // `fn() -> T` keeps the template `Send + Sync` regardless of `T`, as no `T` is ever stored.
+    _type: PhantomData<fn() -> T>,
+}
+
+impl Template {
+    /// Creates a new [`Template`] with a unique id.
+    fn new(compiled_template: CompiledTemplate) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        Self {
+            compiled_template,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the warnings produced while compiling the template.
+    pub fn warnings(&self) -> &[BalsaWarning] {
+        &self.compiled_template.warnings
+    }
+
+    /// Returns a description of the global scope and replacement instructions the compiler
+    /// produced for this template, which is useful for debugging templates.
+    pub fn explain(&self) -> TemplateExplanation {
+        TemplateExplanation::new(&self.compiled_template)
+    }
+
+    /// Returns every parameter the template expects, in the order they are first used, along
+    /// with their types, defaults and options, so that a CMS can generate an edit form for the
+    /// template. Computed parameters are left out, and parameters which are only read by
+    /// computed parameters come last with the type `float`, as they accept any number.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string(
+    ///     "<h1>{{ title : string, friendlyName: \"Page title\" }}</h1>{{ views : int, defaultValue: 0 }}",
+    /// )
+    /// .with_options(CompileOptions::new().allow_parameter_option("friendlyName"))
+    /// .build()?;
+    ///
+    /// let parameters = template.parameters();
+    /// assert_eq!(parameters[0].name, "title");
+    /// assert_eq!(parameters[0].parameter_type, BalsaType::String);
+    /// assert_eq!(
+    ///     parameters[0].option("friendlyName"),
+    ///     Some(&BalsaValue::String("Page title".to_string()))
+    /// );
+    /// assert_eq!(parameters[1].default_value, Some(BalsaValue::Integer(0)));
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn parameters(&self) -> Vec<ParameterInfo> {
+        ParameterInfo::collect(&self.compiled_template)
+    }
+
+    /// Returns a graph of which computed parameters depend on which parameters, so that tooling
+    /// can tell which values to recompute when a parameter changes.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::new(&self.compiled_template)
+    }
+
+    /// Returns every use of a message in the template, in the order they appear, so that
+    /// localization pipelines can generate translation files from templates.
+    ///
+    /// Templates using messages which are missing from their catalog must be built with
+    /// [`CompileOptions::allow_missing_messages`].
+    pub fn extract_messages(&self) -> Vec<ExtractedMessage> {
+        self.compiled_template.extract_messages()
+    }
+
+    /// Renders the template using only the values declared in it and the default values of its
+    /// parameters, e.g. to preview a theme.
+    ///
+    /// Fails with a [`errors::BalsaRenderError::MissingDefaults`] error listing every parameter
+    /// which has no default value.
+    pub fn render_defaults(&self) -> BalsaResult<String> {
+        self.compiled_template.ensure_defaults()?;
+
+        self.render_html_string(&BalsaParameters::new())
+    }
+
+    /// Renders the template in the first of `accepted_locales` which `params` has a
+    /// [`BalsaParameters::localized`] layer for, returning the output along with the locale
+    /// which was used.
+    ///
+    /// `accepted_locales` are language tags in order of preference, such as the entries of an HTTP
+    /// `Accept-Language` header sorted by quality. Parameters like `;q=0.8` and the `*` wildcard
+    /// are ignored. If no accepted locale is available, the default [`Locale`] is used.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string("{{ greeting : string }}").build()?;
+    /// let params = BalsaParameters::new()
+    ///     .string("greeting", "Hello")
+    ///     .localized("fr", BalsaParameters::new().string("greeting", "Bonjour"));
+    ///
+    /// let render = template.render_localized(&params, &["de-DE", "fr-CH;q=0.8", "en;q=0.5"])?;
+    /// assert_eq!(render.output, "Bonjour");
+    /// assert_eq!(render.locale.tag(), "fr-CH");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn render_localized<T: AsParameters>(
+        &self,
+        params: &T,
+        accepted_locales: &[&str],
+    ) -> BalsaResult<LocalizedRender> {
+        let params = params.as_parameters();
+        let locale = params
+            .negotiate_locale(accepted_locales)
+            .unwrap_or_default();
+
+        let options = RenderOptions::new().locale(locale.clone());
+        let output = self.render_html_string_with_options(&params, &options)?;
+
+        Ok(LocalizedRender { output, locale })
+    }
+
+    /// Renders the template with the default [`RenderOptions`], borrowing its static text
+    /// instead of copying it if the template has no blocks to replace.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// # use std::borrow::Cow;
+    /// let template = Balsa::from_string("<footer>Static</footer>").build()?;
+    ///
+    /// let output = template.render_cow(&BalsaParameters::new())?;
+    /// assert!(matches!(output, Cow::Borrowed("<footer>Static</footer>")));
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn render_cow<T: AsParameters>(&self, params: &T) -> BalsaResult<Cow<'_, str>> {
+        if self.compiled_template.replacements.is_empty() {
+            return Ok(Cow::Borrowed(self.compiled_template.trailing()));
+        }
+
+        self.render_html_string(params).map(Cow::Owned)
+    }
+
+    /// Renders the template as a [`RenderStream`] of output chunks, rendering each chunk only
+    /// when it is polled so that very large documents can be sent as they are produced.
+    ///
+    /// Fails before anything is rendered if `params` exceed the limits set in `options`.
+    /// Errors while rendering are yielded by the stream.
+    #[cfg(feature = "tokio")]
+    pub fn render_stream<'a>(
+        &'a self,
+        params: &'a BalsaParameters,
+        options: &'a RenderOptions,
+    ) -> BalsaResult<RenderStream<'a>> {
+        let renderer = balsa_renderer::Renderer::new(&self.compiled_template);
+
+        renderer
+            .render_steps(params, options)
+            .map(RenderStream::new)
+    }
+
+    /// Renders the template, first loading every parameter it reads which isn't in `params` with
+    /// `loader`. The loads run concurrently, and parameters which the loader has no value for
+    /// fall back to their default values.
+    ///
+    /// The arguments of `messageformat` patterns are never loaded, as they are only known once
+    /// the template is rendered.
+    #[cfg(feature = "tokio")]
+    pub async fn render_with_loader<L: DataLoader + ?Sized>(
+        &self,
+        params: &BalsaParameters,
+        options: &RenderOptions,
+        loader: &L,
+    ) -> BalsaResult<String> {
+        let absent = loader::absent_parameters(&self.compiled_template, params, options);
+        let params = loader::load_parameters(loader, params, &absent).await?;
+
+        self.render_html_string_with_options(&params, options)
+    }
+
+    /// Renders the template with the provided [`RenderOptions`], returning the output along with
+    /// a [`RenderReport`] of how long each phase of the render took and which parameters were
+    /// used or fell back to their default values.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string(
+    ///     r#"<h1>{{ title : string }}</h1><p>{{ subtitle : string, defaultValue: "None" }}</p>"#,
+    /// )
+    /// .build()?;
+    /// let params = BalsaParameters::new().string("title", "Hello");
+    ///
+    /// let report = template.render_with_report(&params, &RenderOptions::new())?;
+    /// assert_eq!(report.output, "<h1>Hello</h1><p>None</p>");
+    /// assert_eq!(report.used_parameters, vec!["title"]);
+    /// assert_eq!(report.defaulted_parameters, vec!["subtitle"]);
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn render_with_report<T: AsParameters>(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<RenderReport> {
+        let start = Instant::now();
+        let params = params.as_parameters();
+        let parameters_time = start.elapsed();
+
+        let start = Instant::now();
+        let renderer = balsa_renderer::Renderer::new(&self.compiled_template);
+        let (output, usage) = renderer.render_with_usage(&params, options)?;
+        let render_time = start.elapsed();
+
+        Ok(RenderReport {
+            output_size: output.len(),
+            output,
+            parameters_time,
+            render_time,
+            used_parameters: usage.used,
+            defaulted_parameters: usage.defaulted,
+        })
+    }
+
+    /// Loads a template which was compiled ahead of time with [`Balsa::precompile`] or
+    /// [`BalsaBuilder::precompile`], skipping parsing and compilation entirely.
+    ///
+    /// Fails with a [`BalsaError::ReadTemplateError`] if `bytes` is not a valid precompiled
+    /// template. Precompiled templates carry no [`Template::warnings`].
+    pub fn from_precompiled(bytes: &[u8]) -> BalsaResult<Self> {
+        Ok(Self::new(precompiled::decode(bytes)?))
+    }
+
+    /// Encodes the template in the same format as [`Balsa::precompile`], so that it can be
+    /// cached to disk and loaded again with [`Template::from_bytes`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string("<h1>{{ title : string }}</h1>").build()?;
+    ///
+    /// let loaded = Template::from_bytes(&template.to_bytes())?;
+    /// let params = BalsaParameters::new().string("title", "Hi");
+    /// assert_eq!(loaded.render_html_string(&params)?, "<h1>Hi</h1>");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        precompiled::encode(&self.compiled_template)
+    }
+
+    /// Loads a template encoded with [`Template::to_bytes`]. This is the same as
+    /// [`Template::from_precompiled`].
+    pub fn from_bytes(bytes: &[u8]) -> BalsaResult<Self> {
+        Self::from_precompiled(bytes)
+    }
+
+    /// Returns a copy of the template in which the parameter `name` defaults to `value`,
+    /// overriding any default value written in the template. Parameters which don't appear in
+    /// the template are ignored.
+    ///
+    /// Fails with a [`errors::BalsaRenderError::InvalidParameterType`] error if `value` can't be
+    /// cast to the type of the parameter.
+    pub fn with_default(&self, name: &str, value: BalsaValue) -> BalsaResult<Self> {
+        Ok(Self::new(
+            self.compiled_template.with_default(name, &value)?,
+        ))
+    }
+
+    /// Returns a copy of the template with `filter` registered as `name`, replacing any filter
+    /// registered as `name` when the template was built.
+    ///
+    /// Filters aren't included in precompiled templates, so templates loaded with
+    /// [`Template::from_precompiled`] must have the filters their parameters use registered
+    /// again, or rendering fails with a [`errors::BalsaRenderError::FilterFailed`] error.
+    pub fn with_filter<F>(&self, name: impl Into<String>, filter: F) -> Self
+    where
+        F: Fn(BalsaValue) -> BalsaResult<BalsaValue> + Send + Sync + 'static,
+    {
+        let mut compiled_template = self.compiled_template.clone();
+        compiled_template
+            .filters
+            .insert(name.into(), Arc::new(filter));
+
+        Self::new(compiled_template)
+    }
+
+    /// Renders every block which only reads parameters in `static_params`, returning a smaller
+    /// template in which their output is static text. Rendering the result only touches the
+    /// remaining blocks, so values which rarely change can be baked in once and the dynamic ones
+    /// filled in for each request.
+    ///
+    /// Baked values are written as with the default [`RenderOptions`], and the arguments of
+    /// `messageformat` parameters which remain are still looked up when rendering.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string("<h1>{{ site : string }}</h1><p>{{ user : string }}</p>")
+    ///     .build()?;
+    /// let baked = template.partially_render(&BalsaParameters::new().string("site", "Balsa"))?;
+    ///
+    /// let output = baked.render_html_string(&BalsaParameters::new().string("user", "Ada"))?;
+    /// assert_eq!(output, "<h1>Balsa</h1><p>Ada</p>");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn partially_render<P: AsParameters>(&self, static_params: &P) -> BalsaResult<Self> {
+        let params = static_params.as_parameters();
+        let renderer = balsa_renderer::Renderer::new(&self.compiled_template);
+        Ok(Self::new(renderer.partially_render(&params)?))
+    }
+
+    /// Returns a new template which renders this template followed directly by `other`, so that
+    /// pages can be assembled from separately compiled fragments.
+    ///
+    /// Positions in `other`, e.g. in errors and [`Template::explain`], are moved to where they
+    /// are in the combined template. Variables declared in one template never apply to the other.
+    /// A parameter used by both templates must have the same type and default value in each,
+    /// otherwise this fails with a [`errors::BalsaCompileError::ConflictingParameter`] error, and
+    /// both templates must have been compiled with the same [`EscapePolicy`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let header = Balsa::from_string("<h1>{{ title : string }}</h1>\n").build()?;
+    /// let body = Balsa::from_string("<p>{{ body : string }}</p>").build()?;
+    /// let page = header.concat(&body)?;
+    ///
+    /// let params = BalsaParameters::new().string("title", "Hi").string("body", "Welcome");
+    /// assert_eq!(page.render_html_string(&params)?, "<h1>Hi</h1>\n<p>Welcome</p>");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn concat(&self, other: &Template) -> BalsaResult<Self> {
+        Ok(Self::new(
+            self.compiled_template.concat(&other.compiled_template)?,
+        ))
+    }
+}
+
+/// Parses and compiles a template with the default [`CompileOptions`].
+///
+/// ```rust
+/// # use balsa::*;
+/// let template: Template = "<h1>{{ title : string }}</h1>".parse()?;
+/// # Ok::<(), BalsaError>(())
+/// ```
+impl FromStr for Template {
+    type Err = BalsaError;
+
+    fn from_str(raw_template: &str) -> BalsaResult<Self> {
+        Balsa::from_string(raw_template).build_once()
+    }
+}
+
+impl TryFrom<&str> for Template {
+    type Error = BalsaError;
+
+    fn try_from(raw_template: &str) -> BalsaResult<Self> {
+        raw_template.parse()
+    }
+}
+
+impl<T: AsParameters> TypedTemplate<T> {
+    /// Returns the warnings produced while compiling the template.
+    pub fn warnings(&self) -> &[BalsaWarning] {
+        self.template.warnings()
+    }
+
+    /// Returns a description of the global scope and replacement instructions the compiler
+    /// produced for this template, which is useful for debugging templates.
+    pub fn explain(&self) -> TemplateExplanation {
+        self.template.explain()
+    }
+
+    /// Returns every parameter the template expects. See [`Template::parameters`].
+    pub fn parameters(&self) -> Vec<ParameterInfo> {
+        self.template.parameters()
+    }
+
+    /// Returns a graph of which computed parameters depend on which parameters. See
+    /// [`Template::dependency_graph`].
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        self.template.dependency_graph()
+    }
+
+    /// Returns every use of a message in the template. See [`Template::extract_messages`].
+    pub fn extract_messages(&self) -> Vec<ExtractedMessage> {
+        self.template.extract_messages()
+    }
+
+    /// Renders the template using only its declarations and parameter defaults. See
+    /// [`Template::render_defaults`].
+    pub fn render_defaults(&self) -> BalsaResult<String> {
+        self.template.render_defaults()
+    }
+
+    /// Renders the template in the best available locale. See [`Template::render_localized`].
+    pub fn render_localized(
+        &self,
+        params: &T,
+        accepted_locales: &[&str],
+    ) -> BalsaResult<LocalizedRender> {
+        self.template.render_localized(params, accepted_locales)
+    }
+
+    /// Renders the template, borrowing the raw template if it has no blocks to replace. See
+    /// [`Template::render_cow`].
+    pub fn render_cow(&self, params: &T) -> BalsaResult<Cow<'_, str>> {
+        self.template.render_cow(params)
+    }
+
+    /// Renders the template along with a report of timing and usage metrics. See
+    /// [`Template::render_with_report`].
+    pub fn render_with_report(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<RenderReport> {
+        self.template.render_with_report(params, options)
+    }
+
+    /// Returns a copy of the template in which the parameter `name` defaults to `value`. See
+    /// [`Template::with_default`].
+    pub fn with_default(&self, name: &str, value: BalsaValue) -> BalsaResult<Self> {
+        Ok(Self {
+            template: self.template.with_default(name, value)?,
+            _type: PhantomData,
+        })
+    }
+
+    /// Renders every block which only reads parameters in `static_params`. See
+    /// [`Template::partially_render`].
+    pub fn partially_render<P: AsParameters>(&self, static_params: &P) -> BalsaResult<Self> {
+        Ok(Self {
+            template: self.template.partially_render(static_params)?,
+            _type: PhantomData,
+        })
+    }
 }
 
 impl<T: AsParameters> BalsaTemplate<T> for Template {
-    fn render_html_string(&self, params: &T) -> BalsaResult<String> {
-        let renderer = balsa_renderer::Renderer::new(&self.raw_template, &self.compiled_template);
+    fn render_html_string_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<String> {
+        let renderer = balsa_renderer::Renderer::new(&self.compiled_template);
+        let params = params.as_parameters();
+
+        renderer.render_with_parameters(&params, options)
+    }
+
+    fn render_to_writer_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+        w: &mut dyn io::Write,
+    ) -> BalsaResult<()> {
+        let renderer = balsa_renderer::Renderer::new(&self.compiled_template);
         let params = params.as_parameters();
 
-        renderer.render_with_parameters(&params)
+        renderer.render_to_writer(&params, options, w)
     }
 }
 
-impl<T: AsParameters + Sync + Send> BalsaTemplate<T> for TypedTemplate<T> {
-    fn render_html_string(&self, params: &T) -> BalsaResult<String> {
-        self.template.render_html_string(params)
+impl<T: AsParameters> BalsaTemplate<T> for TypedTemplate<T> {
+    fn render_html_string_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<String> {
+        self.template
+            .render_html_string_with_options(params, options)
+    }
+
+    fn render_to_writer_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+        w: &mut dyn io::Write,
+    ) -> BalsaResult<()> {
+        self.template
+            .render_to_writer_with_options(params, options, w)
     }
 }
 
-impl BalsaBuilder {
+/// Allows templates shared between threads, e.g. in web framework state, to be rendered directly.
+impl<T, B> BalsaTemplate<T> for Arc<B>
+where
+    B: BalsaTemplate<T> + ?Sized,
+{
+    fn render_html_string_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<String> {
+        (**self).render_html_string_with_options(params, options)
+    }
+
+    fn render_to_writer_with_options(
+        &self,
+        params: &T,
+        options: &RenderOptions,
+        w: &mut dyn io::Write,
+    ) -> BalsaResult<()> {
+        (**self).render_to_writer_with_options(params, options, w)
+    }
+}
+
+impl<S: TemplateSource> BalsaBuilder<S> {
     /// Parses and compiles the template, returning a [`Template`] on success which takes any type
     /// implementing [`AsParameters`] as parameters for rendering.
+    ///
+    /// The template is read from its source every time this is called, so the builder can be
+    /// reused to pick up changes to the source.
     pub fn build(&self) -> BalsaResult<Template> {
-        let raw_template = self.template_source.read_template()?;
-        let tokens = balsa_parser::BalsaParser::parse(raw_template.clone())?;
-        let compiled_template = balsa_compiler::Compiler::compile_from_tokens(&tokens)?;
+        self.check_template_size()?;
+        Self::compile(self.template_source.read_template()?, &self.options)
+    }
 
-        Ok(Template {
-            raw_template,
-            compiled_template,
-        })
+    /// Parses and compiles the template like [`BalsaBuilder::build`], but consumes the builder so
+    /// that the raw template is moved out of its source rather than copied.
+    pub fn build_once(self) -> BalsaResult<Template> {
+        self.check_template_size()?;
+        Self::compile(self.template_source.into_template()?, &self.options)
     }
+
+    /// Erases the type of the builder's [`TemplateSource`].
+    pub fn boxed(self) -> BalsaBuilder
+    where
+        S: 'static,
+    {
+        BalsaBuilder {
+            template_source: Box::new(self.template_source),
+            options: self.options,
+        }
+    }
+
+    /// Fails if the source knows the size of its template and it's larger than the maximum
+    /// template size, so that oversized templates are never read.
+    fn check_template_size(&self) -> BalsaResult<()> {
+        if let Some(max_size) = self.options.max_template_size {
+            if let Some(size) = self.template_source.template_size()? {
+                if size > max_size {
+                    return Err(BalsaError::template_too_large(size, max_size));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses and compiles `raw_template` with the provided `options`.
+    fn compile(raw_template: String, options: &CompileOptions) -> BalsaResult<Template> {
+        if let Some(max_size) = options.max_template_size {
+            if raw_template.len() > max_size {
+                return Err(BalsaError::template_too_large(raw_template.len(), max_size));
+            }
+        }
+
+        let (raw_template, converted_crlf) = options.normalize_template(raw_template);
+        let tokens = balsa_parser::BalsaParser::parse(&raw_template, options)?;
+        let mut compiled_template =
+            balsa_compiler::Compiler::compile_from_tokens(&raw_template, &tokens, options)?;
+        compiled_template.restore_crlf = converted_crlf && options.restore_crlf;
+
+        Ok(Template::new(compiled_template))
+    }
+
+    /// Parses and compiles the template, returning a portable artifact which can be loaded with
+    /// [`Template::from_precompiled`] without parsing the template again.
+    pub fn precompile(&self) -> BalsaResult<Vec<u8>> {
+        let template = self.build()?;
+
+        Ok(precompiled::encode(&template.compiled_template))
+    }
+
+    /// Sets the [`CompileOptions`] used to parse, compile and render the template, replacing any
+    /// previously set options.
+    pub fn with_options(mut self, options: CompileOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the maximum depth to which blocks and values may be nested within the template.
+    ///
+    /// Templates nested deeper than this fail to compile with a [`errors::TemplateParseFail::TooDeep`]
+    /// error, which protects services compiling user-supplied templates from overflowing the
+    /// stack. Defaults to 32.
+    pub fn max_nesting_depth(mut self, max_nesting_depth: usize) -> Self {
+        self.options = self.options.max_nesting_depth(max_nesting_depth);
+        self
+    }
+
+    /// Sets the maximum depth to which templates may be included. See
+    /// [`CompileOptions::max_include_depth`].
+    pub fn max_include_depth(mut self, max_include_depth: usize) -> Self {
+        self.options = self.options.max_include_depth(max_include_depth);
+        self
+    }
+
+    /// Sets the maximum size of the template in bytes.
+    ///
+    /// Larger templates fail to compile with a [`errors::TemplateParseFail::TooLarge`] error
+    /// which includes their actual size. Templates read from a file are checked before the file
+    /// is read, which protects services compiling user uploads from loading huge files into
+    /// memory. Unlimited by default.
+    pub fn max_template_size(mut self, max_template_size: usize) -> Self {
+        self.options = self.options.max_template_size(max_template_size);
+        self
+    }
+
+    /// Sets whether a UTF-8 byte order mark at the start of the template is removed before it is
+    /// parsed. See [`CompileOptions::strip_bom`].
+    pub fn strip_bom(mut self, strip_bom: bool) -> Self {
+        self.options = self.options.strip_bom(strip_bom);
+        self
+    }
+
+    /// Sets whether `\r\n` line endings are converted to `\n` before the template is parsed,
+    /// and whether they are restored in the rendered output. See
+    /// [`CompileOptions::normalize_newlines`] and [`CompileOptions::restore_crlf`].
+    pub fn normalize_newlines(mut self, normalize_newlines: bool, restore_crlf: bool) -> Self {
+        self.options = self
+            .options
+            .normalize_newlines(normalize_newlines)
+            .restore_crlf(restore_crlf);
+        self
+    }
+
+    /// Adds a variable named `name` to the global scope of the template before it is compiled,
+    /// so that values known ahead of time (e.g. a base URL) can be referenced by declarations and
+    /// parameter defaults without being passed to every render.
+    ///
+    /// Variables declared in the template shadow globals with the same name.
+    pub fn global(mut self, name: impl Into<String>, value: BalsaValue) -> Self {
+        self.options = self.options.global(name, value);
+        self
+    }
+
+    /// Sets the [`MessageCatalog`] which `{{ t "key" }}` blocks are translated with, so that copy
+    /// can be kept out of the template and its parameters entirely.
+    pub fn catalog(mut self, catalog: MessageCatalog) -> Self {
+        self.options = self.options.catalog(catalog);
+        self
+    }
+
+    /// Sets whether the template is compiled in sandbox mode. See [`CompileOptions::sandboxed`].
+    pub fn sandboxed(mut self, sandboxed: bool) -> Self {
+        self.options = self.options.sandboxed(sandboxed);
+        self
+    }
+
+    /// Sets the [`TemplateLoader`] which the templates of `{{include "..."}}` blocks are loaded
+    /// with. See [`CompileOptions::loader`].
+    pub fn loader(mut self, loader: impl TemplateLoader + 'static) -> Self {
+        self.options = self.options.loader(loader);
+        self
+    }
+
+    /// Registers `filter` as `name`, so that parameters can be passed through it with the
+    /// `filters` option. See [`CompileOptions::filter`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string("<a href=\"/{{ title : string, filters: [\"slugify\"] }}\">")
+    ///     .with_filter("slugify", |v: BalsaValue| match v {
+    ///         BalsaValue::String(s) => Ok(BalsaValue::String(s.to_lowercase().replace(' ', "-"))),
+    ///         _ => Err(BalsaError::filter_failed("expected a string")),
+    ///     })
+    ///     .build()?;
+    ///
+    /// let params = BalsaParameters::new().string("title", "Hello World");
+    /// assert_eq!(template.render_html_string(&params)?, "<a href=\"/hello-world\">");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn with_filter<F>(mut self, name: impl Into<String>, filter: F) -> Self
+    where
+        F: Fn(BalsaValue) -> BalsaResult<BalsaValue> + Send + Sync + 'static,
+    {
+        self.options = self.options.filter(name, filter);
+        self
+    }
+
+    /// Registers `filter` as `name` like [`BalsaBuilder::with_filter`], marking it as unsafe so that
+    /// sandboxed templates can't use it. See [`CompileOptions::unsafe_filter`].
+    pub fn with_unsafe_filter<F>(mut self, name: impl Into<String>, filter: F) -> Self
+    where
+        F: Fn(BalsaValue) -> BalsaResult<BalsaValue> + Send + Sync + 'static,
+    {
+        self.options = self.options.unsafe_filter(name, filter);
+        self
+    }
+
     /// Parses and compiles the template, returning a [`TypedTemplate<T>`] on success which
     /// requires the specified type (which must implement [`AsParameters`]) as parameters for
     /// rendering.
     pub fn build_struct<T: AsParameters>(&self) -> BalsaResult<TypedTemplate<T>> {
         Ok(TypedTemplate {
             template: self.build()?,
-            _type: PhantomData::default(),
+            _type: PhantomData,
         })
     }
 }
 
 impl Balsa {
     /// Creates a new [`BalsaBuilder`] from a file using the provided path.
-    pub fn from_file<P: AsRef<PathBuf>>(path: P) -> BalsaBuilder {
+    pub fn from_file<P: AsRef<PathBuf>>(path: P) -> BalsaBuilder<FileSource> {
         BalsaBuilder {
-            template_source: Box::new(FileSource {
+            template_source: FileSource {
                 path: path.as_ref().clone(),
-            }),
+            },
+            options: CompileOptions::default(),
         }
     }
+    /// Reads the template at the provided path with [`tokio::fs`], returning a [`BalsaBuilder`]
+    /// once the file has been read so that loading the template never blocks the executor.
+    #[cfg(feature = "tokio")]
+    pub async fn from_file_async(
+        path: impl AsRef<std::path::Path>,
+    ) -> BalsaResult<BalsaBuilder<StringSource>> {
+        let raw_template = tokio::fs::read_to_string(path)
+            .await
+            .map_err(BalsaError::read_template_error)?;
+
+        Ok(Self::from_string(raw_template))
+    }
+
+    /// Parses and compiles the provided template with the default [`CompileOptions`], returning
+    /// a portable artifact which can be loaded with [`Template::from_precompiled`].
+    pub fn precompile(raw_template: impl Into<String>) -> BalsaResult<Vec<u8>> {
+        let template = Self::from_string(raw_template).build_once()?;
+
+        Ok(precompiled::encode(&template.compiled_template))
+    }
+
     /// Creates a new [`BalsaBuilder`] from the provided template as a string.
-    pub fn from_string(raw_template: impl Into<String>) -> BalsaBuilder {
+    pub fn from_string(raw_template: impl Into<String>) -> BalsaBuilder<StringSource> {
         BalsaBuilder {
-            template_source: Box::new(StringSource {
+            template_source: StringSource {
                 raw_template: raw_template.into(),
-            }),
+            },
+            options: CompileOptions::default(),
         }
     }
 }
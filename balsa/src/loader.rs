@@ -0,0 +1,144 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    balsa_compiler::CompiledTemplate, balsa_types::path_root, errors::BalsaError, parser::Position,
+    BalsaParameters, BalsaResult, BalsaValue, RenderOptions,
+};
+
+/// The future returned by [`DataLoader::load`].
+pub type LoadFuture<'a> = Pin<Box<dyn Future<Output = LoadResult> + Send + 'a>>;
+
+type LoadResult = Result<Option<BalsaValue>, String>;
+
+/// A source of parameter values which are fetched lazily, such as a database or an API.
+///
+/// Passed to [`crate::Template::render_with_loader`], which asks the loader for every parameter
+/// the template reads that wasn't passed to the render.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// struct Profiles;
+///
+/// impl DataLoader for Profiles {
+///     fn load<'a>(&'a self, name: &'a str) -> LoadFuture<'a> {
+///         Box::pin(async move {
+///             match name {
+///                 "userName" => Ok(Some(BalsaValue::String("Ada".to_string()))),
+///                 _ => Ok(None),
+///             }
+///         })
+///     }
+/// }
+/// ```
+pub trait DataLoader: Send + Sync {
+    /// Loads the value of the parameter `name`, returning `Ok(None)` if it has no value so that
+    /// its default value is used instead. Errors are reported as
+    /// [`crate::errors::BalsaRenderError::LoadFailed`].
+    fn load<'a>(&'a self, name: &'a str) -> LoadFuture<'a>;
+}
+
+/// Returns the parameters read by `compiled_template` which `parameters` has no value for,
+/// along with the position of the first block reading each of them.
+///
+/// Parameters read by a path like `theme.primaryColor` are loaded whole, i.e. `theme`. The
+/// arguments of `messageformat` patterns are only known when rendering, so they are never
+/// loaded.
+pub(crate) fn absent_parameters(
+    compiled_template: &CompiledTemplate,
+    parameters: &BalsaParameters,
+    options: &RenderOptions,
+) -> Vec<(String, Position)> {
+    let rendered = compiled_template.replacements.iter().filter_map(|r| {
+        Some((
+            path_root(&r.replace_with.parameter()?.variable_name),
+            r.start_pos,
+        ))
+    });
+    let computed_inputs = compiled_template.computed.iter().flat_map(|c| {
+        c.expression
+            .inputs()
+            .into_iter()
+            .map(move |input| (input, c.source_start_pos))
+    });
+
+    let mut absent: Vec<(String, Position)> = Vec::new();
+    for (name, pos) in rendered.chain(computed_inputs) {
+        if compiled_template.computed(name).is_none()
+            && parameters.get_localized(name, &options.locale).is_none()
+            && absent.iter().all(|(n, _)| n != name)
+        {
+            absent.push((name.to_string(), pos));
+        }
+    }
+
+    absent.sort_by_key(|(_, pos)| *pos);
+
+    absent
+}
+
+/// Loads every parameter in `absent` with `loader` concurrently, returning `parameters` with the
+/// loaded values added.
+pub(crate) async fn load_parameters<L: DataLoader + ?Sized>(
+    loader: &L,
+    parameters: &BalsaParameters,
+    absent: &[(String, Position)],
+) -> BalsaResult<BalsaParameters> {
+    let loads = JoinAll {
+        futures: absent
+            .iter()
+            .map(|(name, _)| Some(loader.load(name)))
+            .collect(),
+        results: absent.iter().map(|_| None).collect(),
+    };
+
+    let mut loaded = parameters.clone();
+    for ((name, pos), result) in absent.iter().zip(loads.await) {
+        match result {
+            Ok(Some(value)) => loaded = loaded.insert(name.clone(), value),
+            Ok(None) => {}
+            Err(reason) => return Err(BalsaError::load_failed(*pos, name.clone(), reason)),
+        }
+    }
+
+    Ok(loaded)
+}
+
+/// Polls several loads at once, resolving to their results in order once all of them are done.
+struct JoinAll<'a> {
+    /// The loads which are still pending.
+    futures: Vec<Option<LoadFuture<'a>>>,
+    results: Vec<Option<LoadResult>>,
+}
+
+impl Future for JoinAll<'_> {
+    type Output = Vec<LoadResult>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        for (future, result) in this.futures.iter_mut().zip(&mut this.results) {
+            if let Some(f) = future {
+                if let Poll::Ready(loaded) = f.as_mut().poll(cx) {
+                    *result = Some(loaded);
+                    *future = None;
+                }
+            }
+        }
+
+        if this.futures.iter().any(Option::is_some) {
+            return Poll::Pending;
+        }
+
+        Poll::Ready(
+            this.results
+                .iter_mut()
+                .map(|r| r.take().expect("every load has finished"))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,270 @@
+//! Contains a parser for the subset of ICU MessageFormat accepted by `messageformat` parameters.
+//!
+//! Patterns are made up of text and arguments:
+//! - `{name}` is replaced with the value of the parameter `name`.
+//! - `{name, plural, =0 {none} one {# item} other {# items}}` picks a case by the integer
+//!   parameter `name`, either by its exact value or by its plural category. `#` inside a case is
+//!   replaced with the count.
+//! - `{name, select, admin {Administrator} other {Member}}` picks a case by the string parameter
+//!   `name`.
+//!
+//! Plural and select arguments must have an `other` case. A `'` before `{`, `}` or `#` quotes
+//! text up to the next `'`, and `''` is a literal `'`.
+
+use std::{iter::Peekable, str::Chars};
+
+use crate::PluralRule;
+
+/// The plural categories which may be used as plural selectors.
+const PLURAL_CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// A part of a parsed message pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MessagePart {
+    /// Text which is written as-is.
+    Text(String),
+    /// The value of a parameter.
+    Argument(String),
+    /// The count of the innermost plural argument, written as `#`.
+    Count,
+    /// A case picked by the value of an integer parameter.
+    Plural {
+        name: String,
+        cases: Vec<(String, Vec<MessagePart>)>,
+    },
+    /// A case picked by the value of a string parameter.
+    Select {
+        name: String,
+        cases: Vec<(String, Vec<MessagePart>)>,
+    },
+}
+
+/// Parses a message pattern, returning `None` if it is malformed.
+pub(crate) fn parse(pattern: &str) -> Option<Vec<MessagePart>> {
+    let mut chars = pattern.chars().peekable();
+    let parts = parse_parts(&mut chars, false)?;
+
+    chars.peek().is_none().then_some(parts)
+}
+
+/// Picks the case of a plural argument for `count`, preferring an exact `=n` match over the
+/// count's plural category under `rule`.
+pub(crate) fn plural_case(
+    cases: &[(String, Vec<MessagePart>)],
+    count: i64,
+    rule: PluralRule,
+) -> &[MessagePart] {
+    let exact = format!("={}", count);
+    let category = rule.category(count);
+
+    cases
+        .iter()
+        .find(|(selector, _)| *selector == exact)
+        .or_else(|| cases.iter().find(|(selector, _)| selector == category))
+        .or_else(|| cases.iter().find(|(selector, _)| selector == "other"))
+        .map_or(&[], |(_, parts)| parts)
+}
+
+/// Picks the case of a select argument for `value`, falling back to the `other` case.
+pub(crate) fn select_case<'a>(
+    cases: &'a [(String, Vec<MessagePart>)],
+    value: &str,
+) -> &'a [MessagePart] {
+    cases
+        .iter()
+        .find(|(selector, _)| selector == value)
+        .or_else(|| cases.iter().find(|(selector, _)| selector == "other"))
+        .map_or(&[], |(_, parts)| parts)
+}
+
+/// Parses parts up to the end of the pattern or an unmatched `}`, which is left unconsumed.
+fn parse_parts(chars: &mut Peekable<Chars>, in_plural: bool) -> Option<Vec<MessagePart>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '}' => break,
+            '{' => {
+                flush_text(&mut parts, &mut text);
+                parts.push(parse_argument(chars, in_plural)?);
+            }
+            '#' if in_plural => {
+                chars.next();
+                flush_text(&mut parts, &mut text);
+                parts.push(MessagePart::Count);
+            }
+            '\'' => {
+                chars.next();
+                parse_quoted(chars, &mut text);
+            }
+            c => {
+                chars.next();
+                text.push(c);
+            }
+        }
+    }
+
+    flush_text(&mut parts, &mut text);
+    Some(parts)
+}
+
+/// Parses the text following a `'`, pushing it onto `text`.
+fn parse_quoted(chars: &mut Peekable<Chars>, text: &mut String) {
+    match chars.peek() {
+        Some('\'') => {
+            chars.next();
+            text.push('\'');
+        }
+        Some('{' | '}' | '#') => {
+            while let Some(c) = chars.next() {
+                if c != '\'' {
+                    text.push(c);
+                } else if chars.next_if_eq(&'\'').is_some() {
+                    text.push('\'');
+                } else {
+                    break;
+                }
+            }
+        }
+        _ => text.push('\''),
+    }
+}
+
+/// Parses an argument, starting at its opening `{`.
+fn parse_argument(chars: &mut Peekable<Chars>, in_plural: bool) -> Option<MessagePart> {
+    chars.next();
+    skip_ws(chars);
+    let name = parse_word(chars)?;
+    skip_ws(chars);
+
+    if chars.next_if_eq(&'}').is_some() {
+        return Some(MessagePart::Argument(name));
+    }
+
+    chars.next_if_eq(&',')?;
+    skip_ws(chars);
+    let kind = parse_word(chars)?;
+    skip_ws(chars);
+    chars.next_if_eq(&',')?;
+
+    let is_plural = match kind.as_str() {
+        "plural" => true,
+        "select" => false,
+        _ => return None,
+    };
+
+    let mut cases = Vec::new();
+    loop {
+        skip_ws(chars);
+        if chars.next_if_eq(&'}').is_some() {
+            break;
+        }
+
+        let selector = parse_word(chars)?;
+        let valid = if is_plural {
+            match selector.strip_prefix('=') {
+                Some(n) => n.parse::<i64>().is_ok(),
+                None => PLURAL_CATEGORIES.contains(&selector.as_str()),
+            }
+        } else {
+            true
+        };
+        if !valid {
+            return None;
+        }
+
+        skip_ws(chars);
+        chars.next_if_eq(&'{')?;
+        let parts = parse_parts(chars, is_plural || in_plural)?;
+        chars.next_if_eq(&'}')?;
+
+        cases.push((selector, parts));
+    }
+
+    if !cases.iter().any(|(selector, _)| selector == "other") {
+        return None;
+    }
+
+    Some(if is_plural {
+        MessagePart::Plural { name, cases }
+    } else {
+        MessagePart::Select { name, cases }
+    })
+}
+
+/// Parses a non-empty argument name, keyword or selector.
+fn parse_word(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut word = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '=')) {
+        word.push(c);
+    }
+
+    (!word.is_empty()).then_some(word)
+}
+
+fn skip_ws(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn flush_text(parts: &mut Vec<MessagePart>, text: &mut String) {
+    if !text.is_empty() {
+        parts.push(MessagePart::Text(std::mem::take(text)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_format_parse() {
+        let parts = parse("{name} has {count, plural, =0 {no items} one {# item} other {# items}}")
+            .expect("Pattern should be parsed");
+
+        assert_eq!(
+            parts,
+            vec![
+                MessagePart::Argument("name".to_string()),
+                MessagePart::Text(" has ".to_string()),
+                MessagePart::Plural {
+                    name: "count".to_string(),
+                    cases: vec![
+                        (
+                            "=0".to_string(),
+                            vec![MessagePart::Text("no items".to_string())]
+                        ),
+                        (
+                            "one".to_string(),
+                            vec![MessagePart::Count, MessagePart::Text(" item".to_string())]
+                        ),
+                        (
+                            "other".to_string(),
+                            vec![MessagePart::Count, MessagePart::Text(" items".to_string())]
+                        ),
+                    ],
+                },
+            ]
+        );
+
+        assert_eq!(
+            parse("it''s '{literal}' #"),
+            Some(vec![MessagePart::Text("it's {literal} #".to_string())])
+        );
+
+        for malformed in [
+            "{name",
+            "unmatched }",
+            "{count, plural, one {# item}}",
+            "{count, plural, some {x} other {y}}",
+            "{count, number}",
+        ] {
+            assert_eq!(
+                parse(malformed),
+                None,
+                "Pattern `{}` should be rejected",
+                malformed
+            );
+        }
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use crate::{
+    balsa_parser::DEFAULT_MAX_NESTING_DEPTH, composition::SharedTemplateLoader, filters::Filters,
+    BalsaResult, BalsaValue, Locale, MessageCatalog, PluralRule, TemplateLoader,
+};
+
+/// Options which control how a template is parsed, compiled and rendered.
+///
+/// Built with the builder pattern and passed to [`crate::BalsaBuilder::with_options`].
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let options = CompileOptions::new()
+///     .strict_types(true)
+///     .delimiters("[[", "]]")
+///     .allow_parameter_option("friendlyName")
+///     .escape_policy(EscapePolicy::Html)
+///     .max_template_size(64 * 1024);
+///
+/// let template = Balsa::from_string("<h1>[[ title : string, friendlyName: \"Title\" ]]</h1>")
+///     .with_options(options)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOptions {
+    pub(crate) strict_types: bool,
+    pub(crate) strict_blocks: bool,
+    pub(crate) delimiters: Delimiters,
+    pub(crate) allowed_parameter_options: Vec<String>,
+    pub(crate) escape_policy: EscapePolicy,
+    pub(crate) max_template_size: Option<usize>,
+    pub(crate) strip_bom: bool,
+    pub(crate) normalize_newlines: bool,
+    pub(crate) restore_crlf: bool,
+    pub(crate) max_nesting_depth: usize,
+    pub(crate) max_include_depth: Option<usize>,
+    pub(crate) globals: HashMap<String, BalsaValue>,
+    pub(crate) catalog: MessageCatalog,
+    pub(crate) allow_missing_messages: bool,
+    pub(crate) sandboxed: bool,
+    pub(crate) plural_rule: PluralRule,
+    pub(crate) loader: Option<SharedTemplateLoader>,
+    pub(crate) filters: Filters,
+}
+
+/// Options which control how a compiled template is rendered.
+///
+/// Built with the builder pattern and passed to
+/// [`crate::BalsaTemplate::render_html_string_with_options`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub(crate) locale: Locale,
+    pub(crate) bidi_isolation: bool,
+    pub(crate) hydration_markers: HydrationMarkers,
+    pub(crate) max_output_size: Option<usize>,
+    pub(crate) max_parameters: Option<usize>,
+    pub(crate) max_value_size: Option<usize>,
+    pub(crate) max_loop_iterations: Option<usize>,
+    pub(crate) max_include_depth: Option<usize>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) slots: HashMap<String, String>,
+}
+
+/// A handle which aborts the renders it is passed to through [`RenderOptions::cancellation`].
+///
+/// Clones share the same state, so a request handler can keep one clone and cancel a render
+/// running on another thread, e.g. when the client disconnects.
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// The strings which open and close a block in a template.
+///
+/// Declaration blocks are opened with the opening delimiter followed by `@`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Delimiters {
+    pub(crate) open: String,
+    pub(crate) close: String,
+}
+
+/// How parameter values are escaped when they are written to the rendered output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EscapePolicy {
+    /// Values are written to the output as-is.
+    #[default]
+    None,
+    /// The characters `&`, `<`, `>`, `"` and `'` are replaced with HTML entities.
+    Html,
+}
+
+/// How the values of parameters are marked in the rendered output, so that a client-side script
+/// can find and update them without reloading the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HydrationMarkers {
+    /// Values are written without markers.
+    #[default]
+    None,
+    /// Values are wrapped in a `<span data-balsa="name">` element.
+    Span,
+    /// Values are wrapped in `<!--balsa:name-->` and `<!--/balsa-->` comments, which leave the
+    /// structure of the document unchanged.
+    Comment,
+}
+
+impl CompileOptions {
+    /// Creates a new [`CompileOptions`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether values written in the template must already be of the type they are used
+    /// as, rather than being implicitly cast (e.g. a string literal used as a color). Defaults to
+    /// `false`.
+    pub fn strict_types(mut self, strict_types: bool) -> Self {
+        self.strict_types = strict_types;
+        self
+    }
+
+    /// Sets whether every opening delimiter must start a valid block. Defaults to `false`, in
+    /// which case text which doesn't look like a block (e.g. `{{}}` in inline JavaScript) is
+    /// written to the output as-is.
+    ///
+    /// With strict blocks enabled such text fails to compile with a
+    /// [`crate::errors::TemplateParseFail::MalformedBlock`] error, so that typos can't render
+    /// literally into production pages. Text which starts like a parameter or declaration block
+    /// but is malformed, e.g. the unterminated `{{ title : string }`, always fails to compile.
+    pub fn strict_blocks(mut self, strict_blocks: bool) -> Self {
+        self.strict_blocks = strict_blocks;
+        self
+    }
+
+    /// Sets the strings which open and close a block. Defaults to `{{` and `}}`.
+    ///
+    /// Templates fail to compile with a [`crate::errors::TemplateParseFail::EmptyDelimiter`]
+    /// error if either delimiter is empty.
+    pub fn delimiters(mut self, open: impl Into<String>, close: impl Into<String>) -> Self {
+        self.delimiters = Delimiters {
+            open: open.into(),
+            close: close.into(),
+        };
+        self
+    }
+
+    /// Allows parameter blocks to contain the option `name` in addition to the options Balsa
+    /// understands itself. Such options are accepted and otherwise ignored by the compiler,
+    /// which lets templates carry metadata such as `friendlyName` for other tools.
+    pub fn allow_parameter_option(mut self, name: impl Into<String>) -> Self {
+        self.allowed_parameter_options.push(name.into());
+        self
+    }
+
+    /// Sets how parameter values are escaped when rendering. Defaults to [`EscapePolicy::None`].
+    pub fn escape_policy(mut self, escape_policy: EscapePolicy) -> Self {
+        self.escape_policy = escape_policy;
+        self
+    }
+
+    /// Sets the maximum size of a template in bytes. Larger templates fail to compile with a
+    /// [`crate::errors::TemplateParseFail::TooLarge`] error before they are parsed. Unlimited by
+    /// default.
+    pub fn max_template_size(mut self, max_template_size: usize) -> Self {
+        self.max_template_size = Some(max_template_size);
+        self
+    }
+
+    /// Sets whether a UTF-8 byte order mark at the start of the template is removed before it is
+    /// parsed, so that it is neither rendered nor counted in positions. Defaults to `false`.
+    pub fn strip_bom(mut self, strip_bom: bool) -> Self {
+        self.strip_bom = strip_bom;
+        self
+    }
+
+    /// Sets whether `\r\n` line endings are converted to `\n` before the template is parsed, so
+    /// that positions and string values are the same whether or not the template was saved on
+    /// Windows. Defaults to `false`.
+    pub fn normalize_newlines(mut self, normalize_newlines: bool) -> Self {
+        self.normalize_newlines = normalize_newlines;
+        self
+    }
+
+    /// Sets whether templates which had `\r\n` line endings before they were normalized with
+    /// [`CompileOptions::normalize_newlines`] are rendered with `\r\n` line endings, including
+    /// in parameter values. Defaults to `false`.
+    pub fn restore_crlf(mut self, restore_crlf: bool) -> Self {
+        self.restore_crlf = restore_crlf;
+        self
+    }
+
+    /// Sets the maximum depth to which blocks and values may be nested within the template.
+    /// Defaults to 32.
+    pub fn max_nesting_depth(mut self, max_nesting_depth: usize) -> Self {
+        self.max_nesting_depth = max_nesting_depth;
+        self
+    }
+
+    /// Sets the maximum depth to which `include` blocks may include templates which include other
+    /// templates. Templates included deeper than this fail to compile with a
+    /// [`crate::errors::LimitExceeded::IncludeDepth`] error. Unlimited by default.
+    pub fn max_include_depth(mut self, max_include_depth: usize) -> Self {
+        self.max_include_depth = Some(max_include_depth);
+        self
+    }
+
+    /// Adds a variable named `name` to the global scope of the template before it is compiled,
+    /// as if it had been declared at the very start of the template.
+    pub fn global(mut self, name: impl Into<String>, value: BalsaValue) -> Self {
+        self.globals.insert(name.into(), value);
+        self
+    }
+
+    /// Sets the [`MessageCatalog`] which `t` helper blocks look their messages up in. Templates
+    /// using the `t` helper fail to compile if a message is missing from the catalog.
+    pub fn catalog(mut self, catalog: MessageCatalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    /// Sets whether `t` helper blocks may reference messages which are missing from the
+    /// catalog, in which case the message's ID is rendered in its place. This lets messages be
+    /// extracted with [`crate::Template::extract_messages`] before they are translated. Defaults
+    /// to `false`.
+    pub fn allow_missing_messages(mut self, allow_missing_messages: bool) -> Self {
+        self.allow_missing_messages = allow_missing_messages;
+        self
+    }
+
+    /// Sets whether the template is compiled in sandbox mode, which is required for templates
+    /// written by untrusted users such as uploaded themes. Defaults to `false`.
+    ///
+    /// Sandboxed templates fail to compile with a [`crate::errors::SandboxViolation`] error if
+    /// they use a feature which could compromise the page they are rendered into, such as
+    /// opting parameters out of escaping with `escape: false`, including other templates, or
+    /// passing parameters through a filter registered with [`CompileOptions::unsafe_filter`].
+    pub fn sandboxed(mut self, sandboxed: bool) -> Self {
+        self.sandboxed = sandboxed;
+        self
+    }
+
+    /// Sets the [`PluralRule`] which `plural` helper blocks choose their form with. Defaults to
+    /// [`PluralRule::OneOther`].
+    pub fn plural_rule(mut self, plural_rule: PluralRule) -> Self {
+        self.plural_rule = plural_rule;
+        self
+    }
+
+    /// Sets the [`TemplateLoader`] which the templates of `{{include "..."}}` blocks are loaded
+    /// with. Templates with `include` blocks fail to compile if no loader is set.
+    ///
+    /// The loader is shared by clones of the options, so every template compiled with them
+    /// includes templates from the same place.
+    pub fn loader(mut self, loader: impl TemplateLoader + 'static) -> Self {
+        self.loader = Some(SharedTemplateLoader(Arc::new(loader)));
+        self
+    }
+
+    /// Registers `filter` as `name`, replacing any filter previously registered as `name`.
+    ///
+    /// Parameters are passed through filters with the `filters` option, e.g.
+    /// `{{ title : string, filters: ["slugify", "upper"] }}`, which applies them in order when
+    /// rendering, before the value is escaped. Templates referencing a filter which isn't
+    /// registered fail to compile with a [`crate::errors::BalsaCompileError::UndefinedFilter`]
+    /// error. Filters report failures with [`crate::BalsaError::filter_failed`].
+    pub fn filter<F>(mut self, name: impl Into<String>, filter: F) -> Self
+    where
+        F: Fn(BalsaValue) -> BalsaResult<BalsaValue> + Send + Sync + 'static,
+    {
+        self.filters.insert(name.into(), Arc::new(filter));
+        self
+    }
+
+    /// Registers `filter` as `name` like [`CompileOptions::filter`], marking it as unsafe.
+    ///
+    /// Unsafe filters are those whose output could compromise the page it is rendered into, such
+    /// as a filter which renders Markdown to HTML. Sandboxed templates which use them fail to
+    /// compile with a [`crate::errors::SandboxViolation`] error. See
+    /// [`CompileOptions::sandboxed`].
+    pub fn unsafe_filter<F>(mut self, name: impl Into<String>, filter: F) -> Self
+    where
+        F: Fn(BalsaValue) -> BalsaResult<BalsaValue> + Send + Sync + 'static,
+    {
+        self.filters
+            .insert_with_safety(name.into(), Arc::new(filter), true);
+        self
+    }
+}
+
+impl RenderOptions {
+    /// Creates a new [`RenderOptions`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the [`Locale`] which integer, float and date-time values are written in. Defaults to
+    /// [`Locale::default`], which writes numbers without any grouping and with a `.` decimal
+    /// separator, and date-times in RFC 3339 format.
+    ///
+    /// The locale also selects the layers added with [`crate::BalsaParameters::localized`].
+    pub fn locale(mut self, locale: impl Into<Locale>) -> Self {
+        self.locale = locale.into();
+        self
+    }
+
+    /// Sets whether the values of string parameters are isolated from the surrounding text, so
+    /// that right-to-left user content can't scramble the layout of left-to-right text around
+    /// it (or vice versa). Defaults to `false`.
+    ///
+    /// With [`EscapePolicy::Html`] values are wrapped in a `<bdi>` element, so string parameters
+    /// must not be used inside attributes. Otherwise they are wrapped in the Unicode first strong
+    /// isolate (U+2068) and pop directional isolate (U+2069) characters.
+    pub fn bidi_isolation(mut self, bidi_isolation: bool) -> Self {
+        self.bidi_isolation = bidi_isolation;
+        self
+    }
+
+    /// Sets how the values of parameter blocks and `plural` helpers are marked with the name of
+    /// their parameter, so that a client-side script can re-bind and update them, e.g. in a live
+    /// preview. Defaults to [`HydrationMarkers::None`].
+    ///
+    /// Markers are HTML, so parameters must not be used inside attributes or `<script>` elements
+    /// when they are enabled. Computed parameters are marked like any other parameter.
+    pub fn hydration_markers(mut self, hydration_markers: HydrationMarkers) -> Self {
+        self.hydration_markers = hydration_markers;
+        self
+    }
+
+    /// Sets the maximum size in bytes of the rendered output. Renders which would produce more
+    /// output fail with a [`crate::errors::LimitExceeded::OutputSize`] error as soon as the limit
+    /// is crossed, which keeps untrusted templates and parameters from exhausting memory.
+    /// Unlimited by default.
+    pub fn max_output_size(mut self, max_output_size: usize) -> Self {
+        self.max_output_size = Some(max_output_size);
+        self
+    }
+
+    /// Sets the maximum number of parameters a render may be passed, counting the parameters of
+    /// every [`crate::BalsaParameters::localized`] layer. Renders passed more fail with a
+    /// [`crate::errors::LimitExceeded::ParameterCount`] error before anything is rendered.
+    /// Unlimited by default.
+    pub fn max_parameters(mut self, max_parameters: usize) -> Self {
+        self.max_parameters = Some(max_parameters);
+        self
+    }
+
+    /// Sets the maximum size in bytes of each parameter value, e.g. the length of a string.
+    /// Renders passed a larger value fail with a [`crate::errors::LimitExceeded::ValueSize`]
+    /// error before anything is rendered. Unlimited by default.
+    pub fn max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = Some(max_value_size);
+        self
+    }
+
+    /// Sets the maximum number of items the `#each` sections of a render may repeat their body
+    /// for, counting the items of every section together. Renders which would repeat them more
+    /// often fail with a [`crate::errors::LimitExceeded::LoopIterations`] error as soon as the
+    /// limit is crossed. Unlimited by default.
+    pub fn max_loop_iterations(mut self, max_loop_iterations: usize) -> Self {
+        self.max_loop_iterations = Some(max_loop_iterations);
+        self
+    }
+
+    /// Sets the maximum depth to which templates included in `#each` sections may be rendered
+    /// within each other, such as a menu which includes itself for each level of submenus.
+    /// Renders which would nest them deeper fail with a
+    /// [`crate::errors::LimitExceeded::IncludeDepth`] error. Defaults to 32.
+    pub fn max_include_depth(mut self, max_include_depth: usize) -> Self {
+        self.max_include_depth = Some(max_include_depth);
+        self
+    }
+
+    /// Sets the point in time after which the render is aborted with a
+    /// [`crate::errors::RenderAborted::DeadlineExceeded`] error. The deadline is checked before
+    /// each block is rendered. No deadline by default.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets a [`CancellationToken`] which aborts the render with a
+    /// [`crate::errors::RenderAborted::Cancelled`] error once it is cancelled. The token is
+    /// checked before each block is rendered.
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Fills the slot `name`, i.e. `{{#slot "sidebar"}}`, with `html`, which is written to the
+    /// output as-is. This lets the application place content such as analytics snippets where
+    /// the template's author chooses, without the author being able to edit it. Slots which
+    /// aren't filled render nothing.
+    pub fn slot(mut self, name: impl Into<String>, html: impl Into<String>) -> Self {
+        self.slots.insert(name.into(), html.into());
+        self
+    }
+}
+
+impl CancellationToken {
+    /// Creates a new [`CancellationToken`] which has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every render using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether [`CancellationToken::cancel`] has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Tokens are equal if they are clones of each other.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+impl CompileOptions {
+    /// Removes the byte order mark from `raw_template` and converts its line endings as
+    /// configured, returning it along with whether any `\r\n` line endings were converted.
+    pub(crate) fn normalize_template(&self, mut raw_template: String) -> (String, bool) {
+        if self.strip_bom && raw_template.starts_with('\u{feff}') {
+            raw_template.drain(..'\u{feff}'.len_utf8());
+        }
+
+        if self.normalize_newlines && raw_template.contains("\r\n") {
+            return (raw_template.replace("\r\n", "\n"), true);
+        }
+
+        (raw_template, false)
+    }
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            strict_types: false,
+            strict_blocks: false,
+            delimiters: Delimiters::default(),
+            allowed_parameter_options: Vec::new(),
+            escape_policy: EscapePolicy::default(),
+            max_template_size: None,
+            strip_bom: false,
+            normalize_newlines: false,
+            restore_crlf: false,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            max_include_depth: None,
+            globals: HashMap::new(),
+            catalog: MessageCatalog::default(),
+            allow_missing_messages: false,
+            sandboxed: false,
+            plural_rule: PluralRule::default(),
+            loader: None,
+            filters: Filters::default(),
+        }
+    }
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self {
+            open: "{{".to_string(),
+            close: "}}".to_string(),
+        }
+    }
+}
@@ -1,2 +1,33 @@
-/// Specifies a default value for a parameter block.
+/// Specifies a default value for a parameter block, which is either a literal or the name of a
+/// declared variable, i.e. `defaultValue: defaultSubtitle`.
 pub(crate) const DEFAULT_VALUE: &str = "defaultValue";
+/// Specifies the direction the value of a parameter block is written in.
+pub(crate) const DIRECTION: &str = "direction";
+/// Specifies whether the value of a parameter block is escaped.
+pub(crate) const ESCAPE: &str = "escape";
+/// Specifies the fewest items an array parameter may have.
+pub(crate) const MIN_ITEMS: &str = "minItems";
+/// Specifies the most items an array parameter may have.
+pub(crate) const MAX_ITEMS: &str = "maxItems";
+/// Specifies what a single item of an array parameter is called.
+pub(crate) const ITEM_LABEL: &str = "itemLabel";
+/// Specifies the values a parameter may have, as a list like `["left", "right"]`.
+pub(crate) const ONE_OF: &str = "oneOf";
+/// Specifies whether a parameter may be left out of a render, in which case it is left empty.
+pub(crate) const OPTIONAL: &str = "optional";
+/// Specifies the text written in place of an optional parameter which was left out.
+pub(crate) const PLACEHOLDER: &str = "placeholder";
+/// Specifies the filters the value of a parameter is passed through, as a list like
+/// `["slugify", "upper"]` or the name of a single filter.
+pub(crate) const FILTERS: &str = "filters";
+/// Specifies whether a URL parameter only accepts URLs which are safe to link to.
+pub(crate) const SAFE: &str = "safe";
+/// Specifies the `strftime`-style format a date-time parameter is written with.
+#[cfg(feature = "chrono")]
+pub(crate) const FORMAT: &str = "format";
+/// Specifies the MIME types an image parameter accepts, separated by commas.
+pub(crate) const ACCEPT: &str = "accept";
+/// Specifies the widest an image parameter may be, in pixels.
+pub(crate) const MAX_WIDTH: &str = "maxWidth";
+/// Specifies the tallest an image parameter may be, in pixels.
+pub(crate) const MAX_HEIGHT: &str = "maxHeight";
@@ -1,20 +1,29 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+};
 
-use crate::{balsa_types::BalsaValue, BalsaResult};
+use crate::{
+    balsa_types::{lookup_path, Array, BalsaType, BalsaValue, Currency, Dictionary, Image},
+    errors::LimitExceeded,
+    i18n::normalize_tag,
+    Locale,
+};
 
 /// A struct used for generating a hashmap of parameters using
 /// the builder pattern.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct BalsaParameters {
     parameters: HashMap<String, BalsaValue>,
+    /// Parameters which override these ones when rendering in a locale, keyed by normalized
+    /// language tag.
+    localized: HashMap<String, BalsaParameters>,
 }
 
 impl BalsaParameters {
     /// Creates a new empty parameter list.
     pub fn new() -> Self {
-        Self {
-            parameters: HashMap::new(),
-        }
+        Self::default()
     }
 
     /// Appends a String value to the parameters list.
@@ -37,18 +46,410 @@ impl BalsaParameters {
         self.insert(key, BalsaValue::Float(value.into()))
     }
 
+    /// Appends a boolean value to the parameters list, such as the condition of an `{{if}}`
+    /// block.
+    pub fn bool(&self, key: impl Into<String>, value: bool) -> Self {
+        self.insert(key, BalsaValue::Boolean(value))
+    }
+
+    /// Appends an amount of money in the currency with the ISO 4217 `code` to the parameters
+    /// list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `amount` is not finite or `code` is not a valid currency code. See
+    /// [`Currency::new`].
+    pub fn currency(&self, key: impl Into<String>, amount: f64, code: impl Into<String>) -> Self {
+        self.insert(key, BalsaValue::Currency(Currency::new(amount, code)))
+    }
+
+    /// Appends an image to the parameters list.
+    pub fn image(&self, key: impl Into<String>, value: Image) -> Self {
+        self.insert(key, BalsaValue::Image(value))
+    }
+
+    /// Appends an absolute or relative URL to the parameters list, which is checked when it is
+    /// rendered.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string("<a href=\"{{ link : url, safe: true }}\">Home</a>")
+    ///     .build()?;
+    ///
+    /// let params = BalsaParameters::new().url("link", "https://example.com");
+    /// assert_eq!(
+    ///     template.render_html_string(&params)?,
+    ///     "<a href=\"https://example.com\">Home</a>"
+    /// );
+    ///
+    /// let params = BalsaParameters::new().url("link", "javascript:alert(1)");
+    /// assert!(template.render_html_string(&params).is_err());
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn url(&self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(key, BalsaValue::Url(value.into()))
+    }
+
+    /// Appends a date and time to the parameters list, which is written in RFC 3339 format unless
+    /// the parameter block gives a `format`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// use chrono::{TimeZone, Utc};
+    ///
+    /// let template =
+    ///     Balsa::from_string("<time>{{ published : datetime, format: \"%B %-d, %Y\" }}</time>")
+    ///         .build()?;
+    ///
+    /// let params = BalsaParameters::new()
+    ///     .datetime("published", Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap());
+    /// assert_eq!(template.render_html_string(&params)?, "<time>March 1, 2024</time>");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn datetime(
+        &self,
+        key: impl Into<String>,
+        value: impl Into<chrono::DateTime<chrono::FixedOffset>>,
+    ) -> Self {
+        self.insert(key, BalsaValue::DateTime(value.into()))
+    }
+
+    /// Appends `null` to the parameters list, which is treated as if the parameter wasn't passed.
+    /// Optional parameters without a default value are left empty.
+    pub fn null(&self, key: impl Into<String>) -> Self {
+        self.insert(key, BalsaValue::Null)
+    }
+
+    /// Appends an array value to the parameters list.
+    pub fn array(&self, key: impl Into<String>, value: Array) -> Self {
+        self.insert(key, BalsaValue::Array(value))
+    }
+
+    /// Appends a dictionary value to the parameters list.
+    pub fn dictionary(&self, key: impl Into<String>, value: Dictionary) -> Self {
+        self.insert(key, BalsaValue::Dictionary(value))
+    }
+
+    /// Appends a section, i.e. an array of dictionaries rendered by an `{{#each}}` block, to the
+    /// parameters list. Each item's parameters become the values of one dictionary, sorted by
+    /// key. The localized layers of the items are ignored.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let params = BalsaParameters::new().sections(
+    ///     "posts",
+    ///     vec![
+    ///         BalsaParameters::new().string("title", "Hello").int("views", 3),
+    ///         BalsaParameters::new().string("title", "World").int("views", 5),
+    ///     ],
+    /// );
+    /// ```
+    pub fn sections(&self, key: impl Into<String>, items: Vec<BalsaParameters>) -> Self {
+        let item_type = BalsaType::dictionary_of(BalsaType::Any);
+        let items = items
+            .into_iter()
+            .map(|item| BalsaValue::Dictionary(item.into_dictionary()));
+
+        self.insert(
+            key,
+            BalsaValue::Array(
+                Array::from_values(item_type, items).expect("items are dictionaries of any type"),
+            ),
+        )
+    }
+
+    /// Appends a group of nested `parameters` to the parameters list, which blocks read with
+    /// dot notation, i.e. `{{ theme.primary : color }}`, so that structured data doesn't have to
+    /// be flattened. The parameters become the values of a dictionary, sorted by key, and their
+    /// localized layers are ignored.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let template = Balsa::from_string("<h1 style=\"color: {{ theme.primary : color }}\">Hi</h1>")
+    ///     .build()?;
+    ///
+    /// let params = BalsaParameters::new().nested(
+    ///     "theme",
+    ///     BalsaParameters::new().color("primary", "#ff0000"),
+    /// );
+    /// assert_eq!(
+    ///     template.render_html_string(&params)?,
+    ///     "<h1 style=\"color: #ff0000\">Hi</h1>"
+    /// );
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn nested(&self, key: impl Into<String>, parameters: BalsaParameters) -> Self {
+        self.insert(key, BalsaValue::Dictionary(parameters.into_dictionary()))
+    }
+
+    /// Adds a layer of `parameters` which override these ones when rendering with a
+    /// [`crate::RenderOptions::locale`] matching the language tag `tag`, so that per-language
+    /// content can live in one parameter list.
+    ///
+    /// A locale such as `fr-CA` uses the `fr-CA` layer, then the `fr` layer, and finally these
+    /// parameters. Tags are matched case-insensitively.
+    pub fn localized(&self, tag: &str, parameters: BalsaParameters) -> Self {
+        let mut localized = self.clone();
+        localized.localized.insert(normalize_tag(tag), parameters);
+
+        localized
+    }
+
+    /// Returns a new BalsaParameters with the parameters and localized layers of `other` added,
+    /// overriding any which are already in the list. Localized layers for the same language tag
+    /// are merged in the same way.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let defaults = BalsaParameters::new().string("title", "Untitled").int("views", 0);
+    /// let params = defaults.merge(BalsaParameters::new().string("title", "Hello"));
+    ///
+    /// let template = Balsa::from_string("<h1>{{ title : string }}</h1><p>{{ views : int }}</p>")
+    ///     .build()?;
+    /// assert_eq!(template.render_html_string(&params)?, "<h1>Hello</h1><p>0</p>");
+    /// # Ok::<(), BalsaError>(())
+    /// ```
+    pub fn merge(&self, other: BalsaParameters) -> Self {
+        let mut merged = self.clone();
+        merged.parameters.extend(other.parameters);
+
+        for (tag, layer) in other.localized {
+            let layer = match merged.localized.remove(&tag) {
+                Some(existing) => existing.merge(layer),
+                None => layer,
+            };
+            merged.localized.insert(tag, layer);
+        }
+
+        merged
+    }
+
     /// Returns a new BalsaParameters with the provided
     /// key and value inserted into the parameters map.
-    fn insert(&self, key: impl Into<String>, value: BalsaValue) -> Self {
-        let mut parameters = self.parameters.clone();
-        parameters.insert(key.into(), value);
+    pub(crate) fn insert(&self, key: impl Into<String>, value: BalsaValue) -> Self {
+        let mut parameters = self.clone();
+        parameters.parameters.insert(key.into(), value);
 
-        Self { parameters }
+        parameters
     }
 
-    /// Gets a single value from the parameter list.
+    /// Converts the parameters into a dictionary of any type, sorted by key. The localized layers
+    /// are dropped.
+    fn into_dictionary(self) -> Dictionary {
+        let mut entries: Vec<_> = self.parameters.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Dictionary::from_entries(BalsaType::Any, entries)
+            .expect("any value can be stored in a dictionary of any type")
+    }
+
+    /// Gets a single value from the parameter list. Keys which are paths like
+    /// `theme.primaryColor` look up values nested in the dictionaries in the list.
     pub(crate) fn get(&self, key: impl Into<String>) -> Option<BalsaValue> {
-        self.parameters.get(&key.into()).map(|x| x.to_owned())
+        self.value(&key.into()).cloned()
+    }
+
+    /// Returns the value of `key`, which may be a path like `theme.primaryColor`.
+    fn value(&self, key: &str) -> Option<&BalsaValue> {
+        self.parameters
+            .get(key)
+            .or_else(|| lookup_path(key, |root| self.parameters.get(root)))
+    }
+
+    /// Returns the value of the string parameter `key`, or `None` if it isn't in the list or
+    /// isn't a string. Keys which are paths like `author.name` look up values nested in
+    /// dictionaries, as in templates.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let params = BalsaParameters::new()
+    ///     .string("title", "Hello")
+    ///     .nested("author", BalsaParameters::new().string("name", "Ada"));
+    ///
+    /// assert_eq!(params.get_str("title"), Some("Hello"));
+    /// assert_eq!(params.get_str("author.name"), Some("Ada"));
+    /// assert_eq!(params.get_int("title"), None);
+    /// ```
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.value(key)? {
+            BalsaValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the integer parameter `key`, or `None` if it isn't in the list or
+    /// isn't an integer.
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match self.value(key)? {
+            BalsaValue::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the float parameter `key`, or `None` if it isn't in the list or
+    /// isn't a number. Integers are converted to floats, as they are when rendering.
+    pub fn get_float(&self, key: &str) -> Option<f64> {
+        match self.value(key)? {
+            BalsaValue::Float(f) => Some(*f),
+            BalsaValue::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the color parameter `key`, i.e. `#ff0000`, or `None` if it isn't in
+    /// the list or isn't a color.
+    pub fn get_color(&self, key: &str) -> Option<&str> {
+        match self.value(key)? {
+            BalsaValue::Color(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the names and values of the parameters in the list, sorted by
+    /// name. The values of localized layers aren't included.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &BalsaValue)> {
+        let mut parameters: Vec<_> = self
+            .parameters
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .collect();
+        parameters.sort_by_key(|(key, _)| *key);
+
+        parameters.into_iter()
+    }
+
+    /// Returns an iterator over the names of the parameters in the list, sorted by name. The
+    /// names of localized layers' parameters aren't included.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Gets a single value from the parameter list, preferring the layers for `locale`.
+    pub(crate) fn get_localized(&self, key: &str, locale: &Locale) -> Option<BalsaValue> {
+        locale
+            .fallback_tags()
+            .iter()
+            .find_map(|tag| self.localized.get(tag)?.get(key))
+            .or_else(|| self.get(key))
+    }
+
+    /// Feeds the parameters, including every localized layer, into `state` in a stable order so
+    /// that equal parameter sets hash equally.
+    pub(crate) fn hash_into<H: Hasher>(&self, state: &mut H) {
+        let mut parameters: Vec<_> = self.parameters.iter().collect();
+        parameters.sort_by_key(|(k, _)| *k);
+
+        parameters.len().hash(state);
+        for (k, v) in parameters {
+            k.hash(state);
+            v.hash_into(state);
+        }
+
+        let mut localized: Vec<_> = self.localized.iter().collect();
+        localized.sort_by_key(|(tag, _)| *tag);
+
+        localized.len().hash(state);
+        for (tag, layer) in localized {
+            tag.hash(state);
+            layer.hash_into(state);
+        }
+    }
+
+    /// Checks the parameters, including every localized layer, against the optional limits on
+    /// their count and on the size of each value.
+    pub(crate) fn check_limits(
+        &self,
+        max_count: Option<usize>,
+        max_value_size: Option<usize>,
+    ) -> Result<(), LimitExceeded> {
+        let layers = std::iter::once(self).chain(self.localized.values());
+
+        if let Some(max_count) = max_count {
+            let count = layers.clone().map(|l| l.parameters.len()).sum::<usize>();
+
+            if count > max_count {
+                return Err(LimitExceeded::ParameterCount { max_count });
+            }
+        }
+
+        if let Some(max_size) = max_value_size {
+            let too_large = layers
+                .flat_map(|l| &l.parameters)
+                .find(|(_, value)| value.size() > max_size);
+
+            if let Some((name, _)) = too_large {
+                return Err(LimitExceeded::ValueSize {
+                    parameter_name: name.clone(),
+                    max_size,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the first of `accepted_locales` which has a localized layer, ignoring parameters
+    /// such as `;q=0.8` and the `*` wildcard.
+    pub(crate) fn negotiate_locale(&self, accepted_locales: &[&str]) -> Option<Locale> {
+        accepted_locales
+            .iter()
+            .map(|accepted| accepted.split(';').next().unwrap_or_default().trim())
+            .filter(|tag| !tag.is_empty() && *tag != "*")
+            .map(Locale::new)
+            .find(|locale| {
+                locale
+                    .fallback_tags()
+                    .iter()
+                    .any(|tag| self.localized.contains_key(tag))
+            })
+    }
+}
+
+/// Creates a parameter list from a map of parameter names to values, e.g. one assembled from
+/// database rows.
+impl From<HashMap<String, BalsaValue>> for BalsaParameters {
+    fn from(parameters: HashMap<String, BalsaValue>) -> Self {
+        Self {
+            parameters,
+            localized: HashMap::new(),
+        }
+    }
+}
+
+/// Collects parameter names and values into a parameter list. Later values override earlier
+/// ones with the same name.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let rows = [("title", "Hello"), ("subtitle", "World")];
+/// let params: BalsaParameters = rows
+///     .iter()
+///     .map(|(key, value)| (key.to_string(), BalsaValue::String(value.to_string())))
+///     .collect();
+///
+/// let template = Balsa::from_string("<h1>{{ title : string }}</h1><p>{{ subtitle : string }}</p>")
+///     .build()?;
+/// assert_eq!(template.render_html_string(&params)?, "<h1>Hello</h1><p>World</p>");
+/// # Ok::<(), BalsaError>(())
+/// ```
+impl FromIterator<(String, BalsaValue)> for BalsaParameters {
+    fn from_iter<I: IntoIterator<Item = (String, BalsaValue)>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<HashMap<_, _>>())
+    }
+}
+
+/// Adds parameter names and values to the list, overriding any which are already in it.
+impl Extend<(String, BalsaValue)> for BalsaParameters {
+    fn extend<I: IntoIterator<Item = (String, BalsaValue)>>(&mut self, iter: I) {
+        self.parameters.extend(iter);
     }
 }
 
@@ -78,6 +479,78 @@ pub trait AsParameters {
     fn as_parameters(&self) -> BalsaParameters;
 }
 
+/// Allows a [`BalsaParameters`] list which has already been built to be passed straight to a
+/// template.
+impl AsParameters for BalsaParameters {
+    fn as_parameters(&self) -> BalsaParameters {
+        self.clone()
+    }
+}
+
+/// Allows parameters which are already JSON, e.g. from a CMS, to be passed straight to a
+/// template. Every key of an object becomes a parameter; other values have no parameters.
+///
+/// Strings, booleans, arrays and objects become strings, booleans, arrays and dictionaries of
+/// any type, which are cast to the types the template expects when rendering. Numbers become
+/// integers if they fit in an `i64`, and floats otherwise. `null` values are left out, so that
+/// their default values are used.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// let template = Balsa::from_string("<h1>{{ title : string }}</h1><p>{{ views : int }}</p>")
+///     .build()?;
+///
+/// let params = serde_json::json!({ "title": "Hello", "views": 3 });
+/// assert_eq!(template.render_html_string(&params)?, "<h1>Hello</h1><p>3</p>");
+/// # Ok::<(), BalsaError>(())
+/// ```
+#[cfg(feature = "serde_json")]
+impl AsParameters for serde_json::Value {
+    fn as_parameters(&self) -> BalsaParameters {
+        let mut parameters = BalsaParameters::new();
+
+        if let serde_json::Value::Object(object) = self {
+            for (key, value) in object {
+                if let Some(value) = json_value(value) {
+                    parameters.parameters.insert(key.clone(), value);
+                }
+            }
+        }
+
+        parameters
+    }
+}
+
+/// Converts a JSON value to a [`BalsaValue`], returning `None` for `null`.
+#[cfg(feature = "serde_json")]
+fn json_value(value: &serde_json::Value) -> Option<BalsaValue> {
+    use serde_json::Value;
+
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => BalsaValue::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => BalsaValue::Integer(i),
+            None => BalsaValue::Float(n.as_f64()?),
+        },
+        Value::String(s) => BalsaValue::String(s.clone()),
+        Value::Array(values) => BalsaValue::Array(
+            Array::from_values(BalsaType::Any, values.iter().filter_map(json_value))
+                .expect("any value can be stored in an array of any type"),
+        ),
+        Value::Object(object) => BalsaValue::Dictionary(
+            Dictionary::from_entries(
+                BalsaType::Any,
+                object
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.clone(), json_value(value)?))),
+            )
+            .expect("any value can be stored in a dictionary of any type"),
+        ),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +588,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn collected_parameters() {
+        let mut params: BalsaParameters = [
+            ("title".to_string(), BalsaValue::String("Hello".to_string())),
+            ("views".to_string(), BalsaValue::Integer(1)),
+        ]
+        .into_iter()
+        .collect();
+        params.extend([("views".to_string(), BalsaValue::Integer(2))]);
+
+        assert_eq!(params.get("views"), Some(BalsaValue::Integer(2)));
+
+        let merged = BalsaParameters::from(HashMap::from([(
+            "subtitle".to_string(),
+            BalsaValue::String("World".to_string()),
+        )]))
+        .localized("fr", BalsaParameters::new().string("title", "Bonjour"))
+        .merge(params.localized("fr", BalsaParameters::new().string("subtitle", "Monde")));
+
+        assert_eq!(
+            merged.get("title"),
+            Some(BalsaValue::String("Hello".to_string()))
+        );
+        assert_eq!(
+            merged.get("subtitle"),
+            Some(BalsaValue::String("World".to_string()))
+        );
+        assert_eq!(
+            merged.get_localized("title", &Locale::new("fr")),
+            Some(BalsaValue::String("Bonjour".to_string())),
+            "Localized layers for the same language should be merged"
+        );
+        assert_eq!(
+            merged.get_localized("subtitle", &Locale::new("fr")),
+            Some(BalsaValue::String("Monde".to_string()))
+        );
+    }
+
+    #[test]
+    fn typed_getters() {
+        let params = BalsaParameters::new()
+            .string("title", "Hello")
+            .color("accent", "#ff0000")
+            .int("views", 3)
+            .float("ratio", 1.5)
+            .nested("author", BalsaParameters::new().int("age", 36));
+
+        assert_eq!(params.get_str("title"), Some("Hello"));
+        assert_eq!(params.get_color("accent"), Some("#ff0000"));
+        assert_eq!(params.get_str("accent"), None);
+        assert_eq!(params.get_int("author.age"), Some(36));
+        assert_eq!(params.get_float("ratio"), Some(1.5));
+        assert_eq!(params.get_float("views"), Some(3.0));
+        assert_eq!(params.get_int("missing"), None);
+
+        assert_eq!(
+            params.keys().collect::<Vec<_>>(),
+            ["accent", "author", "ratio", "title", "views"]
+        );
+        assert_eq!(
+            params.iter().nth(4),
+            Some(("views", &BalsaValue::Integer(3)))
+        );
+    }
+
+    #[test]
+    fn localized_parameters() {
+        let params = BalsaParameters::new()
+            .string("title", "Hello")
+            .string("footer", "Footer")
+            .localized("fr", BalsaParameters::new().string("title", "Bonjour"))
+            .localized(
+                "FR_ca",
+                BalsaParameters::new().string("footer", "Pied de page"),
+            );
+
+        let cases = [
+            ("", "Hello", "Footer"),
+            ("de", "Hello", "Footer"),
+            ("fr", "Bonjour", "Footer"),
+            ("fr-CA", "Bonjour", "Pied de page"),
+        ];
+
+        for (tag, title, footer) in cases {
+            let locale = Locale::new(tag);
+
+            assert_eq!(
+                (
+                    params.get_localized("title", &locale),
+                    params.get_localized("footer", &locale)
+                ),
+                (
+                    Some(BalsaValue::String(title.to_string())),
+                    Some(BalsaValue::String(footer.to_string()))
+                ),
+                "Localized parameters resolved incorrectly for locale `{}`",
+                tag
+            );
+        }
+    }
+
+    #[test]
+    fn negotiate_locale() {
+        let params = BalsaParameters::new()
+            .localized("fr", BalsaParameters::new())
+            .localized("pt-BR", BalsaParameters::new());
+
+        let cases: [(&[&str], Option<&str>); 5] = [
+            (&["de", "fr-CH;q=0.8"], Some("fr-CH")),
+            (&["pt-PT", "pt_br"], Some("pt_br")),
+            (&[" FR ", "pt-BR"], Some("FR")),
+            (&["*", "pt"], None),
+            (&[], None),
+        ];
+
+        for (accepted, expected) in cases {
+            assert_eq!(
+                params
+                    .negotiate_locale(accepted)
+                    .map(|locale| locale.tag().to_string()),
+                expected.map(str::to_string),
+                "Locale negotiated incorrectly for `{:?}`",
+                accepted
+            );
+        }
+    }
+
     struct ParameterTestStruct {
         header_text: String,
         red: String,
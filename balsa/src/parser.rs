@@ -10,7 +10,7 @@
 //! let string_literal_p = || {
 //!     middle(
 //!         char_parser('"'),
-//!         take_until_char_parser('"'),
+//!         take_until_str("\""),
 //!         char_parser('"'),
 //!     )
 //! };
@@ -28,23 +28,91 @@
 //!     fmap_chain(ws(), char_parser(']'), |_, _| ()),
 //! );
 //!
-//! let (_, parsed) = p.parse(0, valid_input).expect(&format!(
+//! let (_, parsed) = p.parse(Position::default(), valid_input).expect(&format!(
 //!     "Array parser should successfully parse input `{}`",
 //!     valid_input
 //! ));
 //! ```
 
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    rc::Rc,
+};
+
+/// A location within the parser input.
+///
+/// `offset` is counted in chars from the start of the input, while `line` and `column` are
+/// 1-indexed so they can be shown to users as-is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Position {
+    pub(crate) offset: usize,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl Position {
+    /// Creates a new [`Position`] from the provided offset, line and column.
+    pub(crate) const fn new(offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            offset,
+            line,
+            column,
+        }
+    }
+
+    /// Returns the [`Position`] reached after consuming the provided `consumed` input.
+    pub(crate) fn advance(self, consumed: &str) -> Self {
+        consumed.chars().fold(self, |pos, c| pos.advance_char(c))
+    }
+
+    /// Returns this position in an input which `base` is the position of the start of, i.e. where
+    /// the input was appended to another.
+    pub(crate) fn rebase(self, base: Position) -> Self {
+        if self.line == 1 {
+            Self::new(
+                base.offset + self.offset,
+                base.line,
+                base.column + self.column - 1,
+            )
+        } else {
+            Self::new(
+                base.offset + self.offset,
+                base.line + self.line - 1,
+                self.column,
+            )
+        }
+    }
+
+    /// Returns the [`Position`] reached after consuming the provided `c` char.
+    pub(crate) fn advance_char(self, c: char) -> Self {
+        if c == '\n' {
+            Self::new(self.offset + 1, self.line + 1, 1)
+        } else {
+            Self::new(self.offset + 1, self.line, self.column + 1)
+        }
+    }
+}
+
+impl Default for Position {
+    /// Returns the [`Position`] at the very start of an input.
+    fn default() -> Self {
+        Self::new(0, 1, 1)
+    }
+}
+
 /// Context for a parsed token.
 pub(crate) struct ParseContext {
-    pub(crate) start_pos: i32,
-    pub(crate) end_pos: i32,
+    pub(crate) start_pos: Position,
+    pub(crate) end_pos: Position,
 }
 
 /// Represents a parsed token.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct Parsed<T> {
-    pub(crate) start_pos: i32,
-    pub(crate) end_pos: i32,
+    pub(crate) start_pos: Position,
+    pub(crate) end_pos: Position,
     pub(crate) token: T,
 }
 
@@ -53,26 +121,35 @@ pub(crate) struct Parsed<T> {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub(crate) enum ParseError {
     NotMatched,
-    MalformedInput(i32),
+    /// The input at the position was malformed. See [`commit`] for when this is reported rather
+    /// than [`ParseError::NotMatched`].
+    MalformedInput(Position),
+    /// An opening delimiter at the position was not followed by a valid block.
+    MalformedBlock(Position),
+    /// Parsers were nested deeper than the [`DepthGuard`] allows.
+    TooDeep(Position),
 }
 
 /// The result of running a [`Parser`] on an input.
 pub(crate) type ParseResult<'a, T> = Result<(&'a str, Parsed<T>), ParseError>;
 
 /// This trait describes a generic string parser.
-pub(crate) trait Parser<'a, T>: 'a {
-    fn parse(&self, pos: i32, input: &'a str) -> ParseResult<'a, T>;
+///
+/// Parsers don't borrow from their input, so a single parser can be built once and run on any
+/// number of inputs.
+pub(crate) trait Parser<T>: 'static {
+    fn parse<'a>(&self, pos: Position, input: &'a str) -> ParseResult<'a, T>;
 }
 
-/// A wrapper struct that holds a [`Parser<'a, T>`] in a [`Box`].
-pub(crate) struct ParserB<'a, T: 'a> {
-    parser: Box<dyn Parser<'a, T>>,
+/// A wrapper struct that holds a [`Parser<T>`] in a [`Box`].
+pub(crate) struct ParserB<T> {
+    parser: Box<dyn Parser<T>>,
 }
 
-impl<'a, T> ParserB<'a, T> {
-    pub(crate) fn new<P>(parser: P) -> ParserB<'a, T>
+impl<T> ParserB<T> {
+    pub(crate) fn new<F>(parser: F) -> ParserB<T>
     where
-        P: Parser<'a, T>,
+        F: for<'a> Fn(Position, &'a str) -> ParseResult<'a, T> + 'static,
     {
         ParserB {
             parser: Box::new(parser),
@@ -80,20 +157,8 @@ impl<'a, T> ParserB<'a, T> {
     }
 }
 
-impl<'a, T> Parser<'a, T> for ParserB<'a, T>
-where
-    T: 'a,
-{
-    fn parse(&self, pos: i32, input: &'a str) -> ParseResult<'a, T> {
-        self.parser.parse(pos, input)
-    }
-}
-
-impl<'a, T> Parser<'a, T> for &'a ParserB<'a, T>
-where
-    T: 'a,
-{
-    fn parse(&self, pos: i32, input: &'a str) -> ParseResult<'a, T> {
+impl<T: 'static> Parser<T> for ParserB<T> {
+    fn parse<'a>(&self, pos: Position, input: &'a str) -> ParseResult<'a, T> {
         self.parser.parse(pos, input)
     }
 }
@@ -172,53 +237,38 @@ where
 }
 
 /// Allow any parser function to be treated as a [`Parser`].
-impl<'a, F, T> Parser<'a, T> for F
+impl<F, T> Parser<T> for F
 where
-    F: Fn(i32, &'a str) -> ParseResult<'a, T> + 'a,
+    F: for<'a> Fn(Position, &'a str) -> ParseResult<'a, T> + 'static,
 {
-    fn parse(&self, pos: i32, input: &'a str) -> ParseResult<'a, T> {
+    fn parse<'a>(&self, pos: Position, input: &'a str) -> ParseResult<'a, T> {
         self(pos, input)
     }
 }
 
-trait ParserGenerator<'a, T> {
-    fn gen_parser(&self) -> ParserB<'a, T>;
-}
-
-/// Allow any parser function closure to be treated as a [`Parser`].
-impl<'a, C, T, O> ParserGenerator<'a, T> for C
-where
-    C: Fn() -> O,
-    O: Parser<'a, T>,
-{
-    fn gen_parser(&self) -> ParserB<'a, T> {
-        ParserB::new(self())
-    }
-}
-
-/// Maps a [`Parser<'a, T>`] to a [`Parser<'a, O>`] using the provided
+/// Maps a [`Parser<T>`] to a [`Parser<O>`] using the provided
 /// function `F`.
-pub(crate) fn fmap<'a, P, T: 'a, O: 'a, F>(parser: P, function: F) -> ParserB<'a, O>
+pub(crate) fn fmap<P, T: 'static, O: 'static, F>(parser: P, function: F) -> ParserB<O>
 where
-    P: Parser<'a, T> + 'a,
-    F: Fn(T, ParseContext) -> O + 'a,
+    P: Parser<T>,
+    F: Fn(T, ParseContext) -> O + 'static,
 {
     fmap_result(parser, move |x, ctx| Ok(function(x, ctx)))
 }
 
 /// Converts a [`Parsed`] to a [`ParseContext`].
-fn make_context(start_pos: i32, end_pos: i32) -> ParseContext {
+fn make_context(start_pos: Position, end_pos: Position) -> ParseContext {
     ParseContext { start_pos, end_pos }
 }
 
-/// Maps a [`Parser<'a, T>`] to a [`Parser<'a, O>`] using the provided
+/// Maps a [`Parser<T>`] to a [`Parser<O>`] using the provided
 /// function `F` which can fail.
-pub(crate) fn fmap_result<'a, P, T: 'a, O: 'a, F>(parser: P, function: F) -> ParserB<'a, O>
+pub(crate) fn fmap_result<P, T: 'static, O: 'static, F>(parser: P, function: F) -> ParserB<O>
 where
-    P: Parser<'a, T> + 'a,
-    F: Fn(T, ParseContext) -> Result<O, ParseError> + 'a,
+    P: Parser<T>,
+    F: Fn(T, ParseContext) -> Result<O, ParseError> + 'static,
 {
-    ParserB::new(move |pos: i32, input: &'a str| {
+    ParserB::new(move |pos: Position, input: &str| {
         parser.parse(pos, input).and_then(|(remainder, output)| {
             function(output.token, make_context(output.start_pos, output.end_pos)).map(|token| {
                 (
@@ -234,18 +284,39 @@ where
     })
 }
 
+/// Creates a new [`Parser`] which pairs the token of `parser` with the slice of input it consumed.
+pub(crate) fn with_source<P, T: 'static>(parser: P) -> ParserB<(T, String)>
+where
+    P: Parser<T>,
+{
+    ParserB::new(move |pos: Position, input: &str| {
+        parser.parse(pos, input).map(|(remainder, output)| {
+            let source = input[..input.len() - remainder.len()].to_string();
+
+            (
+                remainder,
+                Parsed {
+                    start_pos: output.start_pos,
+                    end_pos: output.end_pos,
+                    token: (output.token, source),
+                },
+            )
+        })
+    })
+}
+
 /// Creates a new [`Parser`] which chains together two parsers which have token types that are [`Combinable`].
 ///
 /// Parses input with the `left` [`Parser`], then feeds the output into the `right` [`Parser`].
 /// Finally, it combines the two `token`s with the [`Combinable`] trait and returns a single [`Parsed`].
-pub(crate) fn chain<'a, L, R, LT, RT, O>(left: L, right: R) -> ParserB<'a, O>
+pub(crate) fn chain<L, R, LT, RT, O>(left: L, right: R) -> ParserB<O>
 where
-    L: Parser<'a, LT> + 'a,
-    R: Parser<'a, RT> + 'a,
-    LT: Combinable<RT, O> + 'a,
-    RT: 'a,
+    L: Parser<LT>,
+    R: Parser<RT>,
+    LT: Combinable<RT, O> + 'static,
+    RT: 'static,
 {
-    ParserB::new(move |pos: i32, input: &'a str| {
+    ParserB::new(move |pos: Position, input: &str| {
         left.parse(pos, input).and_then(|(remainder, left_parsed)| {
             right
                 .parse(left_parsed.end_pos, remainder)
@@ -255,31 +326,71 @@ where
 }
 
 /// Creates a new [`Parser`] which first tries the `left` [`Parser`], returning its output on
-/// success and returning the output of the `right` [`Parser`] if `left` fails.
-pub(crate) fn or<'a, L, R, T: 'a>(left: L, right: R) -> ParserB<'a, T>
+/// success and returning the output of the `right` [`Parser`] if `left` is not matched.
+///
+/// Any failure other than [`ParseError::NotMatched`] is returned without trying `right`.
+pub(crate) fn or<L, R, T: 'static>(left: L, right: R) -> ParserB<T>
 where
-    L: Parser<'a, T> + 'a,
-    R: Parser<'a, T> + 'a,
+    L: Parser<T>,
+    R: Parser<T>,
 {
-    ParserB::new(move |pos: i32, input: &'a str| {
-        left.parse(pos, input).or_else(|_| right.parse(pos, input))
+    ParserB::new(move |pos: Position, input: &str| {
+        left.parse(pos, input).or_else(|e| match e {
+            ParseError::NotMatched => right.parse(pos, input),
+            e => Err(e),
+        })
     })
 }
 
+/// Creates a new [`Parser`] which runs `parser`, failing with its errors only once the input
+/// starts with `prefix`, at which point `parser` has committed to the input.
+///
+/// Before that, the input may be text which merely looks like what `parser` parses, so a
+/// [`ParseError::MalformedInput`] error is returned as [`ParseError::NotMatched`] to let enclosing
+/// alternations try their other parsers. `prefix` is only checked, not consumed.
+pub(crate) fn commit<C, P, CT: 'static, T: 'static>(prefix: C, parser: P) -> ParserB<T>
+where
+    C: Parser<CT>,
+    P: Parser<T>,
+{
+    ParserB::new(move |pos: Position, input: &str| {
+        parser.parse(pos, input).map_err(|e| match e {
+            ParseError::MalformedInput(_) if prefix.parse(pos, input).is_err() => {
+                ParseError::NotMatched
+            }
+            e => e,
+        })
+    })
+}
+
+/// Creates a new [`Parser`] which tries each of the provided parsers in order, returning the
+/// output of the first one that succeeds.
+///
+/// Expands to nested [`or`] calls, i.e. `choice!(a, b, c)` is equivalent to `or(a, or(b, c))`.
+macro_rules! choice {
+    ($parser:expr $(,)?) => {
+        $parser
+    };
+    ($parser:expr, $($rest:expr),+ $(,)?) => {
+        $crate::parser::or($parser, $crate::parser::choice!($($rest),+))
+    };
+}
+pub(crate) use choice;
+
 /// Creates a new [`Parser`] which chains together two parsers using the provided `combinator`
 /// function to combine the two outputs.
 ///
 /// Parses input with the `left` [`Parser`], then feeds the output into the `right` [`Parser`].
 /// Finally, it combines the two `token`s with the `combinator` function and returns a single [`Parsed`].
-pub(crate) fn fmap_chain<'a, L, R, LT: 'a, RT: 'a, O: 'a, F>(
+pub(crate) fn fmap_chain<L, R, LT: 'static, RT: 'static, O: 'static, F>(
     left: L,
     right: R,
     combinator: F,
-) -> ParserB<'a, O>
+) -> ParserB<O>
 where
-    L: Parser<'a, LT> + 'a,
-    R: Parser<'a, RT> + 'a,
-    F: Fn((LT, ParseContext), (RT, ParseContext)) -> O + 'a,
+    L: Parser<LT>,
+    R: Parser<RT>,
+    F: Fn((LT, ParseContext), (RT, ParseContext)) -> O + 'static,
 {
     fmap_result_chain(left, right, move |x, y| Ok(combinator(x, y)))
 }
@@ -289,17 +400,17 @@ where
 ///
 /// Parses input with the `left` [`Parser`], then feeds the output into the `right` [`Parser`].
 /// Finally, it combines the two `token`s with the `combinator` function and returns a single [`Parsed`].
-pub(crate) fn fmap_result_chain<'a, L, R, LT: 'a, RT: 'a, O: 'a, F>(
+pub(crate) fn fmap_result_chain<L, R, LT: 'static, RT: 'static, O: 'static, F>(
     left: L,
     right: R,
     combinator: F,
-) -> ParserB<'a, O>
+) -> ParserB<O>
 where
-    L: Parser<'a, LT> + 'a,
-    R: Parser<'a, RT> + 'a,
-    F: Fn((LT, ParseContext), (RT, ParseContext)) -> Result<O, ParseError> + 'a,
+    L: Parser<LT>,
+    R: Parser<RT>,
+    F: Fn((LT, ParseContext), (RT, ParseContext)) -> Result<O, ParseError> + 'static,
 {
-    ParserB::new(move |pos: i32, input: &'a str| {
+    ParserB::new(move |pos: Position, input: &str| {
         left.parse(pos, input).and_then(|(remainder, left_parsed)| {
             right
                 .parse(left_parsed.end_pos, remainder)
@@ -329,16 +440,16 @@ where
     })
 }
 
-/// Creates a new [`Parser<'a, Option<T>>`] out of a [`Parser<'a, T>`] where [`Option::None`] is
+/// Creates a new [`Parser<Option<T>>`] out of a [`Parser<T>`] where [`Option::None`] is
 /// returned when nothing is matched rather than failing.
 ///
 /// All other failures will still be returned as an error.
-pub(crate) fn optional<'a, T: 'a, P>(parser: P) -> ParserB<'a, Option<T>>
+pub(crate) fn optional<T: 'static, P>(parser: P) -> ParserB<Option<T>>
 where
-    P: Parser<'a, T>,
+    P: Parser<T>,
 {
     ParserB::new(
-        move |pos: i32, input: &'a str| match parser.parse(pos, input) {
+        move |pos: Position, input: &str| match parser.parse(pos, input) {
             Ok((remainder, parsed)) => Ok((
                 remainder,
                 Parsed {
@@ -367,12 +478,12 @@ where
 ///
 /// Parses input with the `left_p` [`Parser`], then feeds the output into the `right_p` [`Parser`].
 /// Finally, it ignores the right [`Parser`]'s token and returns the left's.
-pub(crate) fn left<'a, L, R, LT: 'a, RT: 'a>(left_p: L, right_p: R) -> ParserB<'a, LT>
+pub(crate) fn left<L, R, LT: 'static, RT: 'static>(left_p: L, right_p: R) -> ParserB<LT>
 where
-    L: Parser<'a, LT> + 'a,
-    R: Parser<'a, RT> + 'a,
+    L: Parser<LT>,
+    R: Parser<RT>,
 {
-    ParserB::new(move |pos: i32, input: &'a str| {
+    ParserB::new(move |pos: Position, input: &str| {
         left_p
             .parse(pos, input)
             .and_then(|(remainder, left_parsed)| {
@@ -397,12 +508,12 @@ where
 ///
 /// Parses input with the `left` [`Parser`], then feeds the output into the `right` [`Parser`].
 /// Finally, it ignores the left [`Parser`]'s token and returns the right's.
-pub(crate) fn right<'a, L, R, LT: 'a, RT: 'a>(left_p: L, right_p: R) -> ParserB<'a, RT>
+pub(crate) fn right<L, R, LT: 'static, RT: 'static>(left_p: L, right_p: R) -> ParserB<RT>
 where
-    L: Parser<'a, LT> + 'a,
-    R: Parser<'a, RT> + 'a,
+    L: Parser<LT>,
+    R: Parser<RT>,
 {
-    ParserB::new(move |pos: i32, input: &'a str| {
+    ParserB::new(move |pos: Position, input: &str| {
         left_p
             .parse(pos, input)
             .and_then(|(remainder, left_parsed)| {
@@ -427,31 +538,98 @@ where
 ///
 /// Parses input with the `left_p` [`Parser`], then feeds the output into the `right_p` [`Parser`].
 /// Finally, it ignores the left and right [`Parser`]'s token and returns the middle's.
-pub(crate) fn middle<'a, L, M, R, LT: 'a, MT: 'a, RT: 'a>(
+pub(crate) fn middle<L, M, R, LT: 'static, MT: 'static, RT: 'static>(
     left_p: L,
     middle_p: M,
     right_p: R,
-) -> ParserB<'a, MT>
+) -> ParserB<MT>
 where
-    L: Parser<'a, LT> + 'a,
-    M: Parser<'a, MT> + 'a,
-    R: Parser<'a, RT> + 'a,
-    MT: 'a,
+    L: Parser<LT>,
+    M: Parser<MT>,
+    R: Parser<RT>,
 {
     right(left_p, left(middle_p, right_p))
 }
 
+/// Creates a new [`Parser`] which parses `open`, then `parser`, then `close`, returning only the
+/// token produced by `parser`.
+///
+/// Useful for parsing delimited constructs such as `{{ ... }}` or `"..."`.
+pub(crate) fn between<O, C, P, OT: 'static, CT: 'static, T: 'static>(
+    open: O,
+    close: C,
+    parser: P,
+) -> ParserB<T>
+where
+    O: Parser<OT>,
+    C: Parser<CT>,
+    P: Parser<T>,
+{
+    middle(open, parser, close)
+}
+
+/// Tracks how deeply [`nested`] parsers are currently running.
+///
+/// Cloned guards share the same depth counter, so a single guard should be created per parse
+/// and handed to every nestable parser in the grammar.
+#[derive(Debug, Clone)]
+pub(crate) struct DepthGuard {
+    depth: Rc<Cell<usize>>,
+    max_depth: usize,
+}
+
+impl DepthGuard {
+    /// Creates a new [`DepthGuard`] which allows at most `max_depth` levels of nesting.
+    pub(crate) fn new(max_depth: usize) -> Self {
+        Self {
+            depth: Rc::new(Cell::new(0)),
+            max_depth,
+        }
+    }
+
+    /// Returns the maximum number of levels of nesting the guard allows.
+    pub(crate) fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+}
+
+/// Creates a new [`Parser`] which counts as one level of nesting in the provided `guard`.
+///
+/// Fails with [`ParseError::TooDeep`] rather than running `parser` if the guard's maximum depth
+/// would be exceeded, so that hostile inputs can't overflow the stack.
+pub(crate) fn nested<P, T>(guard: &DepthGuard, parser: P) -> ParserB<T>
+where
+    P: Parser<T>,
+    T: 'static,
+{
+    let guard = guard.clone();
+
+    ParserB::new(move |pos: Position, input: &str| {
+        let depth = guard.depth.get() + 1;
+
+        if depth > guard.max_depth {
+            return Err(ParseError::TooDeep(pos));
+        }
+
+        guard.depth.set(depth);
+        let result = parser.parse(pos, input);
+        guard.depth.set(depth - 1);
+
+        result
+    })
+}
+
 /// Creates a new [`Parser`] which runs the provided `parser` until it fails, returning
 /// the result as a [`Vec<T>`].
 ///
 /// If no tokens are matched, this parser will return an empty list.
 /// If a parser fails with an error other than [`ParseError::NotMatched`],
 /// this parser will fail and return that error.
-pub(crate) fn many<'a, P, T>(parser: P) -> ParserB<'a, Vec<T>>
+pub(crate) fn many<P, T>(parser: P) -> ParserB<Vec<T>>
 where
-    P: Parser<'a, T> + 'a,
+    P: Parser<T>,
 {
-    ParserB::new(move |pos: i32, input: &'a str| {
+    ParserB::new(move |pos: Position, input: &str| {
         let mut tokens: Vec<T> = Vec::new();
         let mut end_pos = pos;
         let mut remainder = input;
@@ -486,46 +664,199 @@ where
     })
 }
 
+/// Creates a new [`Parser`] which runs the provided `parser` until the `terminator` parser
+/// matches, returning the results of `parser` as a [`Vec<T>`]. The terminator is consumed.
+///
+/// The terminator is tried before each run of `parser`. If `parser` fails before the terminator
+/// is matched, this parser fails with the same error.
+pub(crate) fn many_until<P, E, T, ET>(parser: P, terminator: E) -> ParserB<Vec<T>>
+where
+    P: Parser<T>,
+    E: Parser<ET>,
+{
+    ParserB::new(move |pos: Position, input: &str| {
+        let mut tokens: Vec<T> = Vec::new();
+        let mut end_pos = pos;
+        let mut remainder = input;
+
+        loop {
+            match terminator.parse(end_pos, remainder) {
+                Ok((new_remainder, parsed)) => {
+                    return Ok((
+                        new_remainder,
+                        Parsed {
+                            start_pos: pos,
+                            end_pos: parsed.end_pos,
+                            token: tokens,
+                        },
+                    ))
+                }
+                Err(ParseError::NotMatched) => {}
+                Err(e) => return Err(e),
+            }
+
+            let (new_remainder, parsed) = parser.parse(end_pos, remainder)?;
+            remainder = new_remainder;
+            end_pos = parsed.end_pos;
+            tokens.push(parsed.token);
+        }
+    })
+}
+
+/// Remembers the offsets from which [`many_until_memo`] parsers have run to the end of the input
+/// without matching their terminator.
+///
+/// Cloned memos share the same offsets, so a single memo should be created per grammar and
+/// handed to every memoized parser in it. Offsets are only valid for a single input, so the
+/// grammar should be wrapped in [`memo_scope`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanMemo {
+    unterminated: Rc<RefCell<HashSet<(usize, usize)>>>,
+    parsers: Rc<Cell<usize>>,
+    steps: Rc<Cell<usize>>,
+}
+
+impl ScanMemo {
+    /// Creates a new, empty [`ScanMemo`].
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of times memoized parsers have run their `parser` since the memo was
+    /// last cleared.
+    #[cfg(test)]
+    pub(crate) fn steps(&self) -> usize {
+        self.steps.get()
+    }
+}
+
+/// Creates a new [`Parser`] which clears `memo` before running `parser`, so that offsets found in
+/// a previous input are never used for the current one.
+pub(crate) fn memo_scope<P, T>(memo: &ScanMemo, parser: P) -> ParserB<T>
+where
+    P: Parser<T>,
+    T: 'static,
+{
+    let memo = memo.clone();
+
+    ParserB::new(move |pos: Position, input: &str| {
+        memo.unterminated.borrow_mut().clear();
+        memo.steps.set(0);
+
+        parser.parse(pos, input)
+    })
+}
+
+/// Creates a new [`Parser`] which behaves like [`many_until`], but fails straight away with
+/// [`ParseError::NotMatched`] from any offset it has already run to the end of the input from.
+///
+/// Each offset is scanned at most once, so unterminated bodies which are tried again from
+/// several starting points don't take quadratic time. This relies on `parser` and `terminator`
+/// giving the same result for the same remaining input.
+pub(crate) fn many_until_memo<P, E, T, ET>(
+    memo: &ScanMemo,
+    parser: P,
+    terminator: E,
+) -> ParserB<Vec<T>>
+where
+    P: Parser<T>,
+    E: Parser<ET>,
+{
+    let memo = memo.clone();
+    let id = memo.parsers.get();
+    memo.parsers.set(id + 1);
+
+    ParserB::new(move |pos: Position, input: &str| {
+        let mut tokens: Vec<T> = Vec::new();
+        let mut visited: Vec<usize> = Vec::new();
+        let mut end_pos = pos;
+        let mut remainder = input;
+
+        // The remaining length identifies an offset within the input.
+        let unterminated = |visited: Vec<usize>| {
+            memo.unterminated
+                .borrow_mut()
+                .extend(visited.into_iter().map(|len| (id, len)));
+
+            Err(ParseError::NotMatched)
+        };
+
+        loop {
+            if memo.unterminated.borrow().contains(&(id, remainder.len())) {
+                return unterminated(visited);
+            }
+
+            match terminator.parse(end_pos, remainder) {
+                Ok((new_remainder, parsed)) => {
+                    return Ok((
+                        new_remainder,
+                        Parsed {
+                            start_pos: pos,
+                            end_pos: parsed.end_pos,
+                            token: tokens,
+                        },
+                    ))
+                }
+                Err(ParseError::NotMatched) => {}
+                Err(e) => return Err(e),
+            }
+
+            visited.push(remainder.len());
+            memo.steps.set(memo.steps.get() + 1);
+
+            match parser.parse(end_pos, remainder) {
+                Ok((new_remainder, parsed)) => {
+                    remainder = new_remainder;
+                    end_pos = parsed.end_pos;
+                    tokens.push(parsed.token);
+                }
+                Err(ParseError::NotMatched) => return unterminated(visited),
+                Err(e) => return Err(e),
+            }
+        }
+    })
+}
+
 /// Creates a new [`Parser`] which runs the provided `parser` until it fails, returning
 /// the result as a [`Vec<T>`]. Must match at least one token.
 ///
 /// If no tokens are matched, this parser will return a [`ParseError:NotMatched`] error.
 /// If a parser fails with an error other than [`ParseError::NotMatched`],
 /// this parser will fail and return that error.
-pub(crate) fn one_to_many<'a, P, T>(parser: P) -> ParserB<'a, Vec<T>>
+pub(crate) fn one_to_many<P, T>(parser: P) -> ParserB<Vec<T>>
 where
-    P: Parser<'a, T> + 'a,
-    T: 'a,
+    P: Parser<T>,
+    T: 'static,
 {
     let p = many(parser);
 
-    ParserB::new(move |pos: i32, input: &'a str| match p.parse(pos, input) {
-        Ok((remainder, parsed)) => {
-            // Return NotMatched if no tokens were matched.
-            if parsed.token.is_empty() {
-                Err(ParseError::NotMatched)
-            } else {
-                Ok((remainder, parsed))
+    ParserB::new(
+        move |pos: Position, input: &str| match p.parse(pos, input) {
+            Ok((remainder, parsed)) => {
+                // Return NotMatched if no tokens were matched.
+                if parsed.token.is_empty() {
+                    Err(ParseError::NotMatched)
+                } else {
+                    Ok((remainder, parsed))
+                }
             }
-        }
 
-        res => res,
-    })
+            res => res,
+        },
+    )
 }
 
-/// Creates a [`ParserB<'a, char>`] which parses the given char, returning it
+/// Creates a [`ParserB<char>`] which parses the given char, returning it
 /// as a token.
-pub(crate) fn char_parser<'a>(value: char) -> ParserB<'a, char> {
-    ParserB::new(move |pos: i32, input: &'a str| {
+pub(crate) fn char_parser(value: char) -> ParserB<char> {
+    ParserB::new(move |pos: Position, input: &str| {
         if input.starts_with(value) {
-            let s = String::from(value);
-
             Ok((
-                &input[s.len()..],
+                &input[value.len_utf8()..],
                 Parsed {
                     token: value,
                     start_pos: pos,
-                    end_pos: pos + 1,
+                    end_pos: pos.advance_char(value),
                 },
             ))
         } else {
@@ -534,102 +865,201 @@ pub(crate) fn char_parser<'a>(value: char) -> ParserB<'a, char> {
     })
 }
 
-/// Creates a [`ParserB<'a, String>`] which parses the given string, returning it
-/// as a token.
-pub(crate) fn string_parser<'a>(value: impl Into<String>) -> ParserB<'a, String> {
-    let str_ = value.into();
-    if str_.is_empty() {
-        unimplemented!("should return parser that always errors")
-    }
+/// Creates a [`ParserB<char>`] which parses a single char satisfying `predicate`, returning
+/// it as a token.
+pub(crate) fn satisfy<F>(predicate: F) -> ParserB<char>
+where
+    F: Fn(char) -> bool + 'static,
+{
+    ParserB::new(
+        move |pos: Position, input: &str| match input.chars().next() {
+            Some(c) if predicate(c) => Ok((
+                &input[c.len_utf8()..],
+                Parsed {
+                    token: c,
+                    start_pos: pos,
+                    end_pos: pos.advance_char(c),
+                },
+            )),
+            _ => Err(ParseError::NotMatched),
+        },
+    )
+}
 
-    let mut chars = str_.chars();
-    let first = fmap(char_parser(chars.next().unwrap()), |token, _| {
-        String::from(token)
-    });
+/// Creates a [`ParserB<String>`] which parses the given string, returning it
+/// as a token. A parser for an empty string never matches.
+///
+/// The token is only allocated once the whole string has matched, as grammars try many
+/// keywords at each position which mostly fail.
+pub(crate) fn string_parser(value: impl Into<String>) -> ParserB<String> {
+    let value = value.into();
+
+    ParserB::new(move |pos: Position, input: &str| {
+        if value.is_empty() || !input.starts_with(value.as_str()) {
+            return Err(ParseError::NotMatched);
+        }
 
-    chars.fold(first, |acc, p| chain(acc, char_parser(p)))
+        Ok((
+            &input[value.len()..],
+            Parsed {
+                start_pos: pos,
+                end_pos: pos.advance(&value),
+                token: value.clone(),
+            },
+        ))
+    })
 }
 
-/// Creates a [`ParserB<'a, String>`] which takes characters until the `terminator` char is
-/// reached.
-pub(crate) fn take_until_char_parser<'a>(terminator: char) -> ParserB<'a, String> {
-    ParserB::new(move |pos: i32, input: &'a str| {
-        let token = input
-            .to_string()
-            .chars()
-            .take_while(|x| *x != terminator)
-            .collect::<String>();
+/// Creates a [`ParserB<()>`] which parses the given string like [`string_parser`], but without
+/// allocating a token, for keywords and delimiters which are only checked for.
+pub(crate) fn skip_string_parser(value: impl Into<String>) -> ParserB<()> {
+    let value = value.into();
 
-        if token.is_empty() {
-            Err(ParseError::NotMatched)
-        } else {
-            Ok((
-                &input[token.len()..],
-                Parsed {
-                    start_pos: pos,
-                    end_pos: pos + (token.chars().count() as i32),
-                    token,
-                },
-            ))
+    ParserB::new(move |pos: Position, input: &str| {
+        if value.is_empty() || !input.starts_with(value.as_str()) {
+            return Err(ParseError::NotMatched);
+        }
+
+        Ok((
+            &input[value.len()..],
+            Parsed {
+                start_pos: pos,
+                end_pos: pos.advance(&value),
+                token: (),
+            },
+        ))
+    })
+}
+
+/// Creates a [`ParserB<String>`] which takes characters until the `terminator` string is
+/// reached. Multi-character terminators such as `{{` don't stop the scan at every single `{`.
+pub(crate) fn take_until_str(terminator: impl Into<String>) -> ParserB<String> {
+    let terminator = terminator.into();
+
+    ParserB::new(move |pos: Position, input: &str| {
+        let end = input.find(terminator.as_str()).unwrap_or(input.len());
+
+        if end == 0 {
+            return Err(ParseError::NotMatched);
         }
+
+        let (token, remainder) = input.split_at(end);
+
+        Ok((
+            remainder,
+            Parsed {
+                start_pos: pos,
+                end_pos: pos.advance(token),
+                token: token.to_string(),
+            },
+        ))
     })
 }
 
-/// Creates a [`ParserB<'a, String>`] which takes characters until it reaches one that is not
+/// Creates a [`ParserB<String>`] which takes characters until it reaches one that is not
 /// in the `allowed_chars` array.
-pub(crate) fn take_while_chars_parser<'a>(allowed_chars: Vec<char>) -> ParserB<'a, String> {
-    ParserB::new(move |pos: i32, input: &'a str| {
-        let token = input
-            .to_string()
-            .chars()
-            .take_while(|x| allowed_chars.contains(x))
-            .collect::<String>();
+pub(crate) fn take_while_chars_parser(allowed_chars: Vec<char>) -> ParserB<String> {
+    ParserB::new(move |pos: Position, input: &str| {
+        take_prefix(pos, input, |x| allowed_chars.contains(&x)).map(|(remainder, parsed)| {
+            (
+                remainder,
+                Parsed {
+                    start_pos: parsed.start_pos,
+                    end_pos: parsed.end_pos,
+                    token: parsed.token.to_string(),
+                },
+            )
+        })
+    })
+}
 
-        if token.is_empty() {
-            Err(ParseError::NotMatched)
-        } else {
-            Ok((
-                &input[token.len()..],
+/// Creates a [`ParserB<()>`] which skips characters until it reaches one that is not in the
+/// `allowed_chars` array, like [`take_while_chars_parser`] but without allocating a token.
+pub(crate) fn skip_while_chars_parser(allowed_chars: Vec<char>) -> ParserB<()> {
+    ParserB::new(move |pos: Position, input: &str| {
+        take_prefix(pos, input, |x| allowed_chars.contains(&x)).map(|(remainder, parsed)| {
+            (
+                remainder,
                 Parsed {
-                    start_pos: pos,
-                    end_pos: pos + (token.chars().count() as i32),
-                    token,
+                    start_pos: parsed.start_pos,
+                    end_pos: parsed.end_pos,
+                    token: (),
                 },
-            ))
-        }
+            )
+        })
     })
 }
 
+/// Takes the longest prefix of `input` whose chars all satisfy `predicate`, returning the slice as
+/// a token.
+///
+/// The prefix is located by byte index using [`str::char_indices`] so that multibyte chars are
+/// never split. Fails with [`ParseError::NotMatched`] if the prefix is empty.
+fn take_prefix<'a, F>(pos: Position, input: &'a str, predicate: F) -> ParseResult<'a, &'a str>
+where
+    F: Fn(char) -> bool,
+{
+    let end = input
+        .char_indices()
+        .find(|(_, c)| !predicate(*c))
+        .map(|(i, _)| i)
+        .unwrap_or(input.len());
+
+    if end == 0 {
+        return Err(ParseError::NotMatched);
+    }
+
+    let (token, remainder) = input.split_at(end);
+
+    Ok((
+        remainder,
+        Parsed {
+            start_pos: pos,
+            end_pos: pos.advance(token),
+            token,
+        },
+    ))
+}
+
+/// Creates a [`Parser`] which parses lists of at least one `item`, separated by `delimiter`s.
+///
+/// If no items are found, this [`Parser`] will return a [`ParseError::NotMatched`] error.
+/// Requires [`Fn() -> ParserB<T>`] generators as they are used multiple times.
+pub(crate) fn sep_by1<P, T, D, DT: 'static>(item: P, delimiter: D) -> ParserB<Vec<T>>
+where
+    P: Fn() -> ParserB<T>,
+    D: Fn() -> ParserB<DT>,
+    T: Clone + 'static,
+{
+    chain(item(), many(right(delimiter(), item())))
+}
+
 /// Creates a [`Parser`] which parses lists of `item`s, separated by `delimiter`s.
 ///
 /// If no items are found, this [`Parser`] will return an empty [`Vec<T>`].
-/// Requires [`Fn() -> ParserB<'a, T>`] generators as they are used multiple times.
-pub(crate) fn delimited_list<'a, P, T: 'a, D, DT: 'a>(item: P, delimiter: D) -> ParserB<'a, Vec<T>>
+/// Requires [`Fn() -> ParserB<T>`] generators as they are used multiple times.
+pub(crate) fn delimited_list<P, T, D, DT: 'static>(item: P, delimiter: D) -> ParserB<Vec<T>>
 where
-    P: Fn() -> ParserB<'a, T> + 'a,
-    D: Fn() -> ParserB<'a, DT> + 'a,
-    T: Clone,
+    P: Fn() -> ParserB<T>,
+    D: Fn() -> ParserB<DT>,
+    T: Clone + 'static,
 {
-    fmap(
-        optional(chain(
-            item(),
-            optional(many(fmap_chain(delimiter(), item(), |_, (i, _)| i))),
-        )),
-        |t, _| t.unwrap_or_else(Vec::new),
-    )
+    fmap(optional(sep_by1(item, delimiter)), |t, _| {
+        t.unwrap_or_default()
+    })
 }
 
 /// Creates a [`Parser`] which parses key value pairs in the following format:
 /// <`key`><`delimiter`><`value`>. It returns a tuple of (`KT`, `VT`).
-pub(crate) fn key_sep_value<'a, K, KT: 'a, D, DT: 'a, V, VT: 'a>(
+pub(crate) fn key_sep_value<K, KT: 'static, D, DT: 'static, V, VT: 'static>(
     key: K,
     delimiter: D,
     value: V,
-) -> ParserB<'a, (KT, VT)>
+) -> ParserB<(KT, VT)>
 where
-    K: Parser<'a, KT> + 'a,
-    D: Parser<'a, DT> + 'a,
-    V: Parser<'a, VT> + 'a,
+    K: Parser<KT>,
+    D: Parser<DT>,
+    V: Parser<VT>,
 {
     fmap_chain(key, right(delimiter, value), |(k, _), (v, _)| (k, v))
 }
@@ -642,7 +1072,7 @@ mod tests {
     fn test_char_parser() {
         let p = char_parser('c');
         let (remainder, parsed) = p
-            .parse(0, "cd")
+            .parse(Position::default(), "cd")
             .expect("Char parser `c` should successfully parse input `cd`");
 
         assert_eq!(
@@ -657,7 +1087,7 @@ mod tests {
 
         let p = char_parser('c');
         let err = p
-            .parse(0, "dc")
+            .parse(Position::default(), "dc")
             .expect_err("Char parser `c` should fail on input `dc`");
 
         assert_eq!(
@@ -667,11 +1097,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_position_tracking() {
+        let p = right(
+            take_while_chars_parser(vec![' ', '\n']),
+            string_parser("Hello"),
+        );
+
+        let (_, parsed) = p
+            .parse(Position::default(), "\n\n  Hello")
+            .expect("Position parser should successfully parse input `\\n\\n  Hello`");
+
+        assert_eq!(
+            parsed.end_pos,
+            Position::new(9, 3, 8),
+            "Parser produced incorrect end position for input `\\n\\n  Hello`"
+        );
+    }
+
+    #[test]
+    fn test_multibyte_input() {
+        let p = char_parser('é');
+        let (remainder, parsed) = p
+            .parse(Position::default(), "éa")
+            .expect("Char parser `é` should successfully parse input `éa`");
+
+        assert_eq!(
+            (parsed.token, remainder, parsed.end_pos),
+            ('é', "a", Position::new(1, 1, 2)),
+            "Char parser `é` produced incorrect output for input `éa`"
+        );
+
+        let p = take_until_str("{");
+        let (remainder, parsed) = p
+            .parse(Position::default(), "日本語 🌳{{")
+            .expect("Take until parser should successfully parse multibyte input");
+
+        assert_eq!(
+            (parsed.token.as_str(), remainder, parsed.end_pos),
+            ("日本語 🌳", "{{", Position::new(5, 1, 6)),
+            "Take until parser produced incorrect output for multibyte input"
+        );
+
+        let p = take_while_chars_parser(vec!['ü', 'ß']);
+        let (remainder, parsed) = p
+            .parse(Position::default(), "üßü!")
+            .expect("Take while parser should successfully parse input `üßü!`");
+
+        assert_eq!(
+            (parsed.token.as_str(), remainder, parsed.end_pos),
+            ("üßü", "!", Position::new(3, 1, 4)),
+            "Take while parser produced incorrect output for input `üßü!`"
+        );
+    }
+
     #[test]
     fn test_string_parser() {
         let p = string_parser("Hello");
         let (remainder, parsed) = p
-            .parse(0, "Hello world")
+            .parse(Position::default(), "Hello world")
             .expect("String parser `Hello` should successfully parse input `Hello world`");
 
         assert_eq!(
@@ -686,16 +1170,39 @@ mod tests {
     }
 
     #[test]
-    fn test_string_literal_parser() {
-        let p = middle(
-            char_parser('"'),
-            take_until_char_parser('"'),
-            char_parser('"'),
+    fn test_skip_parsers() {
+        let p = right(
+            skip_string_parser("Hello"),
+            skip_while_chars_parser(vec![' ', '\n']),
+        );
+        let (remainder, parsed) = p
+            .parse(Position::default(), "Hello \n world")
+            .expect("Skip parsers should successfully parse input `Hello \\n world`");
+
+        assert_eq!(
+            (remainder, parsed.end_pos),
+            ("world", Position::new(8, 2, 2)),
+            "Skip parsers produced incorrect output for input `Hello \\n world`"
         );
 
-        let (remainder, parsed) = p.parse(0, "\"Hello! @#$123456789\"").expect(
-            "String literal parser should successfully parse input `\"Hello! @#$123456789\"`",
+        assert_eq!(
+            skip_string_parser("Hello")
+                .parse(Position::default(), "Help")
+                .err(),
+            Some(ParseError::NotMatched),
+            "Skip string parser `Hello` should fail on input `Help`"
         );
+    }
+
+    #[test]
+    fn test_string_literal_parser() {
+        let p = middle(char_parser('"'), take_until_str("\""), char_parser('"'));
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "\"Hello! @#$123456789\"")
+            .expect(
+                "String literal parser should successfully parse input `\"Hello! @#$123456789\"`",
+            );
 
         assert_eq!(
             parsed.token,
@@ -712,6 +1219,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_with_source() {
+        let p = with_source(between(
+            char_parser('('),
+            char_parser(')'),
+            take_until_str(")"),
+        ));
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "(héllo) world")
+            .expect("With source parser should successfully parse input `(héllo) world`");
+
+        assert_eq!(
+            (parsed.token, remainder),
+            (("héllo".to_string(), "(héllo)".to_string()), " world"),
+            "With source parser produced incorrect output for input `(héllo) world`"
+        );
+    }
+
+    #[test]
+    fn test_take_until_str() {
+        let p = take_until_str("{{");
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "a { b } {{ c }}")
+            .expect("Take until str parser should successfully parse input `a { b } {{ c }}`");
+
+        assert_eq!(
+            (parsed.token.as_str(), remainder),
+            ("a { b } ", "{{ c }}"),
+            "Take until str parser produced incorrect output for input `{}`",
+            "a { b } {{ c }}"
+        );
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "no terminator")
+            .expect("Take until str parser should consume input without a terminator");
+
+        assert_eq!(
+            (parsed.token.as_str(), remainder),
+            ("no terminator", ""),
+            "Take until str parser produced incorrect output for input `no terminator`"
+        );
+
+        let err = p
+            .parse(Position::default(), "{{ c }}")
+            .expect_err("Take until str parser should fail on input starting with `{{`");
+
+        assert_eq!(
+            err,
+            ParseError::NotMatched,
+            "Take until str parser should return error `NotMatched` for input `{}`",
+            "{{ c }}"
+        );
+    }
+
     #[test]
     fn test_variable_name_parser() {
         let allowed_chars = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ123456789-_"
@@ -721,7 +1284,7 @@ mod tests {
         let p = take_while_chars_parser(allowed_chars.clone());
 
         let (remainder, parsed) = p
-            .parse(0, "helloWorld: ")
+            .parse(Position::default(), "helloWorld: ")
             .expect("Variable name parser should successfully parse input `helloWorld: `");
 
         assert_eq!(
@@ -746,13 +1309,7 @@ mod tests {
 
         let invalid_input = r#"["hello" "world",, "goodbye", "aaaa"]"#;
 
-        let string_literal_p = || {
-            middle(
-                char_parser('"'),
-                take_until_char_parser('"'),
-                char_parser('"'),
-            )
-        };
+        let string_literal_p = || middle(char_parser('"'), take_until_str("\""), char_parser('"'));
 
         let ws_chars = vec![' ', '\t'];
         let ws = || optional(take_while_chars_parser(ws_chars.clone()));
@@ -767,10 +1324,14 @@ mod tests {
             fmap_chain(ws(), char_parser(']'), |_, _| ()),
         );
 
-        let (remainder, parsed) = p.parse(0, valid_input).expect(&format!(
-            "Array parser should successfully parse input `{}`",
-            valid_input
-        ));
+        let (remainder, parsed) = p
+            .parse(Position::default(), valid_input)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Array parser should successfully parse input `{}`",
+                    valid_input
+                )
+            });
 
         assert_eq!(
             parsed.token, valid_output,
@@ -784,10 +1345,227 @@ mod tests {
             valid_input, "", remainder,
         );
 
-        p.parse(0, invalid_input).expect_err(&format!(
-            "Array parser should not successfully parse input `{}`",
-            invalid_input
-        ));
+        p.parse(Position::default(), invalid_input)
+            .expect_err(&format!(
+                "Array parser should not successfully parse input `{}`",
+                invalid_input
+            ));
+    }
+
+    #[test]
+    fn test_sep_by1() {
+        let digit_p = || take_while_chars_parser("0123456789".chars().collect());
+        let p = sep_by1(digit_p, || char_parser(','));
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "1,23,456]")
+            .expect("sep_by1 parser should successfully parse input `1,23,456]`");
+
+        assert_eq!(
+            parsed.token,
+            vec!["1", "23", "456"],
+            "sep_by1 parser failed to parse `1,23,456]`"
+        );
+
+        assert_eq!(
+            remainder, "]",
+            "sep_by1 parser produced incorrect remainder for input `1,23,456]`"
+        );
+
+        let err = p
+            .parse(Position::default(), "]")
+            .expect_err("sep_by1 parser should fail on input `]`");
+
+        assert_eq!(
+            err,
+            ParseError::NotMatched,
+            "sep_by1 parser should return error `NotMatched` for input `]`"
+        );
+    }
+
+    #[test]
+    fn test_many_until() {
+        let p = many_until(take_until_str("."), string_parser(".."));
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "..rest")
+            .expect("Many until parser should match an immediate terminator");
+
+        assert_eq!(
+            (parsed.token, remainder),
+            (Vec::new(), "rest"),
+            "Many until parser produced incorrect output for input `..rest`"
+        );
+
+        let p = many_until(
+            choice!(take_until_str("."), string_parser(".")),
+            string_parser(".."),
+        );
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "a.b..c")
+            .expect("Many until parser should successfully parse input `a.b..c`");
+
+        assert_eq!(
+            (parsed.token, remainder),
+            (vec!["a".to_string(), ".".to_string(), "b".to_string()], "c"),
+            "Many until parser produced incorrect output for input `a.b..c`"
+        );
+
+        p.parse(Position::default(), "a.b")
+            .expect_err("Many until parser should fail without a terminator");
+    }
+
+    #[test]
+    fn test_many_until_memo() {
+        let memo = ScanMemo::new();
+        let p = memo_scope(
+            &memo,
+            many(choice!(
+                many_until_memo(&memo, satisfy(|_| true), char_parser(')')),
+                fmap(satisfy(|_| true), |_, _| Vec::new()),
+            )),
+        );
+
+        let (_, parsed) = p
+            .parse(Position::default(), "ab)c")
+            .expect("Memoized many until parser should successfully parse input `ab)c`");
+
+        assert_eq!(
+            (parsed.token, memo.steps()),
+            (vec![vec!['a', 'b'], Vec::new()], 4),
+            "Memoized many until parser produced incorrect output for input `ab)c`"
+        );
+
+        let input = "abcdefgh";
+        p.parse(Position::default(), input)
+            .expect("Memoized many until parser should skip unterminated input");
+
+        assert_eq!(
+            memo.steps(),
+            input.len() + 1,
+            "Memoized many until parser should scan each offset of input `{}` once",
+            input
+        );
+    }
+
+    #[test]
+    fn test_between() {
+        let p = between(char_parser('('), char_parser(')'), take_until_str(")"));
+
+        let (remainder, parsed) = p
+            .parse(Position::default(), "(hello) world")
+            .expect("Between parser should successfully parse input `(hello) world`");
+
+        assert_eq!(
+            parsed.token, "hello",
+            "Between parser failed to parse `(hello) world`"
+        );
+
+        assert_eq!(
+            remainder, " world",
+            "Between parser produced incorrect remainder for input `(hello) world`"
+        );
+    }
+
+    #[test]
+    fn test_choice() {
+        let p = || {
+            choice!(
+                fmap(char_parser('a'), |_, _| 1),
+                fmap(char_parser('b'), |_, _| 2),
+                fmap(char_parser('c'), |_, _| 3),
+            )
+        };
+
+        for (input, expected) in [("a", 1), ("b", 2), ("c", 3)] {
+            let (_, parsed) = p()
+                .parse(Position::default(), input)
+                .unwrap_or_else(|_| panic!("Choice parser should successfully parse `{}`", input));
+
+            assert_eq!(
+                parsed.token, expected,
+                "Choice parser produced incorrect token for input `{}`",
+                input
+            );
+        }
+
+        p().parse(Position::default(), "d")
+            .expect_err("Choice parser should fail on input `d`");
+    }
+
+    #[test]
+    fn test_commit() {
+        // Parses `<` followed by a digit, which is malformed if it isn't one.
+        let p = || {
+            commit(
+                string_parser("<#"),
+                right(
+                    char_parser('<'),
+                    fmap_result(satisfy(|_| true), |c, ctx| {
+                        c.to_digit(10)
+                            .ok_or(ParseError::MalformedInput(ctx.start_pos))
+                    }),
+                ),
+            )
+        };
+
+        let (_, parsed) = p()
+            .parse(Position::default(), "<7")
+            .expect("Commit parser should successfully parse input `<7`");
+        assert_eq!(parsed.token, 7);
+
+        assert_eq!(
+            p().parse(Position::default(), "<a").err(),
+            Some(ParseError::NotMatched),
+            "Commit parser should not match input `<a` before its prefix"
+        );
+        assert_eq!(
+            p().parse(Position::default(), "<#").err(),
+            Some(ParseError::MalformedInput(Position::new(1, 1, 2))),
+            "Commit parser should fail on input `<#` after its prefix"
+        );
+    }
+
+    /// Parses nested parentheses such as `((()))`, returning the nesting depth.
+    fn parens_p(guard: DepthGuard) -> ParserB<usize> {
+        let inner_guard = guard.clone();
+        let inner = ParserB::new(move |pos: Position, input: &str| {
+            parens_p(inner_guard.clone()).parse(pos, input)
+        });
+
+        fmap(
+            between(
+                char_parser('('),
+                char_parser(')'),
+                nested(&guard, optional(inner)),
+            ),
+            |inner, _| inner.unwrap_or(0) + 1,
+        )
+    }
+
+    #[test]
+    fn test_nested_depth_guard() {
+        let p = parens_p(DepthGuard::new(3));
+
+        let (_, parsed) = p
+            .parse(Position::default(), "((()))")
+            .expect("Nested parser should successfully parse input `((()))`");
+
+        assert_eq!(
+            parsed.token, 3,
+            "Nested parser produced incorrect depth for input `((()))`"
+        );
+
+        let err = p
+            .parse(Position::default(), "(((())))")
+            .expect_err("Nested parser should fail on input nested past the maximum depth");
+
+        assert_eq!(
+            err,
+            ParseError::TooDeep(Position::new(4, 1, 5)),
+            "Nested parser should return error `TooDeep` inside the fourth level of nesting"
+        );
     }
 
     #[test]
@@ -802,13 +1580,7 @@ mod tests {
         let ws_chars = vec![' ', '\t'];
         let ws = || optional(take_while_chars_parser(ws_chars.clone()));
 
-        let string_literal_p = || {
-            middle(
-                char_parser('"'),
-                take_until_char_parser('"'),
-                char_parser('"'),
-            )
-        };
+        let string_literal_p = || middle(char_parser('"'), take_until_str("\""), char_parser('"'));
 
         let str_element_p = || middle(ws(), string_literal_p(), ws());
 
@@ -820,10 +1592,14 @@ mod tests {
         let valid_output = ("helloWorld".to_string(), "value".to_string());
         let invalid_input = r#"h'elloWorld: "value""#;
 
-        let (remainder, parsed) = p.parse(0, valid_input).expect(&format!(
-            "Key-value parser should successfully parse input `{}`",
-            valid_input
-        ));
+        let (remainder, parsed) = p
+            .parse(Position::default(), valid_input)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Key-value parser should successfully parse input `{}`",
+                    valid_input
+                )
+            });
 
         assert_eq!(
             parsed.token, valid_output,
@@ -837,9 +1613,10 @@ mod tests {
             valid_input, "", remainder,
         );
 
-        p.parse(0, invalid_input).expect_err(&format!(
-            "Key-value parser should not successfully parse input `{}`",
-            invalid_input
-        ));
+        p.parse(Position::default(), invalid_input)
+            .expect_err(&format!(
+                "Key-value parser should not successfully parse input `{}`",
+                invalid_input
+            ));
     }
 }
@@ -0,0 +1,1019 @@
+//! A compact binary encoding of compiled templates, which lets templates be compiled ahead of
+//! time and loaded without parsing.
+//!
+//! The format starts with [`MAGIC`] and [`VERSION`], followed by the static text segments and
+//! end position of the template, the global scope, the escape policy, the replacement
+//! instructions and the computed parameters, and then the templates included in `#each`
+//! sections, each encoded the same way after its path. Integers are little-endian `u64`s and
+//! strings are prefixed with their length in bytes.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, ErrorKind},
+};
+
+use crate::{
+    balsa_compiler::{
+        CompiledTemplate, Compiler, ComputedDescription, EachDescription, EachPart,
+        ImageDescription, ItemsDescription, MessageDescription, ParameterDescription,
+        PartialArgument, PartialDescription, PluralDescription, ReplaceWith,
+        ReplacementInstruction, Scope,
+    },
+    errors::BalsaError,
+    expression::{Expression, Operator},
+    filters::Filters,
+    message_format,
+    options::EscapePolicy,
+    parser::Position,
+    validators::is_valid_url,
+    Array, BalsaResult, BalsaType, BalsaValue, Currency, Dictionary, Image, PluralRule,
+    TextDirection,
+};
+
+#[cfg(feature = "chrono")]
+use crate::balsa_types::{is_valid_format, parse_datetime};
+
+/// Identifies a precompiled Balsa template.
+const MAGIC: &[u8] = b"BALSA";
+/// The version of the format, which is bumped whenever the format changes.
+const VERSION: u8 = 16;
+/// The deepest computed parameter expression, type or value which is decoded, which keeps
+/// malformed artifacts from overflowing the stack.
+const MAX_DEPTH: usize = 1024;
+
+/// Encodes a [`CompiledTemplate`] into a precompiled artifact.
+///
+/// Warnings are not included, as they are only useful while the template is being compiled.
+/// Filters can't be encoded either, so only their names are, and the filters themselves have to
+/// be registered again with [`crate::Template::with_filter`].
+pub(crate) fn encode(compiled_template: &CompiledTemplate) -> Vec<u8> {
+    let mut encoder = Encoder {
+        bytes: MAGIC.to_vec(),
+    };
+    encoder.bytes.push(VERSION);
+
+    encode_template(&mut encoder, compiled_template);
+
+    encoder.usize(compiled_template.partials.len());
+    for (path, partial) in &compiled_template.partials {
+        encoder.string(path);
+        encode_template(&mut encoder, partial);
+    }
+
+    encoder.bytes
+}
+
+/// Appends everything but the included templates of a [`CompiledTemplate`] to the `encoder`.
+fn encode_template(encoder: &mut Encoder, compiled_template: &CompiledTemplate) {
+    encoder.usize(compiled_template.segments.len());
+    for segment in &compiled_template.segments {
+        encoder.string(segment);
+    }
+    encoder.position(compiled_template.end_pos);
+
+    let mut variables = compiled_template
+        .global_scope
+        .variables
+        .iter()
+        .collect::<Vec<(&String, &BalsaValue)>>();
+    variables.sort_by(|a, b| a.0.cmp(b.0));
+
+    encoder.usize(variables.len());
+    for (name, value) in variables {
+        encoder.string(name);
+        encoder.value(value);
+    }
+
+    encoder.bytes.push(match compiled_template.escape_policy {
+        EscapePolicy::None => 0,
+        EscapePolicy::Html => 1,
+    });
+    encoder.bytes.push(u8::from(compiled_template.restore_crlf));
+
+    encoder.usize(compiled_template.replacements.len());
+    for replacement in &compiled_template.replacements {
+        encoder.position(replacement.start_pos);
+        encoder.position(replacement.end_pos);
+
+        match &replacement.replace_with {
+            ReplaceWith::Nothing => encoder.bytes.push(0),
+            ReplaceWith::Parameter(p) => {
+                encoder.bytes.push(1);
+                encoder.parameter(p);
+            }
+            ReplaceWith::Text(text) => {
+                encoder.bytes.push(2);
+                encoder.string(text);
+            }
+            ReplaceWith::Direction(p) => {
+                encoder.bytes.push(4);
+                encoder.parameter(p);
+            }
+            ReplaceWith::Markup(markup) => {
+                encoder.bytes.push(5);
+                encoder.string(markup);
+            }
+            ReplaceWith::Each(e) => {
+                encoder.bytes.push(11);
+                encoder.parameter(&e.parameter);
+                encoder.usize(e.body.len());
+                for part in &e.body {
+                    match part {
+                        EachPart::Markup(markup) => {
+                            encoder.bytes.push(0);
+                            encoder.string(markup);
+                        }
+                        EachPart::Field(p) => {
+                            encoder.bytes.push(1);
+                            encoder.parameter(p);
+                        }
+                        EachPart::Include(partial) => {
+                            encoder.bytes.push(2);
+                            encoder.partial(partial);
+                        }
+                    }
+                }
+            }
+            ReplaceWith::Slot(name) => {
+                encoder.bytes.push(7);
+                encoder.string(name);
+            }
+            ReplaceWith::If(p) => {
+                encoder.bytes.push(8);
+                encoder.parameter(p);
+            }
+            ReplaceWith::Else => encoder.bytes.push(9),
+            ReplaceWith::EndIf => encoder.bytes.push(10),
+            ReplaceWith::Message(m) => {
+                encoder.bytes.push(6);
+                encoder.string(&m.key);
+                match &m.text {
+                    None => encoder.bytes.push(0),
+                    Some(text) => {
+                        encoder.bytes.push(1);
+                        encoder.string(text);
+                    }
+                }
+            }
+            ReplaceWith::Plural(p) => {
+                encoder.bytes.push(3);
+                encoder.parameter(&p.parameter);
+                encoder.bytes.push(match p.rule {
+                    PluralRule::OneOther => 0,
+                    PluralRule::ZeroOneOther => 1,
+                    PluralRule::Invariant => 2,
+                    PluralRule::OneFewMany => 3,
+                });
+                encoder.usize(p.forms.len());
+                for form in &p.forms {
+                    encoder.string(form);
+                }
+            }
+        }
+    }
+
+    encoder.usize(compiled_template.computed.len());
+    for c in &compiled_template.computed {
+        encoder.string(&c.name);
+        encoder.type_(&c.variable_type);
+        encoder.expression(&c.expression);
+        encoder.string(&c.source);
+        encoder.position(c.source_start_pos);
+    }
+}
+
+/// Decodes a precompiled artifact into its [`CompiledTemplate`].
+pub(crate) fn decode(bytes: &[u8]) -> BalsaResult<CompiledTemplate> {
+    let mut decoder = Decoder { bytes };
+
+    if decoder.take(MAGIC.len())? != MAGIC {
+        return Err(invalid_data("not a precompiled Balsa template"));
+    }
+    if decoder.u8()? != VERSION {
+        return Err(invalid_data("unsupported precompiled template version"));
+    }
+
+    let mut compiled_template = decode_template(&mut decoder)?;
+
+    let mut partials = BTreeMap::new();
+    for _ in 0..decoder.usize()? {
+        let path = decoder.string()?;
+        partials.insert(path, decode_template(&mut decoder)?);
+    }
+
+    if !decoder.bytes.is_empty() {
+        return Err(invalid_data("unexpected trailing bytes"));
+    }
+
+    if std::iter::once(&compiled_template)
+        .chain(partials.values())
+        .flat_map(|t| &t.replacements)
+        .any(|r| match &r.replace_with {
+            ReplaceWith::Each(e) => e.body.iter().any(
+                |part| matches!(part, EachPart::Include(i) if !partials.contains_key(&i.path)),
+            ),
+            _ => false,
+        })
+    {
+        return Err(invalid_data("included template is missing"));
+    }
+
+    compiled_template.partials = partials;
+
+    Ok(compiled_template)
+}
+
+/// Decodes everything but the included templates of a [`CompiledTemplate`] from the `decoder`.
+fn decode_template(decoder: &mut Decoder) -> BalsaResult<CompiledTemplate> {
+    let segments = (0..decoder.usize()?)
+        .map(|_| decoder.string())
+        .collect::<BalsaResult<Vec<String>>>()?;
+    let end_pos = decoder.position()?;
+
+    let mut variables = HashMap::new();
+    for _ in 0..decoder.usize()? {
+        let name = decoder.string()?;
+        variables.insert(name, decoder.value(0)?);
+    }
+
+    let escape_policy = match decoder.u8()? {
+        0 => EscapePolicy::None,
+        1 => EscapePolicy::Html,
+        _ => return Err(invalid_data("invalid escape policy")),
+    };
+    let restore_crlf = match decoder.u8()? {
+        0 => false,
+        1 => true,
+        _ => return Err(invalid_data("invalid line ending flag")),
+    };
+
+    let mut replacements = Vec::new();
+    for _ in 0..decoder.usize()? {
+        let start_pos = decoder.position()?;
+        let end_pos = decoder.position()?;
+
+        if start_pos.offset > end_pos.offset {
+            return Err(invalid_data("replacement ends before it starts"));
+        }
+
+        let replace_with = match decoder.u8()? {
+            0 => ReplaceWith::Nothing,
+            1 => ReplaceWith::Parameter(decoder.parameter()?),
+            2 => ReplaceWith::Text(decoder.string()?),
+            3 => {
+                let parameter = decoder.parameter()?;
+                let rule = match decoder.u8()? {
+                    0 => PluralRule::OneOther,
+                    1 => PluralRule::ZeroOneOther,
+                    2 => PluralRule::Invariant,
+                    3 => PluralRule::OneFewMany,
+                    _ => return Err(invalid_data("invalid plural rule")),
+                };
+                let forms = (0..decoder.usize()?)
+                    .map(|_| decoder.string())
+                    .collect::<BalsaResult<Vec<String>>>()?;
+
+                if forms.len() != rule.form_count() || parameter.variable_type != BalsaType::Integer
+                {
+                    return Err(invalid_data("invalid plural"));
+                }
+
+                ReplaceWith::Plural(PluralDescription {
+                    parameter,
+                    rule,
+                    forms,
+                })
+            }
+            4 => {
+                let parameter = decoder.parameter()?;
+
+                if parameter.variable_type != BalsaType::String {
+                    return Err(invalid_data("invalid direction"));
+                }
+
+                ReplaceWith::Direction(parameter)
+            }
+            5 => ReplaceWith::Markup(decoder.string()?),
+            6 => ReplaceWith::Message(MessageDescription {
+                key: decoder.string()?,
+                text: match decoder.u8()? {
+                    0 => None,
+                    1 => Some(decoder.string()?),
+                    _ => return Err(invalid_data("invalid message")),
+                },
+            }),
+            7 => ReplaceWith::Slot(decoder.string()?),
+            8 => {
+                let parameter = decoder.parameter()?;
+
+                if parameter.variable_type != BalsaType::Boolean {
+                    return Err(invalid_data("invalid condition"));
+                }
+
+                ReplaceWith::If(parameter)
+            }
+            9 => ReplaceWith::Else,
+            10 => ReplaceWith::EndIf,
+            11 => {
+                let parameter = decoder.parameter()?;
+
+                if !matches!(&parameter.variable_type, BalsaType::Array(t) if matches!(**t, BalsaType::Dictionary(_)))
+                {
+                    return Err(invalid_data("invalid section"));
+                }
+
+                let body = (0..decoder.usize()?)
+                    .map(|_| match decoder.u8()? {
+                        0 => Ok(EachPart::Markup(decoder.string()?)),
+                        1 => Ok(EachPart::Field(Box::new(decoder.parameter()?))),
+                        2 => Ok(EachPart::Include(decoder.partial()?)),
+                        _ => Err(invalid_data("invalid section part")),
+                    })
+                    .collect::<BalsaResult<Vec<EachPart>>>()?;
+
+                ReplaceWith::Each(EachDescription { parameter, body })
+            }
+            _ => return Err(invalid_data("invalid replacement")),
+        };
+
+        replacements.push(ReplacementInstruction {
+            start_pos,
+            end_pos,
+            replace_with,
+        });
+    }
+
+    let mut computed: Vec<ComputedDescription> = Vec::new();
+    for _ in 0..decoder.usize()? {
+        let name = decoder.string()?;
+        let variable_type = decoder.type_(0)?;
+        let expression = decoder.expression(&computed, 0)?;
+
+        if !matches!(variable_type, BalsaType::Integer | BalsaType::Float)
+            || computed.iter().any(|c| c.name == name)
+        {
+            return Err(invalid_data("invalid computed parameter"));
+        }
+
+        computed.push(ComputedDescription {
+            name,
+            variable_type,
+            expression,
+            source: decoder.string()?,
+            source_start_pos: decoder.position()?,
+        });
+    }
+
+    let replacements = Compiler::optimize_replacements(replacements)?;
+
+    if segments.len() != replacements.len() + 1 {
+        return Err(invalid_data(
+            "static text doesn't match the replacement instructions",
+        ));
+    }
+
+    if replacements
+        .last()
+        .is_some_and(|r| r.end_pos.offset > end_pos.offset)
+    {
+        return Err(invalid_data(
+            "replacement extends past the end of the template",
+        ));
+    }
+
+    Ok(CompiledTemplate {
+        global_scope: Scope { variables },
+        replacements,
+        segments,
+        end_pos,
+        warnings: Vec::new(),
+        escape_policy,
+        restore_crlf,
+        computed,
+        filters: Filters::default(),
+        partials: BTreeMap::new(),
+    })
+}
+
+/// Creates a [`BalsaError::ReadTemplateError`] for a malformed precompiled template.
+fn invalid_data(message: &str) -> BalsaError {
+    BalsaError::read_template_error(io::Error::new(ErrorKind::InvalidData, message))
+}
+
+/// Appends encoded values to a byte buffer.
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn usize(&mut self, value: usize) {
+        self.bytes.extend((value as u64).to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.usize(value.len());
+        self.bytes.extend(value.as_bytes());
+    }
+
+    fn position(&mut self, pos: Position) {
+        self.usize(pos.offset);
+        self.usize(pos.line);
+        self.usize(pos.column);
+    }
+
+    fn partial(&mut self, partial: &PartialDescription) {
+        self.string(&partial.path);
+        self.usize(partial.arguments.len());
+        for (name, argument) in &partial.arguments {
+            self.string(name);
+            match argument {
+                PartialArgument::Field(field) => {
+                    self.bytes.push(0);
+                    self.string(field);
+                }
+                PartialArgument::Value(value) => {
+                    self.bytes.push(1);
+                    self.value(value);
+                }
+            }
+        }
+    }
+
+    fn parameter(&mut self, p: &ParameterDescription) {
+        self.string(&p.variable_name);
+        self.type_(&p.variable_type);
+        match &p.default_value {
+            None => self.bytes.push(0),
+            Some(value) => {
+                self.bytes.push(1);
+                self.value(value);
+            }
+        }
+        self.bytes.push(match p.direction {
+            None => 0,
+            Some(TextDirection::Ltr) => 1,
+            Some(TextDirection::Rtl) => 2,
+            Some(TextDirection::Auto) => 3,
+        });
+        self.bytes.push(u8::from(p.escape));
+        self.usize(p.image.accept.len());
+        for mime_type in &p.image.accept {
+            self.string(mime_type);
+        }
+        self.dimension(p.image.max_width);
+        self.dimension(p.image.max_height);
+        self.usize(p.one_of.len());
+        for choice in &p.one_of {
+            self.value(choice);
+        }
+        match &p.format {
+            None => self.bytes.push(0),
+            Some(format) => {
+                self.bytes.push(1);
+                self.string(format);
+            }
+        }
+        self.bytes.push(u8::from(p.safe));
+        self.bytes.push(u8::from(p.optional));
+        match &p.placeholder {
+            None => self.bytes.push(0),
+            Some(placeholder) => {
+                self.bytes.push(1);
+                self.string(placeholder);
+            }
+        }
+        self.usize(p.filters.len());
+        for filter in &p.filters {
+            self.string(filter);
+        }
+        self.limit(p.items.min_items);
+        self.limit(p.items.max_items);
+        match &p.items.item_label {
+            None => self.bytes.push(0),
+            Some(label) => {
+                self.bytes.push(1);
+                self.string(label);
+            }
+        }
+        self.usize(p.custom_options.len());
+        for (name, value) in &p.custom_options {
+            self.string(name);
+            self.value(value);
+        }
+        self.string(&p.source);
+        self.position(p.source_start_pos);
+        self.position(p.source_end_pos);
+    }
+
+    fn dimension(&mut self, dimension: Option<u32>) {
+        self.limit(dimension.map(|d| d as usize));
+    }
+
+    fn limit(&mut self, limit: Option<usize>) {
+        match limit {
+            None => self.bytes.push(0),
+            Some(n) => {
+                self.bytes.push(1);
+                self.usize(n);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Integer(i) => {
+                self.bytes.push(0);
+                self.bytes.extend(i.to_le_bytes());
+            }
+            Expression::Float(f) => {
+                self.bytes.push(1);
+                self.bytes.extend(f.to_le_bytes());
+            }
+            Expression::Parameter(name) => {
+                self.bytes.push(2);
+                self.string(name);
+            }
+            Expression::Computed(name) => {
+                self.bytes.push(3);
+                self.string(name);
+            }
+            Expression::Negate(operand) => {
+                self.bytes.push(4);
+                self.expression(operand);
+            }
+            Expression::Chain { first, rest } => {
+                self.bytes.push(5);
+                self.expression(first);
+                self.usize(rest.len());
+                for (operator, operand) in rest {
+                    self.bytes.push(match operator {
+                        Operator::Add => 0,
+                        Operator::Subtract => 1,
+                        Operator::Multiply => 2,
+                        Operator::Divide => 3,
+                        Operator::Remainder => 4,
+                    });
+                    self.expression(operand);
+                }
+            }
+        }
+    }
+
+    fn type_(&mut self, type_: &BalsaType) {
+        match type_ {
+            BalsaType::String => self.bytes.push(0),
+            BalsaType::Color => self.bytes.push(1),
+            BalsaType::Integer => self.bytes.push(2),
+            BalsaType::Float => self.bytes.push(3),
+            BalsaType::Currency => self.bytes.push(4),
+            BalsaType::MessageFormat => self.bytes.push(5),
+            BalsaType::Image => self.bytes.push(6),
+            BalsaType::Boolean => self.bytes.push(7),
+            BalsaType::Array(t) => {
+                self.bytes.push(8);
+                self.type_(t);
+            }
+            BalsaType::Dictionary(t) => {
+                self.bytes.push(9);
+                self.type_(t);
+            }
+            BalsaType::Any => self.bytes.push(10),
+            #[cfg(feature = "chrono")]
+            BalsaType::DateTime => self.bytes.push(11),
+            BalsaType::Url => self.bytes.push(12),
+        }
+    }
+
+    fn value(&mut self, value: &BalsaValue) {
+        match value {
+            BalsaValue::String(s) => {
+                self.bytes.push(0);
+                self.string(s);
+            }
+            BalsaValue::Color(c) => {
+                self.bytes.push(1);
+                self.string(c);
+            }
+            BalsaValue::Integer(i) => {
+                self.bytes.push(2);
+                self.bytes.extend(i.to_le_bytes());
+            }
+            BalsaValue::Float(f) => {
+                self.bytes.push(3);
+                self.bytes.extend(f.to_le_bytes());
+            }
+            BalsaValue::Currency(c) => {
+                self.bytes.push(4);
+                self.bytes.extend(c.amount().to_le_bytes());
+                self.string(c.code());
+            }
+            BalsaValue::MessageFormat(m) => {
+                self.bytes.push(5);
+                self.string(m);
+            }
+            BalsaValue::Image(i) => {
+                self.bytes.push(6);
+                self.string(i.src());
+                self.string(i.alt());
+                self.dimension(i.width());
+                self.dimension(i.height());
+            }
+            BalsaValue::Boolean(b) => {
+                self.bytes.push(7);
+                self.bytes.push(u8::from(*b));
+            }
+            #[cfg(feature = "chrono")]
+            BalsaValue::DateTime(d) => {
+                self.bytes.push(10);
+                self.string(&d.to_rfc3339());
+            }
+            BalsaValue::Url(u) => {
+                self.bytes.push(11);
+                self.string(u);
+            }
+            BalsaValue::Null => self.bytes.push(12),
+            BalsaValue::Array(a) => {
+                self.bytes.push(8);
+                self.type_(&a.get_type());
+                self.usize(a.len());
+                for value in a {
+                    self.value(value);
+                }
+            }
+            BalsaValue::Dictionary(d) => {
+                self.bytes.push(9);
+                self.type_(&d.get_type());
+                self.usize(d.len());
+                for (key, value) in d {
+                    self.string(key);
+                    self.value(value);
+                }
+            }
+        }
+    }
+}
+
+/// Reads encoded values from the front of a byte slice.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> BalsaResult<&'a [u8]> {
+        if self.bytes.len() < n {
+            return Err(invalid_data("unexpected end of precompiled template"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> BalsaResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64_bytes(&mut self) -> BalsaResult<[u8; 8]> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+
+        Ok(buf)
+    }
+
+    fn usize(&mut self) -> BalsaResult<usize> {
+        usize::try_from(u64::from_le_bytes(self.u64_bytes()?))
+            .map_err(|_| invalid_data("integer out of range"))
+    }
+
+    fn string(&mut self) -> BalsaResult<String> {
+        let len = self.usize()?;
+
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid_data("invalid UTF-8"))
+    }
+
+    fn position(&mut self) -> BalsaResult<Position> {
+        Ok(Position::new(self.usize()?, self.usize()?, self.usize()?))
+    }
+
+    fn partial(&mut self) -> BalsaResult<PartialDescription> {
+        let path = self.string()?;
+        let arguments = (0..self.usize()?)
+            .map(|_| {
+                let name = self.string()?;
+                let argument = match self.u8()? {
+                    0 => PartialArgument::Field(self.string()?),
+                    1 => PartialArgument::Value(self.value(0)?),
+                    _ => return Err(invalid_data("invalid include argument")),
+                };
+                Ok((name, argument))
+            })
+            .collect::<BalsaResult<Vec<(String, PartialArgument)>>>()?;
+
+        Ok(PartialDescription { path, arguments })
+    }
+
+    fn parameter(&mut self) -> BalsaResult<ParameterDescription> {
+        Ok(ParameterDescription {
+            variable_name: self.string()?,
+            variable_type: self.type_(0)?,
+            default_value: match self.u8()? {
+                0 => None,
+                1 => Some(self.value(0)?),
+                _ => return Err(invalid_data("invalid default value")),
+            },
+            direction: match self.u8()? {
+                0 => None,
+                1 => Some(TextDirection::Ltr),
+                2 => Some(TextDirection::Rtl),
+                3 => Some(TextDirection::Auto),
+                _ => return Err(invalid_data("invalid direction")),
+            },
+            escape: match self.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid_data("invalid escape flag")),
+            },
+            image: self.image()?,
+            one_of: (0..self.usize()?)
+                .map(|_| self.value(0))
+                .collect::<BalsaResult<Vec<BalsaValue>>>()?,
+            format: self.format()?,
+            safe: match self.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid_data("invalid safe flag")),
+            },
+            optional: match self.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid_data("invalid optional flag")),
+            },
+            placeholder: match self.u8()? {
+                0 => None,
+                1 => Some(self.string()?),
+                _ => return Err(invalid_data("invalid placeholder")),
+            },
+            filters: (0..self.usize()?)
+                .map(|_| self.string())
+                .collect::<BalsaResult<Vec<String>>>()?,
+            items: ItemsDescription {
+                min_items: self.limit()?,
+                max_items: self.limit()?,
+                item_label: match self.u8()? {
+                    0 => None,
+                    1 => Some(self.string()?),
+                    _ => return Err(invalid_data("invalid item label")),
+                },
+            },
+            custom_options: (0..self.usize()?)
+                .map(|_| Ok((self.string()?, self.value(0)?)))
+                .collect::<BalsaResult<Vec<(String, BalsaValue)>>>()?,
+            source: self.string()?,
+            source_start_pos: self.position()?,
+            source_end_pos: self.position()?,
+        })
+    }
+
+    fn image(&mut self) -> BalsaResult<ImageDescription> {
+        Ok(ImageDescription {
+            accept: (0..self.usize()?)
+                .map(|_| self.string())
+                .collect::<BalsaResult<Vec<String>>>()?,
+            max_width: self.dimension()?,
+            max_height: self.dimension()?,
+        })
+    }
+
+    fn dimension(&mut self) -> BalsaResult<Option<u32>> {
+        self.limit()?
+            .map(u32::try_from)
+            .transpose()
+            .map_err(|_| invalid_data("invalid dimension"))
+    }
+
+    /// Decodes the format of a date-time parameter, which must be valid as values are written
+    /// with it.
+    fn format(&mut self) -> BalsaResult<Option<String>> {
+        let format = match self.u8()? {
+            0 => return Ok(None),
+            1 => self.string()?,
+            _ => return Err(invalid_data("invalid format")),
+        };
+
+        #[cfg(feature = "chrono")]
+        if !is_valid_format(&format) {
+            return Err(invalid_data("invalid format"));
+        }
+
+        Ok(Some(format))
+    }
+
+    fn limit(&mut self) -> BalsaResult<Option<usize>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.usize()?)),
+            _ => Err(invalid_data("invalid limit")),
+        }
+    }
+
+    /// Decodes an expression nested `depth` levels deep, which may only read the `computed`
+    /// parameters declared before it.
+    fn expression(
+        &mut self,
+        computed: &[ComputedDescription],
+        depth: usize,
+    ) -> BalsaResult<Expression> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data("expression is nested too deeply"));
+        }
+
+        match self.u8()? {
+            0 => Ok(Expression::Integer(i64::from_le_bytes(self.u64_bytes()?))),
+            1 => Ok(Expression::Float(f64::from_le_bytes(self.u64_bytes()?))),
+            2 => Ok(Expression::Parameter(self.string()?)),
+            3 => {
+                let name = self.string()?;
+
+                if !computed.iter().any(|c| c.name == name) {
+                    return Err(invalid_data("undeclared computed parameter"));
+                }
+
+                Ok(Expression::Computed(name))
+            }
+            4 => Ok(Expression::Negate(Box::new(
+                self.expression(computed, depth + 1)?,
+            ))),
+            5 => {
+                let first = Box::new(self.expression(computed, depth + 1)?);
+                let rest = (0..self.usize()?)
+                    .map(|_| {
+                        let operator = match self.u8()? {
+                            0 => Operator::Add,
+                            1 => Operator::Subtract,
+                            2 => Operator::Multiply,
+                            3 => Operator::Divide,
+                            4 => Operator::Remainder,
+                            _ => return Err(invalid_data("invalid operator")),
+                        };
+
+                        Ok((operator, self.expression(computed, depth + 1)?))
+                    })
+                    .collect::<BalsaResult<Vec<(Operator, Expression)>>>()?;
+
+                Ok(Expression::Chain { first, rest })
+            }
+            _ => Err(invalid_data("invalid expression")),
+        }
+    }
+
+    /// Decodes a type nested `depth` levels deep.
+    fn type_(&mut self, depth: usize) -> BalsaResult<BalsaType> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data("type is nested too deeply"));
+        }
+
+        match self.u8()? {
+            0 => Ok(BalsaType::String),
+            1 => Ok(BalsaType::Color),
+            2 => Ok(BalsaType::Integer),
+            3 => Ok(BalsaType::Float),
+            4 => Ok(BalsaType::Currency),
+            5 => Ok(BalsaType::MessageFormat),
+            6 => Ok(BalsaType::Image),
+            7 => Ok(BalsaType::Boolean),
+            8 => Ok(BalsaType::array_of(self.type_(depth + 1)?)),
+            9 => Ok(BalsaType::dictionary_of(self.type_(depth + 1)?)),
+            10 => Ok(BalsaType::Any),
+            #[cfg(feature = "chrono")]
+            11 => Ok(BalsaType::DateTime),
+            12 => Ok(BalsaType::Url),
+            _ => Err(invalid_data("invalid type")),
+        }
+    }
+
+    /// Decodes a value nested `depth` levels deep.
+    fn value(&mut self, depth: usize) -> BalsaResult<BalsaValue> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data("value is nested too deeply"));
+        }
+
+        match self.u8()? {
+            0 => Ok(BalsaValue::String(self.string()?)),
+            1 => Ok(BalsaValue::Color(self.string()?)),
+            2 => Ok(BalsaValue::Integer(i64::from_le_bytes(self.u64_bytes()?))),
+            3 => Ok(BalsaValue::Float(f64::from_le_bytes(self.u64_bytes()?))),
+            4 => {
+                let amount = f64::from_le_bytes(self.u64_bytes()?);
+
+                Currency::checked(amount, &self.string()?)
+                    .map(BalsaValue::Currency)
+                    .ok_or_else(|| invalid_data("invalid currency"))
+            }
+            5 => {
+                let pattern = self.string()?;
+
+                message_format::parse(&pattern)
+                    .map(|_| BalsaValue::MessageFormat(pattern))
+                    .ok_or_else(|| invalid_data("invalid message format"))
+            }
+            6 => {
+                let image = Image::new(self.string()?, self.string()?)
+                    .with_dimensions(self.dimension()?, self.dimension()?);
+
+                Ok(BalsaValue::Image(image))
+            }
+            7 => match self.u8()? {
+                0 => Ok(BalsaValue::Boolean(false)),
+                1 => Ok(BalsaValue::Boolean(true)),
+                _ => Err(invalid_data("invalid boolean")),
+            },
+            8 => {
+                let type_ = self.type_(depth + 1)?;
+                let values = (0..self.usize()?)
+                    .map(|_| self.value(depth + 1))
+                    .collect::<BalsaResult<Vec<BalsaValue>>>()?;
+
+                Array::from_values(type_, values)
+                    .map(BalsaValue::Array)
+                    .map_err(|_| invalid_data("invalid array"))
+            }
+            9 => {
+                let type_ = self.type_(depth + 1)?;
+                let entries = (0..self.usize()?)
+                    .map(|_| Ok((self.string()?, self.value(depth + 1)?)))
+                    .collect::<BalsaResult<Vec<(String, BalsaValue)>>>()?;
+
+                Dictionary::from_entries(type_, entries)
+                    .map(BalsaValue::Dictionary)
+                    .map_err(|_| invalid_data("invalid dictionary"))
+            }
+            #[cfg(feature = "chrono")]
+            10 => parse_datetime(&self.string()?)
+                .map(BalsaValue::DateTime)
+                .ok_or_else(|| invalid_data("invalid date-time")),
+            11 => {
+                let url = self.string()?;
+
+                is_valid_url(&url)
+                    .then_some(BalsaValue::Url(url))
+                    .ok_or_else(|| invalid_data("invalid URL"))
+            }
+            12 => Ok(BalsaValue::Null),
+            _ => Err(invalid_data("invalid value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{balsa_parser, options::CompileOptions};
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let template = "{{@ title : string = \"héllo\", size : int = 3 }}\n<h1>{{ heading : string, defaultValue: title, label: \"Heading\" }}</h1>{{ size : int, defaultValue: size }}{{ plural count \"item\" \"items\" }}<p {{ dir \"ar\" }}>{{ t \"greeting\" }}{{ body : string, direction: \"rtl\" }}{{ widget : string, escape: false, filters: [\"trim\"] }}{{ align : string, oneOf: [\"left\", \"right\"] }}</p>{{@computed half : float = -(size + words) / 2.0 % 7 }}{{@computed twice : int = half * 2 }}{{ twice : int }}{{ hero : image, accept: \"image/png, image/webp\", maxWidth: 800 }}{{ link : url, safe: true, defaultValue: \"/about\" }}{{ subtitle : string, optional: true, placeholder: \"Untitled\" }}{{#slot \"footer\"}}{{#each posts: array<dict>, minItems: 1, maxItems: 5, itemLabel: \"Post\"}}<li>{{ name : string }} {{ views : int, defaultValue: 0 }}</li>{{/each}}{{if showMore, defaultValue: false}}<a>More</a>{{else}}<hr>{{endif}}";
+
+        let options = CompileOptions::new()
+            .escape_policy(EscapePolicy::Html)
+            .allow_missing_messages(true)
+            .allow_parameter_option("label")
+            .filter("trim", |v| {
+                Ok(BalsaValue::String(v.to_string().trim().to_string()))
+            });
+        let tokens = balsa_parser::BalsaParser::parse(template, &options).unwrap();
+        let mut compiled = Compiler::compile_from_tokens(template, &tokens, &options).unwrap();
+
+        let bytes = encode(&compiled);
+        let decoded = decode(&bytes).expect("Encoded template should decode");
+
+        compiled.warnings.clear();
+        compiled.filters = Filters::default();
+        assert_eq!(decoded, compiled, "Precompiled template did not round trip");
+
+        for len in 0..bytes.len() {
+            assert!(
+                decode(&bytes[..len]).is_err(),
+                "Truncated precompiled template of length {} should fail to decode",
+                len
+            );
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_datetime_round_trip() {
+        let template = "<time>{{ published : datetime, defaultValue: 2024-03-01T09:30:00.25+01:00, format: \"%Y-%m-%d\" }}</time>";
+
+        let options = CompileOptions::new();
+        let tokens = balsa_parser::BalsaParser::parse(template, &options).unwrap();
+        let compiled = Compiler::compile_from_tokens(template, &tokens, &options).unwrap();
+
+        assert_eq!(
+            decode(&encode(&compiled)).expect("Encoded template should decode"),
+            compiled,
+            "Precompiled template with a date-time did not round trip"
+        );
+    }
+}
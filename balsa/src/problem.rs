@@ -0,0 +1,351 @@
+use std::fmt::Display;
+
+use crate::{
+    errors::{BalsaCompileError, BalsaRenderError, LimitExceeded, TemplateErrorContext},
+    explain::ExplainedPosition,
+    BalsaError,
+};
+
+/// An [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) problem details object describing a
+/// [`BalsaError`], created with [`BalsaError::to_problem_json`].
+///
+/// Serializing it, e.g. with `serde_json`, produces an `application/problem+json` body which web
+/// backends can return to editors without mapping every error variant by hand. The `position`
+/// and `parameter` members are left out when they don't apply.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ProblemDetails {
+    /// A URI identifying the kind of error, i.e. `urn:balsa:error:undefined_variable`.
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// A short summary of the kind of error, which is the same for every error of that kind.
+    pub title: String,
+    /// The HTTP status code a backend would typically respond with.
+    pub status: u16,
+    /// A description of this occurrence of the error.
+    pub detail: String,
+    /// The kind of error as a machine-readable code, i.e. `undefined_variable`.
+    pub code: String,
+    /// Where in the template the error occurred, if it occurred in a template.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<ExplainedPosition>,
+    /// The name of the parameter, variable or computed parameter the error is about, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameter: Option<String>,
+}
+
+/// The status of errors caused by the template or the parameters it was rendered with.
+const UNPROCESSABLE_ENTITY: u16 = 422;
+
+impl ProblemDetails {
+    /// Creates a new [`ProblemDetails`] describing the error in `ctx`.
+    fn new<T: Display>(
+        code: &str,
+        title: &str,
+        status: u16,
+        ctx: &TemplateErrorContext<T>,
+        parameter: Option<&str>,
+    ) -> Self {
+        Self {
+            problem_type: format!("urn:balsa:error:{}", code),
+            title: title.to_string(),
+            status,
+            detail: ctx.error.to_string(),
+            code: code.to_string(),
+            position: Some(ExplainedPosition {
+                offset: ctx.pos,
+                line: ctx.line,
+                column: ctx.column,
+            }),
+            parameter: parameter.map(str::to_string),
+        }
+    }
+
+    /// Creates a new [`ProblemDetails`] describing a compile error.
+    fn compile_error(error: &BalsaCompileError) -> Self {
+        let status = UNPROCESSABLE_ENTITY;
+        match error {
+            BalsaCompileError::TemplateParseFail(e) => {
+                Self::new("parse_failed", "Template parse failed", status, e, None)
+            }
+            BalsaCompileError::InvalidTypeCast(e) => {
+                Self::new("invalid_type_cast", "Invalid type cast", status, e, None)
+            }
+            BalsaCompileError::InvalidTypeExpression(e) => {
+                Self::new("invalid_type_expression", "Invalid type", status, e, None)
+            }
+            BalsaCompileError::InvalidExpression(e) => {
+                Self::new("invalid_expression", "Invalid expression", status, e, None)
+            }
+            BalsaCompileError::InvalidIdentifierForParameterBlock(e) => Self::new(
+                "invalid_parameter_identifier",
+                "Invalid parameter name",
+                status,
+                e,
+                None,
+            ),
+            BalsaCompileError::InvalidIdentifierForDeclarationBlock(e) => Self::new(
+                "invalid_declaration_identifier",
+                "Invalid variable name",
+                status,
+                e,
+                None,
+            ),
+            BalsaCompileError::InvalidParameter(e) => Self::new(
+                "invalid_parameter",
+                "Invalid parameter option",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaCompileError::DuplicateOption(e) => {
+                Self::new("duplicate_option", "Duplicate option", status, e, None)
+            }
+            BalsaCompileError::UndefinedVariable(e) => Self::new(
+                "undefined_variable",
+                "Undefined variable",
+                status,
+                e,
+                Some(&e.error.variable_name),
+            ),
+            BalsaCompileError::ConflictingParameter(e) => Self::new(
+                "conflicting_parameter",
+                "Conflicting parameter",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaCompileError::UsedBeforeDeclaration(e) => Self::new(
+                "used_before_declaration",
+                "Variable used before declaration",
+                status,
+                e,
+                Some(&e.error.variable_name),
+            ),
+            BalsaCompileError::OverlappingBlocks(e) => {
+                Self::new("overlapping_blocks", "Overlapping blocks", status, e, None)
+            }
+            BalsaCompileError::InvalidHelperArguments(e) => Self::new(
+                "invalid_helper_arguments",
+                "Invalid helper arguments",
+                status,
+                e,
+                None,
+            ),
+            BalsaCompileError::MissingMessage(e) => {
+                Self::new("missing_message", "Missing message", status, e, None)
+            }
+            BalsaCompileError::InvalidDirection(e) => {
+                Self::new("invalid_direction", "Invalid direction", status, e, None)
+            }
+            BalsaCompileError::InvalidBoolean(e) => {
+                Self::new("invalid_boolean", "Invalid boolean", status, e, None)
+            }
+            BalsaCompileError::SandboxViolation(e) => {
+                Self::new("sandbox_violation", "Sandbox violation", status, e, None)
+            }
+            BalsaCompileError::InvalidComputedType(e) => Self::new(
+                "invalid_computed_type",
+                "Invalid computed parameter type",
+                status,
+                e,
+                Some(&e.error.computed_name),
+            ),
+            BalsaCompileError::DuplicateComputed(e) => Self::new(
+                "duplicate_computed",
+                "Duplicate computed parameter",
+                status,
+                e,
+                Some(&e.error.computed_name),
+            ),
+            BalsaCompileError::UndefinedMacro(e) => {
+                Self::new("undefined_macro", "Undefined macro", status, e, None)
+            }
+            BalsaCompileError::DuplicateMacro(e) => {
+                Self::new("duplicate_macro", "Duplicate macro", status, e, None)
+            }
+            BalsaCompileError::InvalidSectionType(e) => Self::new(
+                "invalid_section_type",
+                "Invalid section type",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaCompileError::InvalidItemLimits(e) => Self::new(
+                "invalid_item_limits",
+                "Invalid item limits",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaCompileError::ConflictingEscapePolicy(e) => Self::new(
+                "conflicting_escape_policy",
+                "Conflicting escape policy",
+                status,
+                e,
+                None,
+            ),
+            BalsaCompileError::UndefinedTemplate(e) => {
+                Self::new("undefined_template", "Undefined template", status, e, None)
+            }
+            BalsaCompileError::UnbalancedConditional(e) => Self::new(
+                "unbalanced_conditional",
+                "Unbalanced conditional",
+                status,
+                e,
+                None,
+            ),
+            BalsaCompileError::IncludeFailed(e) => {
+                Self::new("include_failed", "Include failed", status, e, None)
+            }
+            BalsaCompileError::UndefinedFilter(e) => {
+                Self::new("undefined_filter", "Undefined filter", status, e, None)
+            }
+            BalsaCompileError::TemplateFailed(e) => {
+                Self::new("template_failed", "Template failed", status, e, None)
+            }
+            BalsaCompileError::LimitExceeded(e) => {
+                Self::new("limit_exceeded", "Limit exceeded", 413, e, None)
+            }
+        }
+    }
+
+    /// Creates a new [`ProblemDetails`] describing a render error.
+    fn render_error(error: &BalsaRenderError) -> Self {
+        let status = UNPROCESSABLE_ENTITY;
+        match error {
+            BalsaRenderError::MissingParameter(e) => Self::new(
+                "missing_parameter",
+                "Missing parameter",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaRenderError::InvalidParameterType(e) => Self::new(
+                "invalid_parameter_type",
+                "Invalid parameter type",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaRenderError::MissingDefaults(e) => Self::new(
+                "missing_defaults",
+                "Missing default values",
+                status,
+                e,
+                e.error.parameter_names.first().map(String::as_str),
+            ),
+            BalsaRenderError::LimitExceeded(e) => {
+                let parameter = match &e.error {
+                    LimitExceeded::ValueSize { parameter_name, .. } => {
+                        Some(parameter_name.as_str())
+                    }
+                    LimitExceeded::OutputSize { .. }
+                    | LimitExceeded::ParameterCount { .. }
+                    | LimitExceeded::LoopIterations { .. }
+                    | LimitExceeded::IncludeDepth { .. } => None,
+                };
+
+                Self::new("limit_exceeded", "Limit exceeded", 413, e, parameter)
+            }
+            BalsaRenderError::Aborted(e) => {
+                Self::new("render_aborted", "Render aborted", 503, e, None)
+            }
+            BalsaRenderError::ComputationFailed(e) => Self::new(
+                "computation_failed",
+                "Computation failed",
+                status,
+                e,
+                Some(&e.error.computed_name),
+            ),
+            BalsaRenderError::LoadFailed(e) => Self::new(
+                "load_failed",
+                "Parameter failed to load",
+                502,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaRenderError::InvalidItemCount(e) => Self::new(
+                "invalid_item_count",
+                "Invalid number of items",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaRenderError::DisallowedValue(e) => Self::new(
+                "disallowed_value",
+                "Value not allowed",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaRenderError::UnsafeUrl(e) => Self::new(
+                "unsafe_url",
+                "Unsafe URL",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+            BalsaRenderError::FilterFailed(e) => Self::new(
+                "filter_failed",
+                "Filter failed",
+                status,
+                e,
+                Some(&e.error.parameter_name),
+            ),
+        }
+    }
+}
+
+impl BalsaError {
+    /// Returns an [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) problem details object
+    /// describing the error, with a machine-readable code, its message, and where it occurred in
+    /// the template, which serializes to an `application/problem+json` body.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use balsa::*;
+    /// let error = Balsa::from_string("<h1>{{ title : string, defaultValue: missing }}</h1>")
+    ///     .build()
+    ///     .unwrap_err();
+    /// let problem = error.to_problem_json();
+    ///
+    /// assert_eq!(problem.code, "undefined_variable");
+    /// assert_eq!(problem.status, 422);
+    /// assert_eq!(problem.parameter.as_deref(), Some("missing"));
+    /// ```
+    pub fn to_problem_json(&self) -> ProblemDetails {
+        match self {
+            BalsaError::ReadTemplateError(e) => ProblemDetails {
+                problem_type: "urn:balsa:error:read_template_error".to_string(),
+                title: "Template could not be read".to_string(),
+                status: 500,
+                detail: e.to_string(),
+                code: "read_template_error".to_string(),
+                position: None,
+                parameter: None,
+            },
+            BalsaError::CompileError(e) => ProblemDetails::compile_error(e),
+            BalsaError::MultipleCompileErrors(errors) => ProblemDetails {
+                problem_type: "urn:balsa:error:multiple_compile_errors".to_string(),
+                title: "Template failed to compile".to_string(),
+                status: UNPROCESSABLE_ENTITY,
+                detail: self.to_string(),
+                code: "multiple_compile_errors".to_string(),
+                position: errors
+                    .first()
+                    .and_then(|e| ProblemDetails::compile_error(e).position),
+                parameter: None,
+            },
+            BalsaError::RenderError(e) => ProblemDetails::render_error(e),
+            BalsaError::WriteOutputError(e) => ProblemDetails {
+                problem_type: "urn:balsa:error:write_output_error".to_string(),
+                title: "Output could not be written".to_string(),
+                status: 500,
+                detail: e.to_string(),
+                code: "write_output_error".to_string(),
+                position: None,
+                parameter: None,
+            },
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    AsParameters, BalsaBuilder, BalsaError, BalsaResult, BalsaTemplate, CompileOptions, FileLoader,
+    FileSource, RenderOptions, Template,
+};
+
+/// The templates of a registry keyed by their names, along with the errors of those which failed
+/// to compile since the registry started watching their directory.
+type Entries = BTreeMap<String, BalsaResult<Arc<Template>>>;
+
+/// Templates compiled once from a directory, which are looked up by their paths relative to it,
+/// e.g. `pages/home.html`.
+///
+/// Unless the [`CompileOptions`] set a loader, templates are compiled with a [`FileLoader`]
+/// rooted at the directory, so `{{include "partials/footer.html"}}` includes the template the
+/// registry knows by the same name. Clones share the compiled templates, so a registry can be
+/// built at startup and handed to every thread serving requests.
+///
+/// With the `notify` feature, [`BalsaRegistry::watch`] builds a registry which recompiles its
+/// templates whenever their files change, for development servers.
+///
+/// # Example
+/// ```rust,no_run
+/// # use balsa::*;
+/// let registry = BalsaRegistry::from_glob("templates", "**/*.html")?;
+///
+/// let params = BalsaParameters::new().string("title", "Home");
+/// let html = registry.render("pages/home.html", &params)?;
+/// # Ok::<(), BalsaError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BalsaRegistry {
+    entries: Arc<Mutex<Entries>>,
+    /// Watches the directory of a registry built with [`BalsaRegistry::watch`] until every clone
+    /// of the registry is dropped.
+    #[cfg(feature = "notify")]
+    _watcher: Option<Arc<notify::RecommendedWatcher>>,
+}
+
+impl BalsaRegistry {
+    /// Compiles every file in the directory at `root` and its subdirectories with the default
+    /// [`CompileOptions`].
+    ///
+    /// Fails with a [`crate::errors::BalsaCompileError::TemplateFailed`] error naming the first
+    /// template which fails to compile, or a [`BalsaError::ReadTemplateError`] if the directory
+    /// or one of its files can't be read.
+    pub fn from_dir(root: impl AsRef<Path>) -> BalsaResult<Self> {
+        Self::from_dir_with_options(root, &CompileOptions::default())
+    }
+
+    /// Compiles every file in the directory at `root` and its subdirectories with the provided
+    /// [`CompileOptions`]. See [`BalsaRegistry::from_dir`].
+    pub fn from_dir_with_options(
+        root: impl AsRef<Path>,
+        options: &CompileOptions,
+    ) -> BalsaResult<Self> {
+        Self::from_glob_with_options(root, "**", options)
+    }
+
+    /// Compiles the files in the directory at `root` whose paths relative to it match `pattern`
+    /// with the default [`CompileOptions`]. See [`BalsaRegistry::from_dir`].
+    ///
+    /// Patterns are matched against `/`-separated paths like `pages/home.html`: `*` matches any
+    /// part of a single directory or file name, `?` any single character, and `**` any number of
+    /// directories.
+    pub fn from_glob(root: impl AsRef<Path>, pattern: &str) -> BalsaResult<Self> {
+        Self::from_glob_with_options(root, pattern, &CompileOptions::default())
+    }
+
+    /// Compiles the files in the directory at `root` whose paths relative to it match `pattern`
+    /// with the provided [`CompileOptions`]. See [`BalsaRegistry::from_glob`].
+    pub fn from_glob_with_options(
+        root: impl AsRef<Path>,
+        pattern: &str,
+        options: &CompileOptions,
+    ) -> BalsaResult<Self> {
+        let root = root.as_ref();
+        let compiled = compile_entries(root, pattern, &registry_options(root, options))
+            .map_err(BalsaError::read_template_error)?;
+
+        // Templates are never recompiled unless the directory is watched, so all of them must
+        // compile.
+        let mut entries = BTreeMap::new();
+        for (name, template) in compiled {
+            entries.insert(name, Ok(template?));
+        }
+
+        Ok(Self {
+            entries: Arc::new(Mutex::new(entries)),
+            #[cfg(feature = "notify")]
+            _watcher: None,
+        })
+    }
+
+    /// Compiles the files in the directory at `root` whose paths relative to it match `pattern`
+    /// like [`BalsaRegistry::from_glob_with_options`], then recompiles them whenever a file in
+    /// the directory is created, changed or removed, so that a development server picks up
+    /// edits without restarting.
+    ///
+    /// Templates which fail to compile don't fail the registry. Instead, rendering them returns
+    /// their error until they are fixed. Fails only if the directory can't be read or watched.
+    /// The directory is watched until every clone of the registry is dropped.
+    #[cfg(feature = "notify")]
+    pub fn watch(
+        root: impl AsRef<Path>,
+        pattern: &str,
+        options: &CompileOptions,
+    ) -> BalsaResult<Self> {
+        use notify::Watcher;
+
+        let root = root.as_ref().to_path_buf();
+        let options = registry_options(&root, options);
+        let entries =
+            compile_entries(&root, pattern, &options).map_err(BalsaError::read_template_error)?;
+        let entries = Arc::new(Mutex::new(entries));
+
+        let watched = Arc::clone(&entries);
+        let watched_root = root.clone();
+        let pattern = pattern.to_string();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                // Compiling the templates reads them, which must not compile them again.
+                if matches!(&event, Ok(event) if event.kind.is_access()) {
+                    return;
+                }
+
+                // The templates are kept as they were if the directory can't be read.
+                if let Ok(recompiled) = compile_entries(&watched_root, &pattern, &options) {
+                    *lock(&watched) = recompiled;
+                }
+            })
+            .map_err(watch_error)?;
+        watcher
+            .watch(&root, notify::RecursiveMode::Recursive)
+            .map_err(watch_error)?;
+
+        Ok(Self {
+            entries,
+            _watcher: Some(Arc::new(watcher)),
+        })
+    }
+
+    /// Returns the template named `name`, if there is one which compiled successfully.
+    pub fn get(&self, name: &str) -> Option<Arc<Template>> {
+        match lock(&self.entries).get(name) {
+            Some(Ok(template)) => Some(Arc::clone(template)),
+            _ => None,
+        }
+    }
+
+    /// Returns the names of the templates in the registry, in order.
+    pub fn names(&self) -> Vec<String> {
+        lock(&self.entries).keys().cloned().collect()
+    }
+
+    /// Returns the number of templates in the registry.
+    pub fn len(&self) -> usize {
+        lock(&self.entries).len()
+    }
+
+    /// Returns `true` if the registry has no templates.
+    pub fn is_empty(&self) -> bool {
+        lock(&self.entries).is_empty()
+    }
+
+    /// Renders the template named `name` with the specified `params` argument.
+    ///
+    /// Fails with a [`crate::errors::BalsaCompileError::UndefinedTemplate`] error if there is
+    /// no template named `name`, or the error the template last failed to compile with if the
+    /// registry is watching its directory.
+    pub fn render<T: AsParameters>(&self, name: &str, params: &T) -> BalsaResult<String> {
+        self.render_with_options(name, params, &RenderOptions::default())
+    }
+
+    /// Renders the template named `name` with the specified `params` argument and
+    /// [`RenderOptions`]. See [`BalsaRegistry::render`].
+    pub fn render_with_options<T: AsParameters>(
+        &self,
+        name: &str,
+        params: &T,
+        options: &RenderOptions,
+    ) -> BalsaResult<String> {
+        // The lock is released before rendering, so a slow render doesn't hold up a reload.
+        let template = match lock(&self.entries).get(name) {
+            Some(Ok(template)) => Arc::clone(template),
+            Some(Err(e)) => return Err(e.duplicate()),
+            None => return Err(BalsaError::undefined_template(name.to_string())),
+        };
+
+        template.render_html_string_with_options(params, options)
+    }
+}
+
+/// Returns the `options` templates in the directory at `root` are compiled with, which load
+/// included templates from the directory unless they set a loader.
+fn registry_options(root: &Path, options: &CompileOptions) -> CompileOptions {
+    match options.loader {
+        Some(_) => options.clone(),
+        None => options.clone().loader(FileLoader::new(root)),
+    }
+}
+
+/// Compiles the files in the directory at `root` whose paths match `pattern`, keyed by their
+/// names. Fails only if the directory can't be read.
+fn compile_entries(root: &Path, pattern: &str, options: &CompileOptions) -> io::Result<Entries> {
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let mut entries = BTreeMap::new();
+    for file in files {
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let segments: Vec<&str> = name.split('/').collect();
+
+        if !glob_matches(&pattern, &segments) {
+            continue;
+        }
+
+        let template = BalsaBuilder {
+            template_source: FileSource { path: file.clone() },
+            options: options.clone(),
+        }
+        .build_once()
+        .map(Arc::new)
+        .map_err(|e| BalsaError::template_failed(name.clone(), e));
+
+        entries.insert(name, template);
+    }
+
+    Ok(entries)
+}
+
+/// Creates a [`BalsaError::ReadTemplateError`] for a directory which couldn't be watched.
+#[cfg(feature = "notify")]
+fn watch_error(error: notify::Error) -> BalsaError {
+    BalsaError::read_template_error(io::Error::other(error))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // The templates are swapped out whole, so a poisoned lock can be recovered.
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Pushes the paths of every file in `dir` and its subdirectories onto `files`.
+pub(crate) fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the segments of a path match the segments of a glob `pattern`, where `**` matches any
+/// number of segments.
+fn glob_matches(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (Some((&"**", rest)), _) => {
+            glob_matches(rest, path) || (!path.is_empty() && glob_matches(pattern, &path[1..]))
+        }
+        (Some((p, pattern)), Some((s, path))) => {
+            let p: Vec<char> = p.chars().collect();
+            let s: Vec<char> = s.chars().collect();
+
+            segment_matches(&p, &s) && glob_matches(pattern, path)
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Whether a single path `segment` matches a `pattern` segment, where `*` matches any number of
+/// characters and `?` any single character.
+fn segment_matches(pattern: &[char], segment: &[char]) -> bool {
+    match (pattern.split_first(), segment.split_first()) {
+        (Some(('*', rest)), _) => {
+            segment_matches(rest, segment)
+                || (!segment.is_empty() && segment_matches(pattern, &segment[1..]))
+        }
+        (Some(('?', pattern)), Some((_, segment))) => segment_matches(pattern, segment),
+        (Some((p, pattern)), Some((s, segment))) => p == s && segment_matches(pattern, segment),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        let matches = |pattern: &str, path: &str| {
+            glob_matches(
+                &pattern.split('/').collect::<Vec<_>>(),
+                &path.split('/').collect::<Vec<_>>(),
+            )
+        };
+
+        assert!(matches("**", "index.html"));
+        assert!(matches("**", "pages/blog/post.html"));
+        assert!(matches("**/*.html", "index.html"));
+        assert!(matches("**/*.html", "pages/blog/post.html"));
+        assert!(matches("pages/*.html", "pages/home.html"));
+        assert!(matches("pages/**/post.html", "pages/post.html"));
+        assert!(matches("pages/h?me.*", "pages/home.html"));
+
+        assert!(!matches("**/*.html", "pages/notes.txt"));
+        assert!(!matches("pages/*.html", "pages/blog/post.html"));
+        assert!(!matches("pages/*.html", "partials/footer.html"));
+        assert!(!matches("pages/h?me.html", "pages/hme.html"));
+        assert!(!matches("*", "pages/home.html"));
+    }
+}
@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+/// The output of [`crate::Template::render_with_report`], along with metrics describing the
+/// render.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RenderReport {
+    /// The rendered template.
+    pub output: String,
+    /// The size of the output in bytes.
+    pub output_size: usize,
+    /// The time spent converting the parameters with [`crate::AsParameters::as_parameters`].
+    pub parameters_time: Duration,
+    /// The time spent checking the parameters against the render's limits and rendering the
+    /// template.
+    pub render_time: Duration,
+    /// The parameters whose passed values were used, in the order they were first used.
+    pub used_parameters: Vec<String>,
+    /// The parameters which weren't passed and fell back to their default values, in the order
+    /// they were first used.
+    pub defaulted_parameters: Vec<String>,
+}
@@ -0,0 +1,43 @@
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+use crate::{balsa_renderer::RenderSteps, BalsaResult};
+
+/// A render which yields its output in chunks as it is produced, created with
+/// [`crate::Template::render_stream`].
+///
+/// Each chunk holds the output of one block along with the static text preceding it, and the
+/// final chunk holds the text after the last block. Nothing is rendered until the next chunk is
+/// polled, so a slow consumer never causes output to pile up in memory. The stream ends after
+/// the first error.
+pub struct RenderStream<'a> {
+    steps: RenderSteps<'a>,
+}
+
+impl<'a> RenderStream<'a> {
+    pub(crate) fn new(steps: RenderSteps<'a>) -> Self {
+        Self { steps }
+    }
+}
+
+impl Stream for RenderStream<'_> {
+    type Item = BalsaResult<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = self.get_mut().steps.next();
+
+        Poll::Ready(chunk.map(|chunk| chunk.map(Bytes::from)))
+    }
+}
+
+impl fmt::Debug for RenderStream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderStream").finish_non_exhaustive()
+    }
+}
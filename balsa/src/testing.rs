@@ -0,0 +1,261 @@
+use std::{fs, path::Path};
+
+use crate::{
+    balsa_compiler::{EachPart, ParameterDescription, ReplaceWith},
+    Array, BalsaParameters, BalsaType, BalsaValue, Currency, Dictionary, Image, Template,
+};
+
+/// The environment variable which makes [`assert_snapshot`] overwrite snapshots with the actual
+/// output rather than comparing against them, e.g. `BALSA_UPDATE_SNAPSHOTS=1 cargo test`.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "BALSA_UPDATE_SNAPSHOTS";
+
+/// Asserts that a template renders to the expected output with the provided parameters,
+/// panicking with the render error if it fails to render.
+///
+/// Works with any template implementing [`crate::BalsaTemplate`] for the parameters.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// use balsa::testing::assert_renders_to;
+///
+/// let template = Balsa::from_string("<h1>{{ title : string }}</h1>").build()?;
+///
+/// assert_renders_to!(template, BalsaParameters::new().string("title", "Hi"), "<h1>Hi</h1>");
+/// # Ok::<(), BalsaError>(())
+/// ```
+#[macro_export]
+macro_rules! assert_renders_to {
+    ($template:expr, $params:expr, $expected:expr $(,)?) => {{
+        use $crate::BalsaTemplate as _;
+
+        match $template.render_html_string(&$params) {
+            Ok(output) => assert_eq!(output, $expected, "template rendered unexpected output"),
+            Err(e) => panic!("template failed to render: {}", e),
+        }
+    }};
+}
+
+pub use crate::assert_renders_to;
+
+/// Asserts that `actual` matches the golden file at `path`, so that large rendered pages can be
+/// checked without writing their output into the test.
+///
+/// The file is written with `actual` instead if it doesn't exist yet, or if the
+/// [`UPDATE_SNAPSHOTS_VAR`] environment variable is set, so that snapshots can be reviewed and
+/// committed like any other change.
+///
+/// # Panics
+///
+/// Panics if the file's contents differ from `actual`, showing the first line which differs, or
+/// if the file can't be read or written.
+#[track_caller]
+pub fn assert_snapshot(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() || !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .unwrap_or_else(|e| panic!("failed to create `{}`: {}", parent.display(), e));
+        }
+
+        fs::write(path, actual)
+            .unwrap_or_else(|e| panic!("failed to write snapshot `{}`: {}", path.display(), e));
+        return;
+    }
+
+    let expected = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("failed to read snapshot `{}`: {}", path.display(), e));
+    if expected == actual {
+        return;
+    }
+
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (e, a) => panic!(
+                "output differs from snapshot `{}` at line {}\n  expected: {}\n    actual: {}\nset {} to update the snapshot",
+                path.display(),
+                line,
+                e.unwrap_or("<end of snapshot>"),
+                a.unwrap_or("<end of output>"),
+                UPDATE_SNAPSHOTS_VAR
+            ),
+        }
+    }
+}
+
+/// Returns a value for every parameter of `template` which has no default value, and for every
+/// `#each` section, so that templates can be rendered in tests without writing out their
+/// parameters.
+///
+/// The values only depend on the names and types of the parameters: strings are the parameter's
+/// name in brackets, i.e. `[title]`, numbers are `1`, and arrays have as few items as their
+/// `minItems` allows, but at least one if their `maxItems` allows it. Conditions are `true`, so
+/// that every conditional section is rendered, locales for `dir` blocks are `en`, and date-times
+/// are the Unix epoch.
+///
+/// # Example
+/// ```rust
+/// # use balsa::*;
+/// use balsa::testing::placeholder_parameters;
+///
+/// let template = Balsa::from_string("<h1>{{ title : string }}</h1><p>{{ count : int }}</p>")
+///     .build()?;
+///
+/// let params = placeholder_parameters(&template);
+/// assert_eq!(template.render_html_string(&params)?, "<h1>[title]</h1><p>1</p>");
+/// # Ok::<(), BalsaError>(())
+/// ```
+pub fn placeholder_parameters(template: &Template) -> BalsaParameters {
+    let compiled_template = &template.compiled_template;
+    let mut parameters = BalsaParameters::new();
+
+    for replacement in &compiled_template.replacements {
+        let p = match replacement.replace_with.parameter() {
+            Some(p) => p,
+            None => continue,
+        };
+
+        // Sections always default to no items, which may be fewer than their `minItems`.
+        let is_section = matches!(replacement.replace_with, ReplaceWith::Each(_));
+        if (!is_section && compiled_template.default_value(&p.variable_name).is_some())
+            || compiled_template.computed(&p.variable_name).is_some()
+            || parameters.get(&p.variable_name).is_some()
+        {
+            continue;
+        }
+
+        let value = match &replacement.replace_with {
+            ReplaceWith::Direction(_) => BalsaValue::String("en".to_string()),
+            ReplaceWith::Each(e) => {
+                let fields: Vec<&ParameterDescription> = e
+                    .body
+                    .iter()
+                    .filter_map(|part| match part {
+                        EachPart::Field(field) => Some(field.as_ref()),
+                        EachPart::Markup(_) | EachPart::Include(_) => None,
+                    })
+                    .collect();
+                let item = Dictionary::from_entries(
+                    BalsaType::Any,
+                    fields.iter().map(|field| {
+                        let value = field.default_value.clone().unwrap_or_else(|| {
+                            placeholder(&field.variable_name, &field.variable_type)
+                        });
+
+                        (field.variable_name.clone(), value)
+                    }),
+                )
+                .expect("any value can be stored in a dictionary of any type");
+
+                let items = vec![BalsaValue::Dictionary(item); item_count(p)];
+                BalsaValue::Array(
+                    Array::from_values(element_type(&p.variable_type), items)
+                        .expect("items are dictionaries of any type"),
+                )
+            }
+            _ => match &p.variable_type {
+                BalsaType::Array(_) => {
+                    let element_type = element_type(&p.variable_type);
+                    let items = vec![placeholder(&p.variable_name, &element_type); item_count(p)];
+
+                    BalsaValue::Array(
+                        Array::from_values(element_type, items)
+                            .expect("placeholders have the type they were created for"),
+                    )
+                }
+                type_ => placeholder(&p.variable_name, type_),
+            },
+        };
+
+        parameters = parameters.insert(p.variable_name.clone(), value);
+    }
+
+    parameters
+}
+
+/// Returns the placeholder value of type `type_` for the parameter `name`.
+fn placeholder(name: &str, type_: &BalsaType) -> BalsaValue {
+    match type_ {
+        BalsaType::String | BalsaType::MessageFormat | BalsaType::Any => {
+            BalsaValue::String(format!("[{}]", name))
+        }
+        BalsaType::Color => BalsaValue::Color("#000000".to_string()),
+        BalsaType::Integer => BalsaValue::Integer(1),
+        BalsaType::Float => BalsaValue::Float(1.0),
+        BalsaType::Boolean => BalsaValue::Boolean(true),
+        BalsaType::Currency => BalsaValue::Currency(Currency::new(1.0, "USD")),
+        BalsaType::Image => BalsaValue::Image(Image::new(
+            format!("/placeholder/{}.png", name),
+            format!("[{}]", name),
+        )),
+        BalsaType::Url => BalsaValue::Url(format!("/placeholder/{}", name)),
+        #[cfg(feature = "chrono")]
+        BalsaType::DateTime => BalsaValue::DateTime(chrono::DateTime::UNIX_EPOCH.fixed_offset()),
+        BalsaType::Array(element_type) => BalsaValue::Array(Array::new((**element_type).clone())),
+        BalsaType::Dictionary(value_type) => {
+            BalsaValue::Dictionary(Dictionary::new((**value_type).clone()))
+        }
+    }
+}
+
+/// Returns the type of the items of the array type `type_`.
+fn element_type(type_: &BalsaType) -> BalsaType {
+    match type_ {
+        BalsaType::Array(element_type) => (**element_type).clone(),
+        _ => unreachable!("only array parameters have items"),
+    }
+}
+
+/// Returns the number of items the placeholder of the array parameter `p` has.
+fn item_count(p: &ParameterDescription) -> usize {
+    let count = p.items.min_items.unwrap_or(0).max(1);
+
+    p.items
+        .max_items
+        .map_or(count, |max_items| count.min(max_items))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Balsa;
+
+    use super::*;
+
+    #[test]
+    fn test_placeholder_parameters() {
+        let template = Balsa::from_string(
+            "<p {{ dir locale }}>{{ title : string }} {{ title : string }} {{ price : currency }} {{ subtitle : string, defaultValue: \"Sub\" }}</p>{{ hero : image }}{{#each posts: array<dict>, minItems: 2}}<li>{{ heading : string }} ({{ views : int }})</li>{{/each}}",
+        )
+        .build()
+        .unwrap();
+
+        assert_renders_to!(
+            template,
+            placeholder_parameters(&template),
+            "<p dir=\"ltr\">[title] [title] 1.00 USD Sub</p><img src=\"/placeholder/hero.png\" alt=\"[hero]\"><li>[heading] (1)</li><li>[heading] (1)</li>"
+        );
+    }
+
+    #[test]
+    fn test_assert_snapshot() {
+        let path = std::env::temp_dir()
+            .join(format!("balsa-snapshots-{}", std::process::id()))
+            .join("page.html");
+
+        assert_snapshot(&path, "<h1>Hi</h1>\n<p>Hello</p>\n");
+        assert_snapshot(&path, "<h1>Hi</h1>\n<p>Hello</p>\n");
+        let mismatch = std::panic::catch_unwind(|| {
+            assert_snapshot(&path, "<h1>Hi</h1>\n<p>Bye</p>\n");
+        });
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        let message = mismatch.unwrap_err();
+        let message = message.downcast_ref::<String>().unwrap();
+        assert!(message.contains("at line 2\n  expected: <p>Hello</p>\n    actual: <p>Bye</p>"));
+    }
+}
@@ -1,18 +1,264 @@
-use regex::Regex;
-
 // TODO: doesn't reject values like `rgb(355, 255, 255)`
-/// RegEx for matching CSS color types (hex, rgb, hsl, color names).
-///
-/// Thanks to Olmo Kramer and Anton Frattaroli!
-/// Slightly modified from: https://gist.github.com/olmokramer/82ccce673f86db7cda5e#gistcomment-3227016
-const CSS_COLOR_REGEX: &str = r"^(#(?:[0-9a-f]{2}){2,4}$|(#[0-9a-f]{3}$)|(rgb|hsl)a?\((-?\d+%?[,\s]+){2,3}\s*[\d\.]+%?\)$|black$|silver$|gray$|whitesmoke$|maroon$|red$|purple$|fuchsia$|green$|lime$|olivedrab$|yellow$|navy$|blue$|teal$|aquamarine$|orange$|aliceblue$|antiquewhite$|aqua$|azure$|beige$|bisque$|blanchedalmond$|blueviolet$|brown$|burlywood$|cadetblue$|chartreuse$|chocolate$|coral$|cornflowerblue$|cornsilk$|crimson$|currentcolor$|darkblue$|darkcyan$|darkgoldenrod$|darkgray$|darkgreen$|darkgrey$|darkkhaki$|darkmagenta$|darkolivegreen$|darkorange$|darkorchid$|darkred$|darksalmon$|darkseagreen$|darkslateblue$|darkslategray$|darkslategrey$|darkturquoise$|darkviolet$|deeppink$|deepskyblue$|dimgray$|dimgrey$|dodgerblue$|firebrick$|floralwhite$|forestgreen$|gainsboro$|ghostwhite$|goldenrod$|gold$|greenyellow$|grey$|honeydew$|hotpink$|indianred$|indigo$|ivory$|khaki$|lavenderblush$|lavender$|lawngreen$|lemonchiffon$|lightblue$|lightcoral$|lightcyan$|lightgoldenrodyellow$|lightgray$|lightgreen$|lightgrey$|lightpink$|lightsalmon$|lightseagreen$|lightskyblue$|lightslategray$|lightslategrey$|lightsteelblue$|lightyellow$|limegreen$|linen$|mediumaquamarine$|mediumblue$|mediumorchid$|mediumpurple$|mediumseagreen$|mediumslateblue$|mediumspringgreen$|mediumturquoise$|mediumvioletred$|midnightblue$|mintcream$|mistyrose$|moccasin$|navajowhite$|oldlace$|olive$|orangered$|orchid$|palegoldenrod$|palegreen$|paleturquoise$|palevioletred$|papayawhip$|peachpuff$|peru$|pink$|plum$|powderblue$|rosybrown$|royalblue$|saddlebrown$|salmon$|sandybrown$|seagreen$|seashell$|sienna$|skyblue$|slateblue$|slategray$|slategrey$|snow$|springgreen$|steelblue$|tan$|thistle$|tomato$|transparent$|turquoise$|violet$|wheat$|white$|yellowgreen$|rebeccapurple$)";
-
-/// Validates that a color matches a CSS-accepted color standard.
+/// Validates that a color matches a CSS-accepted color standard: a 3, 4, 6 or 8 digit lowercase
+/// hex code, an `rgb()`, `rgba()`, `hsl()` or `hsla()` function, or a color name.
 pub(crate) fn is_valid_color(color: &str) -> bool {
-    let regex =
-        Regex::new(CSS_COLOR_REGEX).expect("error parsing CSS color regex for `validate_color`");
+    if let Some(hex) = color.strip_prefix('#') {
+        return matches!(hex.len(), 3 | 4 | 6 | 8)
+            && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    }
+
+    if let Some(arguments) = color_function_arguments(color) {
+        return is_valid_color_arguments(arguments);
+    }
+
+    CSS_COLOR_NAMES.binary_search(&color).is_ok()
+}
 
-    regex.is_match(color)
+/// The CSS color names accepted as colors, sorted so they can be binary searched.
+const CSS_COLOR_NAMES: &[&str] = &[
+    "aliceblue",
+    "antiquewhite",
+    "aqua",
+    "aquamarine",
+    "azure",
+    "beige",
+    "bisque",
+    "black",
+    "blanchedalmond",
+    "blue",
+    "blueviolet",
+    "brown",
+    "burlywood",
+    "cadetblue",
+    "chartreuse",
+    "chocolate",
+    "coral",
+    "cornflowerblue",
+    "cornsilk",
+    "crimson",
+    "currentcolor",
+    "darkblue",
+    "darkcyan",
+    "darkgoldenrod",
+    "darkgray",
+    "darkgreen",
+    "darkgrey",
+    "darkkhaki",
+    "darkmagenta",
+    "darkolivegreen",
+    "darkorange",
+    "darkorchid",
+    "darkred",
+    "darksalmon",
+    "darkseagreen",
+    "darkslateblue",
+    "darkslategray",
+    "darkslategrey",
+    "darkturquoise",
+    "darkviolet",
+    "deeppink",
+    "deepskyblue",
+    "dimgray",
+    "dimgrey",
+    "dodgerblue",
+    "firebrick",
+    "floralwhite",
+    "forestgreen",
+    "fuchsia",
+    "gainsboro",
+    "ghostwhite",
+    "gold",
+    "goldenrod",
+    "gray",
+    "green",
+    "greenyellow",
+    "grey",
+    "honeydew",
+    "hotpink",
+    "indianred",
+    "indigo",
+    "ivory",
+    "khaki",
+    "lavender",
+    "lavenderblush",
+    "lawngreen",
+    "lemonchiffon",
+    "lightblue",
+    "lightcoral",
+    "lightcyan",
+    "lightgoldenrodyellow",
+    "lightgray",
+    "lightgreen",
+    "lightgrey",
+    "lightpink",
+    "lightsalmon",
+    "lightseagreen",
+    "lightskyblue",
+    "lightslategray",
+    "lightslategrey",
+    "lightsteelblue",
+    "lightyellow",
+    "lime",
+    "limegreen",
+    "linen",
+    "maroon",
+    "mediumaquamarine",
+    "mediumblue",
+    "mediumorchid",
+    "mediumpurple",
+    "mediumseagreen",
+    "mediumslateblue",
+    "mediumspringgreen",
+    "mediumturquoise",
+    "mediumvioletred",
+    "midnightblue",
+    "mintcream",
+    "mistyrose",
+    "moccasin",
+    "navajowhite",
+    "navy",
+    "oldlace",
+    "olive",
+    "olivedrab",
+    "orange",
+    "orangered",
+    "orchid",
+    "palegoldenrod",
+    "palegreen",
+    "paleturquoise",
+    "palevioletred",
+    "papayawhip",
+    "peachpuff",
+    "peru",
+    "pink",
+    "plum",
+    "powderblue",
+    "purple",
+    "rebeccapurple",
+    "red",
+    "rosybrown",
+    "royalblue",
+    "saddlebrown",
+    "salmon",
+    "sandybrown",
+    "seagreen",
+    "seashell",
+    "sienna",
+    "silver",
+    "skyblue",
+    "slateblue",
+    "slategray",
+    "slategrey",
+    "snow",
+    "springgreen",
+    "steelblue",
+    "tan",
+    "teal",
+    "thistle",
+    "tomato",
+    "transparent",
+    "turquoise",
+    "violet",
+    "wheat",
+    "white",
+    "whitesmoke",
+    "yellow",
+    "yellowgreen",
+];
+
+/// Returns the arguments between the parentheses of an `rgb()`, `rgba()`, `hsl()` or `hsla()`
+/// color function.
+fn color_function_arguments(color: &str) -> Option<&str> {
+    let rest = color
+        .strip_prefix("rgb")
+        .or_else(|| color.strip_prefix("hsl"))?;
+    let rest = rest.strip_prefix('a').unwrap_or(rest);
+
+    rest.strip_prefix('(')?.strip_suffix(')')
+}
+
+/// Validates the arguments of a color function, which are two or three integers or percentages
+/// followed by a number or percentage, separated by commas or whitespace.
+fn is_valid_color_arguments(arguments: &str) -> bool {
+    let mut rest = arguments;
+    let mut count = 0;
+
+    while count < 3 {
+        match leading_color_argument(rest) {
+            Some(after) => {
+                rest = after;
+                count += 1;
+            }
+            None => break,
+        }
+    }
+
+    let last = rest.trim_start();
+    let last = last.strip_suffix('%').unwrap_or(last);
+
+    count >= 2 && !last.is_empty() && last.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+}
+
+/// Strips a possibly negative integer or percentage and the separators following it from the
+/// start of `arguments`.
+fn leading_color_argument(arguments: &str) -> Option<&str> {
+    let rest = arguments.strip_prefix('-').unwrap_or(arguments);
+
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+
+    let rest = &rest[digits..];
+    let rest = rest.strip_prefix('%').unwrap_or(rest);
+
+    let separated = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    (separated.len() < rest.len()).then_some(separated)
+}
+
+/// Validates that a URL is well-formed: either absolute, starting with a scheme such as
+/// `https:`, or relative, such as `/posts/1`, `../about` or `#top`. URLs may not be empty or
+/// contain whitespace, control characters or any of [`URL_DISALLOWED_CHARACTERS`].
+pub(crate) fn is_valid_url(url: &str) -> bool {
+    if url.is_empty()
+        || url
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || URL_DISALLOWED_CHARACTERS.contains(c))
+    {
+        return false;
+    }
+
+    match url_scheme(url) {
+        Some(scheme) => {
+            let mut chars = scheme.chars();
+
+            chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                && url.len() > scheme.len() + 1
+        }
+        None => true,
+    }
+}
+
+/// Returns whether a valid URL is safe to link to, meaning it is relative or its scheme is one
+/// of [`SAFE_URL_SCHEMES`]. Schemes such as `javascript:` and `data:` aren't safe.
+pub(crate) fn is_safe_url(url: &str) -> bool {
+    url_scheme(url)
+        .is_none_or(|scheme| SAFE_URL_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()))
+}
+
+/// The characters which may not appear in a URL unless they are percent-encoded.
+const URL_DISALLOWED_CHARACTERS: &str = "<>\"`{}|\\^";
+
+/// The schemes of URLs which are safe to link to.
+const SAFE_URL_SCHEMES: &[&str] = &["http", "https", "mailto", "tel"];
+
+/// Returns the scheme of `url`, i.e. `https` for `https://example.com`, which is the text before
+/// the first colon if it comes before any `/`, `?` or `#`.
+fn url_scheme(url: &str) -> Option<&str> {
+    let end = url.find([':', '/', '?', '#'])?;
+
+    url[end..].starts_with(':').then(|| &url[..end])
 }
 
 #[cfg(test)]
@@ -30,6 +276,10 @@ mod tests {
             "hsl(0,0,0)",
             "hsla(0,123,244,0.2)",
             "purple",
+            "#abc",
+            "#abcd",
+            "rgb(10%, -5 20%)",
+            "hsl(120 50% 50% .5)",
         ];
         let invalid_colors = vec![
             "#lololl",
@@ -37,6 +287,13 @@ mod tests {
             "a  rgb(255,255,255)",
             "rustcolor",
             "rbg(0,0,0)",
+            "#ab",
+            "#ABCDEF",
+            "rgb(0,0)",
+            "rgb(0,0,0,0,0)",
+            "rgb(0,0,0",
+            "hsl(0,0,-1)",
+            "Purple",
         ];
 
         for color in valid_colors {
@@ -55,4 +312,63 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_validate_url() {
+        let valid_urls = vec![
+            "https://example.com/posts?page=2#comments",
+            "HTTP://EXAMPLE.COM",
+            "mailto:hello@example.com",
+            "/posts/1",
+            "../about",
+            "#top",
+            "?q=balsa",
+            "posts/1:2",
+            "javascript:alert(1)",
+            "git+ssh://example.com/repo.git",
+        ];
+        let invalid_urls = vec![
+            "",
+            "https://example.com/a b",
+            "https:",
+            "<script>",
+            "https://example.com/\"",
+            "1http://example.com",
+            "my file.html",
+            "java\tscript:alert(1)",
+        ];
+
+        for url in valid_urls {
+            assert!(
+                is_valid_url(url),
+                "`is_valid_url` incorrectly returned `false` for valid URL `{}`",
+                url
+            );
+        }
+
+        for url in invalid_urls {
+            assert!(
+                !is_valid_url(url),
+                "`is_valid_url` incorrectly returned `true` for invalid URL `{}`",
+                url
+            );
+        }
+
+        for url in [
+            "https://example.com",
+            "MailTo:a@example.com",
+            "/posts",
+            "#top",
+        ] {
+            assert!(is_safe_url(url), "URL `{}` should be safe", url);
+        }
+
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "data:text/html,hi",
+        ] {
+            assert!(!is_safe_url(url), "URL `{}` should be unsafe", url);
+        }
+    }
 }
@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+use crate::{errors::TemplateErrorContext, parser::Position};
+
+/// Represents a non-fatal issue found while compiling a template.
+///
+/// Warnings never prevent a template from compiling, but point out constructs which are most
+/// likely mistakes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BalsaWarning {
+    /// A variable was declared in a declaration block but never referenced.
+    UnusedDeclaration(TemplateErrorContext<UnusedDeclaration>),
+}
+
+/// Represents a declared variable which is never referenced in the template.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnusedDeclaration {
+    /// The name of the unused variable.
+    pub variable_name: String,
+}
+
+impl Display for BalsaWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnusedDeclaration(w) => write!(f, "warning: {}", w),
+        }
+    }
+}
+
+impl Display for UnusedDeclaration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "variable `{}` is declared but never used",
+            self.variable_name
+        )
+    }
+}
+
+impl BalsaWarning {
+    /// Returns the warning moved to where it is in an input which `base` is the position of the
+    /// start of.
+    pub(crate) fn rebase(self, base: Position) -> Self {
+        match self {
+            Self::UnusedDeclaration(w) => Self::UnusedDeclaration(w.rebase(base)),
+        }
+    }
+}
+
+// Warning constructor functions.
+impl BalsaWarning {
+    /// Creates a new [`BalsaWarning::UnusedDeclaration`] which wraps an [`UnusedDeclaration`]
+    /// with the provided variable name.
+    pub(crate) fn unused_declaration(pos: Position, variable_name: String) -> Self {
+        Self::UnusedDeclaration(TemplateErrorContext::new(
+            pos,
+            UnusedDeclaration { variable_name },
+        ))
+    }
+}
@@ -0,0 +1,77 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{Balsa, BalsaTemplate, Template};
+
+/// A template compiled in the browser with [`compile`], e.g. to preview edits to a template in a
+/// CMS before they are saved.
+///
+/// Compiling and rendering never touch the filesystem or the clock, so they work in
+/// `wasm32-unknown-unknown`. Templates have no loader, so `include` blocks fail to compile.
+#[wasm_bindgen(js_name = Template)]
+#[derive(Debug)]
+pub struct WasmTemplate {
+    template: Template,
+}
+
+/// Compiles `template` with the default [`crate::CompileOptions`], throwing an `Error` with the
+/// compile errors' messages if it fails.
+#[wasm_bindgen]
+pub fn compile(template: &str) -> Result<WasmTemplate, JsError> {
+    let template = Balsa::from_string(template)
+        .build()
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    Ok(WasmTemplate { template })
+}
+
+#[wasm_bindgen(js_class = Template)]
+impl WasmTemplate {
+    /// Renders the template with `json_params`, an object of parameters like the JSON a server
+    /// would pass to [`Template::render_html_string`]. `undefined` and `null` render the
+    /// template with no parameters.
+    pub fn render(&self, json_params: JsValue) -> Result<String, JsError> {
+        let json = if json_params.is_undefined() || json_params.is_null() {
+            None
+        } else {
+            js_sys::JSON::stringify(&json_params)
+                .map_err(|_| JsError::new("parameters must be a JSON object"))?
+                .as_string()
+        };
+
+        self.render_json(json.as_deref().unwrap_or("{}"))
+            .map_err(|e| JsError::new(&e))
+    }
+}
+
+impl WasmTemplate {
+    /// Renders the template with the parameters in the JSON string `json`, returning the message
+    /// of the error thrown if it fails.
+    fn render_json(&self, json: &str) -> Result<String, String> {
+        let params: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+        self.template
+            .render_html_string(&params)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_json() {
+        let template = WasmTemplate {
+            template: Balsa::from_string("<h1>{{ title : string }}</h1>")
+                .build()
+                .unwrap(),
+        };
+
+        assert_eq!(
+            template.render_json(r#"{ "title": "Preview" }"#).unwrap(),
+            "<h1>Preview</h1>"
+        );
+        assert!(template.render_json("{ title").is_err());
+        assert!(template.render_json("[]").is_err());
+    }
+}
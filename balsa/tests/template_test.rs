@@ -1,4 +1,21 @@
-use balsa::{AsParameters, Balsa, BalsaParameters, BalsaTemplate};
+use balsa::{
+    errors::{
+        BalsaCompileError, BalsaRenderError, ComputationError, IncludeFailure, LimitExceeded,
+        MissingParameter, RenderAborted, TemplateParseFail, UnbalancedConditional,
+    },
+    explain::{ExplainedItems, ExplainedReplaceWith},
+    AsParameters, Balsa, BalsaBuilder, BalsaError, BalsaParameters, BalsaRegistry, BalsaTemplate,
+    BalsaType, BalsaValue, CancellationToken, CompileCache, CompileOptions, Dictionary,
+    EscapePolicy, ExtractedMessage, FileLoader, HydrationMarkers, Image, Locale, MessageCatalog,
+    PluralRule, RenderCache, RenderOptions, Template, TypedTemplate,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 struct TemplateParams {
     document_title: String,
@@ -53,3 +70,3703 @@ fn template_test() {
 
     assert_eq!(output, expected_output);
 }
+
+#[test]
+fn compile_error_position_test() {
+    let test_template = "<html>\n    <p>{{ title : 5 }}</p>\n</html>";
+
+    let err = Balsa::from_string(test_template)
+        .build()
+        .expect_err("Template with an invalid type expression should fail to compile");
+
+    match err {
+        BalsaError::CompileError(BalsaCompileError::InvalidTypeExpression(ctx)) => {
+            assert_eq!(
+                (ctx.pos, ctx.line, ctx.column),
+                (14, 2, 8),
+                "Compile error reported at incorrect position"
+            );
+        }
+        e => panic!(
+            "Expected `InvalidTypeExpression` compile error, got: {:?}",
+            e
+        ),
+    }
+}
+
+#[test]
+fn non_ascii_template_test() {
+    let test_template = r#"<title>{{ documentTitle : string }}</title><p lang="ja">日本語 🌳 — {{ headerText : string }} — ¡{{ name : string, defaultValue: "Zoë" }}!</p>"#;
+    let expected_output = r#"<title>Café</title><p lang="ja">日本語 🌳 — こんにちは — ¡Zoë!</p>"#;
+
+    let template = Balsa::from_string(test_template)
+        .build_struct::<TemplateParams>()
+        .expect("Template with non-ASCII characters should successfully compile");
+
+    let input = TemplateParams {
+        document_title: "Café".to_string(),
+        header_text: "こんにちは".to_string(),
+    };
+
+    let output = template
+        .render_html_string(&input)
+        .expect("Template with non-ASCII characters should successfully render");
+
+    assert_eq!(output, expected_output);
+}
+
+#[test]
+fn max_nesting_depth_test() {
+    let test_template = "<p>{{ title : string }}</p>";
+
+    let err = Balsa::from_string(test_template)
+        .max_nesting_depth(0)
+        .build()
+        .expect_err("Template nested deeper than the maximum depth should fail to compile");
+
+    match err {
+        BalsaError::CompileError(BalsaCompileError::TemplateParseFail(ctx)) => {
+            assert_eq!(
+                ctx.error,
+                TemplateParseFail::TooDeep { max_depth: 0 },
+                "Incorrect parse failure for template exceeding the maximum nesting depth"
+            );
+        }
+        e => panic!("Expected `TemplateParseFail` compile error, got: {:?}", e),
+    }
+}
+
+#[test]
+fn block_like_text_test() {
+    for text in [
+        "{{ 99999999999999999999 }}",
+        r#"{{ "C:\path" }}"#,
+        "{{ 2024-13-45 }}",
+    ] {
+        let test_template = format!("<p>{}</p>", text);
+
+        let output = Balsa::from_string(test_template.as_str())
+            .build()
+            .and_then(|template| template.render_defaults())
+            .unwrap_or_else(|e| panic!("`{}` should render as-is, got: {}", text, e));
+
+        assert_eq!(
+            output, test_template,
+            "Text which isn't a block should be written as-is"
+        );
+    }
+
+    let err = Balsa::from_string("<p>{{ views : int, defaultValue: 99999999999999999999 }}</p>")
+        .build()
+        .expect_err("Parameter block with an out of range literal should fail to compile");
+
+    match err {
+        BalsaError::CompileError(BalsaCompileError::TemplateParseFail(ctx)) => {
+            assert_eq!(
+                (ctx.line, ctx.column),
+                (1, 34),
+                "Parse failure reported at incorrect position"
+            );
+        }
+        e => panic!("Expected `TemplateParseFail` compile error, got: {:?}", e),
+    }
+}
+
+#[test]
+fn declared_default_value_test() {
+    let test_template = r##"{{@ brandColor : color = "#ff0000" }}<h1 style="color: {{ accent : color, defaultValue: brandColor }}">{{ headerText : string }}</h1>"##;
+    let expected_output = r##"<h1 style="color: #ff0000">Hello world :)</h1>"##;
+
+    let template = Balsa::from_string(test_template)
+        .build_struct::<TemplateParams>()
+        .expect("Template with a declared default value should successfully compile");
+
+    let input = TemplateParams {
+        document_title: "Title!!".to_string(),
+        header_text: "Hello world :)".to_string(),
+    };
+
+    let output = template
+        .render_html_string(&input)
+        .expect("Template with a declared default value should successfully render");
+
+    assert_eq!(output, expected_output);
+}
+
+#[test]
+fn spread_default_value_test() {
+    let template = Balsa::from_string(
+        r#"<title>{{ title : string }}</title><h1>{{ title : string, defaultValue: "Untitled" }}</h1>"#,
+    )
+    .build()
+    .expect("Template with one default value for a parameter should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Every use of the parameter should have its default value"),
+        "<title>Untitled</title><h1>Untitled</h1>"
+    );
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().string("title", "Home"))
+            .expect("Template should successfully render"),
+        "<title>Home</title><h1>Home</h1>"
+    );
+}
+
+#[test]
+fn declared_bool_default_test() {
+    let template = Balsa::from_string(
+        r#"{{@ showBanner : bool = true, defaultSubtitle : string = "Welcome" }}{{if banner, defaultValue: showBanner}}<h2>{{ subtitle : string, defaultValue: defaultSubtitle }}</h2>{{endif}}"#,
+    )
+    .build()
+    .expect("Template with declared booleans should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Conditions should default to declared booleans"),
+        "<h2>Welcome</h2>"
+    );
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().bool("banner", false))
+            .expect("Template should successfully render"),
+        ""
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{@ showBanner : bool = maybe }}").build(),
+            Err(BalsaError::CompileError(BalsaCompileError::UndefinedVariable(ref e)))
+                if e.variable_name == "maybe"
+        ),
+        "Names other than `true` and `false` should be declared variables"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{@ subtitle : string = true }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidTypeCast(_)
+            ))
+        ),
+        "Boolean literals should not be declared as strings"
+    );
+}
+
+#[test]
+fn substitution_test() {
+    let template = Balsa::from_string(
+        r##"{{@ brandColor : color = "#ff5500", theme : dict = { gap: 4 }, siteName : string = "<Balsa>" }}<h1 style="color: {{ $brandColor }}">{{ $siteName }}</h1><a style="color: {{$brandColor}}; gap: {{ $theme.gap }}px">{{ title : string }}</a>"##,
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .build()
+    .expect("Template with substitutions should successfully compile");
+
+    assert!(template.warnings().is_empty());
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().string("title", "Home"))
+            .expect("Template with substitutions should successfully render"),
+        r#"<h1 style="color: #ff5500">&lt;Balsa&gt;</h1><a style="color: #ff5500; gap: 4px">Home</a>"#
+    );
+
+    assert!(matches!(
+        Balsa::from_string("{{ $brandColor }}").build(),
+        Err(BalsaError::CompileError(BalsaCompileError::UndefinedVariable(ref e)))
+            if e.error.variable_name == "brandColor"
+    ));
+    assert!(matches!(
+        Balsa::from_string(r##"{{ $brandColor }}{{@ brandColor : color = "#ff5500" }}"##).build(),
+        Err(BalsaError::CompileError(
+            BalsaCompileError::UsedBeforeDeclaration(_)
+        ))
+    ));
+}
+
+#[test]
+fn filter_test() {
+    let builder = Balsa::from_string(
+        r#"<a href="/posts/{{ title : string, filters: ["slugify"] }}">{{ title : string, filters: ["slugify", "upper"] }}</a>{{#each tags: array<dict>}}<i>{{ name : string, filters: "upper" }}</i>{{/each}}"#,
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .with_filter("slugify", |v: BalsaValue| match v {
+        BalsaValue::String(s) if !s.is_empty() => {
+            Ok(BalsaValue::String(s.to_lowercase().replace(' ', "-")))
+        }
+        _ => Err(BalsaError::filter_failed("can't slugify an empty string")),
+    })
+    .with_filter("upper", |v: BalsaValue| match v {
+        BalsaValue::String(s) => Ok(BalsaValue::String(s.to_uppercase())),
+        v => Ok(v),
+    });
+    let template = builder
+        .build()
+        .expect("Template with filters should successfully compile");
+
+    let params = BalsaParameters::new()
+        .string("title", "Hello World")
+        .sections(
+            "tags",
+            vec![BalsaParameters::new().string("name", "<rust>")],
+        );
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template with filters should successfully render"),
+        r#"<a href="/posts/hello-world">HELLO-WORLD</a><i>&lt;RUST&gt;</i>"#
+    );
+
+    let err = template
+        .render_html_string(&params.string("title", ""))
+        .unwrap_err();
+    assert_eq!(err.code(), "B0411");
+    assert!(matches!(
+        err,
+        BalsaError::RenderError(BalsaRenderError::FilterFailed(ref e))
+            if e.error.parameter_name == "title"
+                && e.error.filter_name == "slugify"
+                && e.error.reason == "can't slugify an empty string"
+    ));
+
+    assert!(matches!(
+        Balsa::from_string(r#"{{ title : string, filters: ["slugify"] }}"#).build(),
+        Err(BalsaError::CompileError(BalsaCompileError::UndefinedFilter(ref e)))
+            if e.error.filter_name == "slugify"
+    ));
+
+    // Filters aren't precompiled, so they have to be registered again.
+    let precompiled = Template::from_precompiled(&builder.precompile().unwrap()).unwrap();
+    assert!(matches!(
+        precompiled.render_html_string(&params),
+        Err(BalsaError::RenderError(BalsaRenderError::FilterFailed(_)))
+    ));
+    let precompiled = precompiled
+        .with_filter("slugify", |v: BalsaValue| Ok(v))
+        .with_filter("upper", |v: BalsaValue| Ok(v));
+    assert_eq!(
+        precompiled.render_html_string(&params).unwrap(),
+        r#"<a href="/posts/Hello World">Hello World</a><i>&lt;rust&gt;</i>"#
+    );
+}
+
+#[test]
+fn float_literal_test() {
+    let template = Balsa::from_string(
+        "{{@ ratio : float = 1.5 }}<p>{{ scale : float, defaultValue: ratio }} {{ offset : float, defaultValue: -0.25 }}</p>",
+    )
+    .build()
+    .expect("Template with float literals should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Template with float literals should successfully render"),
+        "<p>1.5 -0.25</p>"
+    );
+}
+
+#[test]
+fn string_escape_test() {
+    let template = Balsa::from_string(
+        r#"<p>{{ quote : string, defaultValue: "She said \"hi\" \u{2014} twice" }}</p><pre>{{ code : string, defaultValue: "a\\b\tc" }}</pre>"#,
+    )
+    .build()
+    .expect("Template with escaped string literals should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Template with escaped string literals should successfully render"),
+        "<p>She said \"hi\" \u{2014} twice</p><pre>a\\b\tc</pre>"
+    );
+
+    assert!(
+        Balsa::from_string(r#"{{ quote : string, defaultValue: "\q" }}"#)
+            .build()
+            .is_err(),
+        "Unknown escape sequences should fail to compile"
+    );
+
+    // Backslashes in text which isn't a block are written as-is, including in macro bodies.
+    let test_template = r#"<code>{{ "C:\Users\q" }} {{ "\u{zz}" }}</code>{{#macro path()}}{{ "D:\x" }}{{/macro}}{{ path() }}"#;
+    assert_eq!(
+        Balsa::from_string(test_template)
+            .build()
+            .expect("Text with backslashes outside of blocks should successfully compile")
+            .render_defaults()
+            .expect("Text with backslashes outside of blocks should successfully render"),
+        r#"<code>{{ "C:\Users\q" }} {{ "\u{zz}" }}</code>{{ "D:\x" }}"#
+    );
+}
+
+#[test]
+fn optional_parameter_test() {
+    let template = Balsa::from_string(
+        r#"<h1>{{ title : string }}</h1><p>{{ subtitle : string, optional: true }}</p><p>{{ note : string, optional: true, placeholder: "No notes" }}</p>{{if featured, optional: true}}<b>Featured</b>{{endif}}<ul>{{#each links: array<dict>, optional: true}}<li>{{ name : string }}{{ rel : string, optional: true }}</li>{{/each}}</ul>"#,
+    )
+    .build()
+    .expect("Template with optional parameters should successfully compile");
+    let params = BalsaParameters::new().string("title", "Hello");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Optional parameters may be left out"),
+        "<h1>Hello</h1><p></p><p>No notes</p><ul></ul>"
+    );
+    assert_eq!(
+        template
+            .render_html_string(&params.null("subtitle").null("links"))
+            .expect("Optional parameters may be passed null"),
+        "<h1>Hello</h1><p></p><p>No notes</p><ul></ul>"
+    );
+    assert_eq!(
+        template
+            .render_html_string(
+                &params
+                    .string("subtitle", "World")
+                    .string("note", "Draft")
+                    .bool("featured", true)
+                    .sections("links", vec![BalsaParameters::new().string("name", "Home")]),
+            )
+            .expect("Optional parameters which are passed should be written"),
+        "<h1>Hello</h1><p>World</p><p>Draft</p><b>Featured</b><ul><li>Home</li></ul>"
+    );
+
+    let err = template
+        .render_html_string(&BalsaParameters::new().null("title"))
+        .expect_err("Required parameters passed null should be missing");
+    assert!(matches!(
+        err,
+        BalsaError::RenderError(BalsaRenderError::MissingParameter(ref e))
+            if e.error.parameter_name == "title"
+    ));
+
+    let defaulted = Balsa::from_string(
+        r#"{{ title : string, defaultValue: "Hi" }}{{ subtitle : string, optional: true }}"#,
+    )
+    .build()
+    .expect("Template with an optional parameter should successfully compile");
+    assert_eq!(
+        defaulted
+            .render_defaults()
+            .expect("Optional parameters don't need default values"),
+        "Hi"
+    );
+
+    let parameters = template.parameters();
+    assert!(!parameters[0].optional);
+    assert!(parameters[1].optional);
+    assert_eq!(parameters[2].placeholder.as_deref(), Some("No notes"));
+
+    for invalid_template in [
+        r#"{{ note : string, placeholder: "No notes" }}"#,
+        r#"{{ note : string, optional: "yes" }}"#,
+    ] {
+        assert!(
+            Balsa::from_string(invalid_template).build().is_err(),
+            "`{}` should fail to compile",
+            invalid_template
+        );
+    }
+}
+
+#[test]
+fn url_test() {
+    let template = Balsa::from_string(
+        r#"<a href="{{ link : url, safe: true, defaultValue: "/about" }}">{{ label : string }}</a>"#,
+    )
+    .build()
+    .expect("Template with a URL should successfully compile");
+    let params = BalsaParameters::new().string("label", "Home");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template with a default URL should successfully render"),
+        r#"<a href="/about">Home</a>"#
+    );
+    assert_eq!(
+        template
+            .render_html_string(&params.string("link", "https://example.com/?q=1&page=2"))
+            .expect("Well-formed strings should be casted to URLs"),
+        r#"<a href="https://example.com/?q=1&page=2">Home</a>"#
+    );
+
+    for unsafe_url in [
+        "javascript:alert(1)",
+        "JavaScript:alert(1)",
+        "data:text/html,hi",
+    ] {
+        let err = template
+            .render_html_string(&params.url("link", unsafe_url))
+            .expect_err("Unsafe URLs should fail to render");
+
+        match err {
+            BalsaError::RenderError(BalsaRenderError::UnsafeUrl(ref e)) => {
+                assert_eq!(e.error.parameter_name, "link");
+                assert_eq!(e.error.url, unsafe_url);
+            }
+            _ => panic!("Expected an unsafe URL error, got {:?}", err),
+        }
+        assert_eq!(err.code(), "B0410");
+    }
+
+    assert!(
+        template
+            .render_html_string(&params.url("link", "https://example.com/a b"))
+            .is_err(),
+        "Malformed URLs should fail to render"
+    );
+    assert_eq!(
+        Balsa::from_string("{{ link : url }}")
+            .build()
+            .unwrap()
+            .render_html_string(&BalsaParameters::new().string("link", "javascript:void(0)"))
+            .expect("URLs which aren't marked safe may have any scheme"),
+        "javascript:void(0)"
+    );
+
+    for invalid_template in [
+        r#"{{ link : url, defaultValue: "not a url" }}"#,
+        r#"{{ title : string, safe: true }}"#,
+        r#"{{ link : url, safe: "yes" }}"#,
+    ] {
+        assert!(
+            Balsa::from_string(invalid_template).build().is_err(),
+            "`{}` should fail to compile",
+            invalid_template
+        );
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn datetime_test() {
+    use chrono::{FixedOffset, TimeZone};
+
+    let template = Balsa::from_string(
+        r#"<time datetime="{{ published : datetime, defaultValue: 2024-03-01 }}">{{ published : datetime, defaultValue: 2024-03-01, format: "%d/%m/%Y %H:%M" }}</time>"#,
+    )
+    .build()
+    .expect("Template with date-times should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Template with a default date-time should successfully render"),
+        r#"<time datetime="2024-03-01T00:00:00+00:00">01/03/2024 00:00</time>"#
+    );
+
+    let published = FixedOffset::east_opt(3600)
+        .unwrap()
+        .with_ymd_and_hms(2024, 12, 24, 18, 5, 0)
+        .unwrap();
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().datetime("published", published))
+            .expect("Template should successfully render a date-time parameter"),
+        r#"<time datetime="2024-12-24T18:05:00+01:00">24/12/2024 18:05</time>"#
+    );
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().string("published", "2024-12-24T18:05:00Z"))
+            .expect("ISO 8601 strings should be casted to date-times"),
+        r#"<time datetime="2024-12-24T18:05:00+00:00">24/12/2024 18:05</time>"#
+    );
+
+    let localized = Balsa::from_string(
+        r#"<p>{{ published : datetime }}</p><p>{{ published : datetime, format: "%A %-d %B %Y, 100%%" }}</p>"#,
+    )
+    .build()
+    .expect("Template with date-times should successfully compile");
+    let render = |locale: &str| {
+        localized
+            .render_html_string_with_options(
+                &BalsaParameters::new().datetime("published", published),
+                &RenderOptions::new().locale(Locale::new(locale)),
+            )
+            .expect("Template should successfully render a localized date-time")
+    };
+    assert_eq!(
+        render("fr-FR"),
+        "<p>24 décembre 2024 à 18:05</p><p>mardi 24 décembre 2024, 100%</p>"
+    );
+    assert_eq!(
+        render("en-US"),
+        "<p>December 24, 2024, 6:05 PM</p><p>Tuesday 24 December 2024, 100%</p>"
+    );
+    assert_eq!(
+        render("sv"),
+        "<p>2024-12-24 18:05</p><p>Tuesday 24 December 2024, 100%</p>"
+    );
+
+    for invalid_template in [
+        "{{ published : datetime, defaultValue: 2024-13-01 }}",
+        r#"{{ published : datetime, format: "%Y-%" }}"#,
+        r#"{{ title : string, format: "%Y" }}"#,
+    ] {
+        assert!(
+            Balsa::from_string(invalid_template).build().is_err(),
+            "`{}` should fail to compile",
+            invalid_template
+        );
+    }
+
+    // Date-like text which isn't a block is written as-is.
+    let test_template = "<p>{{ 2024-13-45 }} to {{ 2024-02-30T25:00 }}</p>";
+    assert_eq!(
+        Balsa::from_string(test_template)
+            .build()
+            .expect("Date-like text outside of blocks should successfully compile")
+            .render_defaults()
+            .expect("Date-like text outside of blocks should successfully render"),
+        test_template
+    );
+}
+
+#[test]
+fn color_literal_test() {
+    let template = Balsa::from_string(
+        r#"{{@ brandColor : color = #ff0000 }}<h1 style="color: {{ accent : color, defaultValue: brandColor }}; background: {{ background : color, defaultValue: #fafafa }}">Hi</h1>"#,
+    )
+    .build()
+    .expect("Template with color literals should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Template with color literals should successfully render"),
+        r#"<h1 style="color: #ff0000; background: #fafafa">Hi</h1>"#
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ accent : color, defaultValue: #ff00f }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::TemplateParseFail(_)
+            ))
+        ),
+        "Invalid color literals should be rejected"
+    );
+}
+
+#[test]
+fn array_literal_test() {
+    let template = Balsa::from_string(
+        r#"{{@ sizes : array<string> = ["small", "large"], weights : array<float> = [1, 2.5] }}<p class="{{ size : string, oneOf: sizes, defaultValue: "small" }}">{{ weights : array<float>, defaultValue: weights }}</p>"#,
+    )
+    .build()
+    .expect("Template with array literals should successfully compile");
+
+    assert_eq!(
+        template.parameters()[0].one_of,
+        vec![
+            BalsaValue::String("small".to_string()),
+            BalsaValue::String("large".to_string()),
+        ]
+    );
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Template with array literals should successfully render"),
+        r#"<p class="small">[1, 2.5]</p>"#
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string(r#"{{@ ids : array<int> = [1, "two"] }}"#).build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidTypeCast(_)
+            ))
+        ),
+        "Items of array literals should have the element type"
+    );
+}
+
+#[test]
+fn dictionary_literal_test() {
+    let template = Balsa::from_string(
+        r#"{{@ theme : dict<color> = { primary: #ff0000, secondary: #00ff00 }, links : array<dict> = [{ label: "Home", href: "/" }, { label: "Blog", href: "/blog" }] }}<h1 style="color: {{ accent : color, defaultValue: theme.primary }}">{{ brand.name : string, defaultValue: "Acme" }}</h1><nav>{{#each links: array<dict>, defaultValue: links}}<a href="{{ href : string }}">{{ label : string }}</a>{{/each}}</nav>"#,
+    )
+    .build()
+    .expect("Template with dictionary literals should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Template with dictionary literals should successfully render"),
+        r#"<h1 style="color: #ff0000">Acme</h1><nav><a href="/">Home</a><a href="/blog">Blog</a></nav>"#
+    );
+
+    let params = BalsaParameters::new().dictionary(
+        "brand",
+        Dictionary::of_strings([("name", "Balsa"), ("slogan", "Sweet")]),
+    );
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template with dot access should successfully render"),
+        r#"<h1 style="color: #ff0000">Balsa</h1><nav><a href="/">Home</a><a href="/blog">Blog</a></nav>"#,
+        "Dot access should read entries of dictionary parameters"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string(
+                r#"{{@ theme : dict<color> = { primary: #ff0000 } }}{{ accent : color, defaultValue: theme.tertiary }}"#
+            )
+            .build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::UndefinedVariable(_)
+            ))
+        ),
+        "Missing keys of declared dictionaries should be rejected"
+    );
+}
+
+#[test]
+fn multiple_compile_errors_test() {
+    let template = "<h1>{{ title : strnig }}</h1>\n<p>{{ body : string, defaultValue: missing }}</p>\n{{if visible}}";
+
+    let errors = match Balsa::from_string(template).build() {
+        Err(BalsaError::MultipleCompileErrors(errors)) => errors,
+        result => panic!("Expected multiple compile errors, got {:?}", result.err()),
+    };
+    assert!(
+        matches!(
+            errors.as_slice(),
+            [
+                BalsaCompileError::InvalidTypeExpression(type_error),
+                BalsaCompileError::UndefinedVariable(variable_error),
+                BalsaCompileError::UnbalancedConditional(conditional_error),
+            ] if type_error.line == 1 && variable_error.line == 2 && conditional_error.line == 3
+        ),
+        "Every block which fails to compile should be reported, got {:?}",
+        errors
+    );
+
+    let error = Balsa::from_string("<h1>{{ title : strnig }}</h1>")
+        .build()
+        .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            BalsaError::CompileError(BalsaCompileError::InvalidTypeExpression(_))
+        ),
+        "A single error should be returned on its own"
+    );
+
+    let error = Balsa::from_string(
+        "{{@ brand : color = \"red\" }}<p>{{ accent : color, defaultValue: brand }}</p>",
+    )
+    .with_options(CompileOptions::new().strict_types(true))
+    .build()
+    .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            BalsaError::CompileError(BalsaCompileError::InvalidTypeCast(_))
+        ),
+        "Variables whose declaration failed shouldn't be reported again, got {:?}",
+        error
+    );
+}
+
+#[test]
+fn error_source_and_code_test() {
+    let render = || -> Result<String, Box<dyn Error>> {
+        let template = Balsa::from_string("<h1>{{ title : string }}</h1>").build()?;
+
+        Ok(template.render_html_string(&BalsaParameters::new())?)
+    };
+
+    let error = render().expect_err("Rendering without a required parameter should fail");
+    let error = error
+        .downcast_ref::<BalsaError>()
+        .expect("Errors should convert into boxed standard errors");
+    assert_eq!(error.code(), "B0401");
+
+    let source = error
+        .source()
+        .and_then(|e| e.downcast_ref::<BalsaRenderError>())
+        .expect("The source of a render error should be the render error");
+    assert_eq!(source.code(), "B0401");
+    assert!(
+        matches!(
+            source.source().and_then(|e| e.downcast_ref::<MissingParameter>()),
+            Some(e) if e.parameter_name == "title"
+        ),
+        "The source chain should end at the wrapped error"
+    );
+
+    let error = Balsa::from_string("{{ title : string }")
+        .build()
+        .unwrap_err();
+    assert_eq!(error.code(), "B0104");
+}
+
+#[test]
+fn unused_declaration_warning_test() {
+    let test_template = r#"{{@ unused : string = "hello", used : string = "world" }}<h1>{{ headerText : string, defaultValue: used }}</h1>"#;
+
+    let template = Balsa::from_string(test_template)
+        .build()
+        .expect("Template with an unused declaration should successfully compile");
+
+    let warnings = template
+        .warnings()
+        .iter()
+        .map(|w| w.to_string())
+        .collect::<Vec<String>>();
+
+    assert_eq!(
+        warnings,
+        vec!["warning: variable `unused` is declared but never used at line 1, column 1"]
+    );
+}
+
+#[test]
+fn balsa_parameters_render_test() {
+    let test_template = r#"<h1>{{ headerText : string }}</h1>"#;
+
+    let template = Balsa::from_string(test_template)
+        .build()
+        .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new().string("headerText", "Hello world :)");
+
+    let output = template
+        .render_html_string(&params)
+        .expect("Template should successfully render with a `BalsaParameters`");
+
+    assert_eq!(output, "<h1>Hello world :)</h1>");
+}
+
+#[test]
+fn nested_parameters_test() {
+    struct Theme {
+        primary: String,
+        font_size: i64,
+    }
+
+    struct Page {
+        title: String,
+        theme: Theme,
+    }
+
+    impl AsParameters for Page {
+        fn as_parameters(&self) -> BalsaParameters {
+            BalsaParameters::new().string("title", &self.title).nested(
+                "site",
+                BalsaParameters::new().nested(
+                    "theme",
+                    BalsaParameters::new()
+                        .color("primary", &self.theme.primary)
+                        .int("fontSize", self.theme.font_size),
+                ),
+            )
+        }
+    }
+
+    let template = Balsa::from_string(
+        r#"<h1 style="color: {{ site.theme.primary : color }}; font-size: {{ site.theme.fontSize : int }}px">{{ title : string }}</h1>{{ site.theme.accent : color, defaultValue: #000 }}"#,
+    )
+    .build()
+    .expect("Template with nested parameters should successfully compile");
+
+    let page = Page {
+        title: "Hello".to_string(),
+        theme: Theme {
+            primary: "#ff0000".to_string(),
+            font_size: 16,
+        },
+    };
+
+    assert_eq!(
+        template
+            .render_html_string(&page)
+            .expect("Template with nested parameters should successfully render"),
+        r#"<h1 style="color: #ff0000; font-size: 16px">Hello</h1>#000"#
+    );
+    assert!(
+        matches!(
+            template.render_html_string(&BalsaParameters::new().string("title", "Hello")),
+            Err(BalsaError::RenderError(BalsaRenderError::MissingParameter(ref e)))
+                if e.error.parameter_name == "site.theme.primary"
+        ),
+        "Missing nested parameters should be reported by their path"
+    );
+}
+
+#[test]
+fn compile_options_test() {
+    let test_template = r#"[[@ greeting : string = "<b>Hi</b>" ]]<h1>[[ headerText : string, friendlyName: "Header", defaultValue: greeting ]]</h1>{{ untouched }}"#;
+
+    let template = Balsa::from_string(test_template)
+        .with_options(
+            CompileOptions::new()
+                .delimiters("[[", "]]")
+                .allow_parameter_option("friendlyName")
+                .escape_policy(EscapePolicy::Html),
+        )
+        .build()
+        .expect("Template with custom delimiters should successfully compile");
+
+    let output = template
+        .render_html_string(&BalsaParameters::new())
+        .expect("Template with custom delimiters should successfully render");
+
+    assert_eq!(output, "<h1>&lt;b&gt;Hi&lt;/b&gt;</h1>{{ untouched }}");
+}
+
+#[test]
+fn raw_block_test() {
+    let template = Balsa::from_string(
+        "<h1>{{ title : string }}</h1>{{raw}}<p>{{ title : string }} and {{ greeting }}</p>{{ endraw }}",
+    )
+    .with_options(CompileOptions::new().strict_blocks(true))
+    .build()
+    .expect("Template with a raw block should successfully compile");
+
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().string("title", "Docs"))
+            .expect("Template with a raw block should successfully render"),
+        "<h1>Docs</h1><p>{{ title : string }} and {{ greeting }}</p>"
+    );
+    assert_eq!(
+        template.parameters().len(),
+        1,
+        "Blocks in a raw block should not be parameters"
+    );
+}
+
+#[test]
+fn strict_blocks_test() {
+    let template = "<h1>{{ title }}</h1><script>f({{}})</script>";
+
+    let output = Balsa::from_string(template)
+        .build()
+        .expect("Template should successfully compile")
+        .render_html_string(&BalsaParameters::new())
+        .expect("Template should successfully render");
+    assert_eq!(
+        output, template,
+        "Text which isn't a block should be written as-is"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string(template)
+                .with_options(CompileOptions::new().strict_blocks(true))
+                .build(),
+            Err(BalsaError::CompileError(BalsaCompileError::TemplateParseFail(e)))
+                if e.error == TemplateParseFail::MalformedBlock && e.column == 5
+        ),
+        "Text which isn't a block should fail to compile with strict blocks enabled"
+    );
+
+    for (template, column) in [
+        ("<h1>{{ title : strnig }</h1>", 5),
+        ("<h1>{{ title : string }</h1>", 5),
+        (
+            "<p>{{{ x : string }}</p>\n<p>{{ theme.primary: color, defaultValue }}</p>",
+            4,
+        ),
+        ("<p>{{@ x : string }}</p>", 4),
+    ] {
+        assert!(
+            matches!(
+                Balsa::from_string(template).build(),
+                Err(BalsaError::CompileError(BalsaCompileError::TemplateParseFail(e)))
+                    if e.error == TemplateParseFail::MalformedBlock && e.column == column
+            ),
+            "Malformed block in `{}` should fail to compile",
+            template
+        );
+    }
+}
+
+#[test]
+fn duplicate_option_test() {
+    let result =
+        Balsa::from_string("<p>\n  {{ x : string, defaultValue: \"a\", defaultValue: \"b\" }}</p>")
+            .build();
+
+    assert!(
+        matches!(
+            result,
+            Err(BalsaError::CompileError(BalsaCompileError::DuplicateOption(e)))
+                if e.option_name == "defaultValue" && e.line == 2 && e.column == 3
+        ),
+        "Repeated options should fail to compile"
+    );
+}
+
+#[test]
+fn strict_types_test() {
+    let test_template =
+        r##"{{@ brandColor : color = "#ff0000" }}{{ accent : color, defaultValue: brandColor }}"##;
+
+    Balsa::from_string(test_template)
+        .build()
+        .expect("Implicit casts should be allowed by default");
+
+    let err = Balsa::from_string(test_template)
+        .with_options(CompileOptions::new().strict_types(true))
+        .build()
+        .expect_err("Implicit casts should fail to compile with strict types");
+
+    assert!(
+        matches!(
+            err,
+            BalsaError::CompileError(BalsaCompileError::InvalidTypeCast(_))
+        ),
+        "Expected `InvalidTypeCast` compile error, got: {:?}",
+        err
+    );
+}
+
+#[test]
+fn max_template_size_test() {
+    let err = Balsa::from_string("<h1>{{ headerText : string }}</h1>")
+        .with_options(CompileOptions::new().max_template_size(8))
+        .build()
+        .expect_err("Template larger than the maximum size should fail to compile");
+
+    match err {
+        BalsaError::CompileError(BalsaCompileError::TemplateParseFail(ctx)) => assert_eq!(
+            ctx.error,
+            TemplateParseFail::TooLarge {
+                size: 34,
+                max_size: 8
+            }
+        ),
+        e => panic!("Expected `TooLarge` parse failure, got: {:?}", e),
+    }
+}
+
+#[test]
+fn max_template_size_file_test() {
+    let path = std::env::temp_dir().join(format!("balsa-large-{}.html", std::process::id()));
+    std::fs::write(&path, "<p>{{ body : string }}</p>".repeat(100))
+        .expect("Failed to write temporary template");
+
+    let builder = Balsa::from_file(Box::new(path.clone())).max_template_size(1024);
+    let result = builder.build();
+    std::fs::remove_file(&path).ok();
+
+    let err = result.expect_err("File larger than the maximum size should fail to compile");
+    assert_eq!(
+        err.to_string(),
+        "compile error: template of 2600 bytes exceeds the maximum size of 1024 bytes at line 1, column 1"
+    );
+}
+
+#[test]
+fn newline_normalization_test() {
+    let raw_template = "\u{feff}<h1>{{ title : string }}</h1>\r\n<p>{{ body : string }}</p>\r\n";
+    let params = BalsaParameters::new()
+        .string("title", "Hi")
+        .string("body", "a\nb");
+
+    let template = Balsa::from_string(raw_template)
+        .strip_bom(true)
+        .normalize_newlines(true, false)
+        .build()
+        .expect("Template should successfully compile");
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<h1>Hi</h1>\n<p>a\nb</p>\n"
+    );
+    let body = &template.explain().replacements[1].start;
+    assert_eq!((body.offset, body.line, body.column), (33, 2, 4));
+
+    let restored = Balsa::from_string(raw_template)
+        .strip_bom(true)
+        .normalize_newlines(true, true)
+        .build()
+        .expect("Template should successfully compile");
+    assert_eq!(
+        restored
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<h1>Hi</h1>\r\n<p>a\r\nb</p>\r\n"
+    );
+
+    let untouched = Balsa::from_string(raw_template)
+        .build()
+        .expect("Template should successfully compile");
+    assert_eq!(
+        untouched
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "\u{feff}<h1>Hi</h1>\r\n<p>a\nb</p>\r\n"
+    );
+}
+
+#[test]
+fn builder_globals_test() {
+    let test_template = r#"<a href="{{ link : string, defaultValue: baseUrl }}">{{ siteName : string, defaultValue: siteName }}</a>"#;
+
+    let template = Balsa::from_string(test_template)
+        .global(
+            "baseUrl",
+            BalsaValue::String("https://example.com".to_string()),
+        )
+        .global("siteName", BalsaValue::String("Example".to_string()))
+        .build()
+        .expect("Template referencing builder globals should successfully compile");
+
+    let output = template
+        .render_html_string(&BalsaParameters::new())
+        .expect("Template referencing builder globals should successfully render");
+
+    assert_eq!(output, r#"<a href="https://example.com">Example</a>"#);
+    assert!(
+        template.warnings().is_empty(),
+        "Builder globals should never be reported as unused"
+    );
+}
+
+#[test]
+fn with_default_test() {
+    let test_template = r##"<h1 style="color: {{ accentColor : color, defaultValue: "#ff0000" }}">{{ headerText : string }}</h1>"##;
+
+    let template = Balsa::from_string(test_template)
+        .build()
+        .expect("Template should successfully compile");
+
+    let themed = template
+        .with_default("accentColor", BalsaValue::Color("#00ff00".to_string()))
+        .expect("Overriding a default with a value of the same type should succeed");
+
+    let params = BalsaParameters::new().string("headerText", "Hello world :)");
+
+    assert_eq!(
+        themed
+            .render_html_string(&params)
+            .expect("Template with an overridden default should successfully render"),
+        r##"<h1 style="color: #00ff00">Hello world :)</h1>"##
+    );
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Original template should successfully render"),
+        r##"<h1 style="color: #ff0000">Hello world :)</h1>"##,
+        "Overriding a default should not modify the original template"
+    );
+
+    let err = template
+        .with_default("accentColor", BalsaValue::Integer(3))
+        .expect_err("Overriding a default with a value of the wrong type should fail");
+
+    assert!(
+        matches!(
+            err,
+            BalsaError::RenderError(BalsaRenderError::InvalidParameterType(_))
+        ),
+        "Expected `InvalidParameterType` render error, got: {:?}",
+        err
+    );
+}
+
+#[test]
+fn render_defaults_test() {
+    let template = Balsa::from_string(
+        r#"{{@ title : string = "Preview" }}<h1>{{ headerText : string, defaultValue: title }}</h1>"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Template with defaults for every parameter should render"),
+        "<h1>Preview</h1>"
+    );
+
+    let template = Balsa::from_string(
+        "<h1>{{ headerText : string }}</h1><p>{{ body : string }}</p>{{ headerText : string }}",
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    match template.render_defaults() {
+        Err(BalsaError::RenderError(BalsaRenderError::MissingDefaults(ctx))) => {
+            assert_eq!(ctx.pos, 4);
+            assert_eq!(ctx.parameter_names, vec!["headerText", "body"]);
+        }
+        r => panic!("Expected `MissingDefaults` render error, got: {:?}", r),
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn compile_dir_parallel_test() {
+    let dir = std::env::temp_dir().join(format!("balsa-dir-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("partials")).expect("Failed to create temporary directory");
+    for (name, template) in [
+        ("index.html", "<h1>{{ title : string }}</h1>"),
+        ("partials/footer.html", "<footer>{{ year : int }}</footer>"),
+        ("broken.html", "{{ title : strnig }}"),
+    ] {
+        std::fs::write(dir.join(name), template).expect("Failed to write temporary template");
+    }
+
+    let compiled = Balsa::compile_dir_parallel(&dir);
+    std::fs::remove_dir_all(&dir).ok();
+    let compiled = compiled.expect("Directory should be read");
+
+    assert!(!compiled.is_ok());
+    assert_eq!(
+        compiled.templates.keys().collect::<Vec<_>>(),
+        vec![
+            std::path::Path::new("index.html"),
+            std::path::Path::new("partials/footer.html")
+        ]
+    );
+    assert!(matches!(
+        compiled.errors.get(std::path::Path::new("broken.html")),
+        Some(BalsaError::CompileError(_))
+    ));
+
+    assert!(Balsa::compile_dir_parallel(dir.join("missing")).is_err());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn render_with_loader_test() {
+    use balsa::{errors::LoadFailed, DataLoader, LoadFuture};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    struct Loader {
+        started: AtomicUsize,
+        loaded: Mutex<Vec<String>>,
+    }
+
+    impl DataLoader for Loader {
+        fn load<'a>(&'a self, name: &'a str) -> LoadFuture<'a> {
+            Box::pin(async move {
+                self.loaded.lock().unwrap().push(name.to_string());
+                self.started.fetch_add(1, Ordering::SeqCst);
+
+                // Waits for the other loads to start, which only happens if they run concurrently.
+                for _ in 0..100 {
+                    if self.started.load(Ordering::SeqCst) == 3 {
+                        break;
+                    }
+                    tokio::task::yield_now().await;
+                }
+
+                match name {
+                    "userName" => Ok(Some(BalsaValue::String("Ada".to_string()))),
+                    "unread" => Ok(Some(BalsaValue::Integer(4))),
+                    "broken" => Err("connection refused".to_string()),
+                    _ => Ok(None),
+                }
+            })
+        }
+    }
+
+    let template = Balsa::from_string(
+        r#"{{@computed doubled : int = unread * 2 }}<p>{{ greeting : string }} {{ userName : string }}: {{ doubled : int }} ({{ theme : string, defaultValue: "light" }})</p>"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+    let loader = Loader {
+        started: AtomicUsize::new(0),
+        loaded: Mutex::new(Vec::new()),
+    };
+    let params = BalsaParameters::new().string("greeting", "Hi");
+
+    assert_eq!(
+        template
+            .render_with_loader(&params, &RenderOptions::new(), &loader)
+            .await
+            .expect("Template should successfully render"),
+        "<p>Hi Ada: 8 (light)</p>"
+    );
+
+    let mut loaded = loader.loaded.into_inner().unwrap();
+    loaded.sort();
+    assert_eq!(
+        loaded,
+        vec!["theme", "unread", "userName"],
+        "Only absent parameters should be loaded"
+    );
+
+    let broken = Balsa::from_string("{{ broken : string }}")
+        .build()
+        .expect("Template should successfully compile");
+    let loader = Loader {
+        started: AtomicUsize::new(2),
+        loaded: Mutex::new(Vec::new()),
+    };
+
+    assert!(matches!(
+        broken
+            .render_with_loader(&BalsaParameters::new(), &RenderOptions::new(), &loader)
+            .await,
+        Err(BalsaError::RenderError(BalsaRenderError::LoadFailed(e)))
+            if e.error == LoadFailed {
+                parameter_name: "broken".to_string(),
+                reason: "connection refused".to_string(),
+            }
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn render_stream_test() {
+    use futures_core::Stream;
+    use std::pin::Pin;
+
+    let template = Balsa::from_string("<h1>{{ title : string }}</h1><p>{{ body : string }}</p>!")
+        .build()
+        .expect("Template should successfully compile");
+    let options = RenderOptions::new();
+
+    // Collects the chunks of a stream, stopping at the first error.
+    async fn collect(mut stream: balsa::RenderStream<'_>) -> Result<Vec<String>, BalsaError> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            chunks.push(String::from_utf8(chunk?.to_vec()).unwrap());
+        }
+
+        Ok(chunks)
+    }
+
+    let params = BalsaParameters::new()
+        .string("title", "Hello")
+        .string("body", "World");
+    let chunks = collect(template.render_stream(&params, &options).unwrap())
+        .await
+        .expect("Template should successfully render");
+    assert_eq!(chunks, vec!["<h1>Hello", "</h1><p>World", "</p>!"]);
+
+    let missing = BalsaParameters::new().string("title", "Hello");
+    assert!(collect(template.render_stream(&missing, &options).unwrap())
+        .await
+        .is_err());
+
+    let limited = RenderOptions::new().max_output_size(12);
+    assert!(matches!(
+        collect(template.render_stream(&params, &limited).unwrap()).await,
+        Err(BalsaError::RenderError(BalsaRenderError::LimitExceeded(_)))
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn from_file_async_test() {
+    let path = std::env::temp_dir().join(format!("balsa-async-{}.html", std::process::id()));
+    tokio::fs::write(&path, "<h1>{{ headerText : string }}</h1>")
+        .await
+        .expect("Failed to write temporary template");
+
+    let template = Balsa::from_file_async(&path)
+        .await
+        .expect("Template file should be read asynchronously")
+        .build()
+        .expect("Template should successfully compile");
+    tokio::fs::remove_file(&path).await.ok();
+
+    let params = BalsaParameters::new().string("headerText", "Hello world :)");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<h1>Hello world :)</h1>"
+    );
+}
+
+#[test]
+fn precompile_test() {
+    let bytes = Balsa::precompile(r#"<h1>{{ headerText : string, defaultValue: "Hi" }}</h1>"#)
+        .expect("Template should successfully precompile");
+
+    let template = Template::from_precompiled(&bytes).expect("Precompiled template should load");
+
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Precompiled template should successfully render"),
+        "<h1>Hi</h1>"
+    );
+
+    assert!(
+        matches!(
+            Template::from_precompiled(b"<h1>not precompiled</h1>"),
+            Err(BalsaError::ReadTemplateError(_))
+        ),
+        "Loading an invalid precompiled template should fail"
+    );
+}
+
+#[test]
+fn to_bytes_test() {
+    let template = Balsa::from_string(
+        r#"<ul>{{#each posts: array<dict>, minItems: 1}}<li>{{ title : string }}</li>{{/each}}</ul>"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let loaded = Template::from_bytes(&template.to_bytes()).expect("Encoded template should load");
+    let params = BalsaParameters::new()
+        .sections("posts", vec![BalsaParameters::new().string("title", "Hi")]);
+
+    assert_eq!(
+        loaded.render_html_string(&params).unwrap(),
+        template.render_html_string(&params).unwrap()
+    );
+    assert_eq!(loaded.parameters(), template.parameters());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_test() {
+    let template = Balsa::from_string(r#"<h1>{{ title : string, defaultValue: "Hi" }}</h1>"#)
+        .build()
+        .expect("Template should successfully compile");
+
+    let json = serde_json::to_string(&template).expect("Template should serialize");
+    let loaded: Template = serde_json::from_str(&json).expect("Template should deserialize");
+    assert_eq!(loaded.render_defaults().unwrap(), "<h1>Hi</h1>");
+
+    let mut truncated: serde_json::Value = serde_json::from_str(&json).unwrap();
+    truncated["segments"].as_array_mut().unwrap().pop();
+    assert!(
+        serde_json::from_value::<Template>(truncated).is_err(),
+        "Templates whose static text doesn't match their replacements should be rejected"
+    );
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn json_parameters_test() {
+    let template = Balsa::from_string(
+        r#"<h1 style="color: {{ accent : color }}">{{ title : string }}</h1>{{if featured}}<b>{{ rating : float }}</b>{{endif}}<p>{{ tags : array<string> }}</p><ul>{{#each posts: array<dict>}}<li>{{ name : string }} ({{ views : int, defaultValue: 0 }})</li>{{/each}}</ul>{{ footer : string, defaultValue: "Bye" }}"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let params = serde_json::json!({
+        "accent": "#ff0000",
+        "title": "Hi",
+        "featured": true,
+        "rating": 4,
+        "tags": ["a", "b"],
+        "posts": [{ "name": "First", "views": 3 }, { "name": "Second", "views": null }],
+        "footer": null,
+        "unused": { "nested": [1.5] }
+    });
+
+    assert_eq!(
+        template.render_html_string(&params).unwrap(),
+        template
+            .render_html_string(
+                &BalsaParameters::new()
+                    .color("accent", "#ff0000")
+                    .string("title", "Hi")
+                    .bool("featured", true)
+                    .float("rating", 4.0)
+                    .array("tags", balsa::Array::of_strings(["a", "b"]))
+                    .sections(
+                        "posts",
+                        vec![
+                            BalsaParameters::new()
+                                .string("name", "First")
+                                .int("views", 3),
+                            BalsaParameters::new().string("name", "Second"),
+                        ]
+                    )
+            )
+            .unwrap()
+    );
+    assert!(matches!(
+        template.render_html_string(&serde_json::json!(["not", "an", "object"])),
+        Err(BalsaError::RenderError(BalsaRenderError::MissingParameter(
+            _
+        )))
+    ));
+}
+
+#[test]
+fn from_str_test() {
+    let template: Template = "<h1>{{ headerText : string }}</h1>"
+        .parse()
+        .expect("Template should successfully parse");
+
+    let params = BalsaParameters::new().string("headerText", "Hello world :)");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<h1>Hello world :)</h1>"
+    );
+
+    assert!(
+        matches!(
+            Template::try_from("{{ headerText : string, defaultValue: undefinedVariable }}"),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::UndefinedVariable(_)
+            ))
+        ),
+        "Converting an invalid template should fail to compile"
+    );
+}
+
+#[test]
+fn build_once_test() {
+    let params = BalsaParameters::new().string("headerText", "Hello world :)");
+
+    let template = Balsa::from_string("<h1>{{ headerText : string }}</h1>")
+        .build_once()
+        .expect("Template should successfully compile");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<h1>Hello world :)</h1>"
+    );
+
+    let builders: Vec<BalsaBuilder> = vec![
+        Balsa::from_string("<h1>{{ headerText : string }}</h1>").boxed(),
+        Balsa::from_string("<h2>{{ headerText : string }}</h2>").boxed(),
+    ];
+
+    let rendered = builders
+        .iter()
+        .map(|builder| {
+            builder
+                .build()
+                .expect("Template should successfully compile")
+                .render_html_string(&params)
+                .expect("Template should successfully render")
+        })
+        .collect::<Vec<String>>();
+
+    assert_eq!(
+        rendered,
+        vec!["<h1>Hello world :)</h1>", "<h2>Hello world :)</h2>"]
+    );
+}
+
+#[test]
+fn translation_test() {
+    let catalog = MessageCatalog::new()
+        .message("welcome.title", "Welcome to <Balsa>")
+        .message("welcome.subtitle", "Keep copy out of your templates");
+
+    let template = Balsa::from_string(
+        "{{@ subtitleKey : string = \"welcome.subtitle\" }}<h1>{{ t \"welcome.title\" }}</h1><p>{{t subtitleKey}}</p>",
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .catalog(catalog.clone())
+    .build()
+    .expect("Template should successfully compile");
+
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new())
+            .expect("Template should successfully render"),
+        "<h1>Welcome to &lt;Balsa&gt;</h1><p>Keep copy out of your templates</p>"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ t \"missing\" }}")
+                .catalog(catalog.clone())
+                .build(),
+            Err(BalsaError::CompileError(BalsaCompileError::MissingMessage(e))) if e.key == "missing"
+        ),
+        "Translating a message missing from the catalog should fail to compile"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ t \"welcome.title\" \"welcome.subtitle\" }}")
+                .catalog(catalog)
+                .build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidHelperArguments(_)
+            ))
+        ),
+        "Translating with more than one argument should fail to compile"
+    );
+}
+
+#[test]
+fn extract_messages_test() {
+    let template = Balsa::from_string(
+        "{{@ footerKey : string = \"footer\" }}<h1>{{ t \"title\" }}</h1>\n<p>{{ t footerKey }}</p>",
+    )
+    .with_options(CompileOptions::new().allow_missing_messages(true))
+    .catalog(MessageCatalog::new().message("title", "Hello"))
+    .build()
+    .expect("Template with missing messages should compile when they are allowed");
+
+    assert_eq!(
+        template.extract_messages(),
+        vec![
+            ExtractedMessage {
+                key: "title".to_string(),
+                translation: Some("Hello".to_string()),
+                line: 1,
+                column: 41,
+            },
+            ExtractedMessage {
+                key: "footer".to_string(),
+                translation: None,
+                line: 2,
+                column: 4,
+            },
+        ]
+    );
+
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new())
+            .expect("Template should successfully render"),
+        "<h1>Hello</h1>\n<p>footer</p>",
+        "Missing messages should render their ID"
+    );
+}
+
+#[test]
+fn plural_test() {
+    let template = Balsa::from_string(
+        "{{ plural count \"item\" \"items\" }}, {{ plural 1 \"file\" \"files\" }}{{ count : int, defaultValue: 1 }}",
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    assert_eq!(render_count(&template, 1), "item, file1");
+    assert_eq!(render_count(&template, 0), "items, file0");
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Plural count should use the parameter's default value"),
+        "item, file1"
+    );
+
+    let template =
+        Balsa::from_string("{{ count : int }} {{ plural count \"fichier\" \"fichiers\" }}")
+            .with_options(CompileOptions::new().plural_rule(PluralRule::ZeroOneOther))
+            .build()
+            .expect("Template should successfully compile");
+
+    assert_eq!(render_count(&template, 0), "0 fichier");
+    assert_eq!(render_count(&template, 2), "2 fichiers");
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ plural count \"item\" }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidHelperArguments(e)
+            )) if e.expected == 3 && e.received == 2
+        ),
+        "Pluralizing with the wrong number of forms should fail to compile"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ count : string }}{{ plural count \"item\" \"items\" }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::ConflictingParameter(_)
+            ))
+        ),
+        "Plural counts should be integer parameters"
+    );
+}
+
+#[test]
+fn locale_test() {
+    let template = Balsa::from_string("{{ visitors : int }} / {{ ratio : float }}")
+        .build()
+        .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new()
+        .int("visitors", 1234567)
+        .float("ratio", 1234.5);
+
+    let render = |locale: Locale| {
+        template
+            .render_html_string_with_options(&params, &RenderOptions::new().locale(locale))
+            .expect("Template should successfully render")
+    };
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "1234567 / 1234.5"
+    );
+    assert_eq!(render(Locale::new("en-US")), "1,234,567 / 1,234.5");
+    assert_eq!(render(Locale::new("de-DE")), "1.234.567 / 1.234,5");
+    assert_eq!(
+        render(Locale::new("fr-FR").group_separator(None)),
+        "1234567 / 1234,5"
+    );
+}
+
+#[test]
+fn direction_test() {
+    let template = Balsa::from_string(
+        "<html {{ dir siteLocale }}><p {{dir \"he\"}}>{{ body : string, direction: \"auto\" }}</p>{{ siteLocale : string, defaultValue: \"en\" }}</html>",
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .build()
+    .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new()
+        .string("siteLocale", "ar-EG")
+        .string("body", "<b>");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<html dir=\"rtl\"><p dir=\"rtl\">&lt;b&gt;</p>ar-EG</html>"
+    );
+
+    assert_eq!(
+        template
+            .with_default("body", BalsaValue::String("hi".to_string()))
+            .expect("Default value should be a string")
+            .render_defaults()
+            .expect("Template should render with its defaults"),
+        "<html dir=\"ltr\"><p dir=\"rtl\">hi</p>en</html>"
+    );
+
+    assert!(
+        template
+            .explain()
+            .to_string()
+            .contains("parameter body: string (auto)"),
+        "Explanation should include the parameter's direction"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ body : string, direction: \"up\" }}").build(),
+            Err(BalsaError::CompileError(BalsaCompileError::InvalidDirection(e))) if e.direction == "up"
+        ),
+        "Parameters with an invalid direction should fail to compile"
+    );
+}
+
+#[test]
+fn localized_parameters_test() {
+    let template = Balsa::from_string("<h1>{{ title : string }}</h1><p>{{ price : float }}</p>")
+        .build()
+        .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new()
+        .string("title", "Welcome")
+        .float("price", 2.5)
+        .localized("fr", BalsaParameters::new().string("title", "Bienvenue"));
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<h1>Welcome</h1><p>2.5</p>"
+    );
+    assert_eq!(
+        template
+            .render_html_string_with_options(&params, &RenderOptions::new().locale("fr-FR"))
+            .expect("Template should successfully render"),
+        "<h1>Bienvenue</h1><p>2,5</p>"
+    );
+}
+
+#[test]
+fn bidi_isolation_test() {
+    let params = BalsaParameters::new()
+        .string("name", "<שלום>")
+        .int("count", 3);
+    let options = RenderOptions::new().bidi_isolation(true);
+
+    let render = |escape_policy: EscapePolicy| {
+        Balsa::from_string("{{ name : string }}: {{ count : int }}")
+            .with_options(CompileOptions::new().escape_policy(escape_policy))
+            .build()
+            .expect("Template should successfully compile")
+            .render_html_string_with_options(&params, &options)
+            .expect("Template should successfully render")
+    };
+
+    assert_eq!(render(EscapePolicy::Html), "<bdi>&lt;שלום&gt;</bdi>: 3");
+    assert_eq!(render(EscapePolicy::None), "\u{2068}<שלום>\u{2069}: 3");
+}
+
+#[test]
+fn message_format_test() {
+    let template = Balsa::from_string("<p>{{ summary : messageformat }}</p>")
+        .build()
+        .expect("Template should successfully compile");
+
+    let pattern = "{name} {role, select, admin {manages} other {owns}} \
+        {count, plural, =0 {no projects} one {# project} other {# projects}}";
+
+    let render = |params: BalsaParameters, locale: &str| {
+        template
+            .render_html_string_with_options(
+                &params.string("summary", pattern),
+                &RenderOptions::new().locale(locale),
+            )
+            .expect("Template should successfully render")
+    };
+
+    let params = || BalsaParameters::new().string("name", "Ana");
+
+    assert_eq!(
+        render(params().string("role", "admin").int("count", 0), "en"),
+        "<p>Ana manages no projects</p>"
+    );
+    assert_eq!(
+        render(params().string("role", "guest").int("count", 1), "en"),
+        "<p>Ana owns 1 project</p>"
+    );
+    assert_eq!(
+        render(params().string("role", "guest").int("count", 1200), "en"),
+        "<p>Ana owns 1,200 projects</p>"
+    );
+
+    // French uses `one` for 0 as well as 1.
+    let output = template
+        .render_html_string_with_options(
+            &BalsaParameters::new().int("count", 0).string(
+                "summary",
+                "{count, plural, one {# projet} other {# projets}}",
+            ),
+            &RenderOptions::new().locale("fr"),
+        )
+        .expect("Template should successfully render");
+    assert_eq!(output, "<p>0 projet</p>");
+
+    assert!(
+        template
+            .render_html_string(&params().string("summary", pattern))
+            .is_err(),
+        "Rendering should fail when an argument is missing"
+    );
+    assert!(
+        template
+            .render_html_string(&BalsaParameters::new().string("summary", "{count, plural"))
+            .is_err(),
+        "Rendering should fail when the pattern is malformed"
+    );
+
+    // Arguments fall back to the default values of their parameters.
+    let defaulted = Balsa::from_string(
+        r#"{{ n : int, defaultValue: 2 }} {{ m : messageformat, defaultValue: "{n} items" }}"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+    let report = defaulted
+        .render_with_report(&BalsaParameters::new(), &RenderOptions::new())
+        .expect("Arguments with default values should successfully render");
+    assert_eq!(report.output, "2 2 items");
+    assert_eq!(report.defaulted_parameters, vec!["n", "m"]);
+}
+
+#[test]
+fn render_localized_test() {
+    let template = Balsa::from_string("{{ greeting : string }} {{ total : float }}")
+        .build()
+        .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new()
+        .string("greeting", "Hello")
+        .float("total", 1234.5)
+        .localized("de", BalsaParameters::new().string("greeting", "Hallo"));
+
+    let render = template
+        .render_localized(&params, &["fr-FR", "de-AT;q=0.7"])
+        .expect("Template should successfully render");
+    assert_eq!(render.output, "Hallo 1.234,5");
+    assert_eq!(render.locale.tag(), "de-AT");
+
+    let render = template
+        .render_localized(&params, &["fr-FR"])
+        .expect("Template should successfully render");
+    assert_eq!(render.output, "Hello 1234.5");
+    assert_eq!(render.locale, Locale::default());
+}
+
+#[test]
+fn escape_override_test() {
+    let template = Balsa::from_string(
+        "<div>{{ widgetHtml : string, escape: false }}</div><p>{{ caption : string }}</p>",
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .build()
+    .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new()
+        .string("widgetHtml", "<b>trusted</b>")
+        .string("caption", "<i>untrusted</i>");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<div><b>trusted</b></div><p>&lt;i&gt;untrusted&lt;/i&gt;</p>"
+    );
+
+    let explained = template.explain().to_string();
+    assert!(
+        explained.contains("parameter widgetHtml: string (unescaped)"),
+        "Explanation should flag unescaped parameters: {}",
+        explained
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ widgetHtml : string, escape: \"no\" }}").build(),
+            Err(BalsaError::CompileError(BalsaCompileError::InvalidBoolean(e))) if e.value == "no"
+        ),
+        "Escape options other than `true` or `false` should fail to compile"
+    );
+}
+
+#[test]
+fn max_output_size_test() {
+    let template = Balsa::from_string("<p>{{ body : string }}</p><footer></footer>")
+        .build()
+        .expect("Template should successfully compile");
+
+    // Keeps only the exceeded limit of an error, if any.
+    let render = |body: &str, max_size: usize| {
+        template
+            .render_html_string_with_options(
+                &BalsaParameters::new().string("body", body),
+                &RenderOptions::new().max_output_size(max_size),
+            )
+            .map_err(|e| match e {
+                BalsaError::RenderError(BalsaRenderError::LimitExceeded(e)) => Some(e.error),
+                _ => None,
+            })
+    };
+
+    assert_eq!(
+        render("short", 64),
+        Ok("<p>short</p><footer></footer>".to_string())
+    );
+
+    for (body, max_size) in [(&"x".repeat(100)[..], 64), ("short", 20)] {
+        assert_eq!(
+            render(body, max_size),
+            Err(Some(LimitExceeded::OutputSize { max_size })),
+            "Rendering more than {} bytes should fail",
+            max_size
+        );
+    }
+}
+
+#[test]
+fn max_loop_iterations_test() {
+    let template = Balsa::from_string(
+        "{{#each posts: array<dict>}}<li>{{ title : string }}</li>{{/each}}{{#each links: array<dict>}}<a>{{ name : string }}</a>{{/each}}",
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let items = |key: &str, count: usize| {
+        (0..count)
+            .map(|i| BalsaParameters::new().string(key, i.to_string()))
+            .collect::<Vec<_>>()
+    };
+    let render = |posts: usize, links: usize, max_iterations: usize| {
+        template
+            .render_html_string_with_options(
+                &BalsaParameters::new()
+                    .sections("posts", items("title", posts))
+                    .sections("links", items("name", links)),
+                &RenderOptions::new().max_loop_iterations(max_iterations),
+            )
+            .map_err(|e| match e {
+                BalsaError::RenderError(BalsaRenderError::LimitExceeded(e)) => Some(e.error),
+                _ => None,
+            })
+    };
+
+    assert_eq!(
+        render(2, 1, 3),
+        Ok("<li>0</li><li>1</li><a>0</a>".to_string())
+    );
+    assert_eq!(
+        render(4, 0, 3),
+        Err(Some(LimitExceeded::LoopIterations { max_iterations: 3 })),
+        "Sections repeating more often than allowed should fail"
+    );
+    assert_eq!(
+        render(2, 2, 3),
+        Err(Some(LimitExceeded::LoopIterations { max_iterations: 3 })),
+        "The items of every section should count towards the limit"
+    );
+}
+
+#[test]
+fn parameter_limits_test() {
+    let template = Balsa::from_string("<p>{{ body : string }}</p>")
+        .build()
+        .expect("Template should successfully compile");
+    let options = RenderOptions::new().max_parameters(2).max_value_size(8);
+
+    // Keeps only the exceeded limit of an error, if any.
+    let render = |params: &BalsaParameters| {
+        template
+            .render_html_string_with_options(params, &options)
+            .map_err(|e| match e {
+                BalsaError::RenderError(BalsaRenderError::LimitExceeded(e)) => Some(e.error),
+                _ => None,
+            })
+    };
+
+    assert_eq!(
+        render(&BalsaParameters::new().string("body", "short")),
+        Ok("<p>short</p>".to_string())
+    );
+
+    assert_eq!(
+        render(&BalsaParameters::new().string("body", "far too long")),
+        Err(Some(LimitExceeded::ValueSize {
+            parameter_name: "body".to_string(),
+            max_size: 8,
+        }))
+    );
+
+    // Parameters of localized layers count towards the maximum.
+    let params = BalsaParameters::new()
+        .string("body", "short")
+        .string("extra", "x")
+        .localized("fr", BalsaParameters::new().string("body", "court"));
+    assert_eq!(
+        render(&params),
+        Err(Some(LimitExceeded::ParameterCount { max_count: 2 }))
+    );
+}
+
+#[test]
+fn render_report_test() {
+    let template = Balsa::from_string(
+        r#"{{ title : string }} {{ title : string }} {{ tagline : string, defaultValue: "Hi" }} {{ count : int, defaultValue: 3 }} {{ summary : messageformat }}"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+    let params = BalsaParameters::new()
+        .string("title", "Home")
+        .string(
+            "summary",
+            "{user} has {count, plural, one {# item} other {# items}}",
+        )
+        .string("user", "Ann")
+        .int("count", 1);
+
+    let report = template
+        .render_with_report(&params, &RenderOptions::new())
+        .expect("Template should successfully render");
+
+    assert_eq!(report.output, "Home Home Hi 1 Ann has 1 item");
+    assert_eq!(report.output_size, report.output.len());
+    assert_eq!(
+        report.used_parameters,
+        vec!["title", "count", "summary", "user"]
+    );
+    assert_eq!(report.defaulted_parameters, vec!["tagline"]);
+
+    let empty = Balsa::from_string("{{ tagline : string, defaultValue: \"Hi\" }}")
+        .build()
+        .expect("Template should successfully compile")
+        .render_with_report(&BalsaParameters::new(), &RenderOptions::new())
+        .expect("Template should successfully render");
+
+    assert!(empty.used_parameters.is_empty());
+    assert_eq!(empty.defaulted_parameters, vec!["tagline"]);
+}
+
+#[test]
+fn compile_cache_test() {
+    let cache = CompileCache::new(2);
+    let home = "<h1>{{ title : string }}</h1>";
+
+    let first = cache.get_or_compile(home).expect("Template should compile");
+    let second = cache.get_or_compile(home).expect("Template should compile");
+    assert!(
+        Arc::ptr_eq(&first, &second),
+        "The same source should only be compiled once"
+    );
+    assert_eq!(
+        first
+            .render_html_string(&BalsaParameters::new().string("title", "Home"))
+            .expect("Template should render"),
+        "<h1>Home</h1>"
+    );
+
+    // Failed compilations aren't cached.
+    assert!(cache.get_or_compile("{{ title : strnig }}").is_err());
+    assert_eq!(cache.len(), 1);
+
+    // `home` was used more recently than `about`, so `about` is evicted.
+    let about = cache
+        .get_or_compile("<h2>{{ title : string }}</h2>")
+        .unwrap();
+    cache.get_or_compile(home).unwrap();
+    cache
+        .get_or_compile("<h3>{{ title : string }}</h3>")
+        .unwrap();
+
+    assert_eq!(cache.len(), 2);
+    assert!(Arc::ptr_eq(&first, &cache.get_or_compile(home).unwrap()));
+    assert!(!Arc::ptr_eq(
+        &about,
+        &cache
+            .get_or_compile("<h2>{{ title : string }}</h2>")
+            .unwrap()
+    ));
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn render_cow_test() {
+    let params = BalsaParameters::new().string("title", "Home");
+
+    let static_template = Balsa::from_string("<footer>Static</footer>")
+        .build()
+        .expect("Template should successfully compile");
+    assert_eq!(
+        static_template.render_cow(&params).unwrap(),
+        Cow::Borrowed("<footer>Static</footer>")
+    );
+
+    let template = Balsa::from_string("<h1>{{ title : string }}</h1>")
+        .build()
+        .expect("Template should successfully compile");
+    let output = template.render_cow(&params).unwrap();
+    assert!(matches!(output, Cow::Owned(_)));
+    assert_eq!(output, "<h1>Home</h1>");
+
+    assert!(template.render_cow(&BalsaParameters::new()).is_err());
+}
+
+#[test]
+fn render_cache_test() {
+    let cache = RenderCache::new(8);
+    let template = Balsa::from_string("<h1>{{ title : string }}</h1>")
+        .build()
+        .expect("Template should successfully compile");
+    let options = RenderOptions::new();
+    let home = BalsaParameters::new().string("title", "Home");
+
+    let first = cache.render(&template, &home, &options).unwrap();
+    let second = cache
+        .render(
+            &template.clone(),
+            &BalsaParameters::new().string("title", "Home"),
+            &options,
+        )
+        .unwrap();
+    assert!(
+        Arc::ptr_eq(&first, &second),
+        "Identical renders should be cached"
+    );
+    assert_eq!(&*first, "<h1>Home</h1>");
+
+    // Different parameters, options and templates are rendered separately.
+    let about = BalsaParameters::new().string("title", "About");
+    assert_eq!(
+        &*cache.render(&template, &about, &options).unwrap(),
+        "<h1>About</h1>"
+    );
+    cache
+        .render(
+            &template,
+            &home,
+            &RenderOptions::new().locale(Locale::new("fr")),
+        )
+        .unwrap();
+    let defaulted = template
+        .with_default("title", BalsaValue::String("Default".to_string()))
+        .unwrap();
+    cache.render(&defaulted, &home, &options).unwrap();
+    assert_eq!(cache.len(), 4);
+
+    // Failed renders aren't cached.
+    assert!(cache
+        .render(&template, &BalsaParameters::new(), &options)
+        .is_err());
+    assert_eq!(cache.len(), 4);
+
+    // Expired outputs are rendered again.
+    let expiring = RenderCache::new(8).ttl(Duration::ZERO);
+    let first = expiring.render(&template, &home, &options).unwrap();
+    let second = expiring.render(&template, &home, &options).unwrap();
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert_eq!(first, second);
+}
+
+#[test]
+fn computed_parameters_test() {
+    let template = Balsa::from_string(
+        "{{@ wordsPerMinute : int = 200 }}{{@computed readingTime : int = wordCount / wordsPerMinute }}{{@computed minutes : float = readingTime + 0.5 }}<p>{{ readingTime : int }} min ({{ minutes : float }})</p>",
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    assert!(template.warnings().is_empty());
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().int("wordCount", 1099))
+            .expect("Template should successfully render"),
+        "<p>5 min (5.5)</p>"
+    );
+
+    let computed = template.explain().computed;
+    assert_eq!(
+        computed
+            .iter()
+            .map(|c| (c.name.as_str(), c.parameter_type.as_str(), c.inputs.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("readingTime", "int", vec!["wordCount".to_string()]),
+            ("minutes", "float", vec!["readingTime".to_string()]),
+        ]
+    );
+    assert_eq!(computed[0].expression, "wordCount / wordsPerMinute");
+
+    assert!(
+        matches!(
+            template.render_html_string(&BalsaParameters::new()),
+            Err(BalsaError::RenderError(BalsaRenderError::MissingParameter(e)))
+                if e.parameter_name == "wordCount"
+        ),
+        "Computed parameters should fail to render without their inputs"
+    );
+
+    let spaced = Balsa::from_string(
+        "{{@ computed double : int = n * 2 }}{{@ computed : int = 3 }}{{ double : int }} {{ $computed }}",
+    )
+    .build()
+    .expect("Computed blocks with whitespace after the `@` should successfully compile");
+    assert_eq!(
+        spaced
+            .render_html_string(&BalsaParameters::new().int("n", 4))
+            .expect("Template should successfully render"),
+        "8 3"
+    );
+
+    let ratio = Balsa::from_string("{{@computed ratio : int = a / b }}{{ ratio : int }}")
+        .build()
+        .expect("Template should successfully compile");
+    assert!(
+        matches!(
+            ratio.render_html_string(&BalsaParameters::new().int("a", 1).int("b", 0)),
+            Err(BalsaError::RenderError(BalsaRenderError::ComputationFailed(e)))
+                if e.computed_name == "ratio" && e.reason == ComputationError::DivisionByZero
+        ),
+        "Division by zero should fail to render"
+    );
+
+    assert!(matches!(
+        Balsa::from_string("{{@computed x : string = 1 }}").build(),
+        Err(BalsaError::CompileError(
+            BalsaCompileError::InvalidComputedType(_)
+        ))
+    ));
+    assert!(matches!(
+        Balsa::from_string("{{@computed x : int = 1 }}{{@computed x : int = 2 }}").build(),
+        Err(BalsaError::CompileError(
+            BalsaCompileError::DuplicateComputed(_)
+        ))
+    ));
+    assert!(matches!(
+        Balsa::from_string("{{@computed x : int = 1 + }}").build(),
+        Err(BalsaError::CompileError(
+            BalsaCompileError::TemplateParseFail(_)
+        ))
+    ));
+}
+
+#[test]
+fn dependency_graph_test() {
+    let template = Balsa::from_string(
+        "{{@computed readingTime : int = wordCount / 200 }}{{@computed label : float = readingTime * scale + wordCount }}<p>{{ title : string }} {{ label : float }}</p>",
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let graph = template.dependency_graph();
+    assert_eq!(
+        graph
+            .nodes
+            .iter()
+            .map(|n| (n.name.as_str(), n.computed, n.rendered))
+            .collect::<Vec<_>>(),
+        vec![
+            ("label", true, true),
+            ("readingTime", true, false),
+            ("scale", false, false),
+            ("title", false, true),
+            ("wordCount", false, false),
+        ]
+    );
+    assert_eq!(
+        graph.node("label").map(|n| n.dependencies.clone()),
+        Some(vec![
+            "readingTime".to_string(),
+            "scale".to_string(),
+            "wordCount".to_string()
+        ])
+    );
+    assert_eq!(graph.dependents("wordCount"), vec!["label", "readingTime"]);
+    assert_eq!(
+        graph.evaluation_order(),
+        Ok(vec!["scale", "title", "wordCount", "readingTime", "label"])
+    );
+}
+
+#[test]
+fn conditional_declarations_test() {
+    let template = r##"{{@ background : color = "#ffffff", if darkMode: background : color = "#000000" }}<body style="background: {{ bg : color, defaultValue: background }}">"##;
+    let render = |dark_mode: &str| {
+        Balsa::from_string(template)
+            .global("darkMode", BalsaValue::String(dark_mode.to_string()))
+            .build()
+            .expect("Template should successfully compile")
+            .render_defaults()
+            .expect("Template should successfully render")
+    };
+
+    assert_eq!(render("true"), r#"<body style="background: #000000">"#);
+    assert_eq!(render("false"), r#"<body style="background: #ffffff">"#);
+
+    let declared = Balsa::from_string(
+        r#"{{@ compact : string = "true" }}{{@ if compact: gap : int = 4, if false: gap : int = 0 }}{{ gap : int, defaultValue: gap }}"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+    assert!(declared.warnings().is_empty());
+    assert_eq!(
+        declared
+            .render_defaults()
+            .expect("Template should successfully render"),
+        "4"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string(template).build(),
+            Err(BalsaError::CompileError(BalsaCompileError::UndefinedVariable(e)))
+                if e.variable_name == "darkMode"
+        ),
+        "Conditions should be declared"
+    );
+}
+
+#[test]
+fn macros_test() {
+    let template = r#"{{#macro button(label, href)}}<a class="button" href="{{ href : string }}">{{ label : string }}</a>{{/macro}}{{@ home : string = "/" }}{{ button("Sign up", signupUrl) }} {{ button("Home", home) }}"#;
+
+    let params = BalsaParameters::new().string("signupUrl", "/signup?ref=mail");
+    assert_eq!(
+        Balsa::from_string(template)
+            .build()
+            .expect("Template should successfully compile")
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        r#"<a class="button" href="/signup?ref=mail">Sign up</a> <a class="button" href="/">Home</a>"#
+    );
+
+    let compile_error = |template: &str| match Balsa::from_string(template).build() {
+        Err(BalsaError::CompileError(e)) => Some(e),
+        _ => None,
+    };
+
+    assert!(matches!(
+        compile_error(r#"{{ button("Sign up") }}{{#macro button(label)}}{{ label : string }}{{/macro}}"#),
+        Some(BalsaCompileError::UndefinedMacro(e)) if e.macro_name == "button"
+    ));
+    assert!(matches!(
+        compile_error(r#"{{#macro button(label)}}{{ label : string }}{{/macro}}{{ button("a", "b") }}"#),
+        Some(BalsaCompileError::InvalidHelperArguments(e)) if e.expected == 1 && e.received == 2
+    ));
+    assert!(matches!(
+        compile_error("{{#macro a()}}{{/macro}}{{#macro a()}}{{/macro}}"),
+        Some(BalsaCompileError::DuplicateMacro(e)) if e.macro_name == "a"
+    ));
+}
+
+#[test]
+fn slots_test() {
+    let template = Balsa::from_string(
+        r#"{{@ toolbar : string = "admin" }}<main>{{ title : string }}</main>{{#slot "analytics"}}{{#slot toolbar}}"#,
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .build()
+    .expect("Template should successfully compile");
+    let params = BalsaParameters::new().string("title", "<Home>");
+
+    let render = |options: &RenderOptions| {
+        template
+            .render_html_string_with_options(&params, options)
+            .expect("Template should successfully render")
+    };
+
+    assert_eq!(
+        render(&RenderOptions::new()),
+        "<main>&lt;Home&gt;</main>",
+        "Slots which aren't filled should render nothing"
+    );
+    assert_eq!(
+        render(
+            &RenderOptions::new()
+                .slot("analytics", r#"<script src="/a.js"></script>"#)
+                .slot("admin", "<nav>Edit</nav>")
+        ),
+        r#"<main>&lt;Home&gt;</main><script src="/a.js"></script><nav>Edit</nav>"#
+    );
+
+    let cache = RenderCache::new(8);
+    for html in ["<b>1</b>", "<b>2</b>"] {
+        assert_eq!(
+            &*cache
+                .render(
+                    &template,
+                    &params,
+                    &RenderOptions::new().slot("admin", html)
+                )
+                .expect("Template should successfully render"),
+            format!("<main>&lt;Home&gt;</main>{}", html),
+            "Slots should be part of the cache key"
+        );
+    }
+}
+
+#[test]
+fn partially_render_test() {
+    let template = Balsa::from_string(
+        r#"{{@computed total : int = price * quantity }}<h1>{{ site : string }}</h1>
+<p>{{ plural quantity "item" "items" }}: {{ total : int }}</p>
+<p>{{ user : string }} ({{ theme : string, defaultValue: "light" }})</p>{{#slot "toolbar"}}"#,
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .build()
+    .expect("Template should successfully compile");
+
+    let static_params = BalsaParameters::new()
+        .string("site", "<Balsa>")
+        .int("quantity", 3);
+    let baked = template
+        .partially_render(&static_params)
+        .expect("Template should be partially rendered");
+
+    assert_eq!(
+        baked.explain().replacements.len(),
+        4,
+        "Only the total, user, theme and slot should remain"
+    );
+
+    let params = BalsaParameters::new().string("user", "Ada").int("price", 5);
+    let expected = "<h1>&lt;Balsa&gt;</h1>\n<p>items: 15</p>\n<p>Ada (light)</p><nav></nav>";
+    let options = RenderOptions::new().slot("toolbar", "<nav></nav>");
+
+    assert_eq!(
+        baked
+            .render_html_string_with_options(&params, &options)
+            .expect("Partially rendered template should render"),
+        expected
+    );
+    assert_eq!(
+        template
+            .render_html_string_with_options(
+                &BalsaParameters::new()
+                    .string("site", "<Balsa>")
+                    .int("quantity", 3)
+                    .string("user", "Ada")
+                    .int("price", 5),
+                &options
+            )
+            .expect("Template should successfully render"),
+        expected,
+        "Both phases together should render the same output as a single render"
+    );
+
+    match baked.render_html_string(&BalsaParameters::new().int("price", 5)) {
+        Err(BalsaError::RenderError(BalsaRenderError::MissingParameter(e))) => {
+            assert_eq!(e.error.parameter_name, "user");
+            assert_eq!((e.line, e.column), (3, 4));
+        }
+        other => panic!("Expected a missing parameter error, got {:?}", other),
+    }
+
+    assert!(
+        matches!(
+            template.partially_render(&BalsaParameters::new().string("quantity", "three")),
+            Err(BalsaError::RenderError(
+                BalsaRenderError::InvalidParameterType(_)
+            ))
+        ),
+        "Static parameters should be checked when they are baked"
+    );
+}
+
+#[test]
+fn hydration_markers_test() {
+    let template = Balsa::from_string(
+        r#"<p {{ dir "en" }}>{{ name : string }} has {{ count : int }} {{ plural count "item" "items" }}</p>"#,
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .build()
+    .expect("Template should successfully compile");
+    let params = BalsaParameters::new()
+        .string("name", "<Ada>")
+        .int("count", 2);
+
+    let render = |markers: HydrationMarkers| {
+        template
+            .render_html_string_with_options(
+                &params,
+                &RenderOptions::new().hydration_markers(markers),
+            )
+            .expect("Template should successfully render")
+    };
+
+    assert_eq!(
+        render(HydrationMarkers::None),
+        r#"<p dir="ltr">&lt;Ada&gt; has 2 items</p>"#
+    );
+    assert_eq!(
+        render(HydrationMarkers::Span),
+        r#"<p dir="ltr"><span data-balsa="name">&lt;Ada&gt;</span> has <span data-balsa="count">2</span> <span data-balsa="count">items</span></p>"#
+    );
+    assert_eq!(
+        render(HydrationMarkers::Comment),
+        r#"<p dir="ltr"><!--balsa:name-->&lt;Ada&gt;<!--/balsa--> has <!--balsa:count-->2<!--/balsa--> <!--balsa:count-->items<!--/balsa--></p>"#
+    );
+}
+
+#[test]
+fn sections_test() {
+    let template = Balsa::from_string(
+        r#"<ul>{{#each posts: array<dict>}}<li>{{ title: string }} ({{ views: int, defaultValue: 0 }})</li>{{/each}}</ul>"#,
+    )
+    .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+    .build()
+    .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new().sections(
+        "posts",
+        vec![
+            BalsaParameters::new()
+                .string("title", "Tips & tricks")
+                .int("views", 12),
+            BalsaParameters::new().string("title", "Hello"),
+        ],
+    );
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<ul><li>Tips &amp; tricks (12)</li><li>Hello (0)</li></ul>"
+    );
+    assert_eq!(
+        template
+            .render_defaults()
+            .expect("Sections should default to no items"),
+        "<ul></ul>"
+    );
+
+    let missing = BalsaParameters::new().sections("posts", vec![BalsaParameters::new()]);
+    assert!(
+        matches!(
+            template.render_html_string(&missing),
+            Err(BalsaError::RenderError(BalsaRenderError::MissingParameter(ref e)))
+                if e.error.parameter_name == "posts.title"
+        ),
+        "Missing item values should be reported by their path"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{#each posts: array<string>}}{{/each}}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidSectionType(_)
+            ))
+        ),
+        "Sections must be arrays of dictionaries"
+    );
+}
+
+#[test]
+fn item_limits_test() {
+    let template = Balsa::from_string(
+        r#"{{#each posts: array<dict>, minItems: 1, maxItems: 2, itemLabel: "Post"}}<h2>{{ title: string }}</h2>{{/each}}"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let explanation = template.explain();
+    assert!(
+        matches!(
+            &explanation.replacements[0].replace_with,
+            ExplainedReplaceWith::Each { items, .. } if *items == ExplainedItems {
+                min_items: Some(1),
+                max_items: Some(2),
+                item_label: Some("Post".to_string()),
+            }
+        ),
+        "Item limits should be explained"
+    );
+
+    let posts = |n: usize| {
+        BalsaParameters::new().sections(
+            "posts",
+            vec![BalsaParameters::new().string("title", "Hi"); n],
+        )
+    };
+    assert_eq!(
+        template
+            .render_html_string(&posts(2))
+            .expect("Template should successfully render"),
+        "<h2>Hi</h2><h2>Hi</h2>"
+    );
+
+    for n in [0, 3] {
+        assert!(
+            matches!(
+                template.render_html_string(&posts(n)),
+                Err(BalsaError::RenderError(BalsaRenderError::InvalidItemCount(ref e)))
+                    if e.error.count == n
+            ),
+            "Rendering {} items should fail",
+            n
+        );
+    }
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ title: string, minItems: 1 }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidParameter(_)
+            ))
+        ),
+        "Item limits should only be allowed on arrays"
+    );
+    assert!(
+        matches!(
+            Balsa::from_string("{{ tags: array<string>, minItems: 3, maxItems: 1 }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidItemLimits(_)
+            ))
+        ),
+        "minItems should not exceed maxItems"
+    );
+}
+
+#[test]
+fn one_of_test() {
+    let template = Balsa::from_string(
+        r#"<p style="text-align: {{ alignment : string, oneOf: ["left", "center", "right"], defaultValue: "left" }}">{{#each rows: array<dict>}}{{ size : int, oneOf: [1, 2] }}{{/each}}</p>"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    assert_eq!(
+        template.parameters()[0].one_of,
+        vec![
+            BalsaValue::String("left".to_string()),
+            BalsaValue::String("center".to_string()),
+            BalsaValue::String("right".to_string()),
+        ],
+        "Allowed values should be introspectable"
+    );
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().string("alignment", "center"))
+            .expect("Template should successfully render"),
+        r#"<p style="text-align: center"></p>"#
+    );
+
+    assert!(
+        matches!(
+            template.render_html_string(&BalsaParameters::new().string("alignment", "justify")),
+            Err(BalsaError::RenderError(BalsaRenderError::DisallowedValue(ref e)))
+                if e.error.parameter_name == "alignment"
+        ),
+        "Values outside oneOf should be rejected"
+    );
+    assert!(
+        matches!(
+            template.render_html_string(&BalsaParameters::new().sections(
+                "rows",
+                vec![BalsaParameters::new().int("size", 3)]
+            )),
+            Err(BalsaError::RenderError(BalsaRenderError::DisallowedValue(ref e)))
+                if e.error.parameter_name == "rows.size"
+        ),
+        "Fields outside oneOf should be rejected"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string(r#"{{ size : int, oneOf: ["small"] }}"#).build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidTypeCast(_)
+            ))
+        ),
+        "Allowed values should have the type of the parameter"
+    );
+    assert!(
+        matches!(
+            Balsa::from_string(r#"{{ size : string, oneOf: "small" }}"#).build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidTypeCast(_)
+            ))
+        ),
+        "Allowed values should be an array"
+    );
+}
+
+#[test]
+fn image_test() {
+    let template = Balsa::from_string(
+        r#"<figure>{{ hero: image, accept: "image/png, image/webp", maxWidth: 1200 }}</figure>"#,
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new().image(
+        "hero",
+        Image::new("/hero.png?size=large&v=2", "A \"lighthouse\" at dusk").with_size(1200, 630),
+    );
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        r#"<figure><img src="/hero.png?size=large&amp;v=2" alt="A &quot;lighthouse&quot; at dusk" width="1200" height="630"></figure>"#
+    );
+
+    let dictionary = Dictionary::from_entries(
+        BalsaType::Any,
+        [
+            ("src", BalsaValue::String("/logo.svg".to_string())),
+            ("alt", BalsaValue::String(String::new())),
+        ],
+    )
+    .expect("Dictionary should be created");
+    assert_eq!(
+        template
+            .render_html_string(&BalsaParameters::new().dictionary("hero", dictionary))
+            .expect("Dictionaries should be cast to images"),
+        r#"<figure><img src="/logo.svg" alt=""></figure>"#
+    );
+
+    let explanation = template.explain();
+    assert_eq!(
+        explanation.replacements[0].replace_with.to_string(),
+        "parameter hero: image (accept: \"image/png, image/webp\", maxWidth: 1200)"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string(r#"{{ hero: string, accept: "image/png" }}"#).build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidParameter(_)
+            ))
+        ),
+        "Image options should only be allowed on images"
+    );
+    assert!(
+        matches!(
+            Balsa::from_string("{{ hero: image, maxHeight: 5000000000 }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidParameter(_)
+            ))
+        ),
+        "Image dimensions should fit in 32 bits"
+    );
+}
+
+#[test]
+fn concat_test() {
+    let header = Balsa::from_string(
+        "{{@ site : string = \"Balsa\" }}<h1>{{ title : string, defaultValue: site }}</h1>\n",
+    )
+    .build()
+    .expect("Header should successfully compile");
+    let body = Balsa::from_string(
+        "<p>{{ title : string }}</p>\n{{#each links: array<dict>}}<a>{{ label : string }}</a>{{/each}}",
+    )
+    .build()
+    .expect("Body should successfully compile");
+
+    let page = header.concat(&body).expect("Templates should be composed");
+    let params = BalsaParameters::new().string("title", "Home").sections(
+        "links",
+        vec![BalsaParameters::new().string("label", "Docs")],
+    );
+    assert_eq!(
+        page.render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<h1>Home</h1>\n<p>Home</p>\n<a>Docs</a>"
+    );
+
+    let explanation = page.explain();
+    let (start, end) = (
+        &explanation.replacements[3].start,
+        &explanation.replacements[3].end,
+    );
+    assert_eq!((start.line, start.column), (3, 1));
+    assert_eq!((start.offset, end.offset), (108, 172));
+
+    let conflicting = Balsa::from_string("{{ title : int }}")
+        .build()
+        .expect("Template should successfully compile");
+    match header.concat(&conflicting) {
+        Err(BalsaError::CompileError(BalsaCompileError::ConflictingParameter(e))) => {
+            assert_eq!((e.line, e.column), (2, 1));
+            assert_eq!((e.error.first_line, e.error.first_column), (1, 35));
+        }
+        e => panic!("Expected `ConflictingParameter` error, got: {:?}", e),
+    }
+
+    let escaped = Balsa::from_string("{{ body : string }}")
+        .with_options(CompileOptions::new().escape_policy(EscapePolicy::Html))
+        .build()
+        .expect("Template should successfully compile");
+    assert!(
+        matches!(
+            header.concat(&escaped),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::ConflictingEscapePolicy(_)
+            ))
+        ),
+        "Templates with different escape policies should not be composed"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn problem_json_test() {
+    let template = Balsa::from_string("<h1>{{ title : string }}</h1>\n<p>{{ count : int }}</p>")
+        .build()
+        .expect("Template should successfully compile");
+
+    let problem = template
+        .render_html_string(&BalsaParameters::new().string("title", "Hi"))
+        .expect_err("Rendering without `count` should fail")
+        .to_problem_json();
+    assert_eq!(problem.problem_type, "urn:balsa:error:missing_parameter");
+    assert_eq!(problem.code, "missing_parameter");
+    assert_eq!(problem.status, 422);
+    assert_eq!(problem.parameter.as_deref(), Some("count"));
+    let position = problem
+        .position
+        .expect("Render errors should have a position");
+    assert_eq!((position.line, position.column), (2, 4));
+
+    let problem =
+        BalsaError::ReadTemplateError(std::io::ErrorKind::NotFound.into()).to_problem_json();
+    assert_eq!(problem.status, 500);
+    assert_eq!(problem.position, None);
+}
+
+#[test]
+fn parameters_test() {
+    let template = Balsa::from_string(
+        r#"{{@computed total : int = price * 2 }}<h1>{{ title : string, label: "Title" }}</h1>{{ hero : image, accept: "image/png", maxWidth: 800 }}{{ total : int }}{{ title : string, defaultValue: "Home" }}{{#each posts: array<dict>, maxItems: 3, itemLabel: "Post"}}{{ heading : string, defaultValue: "Untitled" }}{{/each}}"#,
+    )
+    .with_options(CompileOptions::new().allow_parameter_option("label"))
+    .build()
+    .expect("Template should successfully compile");
+
+    let parameters = template.parameters();
+    let names: Vec<&str> = parameters.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["title", "hero", "posts", "price"]);
+
+    let title = &parameters[0];
+    assert_eq!(
+        title.default_value,
+        Some(BalsaValue::String("Home".to_string()))
+    );
+    assert_eq!(
+        title.options,
+        [("label".to_string(), BalsaValue::String("Title".to_string()))]
+    );
+
+    let hero = &parameters[1];
+    assert_eq!(hero.parameter_type, BalsaType::Image);
+    assert_eq!(hero.accept, ["image/png"]);
+    assert_eq!(hero.max_width, Some(800));
+
+    let posts = &parameters[2];
+    assert_eq!(
+        (posts.max_items, posts.item_label.as_deref()),
+        (Some(3), Some("Post"))
+    );
+    assert_eq!(posts.fields.len(), 1);
+    assert_eq!(posts.fields[0].name, "heading");
+    assert_eq!(
+        posts.fields[0].default_value,
+        Some(BalsaValue::String("Untitled".to_string()))
+    );
+    assert_eq!(parameters[3].parameter_type, BalsaType::Float);
+}
+
+#[test]
+fn conditional_test() {
+    let template = Balsa::from_string(
+        "<main>{{if showBanner}}<div>{{ banner : string }}</div>{{else}}<hr>{{endif}}{{ if loggedIn, defaultValue: false }}<p>Hi{{if isAdmin}} admin{{endif}}</p>{{ endif }}</main>",
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new()
+        .bool("showBanner", true)
+        .string("banner", "Sale");
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<main><div>Sale</div></main>"
+    );
+
+    // Parameters in skipped sections don't need to be passed.
+    let params = BalsaParameters::new()
+        .bool("showBanner", false)
+        .bool("loggedIn", true)
+        .string("isAdmin", "true");
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "<main><hr><p>Hi admin</p></main>"
+    );
+
+    let baked = template
+        .partially_render(&BalsaParameters::new().bool("showBanner", false))
+        .expect("Template should be partially rendered");
+    assert_eq!(
+        baked
+            .render_html_string(&BalsaParameters::new())
+            .expect("Template should successfully render"),
+        "<main><hr></main>"
+    );
+
+    let bytes = Balsa::from_string(
+        "{{if showBanner, defaultValue: true}}<div>Sale</div>{{else}}<hr>{{endif}}",
+    )
+    .precompile()
+    .expect("Template should be precompiled");
+    let precompiled = Template::from_precompiled(&bytes).expect("Precompiled template should load");
+    assert_eq!(
+        precompiled
+            .render_html_string(&BalsaParameters::new())
+            .expect("Template should successfully render"),
+        "<div>Sale</div>"
+    );
+
+    match template.render_html_string(&BalsaParameters::new().int("showBanner", 1)) {
+        Err(BalsaError::RenderError(BalsaRenderError::InvalidParameterType(_))) => {}
+        e => panic!("Expected `InvalidParameterType` error, got: {:?}", e),
+    }
+
+    for (source, expected, pos) in [
+        ("<p>{{else}}</p>", UnbalancedConditional::UnexpectedElse, 3),
+        (
+            "{{if a}}{{endif}}{{endif}}",
+            UnbalancedConditional::UnexpectedEndIf,
+            17,
+        ),
+        (
+            "{{if a}}1{{else}}2{{else}}3{{endif}}",
+            UnbalancedConditional::DuplicateElse,
+            18,
+        ),
+        (
+            "{{if a}}{{if b}}{{endif}}",
+            UnbalancedConditional::Unclosed,
+            0,
+        ),
+    ] {
+        match Balsa::from_string(source).build() {
+            Err(BalsaError::CompileError(BalsaCompileError::UnbalancedConditional(e))) => {
+                assert_eq!((e.error, e.pos), (expected, pos), "{}", source);
+            }
+            e => panic!("Expected `UnbalancedConditional` error, got: {:?}", e),
+        }
+    }
+
+    match Balsa::from_string("{{if a}}{{endif}}{{ a : string }}").build() {
+        Err(BalsaError::CompileError(BalsaCompileError::ConflictingParameter(_))) => {}
+        e => panic!("Expected `ConflictingParameter` error, got: {:?}", e),
+    }
+}
+
+#[test]
+fn error_line_column_test() {
+    let template = "<html>\n  <body>\n    <h1>{{ title : string }}</h1>\n    <p>{{ title : int }}</p>\n  </body>\n</html>";
+
+    match Balsa::from_string(template).build() {
+        Err(e @ BalsaError::CompileError(BalsaCompileError::ConflictingParameter(_))) => {
+            assert_eq!(
+                e.to_string(),
+                "compile error: parameter `title` has type `int` but was first used with type `string` (first used at line 3, column 9) at line 4, column 8"
+            );
+        }
+        e => panic!("Expected `ConflictingParameter` error, got: {:?}", e),
+    }
+}
+
+#[test]
+fn render_to_writer_test() {
+    struct FailingWriter;
+
+    impl std::io::Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("connection closed"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let template = Balsa::from_string(
+        "<h1>{{ title : string }}</h1>\r\n{{if showBody, defaultValue: true}}<p>{{ body : string }}</p>{{endif}}\r\n",
+    )
+    .build()
+    .expect("Template should successfully compile");
+    let params = BalsaParameters::new()
+        .string("title", "Hi")
+        .string("body", "<b>");
+
+    let mut output = Vec::new();
+    template
+        .render_to_writer(&params, &mut output)
+        .expect("Template should render into the writer");
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        template.render_html_string(&params).unwrap()
+    );
+
+    let mut output = Vec::new();
+    assert!(matches!(
+        template.render_to_writer(&BalsaParameters::new().string("title", "Hi"), &mut output),
+        Err(BalsaError::RenderError(BalsaRenderError::MissingParameter(
+            _
+        )))
+    ));
+    assert_eq!(output, b"<h1>Hi</h1>\r\n");
+
+    let error = template
+        .render_to_writer(&params, &mut FailingWriter)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to write output: connection closed"
+    );
+}
+
+#[test]
+fn include_test() {
+    let partials = HashMap::from([
+        (
+            "partials/header.html".to_string(),
+            "<h1>{{ title : string }}</h1>\n{{include \"partials/nav.html\"}}".to_string(),
+        ),
+        (
+            "partials/nav.html".to_string(),
+            "<nav>{{ title : string }}{{if loggedIn, defaultValue: false}} (you){{endif}}</nav>"
+                .to_string(),
+        ),
+        (
+            "partials/footer.html".to_string(),
+            "<footer>{{ year : int, defaultValue: 2024 }}</footer>".to_string(),
+        ),
+        (
+            "partials/a.html".to_string(),
+            "a{{include \"partials/b.html\"}}".to_string(),
+        ),
+        (
+            "partials/b.html".to_string(),
+            "b{{include \"partials/a.html\"}}".to_string(),
+        ),
+        (
+            "partials/year.html".to_string(),
+            "{{ year : string }}".to_string(),
+        ),
+    ]);
+
+    let builder = Balsa::from_string(
+        "{{include \"partials/header.html\"}}<p>{{ body : string }}</p>{{if showFooter, defaultValue: true}}{{ include \"partials/footer.html\" }}{{endif}}",
+    )
+    .loader(partials.clone());
+    let template = builder
+        .build()
+        .expect("Template should successfully compile");
+    let params = BalsaParameters::new()
+        .string("title", "Hi")
+        .string("body", "Hello")
+        .bool("loggedIn", true);
+
+    assert_eq!(
+        template.render_html_string(&params).unwrap(),
+        "<h1>Hi</h1>\n<nav>Hi (you)</nav><p>Hello</p><footer>2024</footer>"
+    );
+    assert_eq!(
+        template
+            .render_html_string(&params.bool("showFooter", false))
+            .unwrap(),
+        "<h1>Hi</h1>\n<nav>Hi (you)</nav><p>Hello</p>"
+    );
+    assert_eq!(
+        template
+            .parameters()
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>(),
+        ["title", "loggedIn", "body", "showFooter", "year"]
+    );
+
+    let precompiled =
+        Template::from_precompiled(&builder.precompile().expect("Template should precompile"))
+            .expect("Precompiled template should load");
+    assert_eq!(
+        precompiled.render_html_string(&params).unwrap(),
+        template.render_html_string(&params).unwrap()
+    );
+
+    let error = |raw_template: &str| {
+        Balsa::from_string(raw_template)
+            .loader(partials.clone())
+            .build()
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error("{{include \"partials/a.html\"}}"),
+        "compile error: in included template `partials/a.html`: in included template `partials/b.html`: template `partials/a.html` includes itself through `partials/a.html` -> `partials/b.html` -> `partials/a.html` at line 1, column 2 at line 1, column 2 at line 1, column 1"
+    );
+    assert_eq!(
+        error("{{ year : int }}{{include \"partials/year.html\"}}"),
+        "compile error: parameter `year` has type `string` but was first used with type `int` (first used at line 1, column 1) at line 1, column 17"
+    );
+    assert!(matches!(
+        Balsa::from_string("{{include \"partials/missing.html\"}}")
+            .loader(partials.clone())
+            .build(),
+        Err(BalsaError::CompileError(BalsaCompileError::IncludeFailed(e)))
+            if e.error.reason == IncludeFailure::LoadFailed("template not found".to_string())
+    ));
+    assert!(matches!(
+        Balsa::from_string("<p>{{include \"partials/footer.html\"}}</p>").build(),
+        Err(BalsaError::CompileError(BalsaCompileError::IncludeFailed(e)))
+            if e.error.reason == IncludeFailure::NoLoader && e.pos == 3
+    ));
+
+    let include_header = |max_depth: usize| {
+        Balsa::from_string("{{include \"partials/header.html\"}}")
+            .loader(partials.clone())
+            .max_include_depth(max_depth)
+            .build()
+    };
+    assert!(
+        include_header(2).is_ok(),
+        "Templates included within the maximum depth should compile"
+    );
+    assert_eq!(
+        include_header(1).unwrap_err().to_string(),
+        "compile error: in included template `partials/header.html`: includes exceed the maximum depth of 1 at line 2, column 1 at line 1, column 1"
+    );
+    assert!(matches!(
+        include_header(0),
+        Err(BalsaError::CompileError(BalsaCompileError::LimitExceeded(e)))
+            if e.error == LimitExceeded::IncludeDepth { max_depth: 0 }
+    ));
+}
+
+#[test]
+fn include_arguments_test() {
+    let partials = HashMap::from([
+        (
+            "card.html".to_string(),
+            "<h2>{{ title : string }}</h2><p>{{ count : int }} {{plural count \"comment\" \"comments\"}}</p>{{if featured, defaultValue: false}}<b>Featured</b>{{endif}}".to_string(),
+        ),
+        (
+            "total.html".to_string(),
+            "{{@ computed total : int = a + b }}{{ total : int }}".to_string(),
+        ),
+    ]);
+
+    let template = Balsa::from_string(
+        "{{include \"card.html\" title=postTitle count=commentCount}}{{include \"card.html\" title=\"About\" count=1 featured=showAbout}}",
+    )
+    .loader(partials.clone())
+    .build()
+    .expect("Template should successfully compile");
+    let params = BalsaParameters::new()
+        .string("postTitle", "Hello")
+        .int("commentCount", 3)
+        .bool("showAbout", true);
+
+    assert_eq!(
+        template.render_html_string(&params).unwrap(),
+        "<h2>Hello</h2><p>3 comments</p><h2>About</h2><p>1 comment</p><b>Featured</b>"
+    );
+    assert_eq!(
+        template
+            .parameters()
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>(),
+        ["postTitle", "commentCount", "featured", "showAbout"]
+    );
+
+    let computed = Balsa::from_string("{{include \"total.html\" a=x b=2}}")
+        .loader(partials.clone())
+        .build()
+        .expect("Template should successfully compile");
+    assert_eq!(
+        computed
+            .render_html_string(&BalsaParameters::new().int("x", 5))
+            .unwrap(),
+        "7"
+    );
+
+    let error = |raw_template: &str| {
+        Balsa::from_string(raw_template)
+            .loader(partials.clone())
+            .build()
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error("{{include \"card.html\" title=a title=b}}"),
+        "compile error: option `title` provided more than once at line 1, column 1"
+    );
+    assert_eq!(
+        error("{{include \"card.html\" featured=true}}"),
+        "compile error: invalid identifier `true` provided in parameter block at line 1, column 1"
+    );
+    assert_eq!(error("{{include \"card.html\" count=\"many\"}}"),
+        "compile error: failed to cast value `\"many\"` of type `string` to type `int` at line 1, column 1"
+    );
+    assert_eq!(
+        error("{{ dir locale title=a }}"),
+        "compile error: invalid or unknown parameter `title` provided at line 1, column 1"
+    );
+}
+
+#[test]
+fn section_include_test() {
+    let partials = HashMap::from([(
+        "menu.html".to_string(),
+        "<ul>{{#each items: array<dict>, defaultValue: []}}<li>{{ name : string }}{{include \"menu.html\" items=children}}</li>{{/each}}</ul>".to_string(),
+    )]);
+
+    let template = Balsa::from_string("<nav>{{include \"menu.html\" items=menu}}</nav>")
+        .loader(partials.clone())
+        .build()
+        .expect("Templates including themselves in sections should compile");
+    let item = |name: &str, children: Vec<BalsaParameters>| {
+        let item = BalsaParameters::new().string("name", name);
+        match children.is_empty() {
+            true => item,
+            false => item.sections("children", children),
+        }
+    };
+    let params = BalsaParameters::new().sections(
+        "menu",
+        vec![
+            item(
+                "Docs",
+                vec![item("Guide", vec![item("Install", Vec::new())])],
+            ),
+            item("Blog", Vec::new()),
+        ],
+    );
+
+    let expected = "<nav><ul><li>Docs<ul><li>Guide<ul><li>Install<ul></ul></li></ul></li></ul></li><li>Blog<ul></ul></li></ul></nav>";
+    assert_eq!(template.render_html_string(&params).unwrap(), expected);
+
+    let precompiled = Template::from_precompiled(
+        &Balsa::from_string("<nav>{{include \"menu.html\" items=menu}}</nav>")
+            .loader(partials.clone())
+            .precompile()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(precompiled.render_html_string(&params).unwrap(), expected);
+
+    let render = |max_depth: usize| {
+        template
+            .render_html_string_with_options(
+                &params,
+                &RenderOptions::new().max_include_depth(max_depth),
+            )
+            .map_err(|e| match e {
+                BalsaError::RenderError(BalsaRenderError::LimitExceeded(e)) => Some(e.error),
+                _ => None,
+            })
+    };
+    assert_eq!(render(3), Ok(expected.to_string()));
+    assert_eq!(
+        render(2),
+        Err(Some(LimitExceeded::IncludeDepth { max_depth: 2 })),
+        "Sections including templates deeper than allowed should fail"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{include \"menu.html\" items=menu}}")
+                .loader(HashMap::from([(
+                    "menu.html".to_string(),
+                    "{{include \"menu.html\" items=menu}}".to_string(),
+                )]))
+                .build(),
+            Err(BalsaError::CompileError(_))
+        ),
+        "Templates including themselves outside of sections should still fail to compile"
+    );
+}
+
+#[test]
+fn file_loader_test() {
+    let root = std::env::temp_dir().join(format!("balsa-includes-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("partials")).unwrap();
+    std::fs::write(
+        root.join("partials/footer.html"),
+        "<footer>{{ siteName : string }}</footer>",
+    )
+    .unwrap();
+
+    let loader = FileLoader::new(&root);
+    let template = Balsa::from_string("{{include \"partials/footer.html\"}}")
+        .loader(loader.clone())
+        .build();
+    let escaping = Balsa::from_string("{{include \"../secrets.html\"}}")
+        .loader(loader)
+        .build();
+    std::fs::remove_dir_all(&root).ok();
+
+    assert_eq!(
+        template
+            .expect("Template should successfully compile")
+            .render_html_string(&BalsaParameters::new().string("siteName", "Balsa"))
+            .unwrap(),
+        "<footer>Balsa</footer>"
+    );
+    assert!(matches!(
+        escaping,
+        Err(BalsaError::CompileError(BalsaCompileError::IncludeFailed(e)))
+            if matches!(e.error.reason, IncludeFailure::LoadFailed(_))
+    ));
+}
+
+#[test]
+fn registry_test() {
+    let root = std::env::temp_dir().join(format!("balsa-registry-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("pages")).unwrap();
+    std::fs::create_dir_all(root.join("partials")).unwrap();
+    std::fs::write(
+        root.join("pages/home.html"),
+        "<h1>{{ title : string }}</h1>{{include \"partials/footer.html\"}}",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("partials/footer.html"),
+        "<footer>{{ siteName : string }}</footer>",
+    )
+    .unwrap();
+    std::fs::write(root.join("notes.txt"), "{{ title : string, bogus: 1 }}").unwrap();
+
+    let registry = BalsaRegistry::from_glob(&root, "**/*.html");
+    let pages = BalsaRegistry::from_glob(&root, "pages/*.html");
+    let failed = BalsaRegistry::from_dir(&root);
+    std::fs::remove_dir_all(&root).ok();
+
+    let registry = registry.expect("Registry should successfully compile");
+    assert_eq!(
+        registry.names(),
+        ["pages/home.html", "partials/footer.html"]
+    );
+
+    let params = BalsaParameters::new()
+        .string("title", "Home")
+        .string("siteName", "Balsa");
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            let registry = registry.clone();
+            let params = &params;
+            s.spawn(move || {
+                assert_eq!(
+                    registry.render("pages/home.html", params).unwrap(),
+                    "<h1>Home</h1><footer>Balsa</footer>"
+                );
+            });
+        }
+    });
+
+    assert!(matches!(
+        registry.render("pages/missing.html", &params),
+        Err(BalsaError::CompileError(BalsaCompileError::UndefinedTemplate(e)))
+            if e.error.template_name == "pages/missing.html"
+    ));
+    assert_eq!(pages.unwrap().len(), 1);
+    assert!(matches!(
+        failed,
+        Err(BalsaError::CompileError(BalsaCompileError::TemplateFailed(e)))
+            if e.error.template_name == "notes.txt"
+    ));
+}
+
+#[cfg(feature = "notify")]
+#[test]
+fn registry_watch_test() {
+    let root = std::env::temp_dir().join(format!("balsa-watch-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("partials")).unwrap();
+    std::fs::write(
+        root.join("home.html"),
+        "<h1>{{ title : string }}</h1>{{include \"partials/footer.html\"}}",
+    )
+    .unwrap();
+    std::fs::write(root.join("partials/footer.html"), "<footer></footer>").unwrap();
+
+    let registry = BalsaRegistry::watch(&root, "**/*.html", &CompileOptions::new())
+        .expect("Registry should start watching");
+    let params = BalsaParameters::new().string("title", "Home");
+    assert_eq!(
+        registry.render("home.html", &params).unwrap(),
+        "<h1>Home</h1><footer></footer>"
+    );
+
+    // Waits for the watcher to pick up a change, returning the result of the last render.
+    let render_until = |done: &dyn Fn(&balsa::BalsaResult<String>) -> bool| {
+        let mut rendered = registry.render("home.html", &params);
+        for _ in 0..100 {
+            if done(&rendered) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+            rendered = registry.render("home.html", &params);
+        }
+        rendered
+    };
+
+    std::fs::write(root.join("partials/footer.html"), "<footer>v2</footer>").unwrap();
+    assert_eq!(
+        render_until(&|r| r.as_deref().is_ok_and(|html| html.contains("v2"))).unwrap(),
+        "<h1>Home</h1><footer>v2</footer>"
+    );
+
+    std::fs::write(root.join("home.html"), "{{ title : string, bogus: 1 }}").unwrap();
+    assert!(matches!(
+        render_until(&|r| r.is_err()),
+        Err(BalsaError::CompileError(BalsaCompileError::TemplateFailed(e)))
+            if e.error.template_name == "home.html"
+    ));
+
+    std::fs::write(root.join("home.html"), "<h2>{{ title : string }}</h2>").unwrap();
+    let fixed = render_until(&|r| r.as_deref().is_ok_and(|html| html.contains("h2")));
+    std::fs::remove_dir_all(&root).ok();
+    assert_eq!(fixed.unwrap(), "<h2>Home</h2>");
+}
+
+#[test]
+fn render_abort_test() {
+    let template = Balsa::from_string("<h1>{{ title : string }}</h1>")
+        .build()
+        .expect("Template should successfully compile");
+    let params = BalsaParameters::new().string("title", "Hello");
+
+    // Keeps only the reason of an abort error, if any.
+    let render = |options: RenderOptions| {
+        template
+            .render_html_string_with_options(&params, &options)
+            .map_err(|e| match e {
+                BalsaError::RenderError(BalsaRenderError::Aborted(e)) => Some(e.error),
+                _ => None,
+            })
+    };
+
+    let token = CancellationToken::new();
+    let later = Instant::now() + Duration::from_secs(60);
+
+    assert_eq!(
+        render(
+            RenderOptions::new()
+                .deadline(later)
+                .cancellation(token.clone())
+        ),
+        Ok("<h1>Hello</h1>".to_string())
+    );
+    assert_eq!(
+        render(RenderOptions::new().deadline(Instant::now())),
+        Err(Some(RenderAborted::DeadlineExceeded))
+    );
+
+    token.cancel();
+    assert_eq!(
+        render(RenderOptions::new().cancellation(token)),
+        Err(Some(RenderAborted::Cancelled))
+    );
+}
+
+#[test]
+fn sandboxed_test() {
+    let sandboxed = |template: &str| Balsa::from_string(template).sandboxed(true);
+
+    assert!(
+        sandboxed("<p>{{ body : string, escape: true }}</p>")
+            .build()
+            .is_ok(),
+        "Escaped parameters should be allowed in sandboxed templates"
+    );
+    assert!(
+        matches!(
+            sandboxed("<p>{{ body : string, escape: false }}</p>").build(),
+            Err(BalsaError::CompileError(BalsaCompileError::SandboxViolation(e)))
+                if e.feature == "escape: false"
+        ),
+        "Unescaped parameters should fail to compile in sandboxed templates"
+    );
+
+    let partials = HashMap::from([("footer.html".to_string(), "<footer></footer>".to_string())]);
+    assert!(
+        matches!(
+            sandboxed("{{include \"footer.html\"}}").loader(partials).build(),
+            Err(BalsaError::CompileError(BalsaCompileError::SandboxViolation(e)))
+                if e.feature == "include"
+        ),
+        "Includes should fail to compile in sandboxed templates"
+    );
+
+    let markdown = |v: BalsaValue| Ok(BalsaValue::String(format!("<p>{}</p>", v)));
+    let upper = |v: BalsaValue| Ok(BalsaValue::String(v.to_string().to_uppercase()));
+    let filtered = |filter: &str| {
+        sandboxed(&format!(
+            "{{{{ body : string, filters: [\"upper\", \"{}\"] }}}}",
+            filter
+        ))
+        .with_filter("upper", upper)
+        .with_unsafe_filter("markdown", markdown)
+        .build()
+    };
+
+    assert!(
+        filtered("upper").is_ok(),
+        "Filters which aren't marked unsafe should be allowed in sandboxed templates"
+    );
+    assert!(
+        matches!(
+            filtered("markdown"),
+            Err(BalsaError::CompileError(BalsaCompileError::SandboxViolation(e)))
+                if e.feature == "filters: [\"markdown\"]"
+        ),
+        "Unsafe filters should fail to compile in sandboxed templates"
+    );
+    assert!(
+        Balsa::from_string("{{ body : string, filters: [\"markdown\"] }}")
+            .with_unsafe_filter("markdown", markdown)
+            .build()
+            .is_ok(),
+        "Unsafe filters should be allowed outside of sandbox mode"
+    );
+}
+
+/// Builds, renders and precompiles thousands of generated templates, most of them malformed, and
+/// decodes corrupted precompiled artifacts, none of which may panic.
+#[test]
+fn no_panic_test() {
+    const BLOCKS: &[&str] = &[
+        "{{ x : string }}",
+        "{{ x : messageformat }}",
+        "{{ y : int, defaultValue: 1 }}",
+        "{{ z : color, defaultValue: x }}",
+        "{{@ x : string = \"a\" }}",
+        "{{ t \"k\" }}",
+        "{{ plural y \"a\" \"b\" }}",
+        "{{ dir x }}",
+        "{{ w : string, escape: false }}",
+    ];
+
+    // Whole blocks are mixed with loose tokens, whitespace and delimiters.
+    let fragments = "{{ }} {{@ : , = \" string int color float currency messageformat x y \
+        defaultValue direction escape t plural dir \"rtl\" false 1 - é { } # ' \\"
+        .split(' ')
+        .chain([" ", "\n"])
+        .chain(BLOCKS.iter().copied())
+        .collect::<Vec<&str>>();
+
+    // A xorshift generator keeps the inputs reproducible without extra dependencies.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound as u64) as usize
+    };
+
+    let params = BalsaParameters::new()
+        .string("x", "{y, plural, one {# item} other {# items}}")
+        .int("y", 2);
+
+    for _ in 0..3000 {
+        let template = (0..1 + next(24))
+            .map(|_| fragments[next(fragments.len())])
+            .collect::<String>();
+
+        let builder = Balsa::from_string(template.as_str()).with_options(
+            CompileOptions::new()
+                .allow_missing_messages(true)
+                .max_nesting_depth(8),
+        );
+        let Ok(compiled) = builder.build() else {
+            continue;
+        };
+
+        let _ = compiled.render_html_string(&params);
+        let _ = compiled.render_defaults();
+        let _ = compiled.explain().to_string();
+        let _ = compiled.extract_messages();
+
+        let Ok(mut bytes) = builder.precompile() else {
+            continue;
+        };
+        let i = next(bytes.len());
+        bytes[i] ^= 1 << next(8);
+
+        if let Ok(decoded) = Template::from_precompiled(&bytes) {
+            let _ = decoded.render_html_string(&params);
+        }
+    }
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ x : string }}")
+                .with_options(CompileOptions::new().delimiters("", "}}"))
+                .build(),
+            Err(BalsaError::CompileError(BalsaCompileError::TemplateParseFail(e)))
+                if e.error == TemplateParseFail::EmptyDelimiter
+        ),
+        "Empty delimiters should fail to compile rather than panic"
+    );
+}
+
+#[test]
+fn currency_test() {
+    let template = Balsa::from_string(
+        "{{ price : currency, defaultValue: \"1234.5 EUR\" }} ({{ total : currency }})",
+    )
+    .build()
+    .expect("Template should successfully compile");
+
+    let params = BalsaParameters::new().currency("total", -5.0, "usd");
+
+    let render = |tag: &str| {
+        template
+            .render_html_string_with_options(
+                &params,
+                &RenderOptions::new().locale(Locale::new(tag)),
+            )
+            .expect("Template should successfully render")
+    };
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Template should successfully render"),
+        "1234.50 EUR (-5.00 USD)"
+    );
+    assert_eq!(render("en-US"), "€1,234.50 (-$5.00)");
+    assert_eq!(render("de-DE"), "1.234,50\u{a0}€ (-5,00\u{a0}$)");
+
+    let template = Balsa::from_string("{{ price : float }} {{ label : string }}")
+        .build()
+        .expect("Template should successfully compile");
+    let params = BalsaParameters::new()
+        .currency("price", 3.0, "JPY")
+        .currency("label", 3.0, "JPY");
+
+    assert_eq!(
+        template
+            .render_html_string(&params)
+            .expect("Currencies should cast to floats and strings"),
+        "3 3 JPY"
+    );
+
+    assert!(
+        matches!(
+            Balsa::from_string("{{ price : currency, defaultValue: \"12\" }}").build(),
+            Err(BalsaError::CompileError(
+                BalsaCompileError::InvalidTypeCast(_)
+            ))
+        ),
+        "Strings without a currency code should fail to cast to currencies"
+    );
+}
+
+/// Renders `template` with the parameter `count`.
+fn render_count(template: &Template, count: i64) -> String {
+    template
+        .render_html_string(&BalsaParameters::new().int("count", count))
+        .expect("Template should successfully render")
+}
+
+/// Fails to compile unless `T` can be stored in shared state and sent between threads.
+fn assert_send_sync_static<T: Send + Sync + 'static>() {}
+
+#[test]
+fn send_sync_test() {
+    /// A parameters type which is deliberately neither `Send` nor `Sync`.
+    struct LocalParams(std::rc::Rc<String>);
+
+    impl AsParameters for LocalParams {
+        fn as_parameters(&self) -> BalsaParameters {
+            BalsaParameters::new().string("headerText", self.0.as_str())
+        }
+    }
+
+    assert_send_sync_static::<Template>();
+    assert_send_sync_static::<TypedTemplate<TemplateParams>>();
+    assert_send_sync_static::<TypedTemplate<LocalParams>>();
+    assert_send_sync_static::<std::sync::Arc<Template>>();
+
+    let template = std::sync::Arc::new(
+        Balsa::from_string("<h1>{{ headerText : string }}</h1>")
+            .build_struct::<LocalParams>()
+            .expect("Template should successfully compile"),
+    );
+
+    let shared = std::sync::Arc::clone(&template);
+    let output = std::thread::spawn(move || {
+        shared
+            .render_html_string(&LocalParams(std::rc::Rc::new("Hello world :)".to_string())))
+            .expect("Shared template should successfully render")
+    })
+    .join()
+    .expect("Rendering thread should not panic");
+
+    assert_eq!(output, "<h1>Hello world :)</h1>");
+}
@@ -0,0 +1,28 @@
+//! Compiles arbitrary templates and renders those which compile with their default values, none
+//! of which may panic.
+
+#![no_main]
+
+use balsa::{Balsa, BalsaParameters, BalsaTemplate, CompileOptions, RenderOptions};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|raw_template: &str| {
+    let options = CompileOptions::new()
+        .max_template_size(64 * 1024)
+        .max_include_depth(4);
+
+    let Ok(template) = Balsa::from_string(raw_template)
+        .with_options(options)
+        .build()
+    else {
+        return;
+    };
+
+    // Keeps templates repeating sections from producing unbounded output.
+    let render_options = RenderOptions::new()
+        .max_output_size(1024 * 1024)
+        .max_loop_iterations(1024);
+
+    let _ = template.render_defaults();
+    let _ = template.render_html_string_with_options(&BalsaParameters::new(), &render_options);
+});
@@ -0,0 +1,20 @@
+//! Loads arbitrary bytes as a precompiled template and renders those which load, none of which
+//! may panic.
+
+#![no_main]
+
+use balsa::{BalsaParameters, BalsaTemplate, RenderOptions, Template};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|bytes: &[u8]| {
+    let Ok(template) = Template::from_bytes(bytes) else {
+        return;
+    };
+
+    let render_options = RenderOptions::new()
+        .max_output_size(1024 * 1024)
+        .max_loop_iterations(1024);
+
+    let _ = template.render_defaults();
+    let _ = template.render_html_string_with_options(&BalsaParameters::new(), &render_options);
+});